@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syxpack::{alesis, dx7, sequential};
+
+fuzz_target!(|data: &[u8]| {
+    let unpacked = sequential::unpack_data(data);
+    let packed = sequential::pack_data(data);
+    assert_eq!(sequential::unpack_data(&packed), data);
+    let _ = sequential::pack_data(&unpacked);
+
+    let packed = alesis::pack_data(data);
+    assert_eq!(alesis::unpack_data(&packed), data);
+    let _ = alesis::unpack_data(data);
+
+    if let Ok(voice) = dx7::Voice::from_packed(data) {
+        let _ = voice.to_packed();
+        let _ = voice.name();
+    }
+});
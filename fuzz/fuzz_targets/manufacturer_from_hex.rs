@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syxpack::Manufacturer;
+
+fuzz_target!(|s: &str| {
+    if let Ok(manufacturer) = Manufacturer::from_hex(s) {
+        assert_eq!(Manufacturer::from_hex(&manufacturer.to_hex()).unwrap(), manufacturer);
+    }
+});
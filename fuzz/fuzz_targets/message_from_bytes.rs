@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syxpack::{Message, Manufacturer, classify};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = classify(data);
+    let _ = Manufacturer::peek(data);
+    let _ = Manufacturer::from_bytes(data);
+
+    if let Ok(message) = Message::from_bytes(data) {
+        let _ = message.to_bytes();
+        let _ = syxpack::dx7::Dump::from_message(&message);
+        let _ = syxpack::sequential::Dump::from_message(&message).map(|d| d.name());
+        let _ = syxpack::novation::identify(&message);
+        let _ = syxpack::alesis::QsDump::from_message(&message);
+        let _ = syxpack::ensoniq::program_names(&message);
+        let _ = syxpack::proteus::ProteusMessage::from_message(&message);
+        let _ = message.decode_universal();
+        let _ = message.sections();
+        let _ = message.lint();
+        let _ = syxpack::kawai::identify(&message);
+        let _ = syxpack::korg::packed_data(&message);
+        let _ = syxpack::casio::tone_data(&message);
+        let _ = syxpack::roland::JV1080_MAP.describe(&message);
+    }
+});
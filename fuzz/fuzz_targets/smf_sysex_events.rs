@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = syxpack::smf::sysex_events(data);
+
+    // Also exercise the track parser directly behind a valid header.
+    let mut smf = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk".to_vec();
+    smf.extend((data.len() as u32).to_be_bytes());
+    smf.extend(data);
+    let _ = syxpack::smf::sysex_events(&smf);
+});
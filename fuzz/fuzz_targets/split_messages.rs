@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syxpack::{message_count, split_messages, split_messages_with_limit};
+
+fuzz_target!(|data: &[u8]| {
+    let count = message_count(data);
+    let parts = split_messages(data.to_vec());
+    assert!(parts.len() >= count);
+    assert_eq!(parts.concat(), data);
+    let _ = split_messages_with_limit(data.to_vec(), 64);
+    let _ = syxpack::scan::parse_messages(data);
+});
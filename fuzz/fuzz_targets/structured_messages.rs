@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syxpack::Message;
+
+fuzz_target!(|message: Message| {
+    let bytes = message.to_bytes();
+    assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+    let _ = message.decode_universal();
+    let _ = message.sections();
+    let _ = message.lint();
+});
@@ -0,0 +1,199 @@
+//! # alesis
+//!
+//! Helpers for Alesis System Exclusive dumps, including the packing
+//! used by the QS series and the QuadraVerb.
+
+use crate::{Message, Manufacturer, SystemExclusiveError};
+
+/// Alesis manufacturer ID.
+pub const ALESIS: [u8; 3] = [0x00, 0x00, 0x0e];
+
+/// QS series device ID.
+pub const QS: u8 = 0x0e;
+
+/// Size of a packed QS program.
+pub const PROGRAM_SIZE: usize = 400;
+
+/// Number of programs in a QS user bank.
+pub const PROGRAM_COUNT: usize = 128;
+
+const USER_PROGRAM_DUMP: u8 = 0x00;
+const EDIT_PROGRAM_DUMP: u8 = 0x02;
+const USER_MIX_DUMP: u8 = 0x06;
+const EDIT_MIX_DUMP: u8 = 0x08;
+const GLOBAL_DATA_DUMP: u8 = 0x0a;
+
+/// Packs 8-bit data into 7-bit System Exclusive bytes. The data bytes
+/// are treated as one continuous bit stream, least significant bit first,
+/// so that every seven bytes of data become eight bytes.
+pub fn pack_data(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity((data.len() * 8).div_ceil(7));
+    let mut bits: u16 = 0;
+    let mut bit_count = 0;
+    for b in data {
+        bits |= (*b as u16) << bit_count;
+        bit_count += 8;
+        while bit_count >= 7 {
+            result.push((bits & 0x7f) as u8);
+            bits >>= 7;
+            bit_count -= 7;
+        }
+    }
+    if bit_count > 0 {
+        result.push((bits & 0x7f) as u8);
+    }
+    result
+}
+
+/// Unpacks 7-bit System Exclusive bytes into 8-bit data.
+/// Any leftover bits at the end that do not make up a full byte are discarded.
+pub fn unpack_data(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() * 7 / 8);
+    let mut bits: u16 = 0;
+    let mut bit_count = 0;
+    for b in data {
+        bits |= ((*b & 0x7f) as u16) << bit_count;
+        bit_count += 7;
+        if bit_count >= 8 {
+            result.push((bits & 0xff) as u8);
+            bits >>= 8;
+            bit_count -= 8;
+        }
+    }
+    result
+}
+
+/// Alesis QS series dump. The data is kept in packed form.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum QsDump {
+    UserProgram { number: u8, data: Vec<u8> },
+    EditProgram { edit: u8, data: Vec<u8> },
+    UserMix { number: u8, data: Vec<u8> },
+    EditMix { data: Vec<u8> },
+    Global { data: Vec<u8> },
+}
+
+impl QsDump {
+    /// Classifies a QS dump from a System Exclusive message.
+    pub fn from_message(message: &Message) -> Result<Self, SystemExclusiveError> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(ALESIS), payload } => payload,
+            Message::ManufacturerSpecific { .. } => return Err(SystemExclusiveError::InvalidManufacturer),
+            _ => return Err(SystemExclusiveError::InvalidMessage),
+        };
+
+        // Device ID, opcode, program/mix/edit number
+        if payload.len() < 3 || payload[0] != QS {
+            return Err(SystemExclusiveError::InvalidMessage);
+        }
+        let number = payload[2];
+        let data = payload[3..].to_vec();
+
+        match payload[1] {
+            USER_PROGRAM_DUMP if data.len() == PROGRAM_SIZE => Ok(QsDump::UserProgram { number, data }),
+            EDIT_PROGRAM_DUMP if data.len() == PROGRAM_SIZE => Ok(QsDump::EditProgram { edit: number, data }),
+            USER_MIX_DUMP => Ok(QsDump::UserMix { number, data }),
+            EDIT_MIX_DUMP => Ok(QsDump::EditMix { data }),
+            GLOBAL_DATA_DUMP => Ok(QsDump::Global { data }),
+            _ => Err(SystemExclusiveError::InvalidMessage),
+        }
+    }
+
+    /// Makes a System Exclusive message from this dump.
+    pub fn to_message(&self) -> Message {
+        let (opcode, number, data) = match self {
+            QsDump::UserProgram { number, data } => (USER_PROGRAM_DUMP, *number, data),
+            QsDump::EditProgram { edit, data } => (EDIT_PROGRAM_DUMP, *edit, data),
+            QsDump::UserMix { number, data } => (USER_MIX_DUMP, *number, data),
+            QsDump::EditMix { data } => (EDIT_MIX_DUMP, 0x00, data),
+            QsDump::Global { data } => (GLOBAL_DATA_DUMP, 0x00, data),
+        };
+
+        let mut payload = vec![QS, opcode, number];
+        payload.extend(data);
+
+        Message::ManufacturerSpecific {
+            manufacturer: Manufacturer::Extended(ALESIS),
+            payload,
+        }
+    }
+
+    /// Returns `true` if this is a program dump.
+    pub fn is_program(&self) -> bool {
+        matches!(self, QsDump::UserProgram { .. } | QsDump::EditProgram { .. })
+    }
+
+    /// Gets the packed data of this dump.
+    pub fn data(&self) -> &[u8] {
+        match self {
+            QsDump::UserProgram { data, .. }
+            | QsDump::EditProgram { data, .. }
+            | QsDump::UserMix { data, .. }
+            | QsDump::EditMix { data }
+            | QsDump::Global { data } => data,
+        }
+    }
+
+    /// Gets the unpacked data of this dump.
+    pub fn unpacked_data(&self) -> Vec<u8> {
+        unpack_data(self.data())
+    }
+}
+
+/// Returns `true` if the messages make up a complete QS user program bank,
+/// that is 128 user program dumps numbered in order.
+pub fn is_program_bank(messages: &[Message]) -> bool {
+    messages.len() == PROGRAM_COUNT &&
+        messages.iter().enumerate().all(|(index, message)| {
+            matches!(QsDump::from_message(message),
+                Ok(QsDump::UserProgram { number, .. }) if number as usize == index)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn pack_unpack_round_trip() {
+        let data: Vec<u8> = (0..350).map(|i| (i * 7) as u8).collect();
+        let packed = pack_data(&data);
+        assert_eq!(packed.len(), PROGRAM_SIZE);
+        assert!(packed.iter().all(|b| b & 0x80 == 0));
+        assert_eq!(unpack_data(&packed), data);
+    }
+
+    #[test]
+    fn pack_bit_order() {
+        // 0: 0 A6 A5 A4 A3 A2 A1 A0
+        // 1: 0 B5 B4 B3 B2 B1 B0 A7
+        assert_eq!(pack_data(&[0x80, 0x01]), vec![0x00, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn program_bank() {
+        let messages: Vec<Message> = (0..PROGRAM_COUNT)
+            .map(|number| QsDump::UserProgram { number: number as u8, data: vec![0x00; PROGRAM_SIZE] }.to_message())
+            .collect();
+        assert!(is_program_bank(&messages));
+        assert!(!is_program_bank(&messages[1..]));
+    }
+
+    #[test]
+    fn classify_edit_program() {
+        let mut bytes = vec![0xF0, 0x00, 0x00, 0x0E, 0x0E, 0x02, 0x00];
+        bytes.extend(vec![0x00; PROGRAM_SIZE]);
+        bytes.push(0xF7);
+        let dump = QsDump::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert!(dump.is_program());
+        assert_eq!(dump.unpacked_data().len(), 350);
+    }
+
+    proptest! {
+        #[test]
+        fn pack_unpack_property(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+            prop_assert_eq!(unpack_data(&pack_data(&data)), data);
+        }
+    }
+}
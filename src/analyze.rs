@@ -0,0 +1,201 @@
+//! # analyze
+//!
+//! Statistics of payload bytes for reverse-engineering undocumented formats:
+//! the byte histogram, the entropy, the length of repeating records and
+//! a guess of how the data is packed into 7-bit bytes.
+//!
+//! The results are heuristic. They point to where to look, not what the data is.
+//! The smaller utilities like [`byte_histogram`] and [`longest_run`] can
+//! also be used on their own.
+
+use std::fmt;
+
+/// The longest record length looked for by `analyze`.
+pub const MAX_PERIOD: usize = 512;
+
+/// How 8-bit data is likely to be packed into the 7-bit bytes of a payload.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Packing {
+    /// The bytes are used as they are.
+    Plain,
+    /// Each byte is split into two nybbles.
+    Nybbles,
+    /// The high bits of seven bytes are collected into an eighth byte.
+    SevenInEight,
+}
+
+impl fmt::Display for Packing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Packing::Plain => "plain",
+            Packing::Nybbles => "nybbles",
+            Packing::SevenInEight => "7-in-8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A record length at which the data repeats.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Period {
+    /// The record length in bytes.
+    pub length: usize,
+    /// The fraction of bytes that are equal to the byte one record earlier.
+    pub similarity: f64,
+}
+
+/// The statistics of some data.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Analysis {
+    /// The number of occurrences of each byte value.
+    pub histogram: [usize; 256],
+    /// The Shannon entropy in bits per byte, from 0.0 to 8.0.
+    pub entropy: f64,
+    /// The most likely record length, if the data seems to consist of records.
+    pub period: Option<Period>,
+    /// The most likely packing.
+    pub packing: Packing,
+}
+
+/// A run of one repeated byte.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Run {
+    pub offset: usize,
+    pub length: usize,
+    pub value: u8,
+}
+
+/// Computes the statistics of the data, usually the payload of a message.
+pub fn analyze(data: &[u8]) -> Analysis {
+    let histogram = byte_histogram(data);
+    Analysis {
+        histogram,
+        entropy: entropy(&histogram, data.len()),
+        period: period(data),
+        packing: packing(data, &histogram),
+    }
+}
+
+/// Counts the occurrences of each byte value in the data.
+pub fn byte_histogram(data: &[u8]) -> [usize; 256] {
+    let mut histogram = [0usize; 256];
+    for b in data {
+        histogram[*b as usize] += 1;
+    }
+    histogram
+}
+
+/// Finds the longest run of one repeated byte, the first one if there
+/// are several. Long runs are often padding or unused parameter space.
+/// Returns `None` for empty data.
+pub fn longest_run(data: &[u8]) -> Option<Run> {
+    let mut longest: Option<Run> = None;
+    let mut offset = 0;
+    for chunk in data.chunk_by(|a, b| a == b) {
+        if longest.is_none_or(|run| chunk.len() > run.length) {
+            longest = Some(Run { offset, length: chunk.len(), value: chunk[0] });
+        }
+        offset += chunk.len();
+    }
+    longest
+}
+
+/// Returns `true` if 8-bit data seems to be packed into the data with
+/// the high bits of seven bytes collected into an eighth byte.
+pub fn is_probably_7bit_packed(data: &[u8]) -> bool {
+    packing(data, &byte_histogram(data)) == Packing::SevenInEight
+}
+
+fn entropy(histogram: &[usize; 256], total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    histogram.iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Finds the shortest record length with nearly the best similarity,
+/// since multiples of the record length are about as similar.
+fn period(data: &[u8]) -> Option<Period> {
+    let similarities: Vec<Period> = (2..=MAX_PERIOD.min(data.len() / 2))
+        .map(|length| {
+            let equal = data.iter().zip(&data[length..]).filter(|(a, b)| a == b).count();
+            Period { length, similarity: equal as f64 / (data.len() - length) as f64 }
+        })
+        .collect();
+    let best = similarities.iter().map(|p| p.similarity).fold(0.0, f64::max);
+    if best < 0.5 {
+        return None;
+    }
+    similarities.into_iter().find(|p| p.similarity >= best * 0.9)
+}
+
+/// Nybble data only uses the low four bits. In 7-in-8 packed data the
+/// bytes carrying the high bits are mostly zero, since most parameter
+/// values are below 128, so one position in each group of eight is
+/// much more often zero than the others.
+fn packing(data: &[u8], histogram: &[usize; 256]) -> Packing {
+    if data.is_empty() {
+        return Packing::Plain;
+    }
+    if data.len().is_multiple_of(2) && histogram[0x10..].iter().all(|count| *count == 0) {
+        return Packing::Nybbles;
+    }
+    if data.len() >= 16 {
+        let zero_fraction = histogram[0] as f64 / data.len() as f64;
+        for phase in 0..8 {
+            let group: Vec<u8> = data.iter().skip(phase).step_by(8).copied().collect();
+            let zeros = group.iter().filter(|b| **b == 0).count() as f64 / group.len() as f64;
+            if zeros >= 0.75 && zero_fraction < zeros / 2.0 {
+                return Packing::SevenInEight;
+            }
+        }
+    }
+    Packing::Plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entropy_and_histogram() {
+        let analysis = analyze(&[0x01, 0x02, 0x01, 0x02]);
+        assert_eq!(analysis.histogram[0x01], 2);
+        assert_eq!(analysis.entropy, 1.0);
+        assert_eq!(analyze(&[]).entropy, 0.0);
+    }
+
+    #[test]
+    fn records_and_packing() {
+        // Ten records of twelve bytes that differ only in the first byte
+        let data: Vec<u8> = (0..10u8)
+            .flat_map(|i| [i, 0x20, 0x31, 0x42, 0x53, 0x64, 0x75, 0x16, 0x27, 0x38, 0x49, 0x5a])
+            .collect();
+        let analysis = analyze(&data);
+        assert_eq!(analysis.period.map(|p| p.length), Some(12));
+        assert_eq!(analysis.packing, Packing::Plain);
+
+        let nybbles: Vec<u8> = data.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+        assert_eq!(analyze(&nybbles).packing, Packing::Nybbles);
+
+        let packed: Vec<u8> = (1..=32u8).map(|i| if i % 8 == 1 { 0 } else { 0x20 + i }).collect();
+        assert_eq!(analyze(&packed).packing, Packing::SevenInEight);
+        assert!(is_probably_7bit_packed(&packed));
+        assert!(!is_probably_7bit_packed(&data));
+    }
+
+    #[test]
+    fn runs() {
+        assert_eq!(longest_run(&[0x01, 0x00, 0x00, 0x02, 0x7F, 0x7F, 0x7F, 0x00]),
+            Some(Run { offset: 4, length: 3, value: 0x7F }));
+        assert_eq!(longest_run(&[0x01, 0x02]), Some(Run { offset: 0, length: 1, value: 0x01 }));
+        assert_eq!(longest_run(&[]), None);
+        assert_eq!(byte_histogram(&[0x00, 0x00, 0x05])[0x00], 2);
+    }
+}
@@ -0,0 +1,345 @@
+//! # archive
+//!
+//! Bank archives for sharing patch collections with their metadata: a zip
+//! file with the `.syx` files and a JSON manifest in `manifest.json`:
+//!
+//! ```json
+//! {
+//!   "device": "DX7",
+//!   "manufacturer": "43",
+//!   "tags": ["factory", "rom"],
+//!   "notes": "ROM cartridges 1A to 4B",
+//!   "files": [ { "name": "rom1a.syx", "digest": "5d41402abc4b2a76b9719d911017c592", "messages": 1 } ]
+//! }
+//! ```
+//!
+//! The digest of each file is the MD5 digest of its contents, checked when
+//! the archive is unpacked. The files are stored without compression, and
+//! only such archives can be unpacked. Only available with the `archive` feature.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::{Message, Manufacturer, SystemExclusiveError};
+use crate::scan::parse_messages;
+
+/// Name of the manifest file in an archive.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP_VERSION: u16 = 20;
+/// 1980-01-01 in MS-DOS date format, the earliest date zip files can have.
+const DOS_DATE: u16 = 0x0021;
+
+/// Error type for packing and unpacking archives.
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The archive could not be read or written.
+    Io(io::Error),
+    /// The archive is not a valid zip file.
+    Format,
+    /// A file in the archive is compressed.
+    Compressed { name: String },
+    /// The manifest is missing or invalid.
+    Manifest(String),
+    /// A file listed in the manifest is missing or has a different digest.
+    Digest { name: String },
+    /// A file in the archive has invalid messages.
+    Message(SystemExclusiveError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::Io(e) => write!(f, "{}", e),
+            ArchiveError::Format => write!(f, "Invalid zip archive"),
+            ArchiveError::Compressed { name } => write!(f, "File '{}' is compressed", name),
+            ArchiveError::Manifest(e) => write!(f, "Invalid manifest: {}", e),
+            ArchiveError::Digest { name } => write!(f, "File '{}' does not match the manifest", name),
+            ArchiveError::Message(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::Io(e) => Some(e),
+            ArchiveError::Message(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(e: io::Error) -> Self {
+        ArchiveError::Io(e)
+    }
+}
+
+impl From<SystemExclusiveError> for ArchiveError {
+    fn from(e: SystemExclusiveError) -> Self {
+        ArchiveError::Message(e)
+    }
+}
+
+/// A file listed in the manifest.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub name: String,
+    /// The MD5 digest of the file in hex.
+    pub digest: String,
+    /// The number of messages in the file.
+    pub messages: usize,
+}
+
+/// The metadata of an archive.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manufacturer: Option<Manufacturer>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// The files of the archive. Filled in by `Archive::to_bytes`.
+    #[serde(default)]
+    pub files: Vec<ManifestFile>,
+}
+
+/// A collection of `.syx` files with a manifest.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Archive {
+    pub manifest: Manifest,
+    /// The files by name, with their messages.
+    pub files: Vec<(String, Vec<Message>)>,
+}
+
+impl Archive {
+    /// Makes an empty archive with the manifest.
+    pub fn new(manifest: Manifest) -> Self {
+        Archive { manifest, files: Vec::new() }
+    }
+
+    /// Adds a file to the archive.
+    pub fn add(&mut self, name: &str, messages: Vec<Message>) {
+        self.files.push((name.to_string(), messages));
+    }
+
+    /// Makes the zip file of the archive. The file list of the manifest
+    /// is replaced with the files of the archive.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArchiveError> {
+        let contents: Vec<(String, Vec<u8>)> = self.files.iter()
+            .map(|(name, messages)| (name.clone(), messages.iter().flat_map(|m| m.to_bytes()).collect()))
+            .collect();
+        let mut manifest = self.manifest.clone();
+        manifest.files = self.files.iter().zip(&contents)
+            .map(|((name, messages), (_, data))| ManifestFile {
+                name: name.clone(),
+                digest: format!("{:x}", md5::compute(data)),
+                messages: messages.len(),
+            })
+            .collect();
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|e| ArchiveError::Manifest(e.to_string()))?;
+
+        let mut entries = vec![(MANIFEST_FILE.to_string(), json)];
+        entries.extend(contents);
+        Ok(write_zip(&entries))
+    }
+
+    /// Reads an archive from a zip file, checking the files against the
+    /// digests in the manifest. Files not listed in the manifest are ignored.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ArchiveError> {
+        let entries = read_zip(data)?;
+        let json = entries.iter()
+            .find(|(name, _)| name == MANIFEST_FILE)
+            .ok_or_else(|| ArchiveError::Manifest(format!("no {}", MANIFEST_FILE)))?;
+        let manifest: Manifest = serde_json::from_slice(&json.1).map_err(|e| ArchiveError::Manifest(e.to_string()))?;
+
+        let mut files = Vec::new();
+        for file in &manifest.files {
+            let (_, data) = entries.iter()
+                .find(|(name, _)| *name == file.name)
+                .ok_or_else(|| ArchiveError::Digest { name: file.name.clone() })?;
+            if format!("{:x}", md5::compute(data)) != file.digest.to_lowercase() {
+                return Err(ArchiveError::Digest { name: file.name.clone() });
+            }
+            files.push((file.name.clone(), parse_messages(data)?));
+        }
+        Ok(Archive { manifest, files })
+    }
+}
+
+/// Packs the archive into a zip file.
+pub fn pack(archive: &Archive, path: &Path) -> Result<(), ArchiveError> {
+    Ok(fs::write(path, archive.to_bytes()?)?)
+}
+
+/// Unpacks an archive from a zip file.
+pub fn unpack(path: &Path) -> Result<Archive, ArchiveError> {
+    Archive::from_bytes(&fs::read(path)?)
+}
+
+/// Computes the CRC-32 checksum used by zip files.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Writes the fields of a zip header that are the same in the local and
+/// central headers, after the version needed to extract.
+fn write_common_header(out: &mut Vec<u8>, name: &str, data: &[u8]) {
+    out.extend(0u16.to_le_bytes());  // flags
+    out.extend(0u16.to_le_bytes());  // stored
+    out.extend(0u16.to_le_bytes());  // time
+    out.extend(DOS_DATE.to_le_bytes());
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend((name.len() as u16).to_le_bytes());
+    out.extend(0u16.to_le_bytes());  // extra field length
+}
+
+fn write_zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        out.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        out.extend(ZIP_VERSION.to_le_bytes());
+        write_common_header(&mut out, name, data);
+        out.extend(name.as_bytes());
+        out.extend(data);
+
+        central.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        central.extend(ZIP_VERSION.to_le_bytes());  // made by
+        central.extend(ZIP_VERSION.to_le_bytes());  // needed to extract
+        write_common_header(&mut central, name, data);
+        central.extend([0u8; 10]);  // comment length, disk, internal and external attributes
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let central_offset = out.len() as u32;
+    out.extend(&central);
+    out.extend(END_SIGNATURE.to_le_bytes());
+    out.extend([0u8; 4]);  // disk numbers
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    out.extend((central.len() as u32).to_le_bytes());
+    out.extend(central_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes());  // comment length
+    out
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ArchiveError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ArchiveError::Format)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ArchiveError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ArchiveError::Format)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads the files of a zip file stored without compression.
+fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, ArchiveError> {
+    let end = (0..data.len().saturating_sub(21)).rev()
+        .find(|offset| read_u32(data, *offset).ok() == Some(END_SIGNATURE))
+        .ok_or(ArchiveError::Format)?;
+    let count = read_u16(data, end + 10)? as usize;
+    let mut offset = read_u32(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if read_u32(data, offset)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(ArchiveError::Format);
+        }
+        let method = read_u16(data, offset + 10)?;
+        let crc = read_u32(data, offset + 16)?;
+        let size = read_u32(data, offset + 20)? as usize;
+        let name_length = read_u16(data, offset + 28)? as usize;
+        let extra_length = read_u16(data, offset + 30)? as usize;
+        let comment_length = read_u16(data, offset + 32)? as usize;
+        let local = read_u32(data, offset + 42)? as usize;
+        let name = data.get(offset + 46..offset + 46 + name_length).ok_or(ArchiveError::Format)?;
+        let name = String::from_utf8_lossy(name).to_string();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if method != 0 {
+            return Err(ArchiveError::Compressed { name });
+        }
+        if read_u32(data, local)? != LOCAL_HEADER_SIGNATURE {
+            return Err(ArchiveError::Format);
+        }
+        let start = local + 30 + read_u16(data, local + 26)? as usize + read_u16(data, local + 28)? as usize;
+        let contents = data.get(start..start + size).ok_or(ArchiveError::Format)?;
+        if crc32(contents) != crc {
+            return Err(ArchiveError::Format);
+        }
+        entries.push((name, contents.to_vec()));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> Archive {
+        let mut archive = Archive::new(Manifest {
+            device: Some("DX7".to_string()),
+            manufacturer: Some(Manufacturer::Standard(0x43)),
+            tags: vec!["factory".to_string()],
+            notes: None,
+            files: Vec::new(),
+        });
+        archive.add("rom1a.syx", vec![Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7]).unwrap()]);
+        archive.add("rom1b.syx", vec![Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x02, 0xF7]).unwrap()]);
+        archive
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let data = archive().to_bytes().unwrap();
+        assert!(data.starts_with(&[0x50, 0x4B, 0x03, 0x04]));
+        let unpacked = Archive::from_bytes(&data).unwrap();
+        assert_eq!(unpacked.files, archive().files);
+        assert_eq!(unpacked.manifest.device.as_deref(), Some("DX7"));
+        assert_eq!(unpacked.manifest.manufacturer, Some(Manufacturer::Standard(0x43)));
+        assert_eq!(unpacked.manifest.files[1].name, "rom1b.syx");
+        assert_eq!(unpacked.manifest.files[1].digest, format!("{:x}", md5::compute([0xF0, 0x43, 0x00, 0x09, 0x02, 0xF7])));
+
+        let json = String::from_utf8(read_zip(&data).unwrap()[0].1.clone()).unwrap();
+        assert!(json.contains("\"manufacturer\": \"43\""));
+    }
+
+    #[test]
+    fn corrupt_archives() {
+        let mut data = archive().to_bytes().unwrap();
+        let position = data.windows(2).rposition(|w| w == [0x09, 0x02]).unwrap();
+        data[position + 1] = 0x03;
+        assert!(matches!(Archive::from_bytes(&data), Err(ArchiveError::Format)));  // CRC mismatch
+
+        let mut entries = read_zip(&archive().to_bytes().unwrap()).unwrap();
+        entries[2].1 = vec![0xF0, 0x43, 0xF7];
+        let data = write_zip(&entries);
+        assert!(matches!(Archive::from_bytes(&data), Err(ArchiveError::Digest { name }) if name == "rom1b.syx"));
+
+        assert!(matches!(Archive::from_bytes(b"not a zip"), Err(ArchiveError::Format)));
+        assert!(matches!(Archive::from_bytes(&write_zip(&[])), Err(ArchiveError::Manifest(_))));
+    }
+}
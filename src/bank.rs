@@ -0,0 +1,376 @@
+//! # bank
+//!
+//! A collection of System Exclusive messages, as loaded from
+//! or saved to a file.
+//!
+//! A bank loaded from a file keeps track of the messages added, removed
+//! and modified since it was loaded, so that an editor can tell whether
+//! there are unsaved changes and write the file only when there are.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::{Message, Manufacturer, write_messages};
+use crate::device_id::{filter_by_device_id, DeviceIdField};
+use crate::scan::{read_messages, ScanError};
+
+/// The changes made to a bank since it was loaded or last saved.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct BankChanges {
+    /// Indices of the messages that were added, in the bank as it is now.
+    pub added: Vec<usize>,
+    /// Indices of the messages that were removed, in the bank as it was loaded.
+    pub removed: Vec<usize>,
+    /// Indices of the messages whose bytes have changed, in the bank as it is now.
+    pub modified: Vec<usize>,
+}
+
+impl BankChanges {
+    /// Returns `true` if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// A collection of messages in order.
+#[derive(Clone, Default, Debug)]
+pub struct MessageBank {
+    messages: Vec<Message>,
+    /// For each message, its index in the bank as it was loaded,
+    /// or `None` if it was added.
+    origins: Vec<Option<usize>>,
+    /// The digests of the messages as they were loaded.
+    saved: Vec<md5::Digest>,
+    path: Option<PathBuf>,
+}
+
+impl MessageBank {
+    /// Makes an empty bank.
+    pub fn new() -> Self {
+        MessageBank::default()
+    }
+
+    /// Loads a bank from a `.syx` file or a Standard MIDI File.
+    /// The bank has no changes until it is edited.
+    pub fn load(path: &Path) -> Result<Self, ScanError> {
+        let mut bank = MessageBank::from(read_messages(path)?);
+        bank.path = Some(path.to_path_buf());
+        bank.mark_saved();
+        Ok(bank)
+    }
+
+    /// Saves the bank to a `.syx` file atomically.
+    /// Does not change the tracked changes; see `save_changes`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_messages(path, &self.messages)
+    }
+
+    /// Gets the path of the file the bank was loaded from.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Gets the changes made since the bank was loaded or last saved with
+    /// `save_changes`. All the messages of a bank that was not loaded from
+    /// a file count as added.
+    pub fn changes(&self) -> BankChanges {
+        let mut changes = BankChanges::default();
+        let mut kept = vec![false; self.saved.len()];
+        for (index, (message, origin)) in self.messages.iter().zip(&self.origins).enumerate() {
+            match origin {
+                Some(origin) => {
+                    kept[*origin] = true;
+                    if message.digest() != self.saved[*origin] {
+                        changes.modified.push(index);
+                    }
+                },
+                None => changes.added.push(index),
+            }
+        }
+        changes.removed = kept.iter().enumerate().filter(|(_, kept)| !**kept).map(|(index, _)| index).collect();
+        changes
+    }
+
+    /// Returns `true` if there are unsaved changes.
+    pub fn is_dirty(&self) -> bool {
+        !self.changes().is_empty()
+    }
+
+    /// Forgets the changes, making the bank as it is now the saved state.
+    pub fn mark_saved(&mut self) {
+        self.saved = self.digests();
+        self.origins = (0..self.messages.len()).map(Some).collect();
+    }
+
+    /// Saves the bank to the file it was loaded from if there are unsaved
+    /// changes, and forgets the changes. Returns `true` if the file was
+    /// written. Fails with `io::ErrorKind::InvalidInput` if the bank has changes
+    /// but was not loaded from a file.
+    pub fn save_changes(&mut self) -> io::Result<bool> {
+        if !self.is_dirty() {
+            return Ok(false);
+        }
+        let path = self.path.as_deref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the bank was not loaded from a file"))?;
+        write_messages(path, &self.messages)?;
+        self.mark_saved();
+        Ok(true)
+    }
+
+    /// Gets the messages in the bank.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Consumes the bank and returns its messages.
+    pub fn into_messages(self) -> Vec<Message> {
+        self.messages
+    }
+
+    /// Gets the number of messages in the bank.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if there are no messages in the bank.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Adds a message to the end of the bank.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+        self.origins.push(None);
+    }
+
+    /// Inserts a message at the index, moving the later messages up.
+    /// Panics if the index is greater than the number of messages.
+    pub fn insert(&mut self, index: usize, message: Message) {
+        self.messages.insert(index, message);
+        self.origins.insert(index, None);
+    }
+
+    /// Removes the message at the index and returns it.
+    /// Panics if the index is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Message {
+        self.origins.remove(index);
+        self.messages.remove(index)
+    }
+
+    /// Replaces the message at the index and returns the old message.
+    /// The new message counts as a modification of the old one.
+    /// Panics if the index is out of bounds.
+    pub fn replace(&mut self, index: usize, message: Message) -> Message {
+        std::mem::replace(&mut self.messages[index], message)
+    }
+
+    /// Gets a mutable reference to the message at the index.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Message> {
+        self.messages.get_mut(index)
+    }
+
+    /// Gets an iterator over the messages.
+    pub fn iter(&self) -> std::slice::Iter<'_, Message> {
+        self.messages.iter()
+    }
+
+    /// Makes a new bank with the messages that satisfy the predicate.
+    pub fn filter<F>(&self, mut predicate: F) -> MessageBank
+    where
+        F: FnMut(&Message) -> bool,
+    {
+        self.messages.iter().filter(|m| predicate(m)).cloned().collect()
+    }
+
+    /// Makes a new bank with the messages addressed to the device `id`,
+    /// reading the device ID from `field` or the manufacturer preset.
+    pub fn filter_by_device_id(&self, id: u8, field: Option<DeviceIdField>) -> MessageBank {
+        MessageBank::from(filter_by_device_id(&self.messages, id, field))
+    }
+
+    /// Changes the device ID of the messages from `from` to `to`, like
+    /// `Message::remap_device_id`. Returns the number of messages changed.
+    pub fn remap_device_id(&mut self, from: u8, to: u8) -> usize {
+        self.messages.iter_mut().map(|message| message.remap_device_id(from, to)).filter(|changed| *changed).count()
+    }
+
+    /// Groups the manufacturer-specific messages by manufacturer,
+    /// in manufacturer ID order. Universal messages are left out.
+    pub fn by_manufacturer(&self) -> BTreeMap<Manufacturer, Vec<&Message>> {
+        let mut result: BTreeMap<Manufacturer, Vec<&Message>> = BTreeMap::new();
+        for message in &self.messages {
+            if let Message::ManufacturerSpecific { manufacturer, .. } = message {
+                result.entry(*manufacturer).or_default().push(message);
+            }
+        }
+        result
+    }
+
+    /// Computes the MD5 digests of the messages, in order.
+    pub fn digests(&self) -> Vec<md5::Digest> {
+        self.messages.iter().map(|message| message.digest()).collect()
+    }
+
+    /// Computes the MD5 digest of the whole bank as it would be saved.
+    pub fn digest(&self) -> md5::Digest {
+        let mut context = md5::Context::new();
+        for message in &self.messages {
+            context.consume(message.to_bytes());
+        }
+        context.compute()
+    }
+
+    /// Returns `true` if the bank contains the same message more than once.
+    pub fn has_duplicates(&self) -> bool {
+        let mut seen = HashSet::new();
+        !self.messages.iter().all(|message| seen.insert(message.to_bytes()))
+    }
+
+    /// Removes repeated messages, keeping the first occurrence of each.
+    /// Returns the number of messages removed.
+    pub fn dedup(&mut self) -> usize {
+        let before = self.messages.len();
+        let mut seen = HashSet::new();
+        (self.messages, self.origins) = std::mem::take(&mut self.messages).into_iter()
+            .zip(std::mem::take(&mut self.origins))
+            .filter(|(message, _)| seen.insert(message.to_bytes()))
+            .unzip();
+        before - self.messages.len()
+    }
+}
+
+/// Banks are equal if they have the same messages, whatever their changes.
+impl PartialEq for MessageBank {
+    fn eq(&self, other: &Self) -> bool {
+        self.messages == other.messages
+    }
+}
+
+impl Eq for MessageBank {}
+
+impl From<Vec<Message>> for MessageBank {
+    fn from(messages: Vec<Message>) -> Self {
+        let origins = vec![None; messages.len()];
+        MessageBank { messages, origins, saved: Vec::new(), path: None }
+    }
+}
+
+impl FromIterator<Message> for MessageBank {
+    fn from_iter<I: IntoIterator<Item = Message>>(iter: I) -> Self {
+        MessageBank::from(iter.into_iter().collect::<Vec<Message>>())
+    }
+}
+
+impl IntoIterator for MessageBank {
+    type Item = Message;
+    type IntoIter = std::vec::IntoIter<Message>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MessageBank {
+    type Item = &'a Message;
+    type IntoIter = std::slice::Iter<'a, Message>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn message(bytes: &[u8]) -> Message {
+        Message::from_bytes(bytes).unwrap()
+    }
+
+    fn sample_bank() -> MessageBank {
+        vec![
+            message(&[0xF0, 0x43, 0x00, 0x01, 0xF7]),
+            message(&[0xF0, 0x41, 0x10, 0x02, 0xF7]),
+            message(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+            message(&[0xF0, 0x43, 0x00, 0x01, 0xF7]),
+        ].into()
+    }
+
+    #[test]
+    fn group_and_filter() {
+        let bank = sample_bank();
+        let groups = bank.by_manufacturer();
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(),
+            vec![Manufacturer::Standard(0x41), Manufacturer::Standard(0x43)]);
+        assert_eq!(groups[&Manufacturer::Standard(0x43)].len(), 2);
+
+        let universal = bank.filter(|m| m.is_universal());
+        assert_eq!(universal.len(), 1);
+        assert_eq!(bank.filter_by_device_id(0x7f, None).len(), 1);
+        let mut remapped = bank.clone();
+        assert_eq!(remapped.remap_device_id(0x7f, 0x10), 1);
+        assert_eq!(remapped.filter_by_device_id(0x10, None).len(), 2);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut bank = sample_bank();
+        assert!(bank.has_duplicates());
+        assert_eq!(bank.dedup(), 1);
+        assert_eq!(bank.len(), 3);
+        assert!(!bank.has_duplicates());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("syxpack-bank-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bank.syx");
+
+        let bank = sample_bank();
+        bank.save(&path).unwrap();
+        let loaded = MessageBank::load(&path).unwrap();
+        assert_eq!(loaded, bank);
+        assert_eq!(loaded.digest(), md5::compute(fs::read(&path).unwrap()));
+        assert_eq!(loaded.digests()[0], loaded.digests()[3]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn track_changes() {
+        let dir = std::env::temp_dir().join(format!("syxpack-bank-changes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bank.syx");
+        sample_bank().save(&path).unwrap();
+
+        let mut bank = MessageBank::load(&path).unwrap();
+        assert_eq!(bank.path(), Some(path.as_path()));
+        assert!(!bank.is_dirty());
+        assert!(!bank.save_changes().unwrap());
+
+        bank.remove(1);
+        bank.push(message(&[0xF0, 0x7D, 0x01, 0xF7]));
+        bank.get_mut(0).unwrap().remap_device_id(0x00, 0x05);
+        assert_eq!(bank.remap_device_id(0x7f, 0x10), 1);
+        assert_eq!(bank.changes(), BankChanges { added: vec![3], removed: vec![1], modified: vec![0, 1] });
+
+        // Changing a message back is not a change
+        bank.replace(1, message(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]));
+        assert_eq!(bank.changes().modified, vec![0]);
+        assert_eq!(bank.dedup(), 0);
+
+        assert!(bank.save_changes().unwrap());
+        assert!(!bank.is_dirty());
+        assert_eq!(MessageBank::load(&path).unwrap(), bank);
+
+        let mut unsaved = sample_bank();
+        assert_eq!(unsaved.changes().added, vec![0, 1, 2, 3]);
+        assert_eq!(unsaved.save_changes().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(unsaved.dedup(), 1);
+        assert_eq!(unsaved.changes().added, vec![0, 1, 2]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
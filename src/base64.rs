@@ -0,0 +1,123 @@
+//! # base64
+//!
+//! Base64 encoding of messages and `data:` URIs, for embedding messages
+//! in JSON configuration files and web pages.
+//!
+//! The encoding is the standard one of RFC 4648, with padding. Decoding
+//! also accepts missing padding and ignores whitespace.
+
+use crate::{Message, SystemExclusiveError};
+
+/// MIME type used in the data URIs made by `Message::to_data_uri`.
+pub const MIME_TYPE: &str = "application/x-sysex";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the data in base64.
+pub fn encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3f] as char);
+            }
+            else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decodes base64 text. Returns `None` if the text is not valid base64.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let digits = match digits.iter().position(|b| *b == b'=') {
+        Some(padding) if digits[padding..].iter().all(|b| *b == b'=') && digits.len().is_multiple_of(4) => &digits[..padding],
+        Some(_) => return None,
+        None => &digits[..],
+    };
+    if digits.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut bits = 0u32;
+        for (i, digit) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|a| a == digit)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            result.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(result)
+}
+
+impl Message {
+    /// Encodes the bytes of the message in base64.
+    pub fn to_base64(&self) -> String {
+        encode(&self.to_bytes())
+    }
+
+    /// Makes a message of its bytes encoded in base64.
+    pub fn from_base64(text: &str) -> Result<Self, SystemExclusiveError> {
+        let data = decode(text).ok_or(SystemExclusiveError::InvalidMessage)?;
+        Message::from_bytes(&data)
+    }
+
+    /// Makes a `data:` URI of the message, like `data:application/x-sysex;base64,8H5/CQH3`.
+    pub fn to_data_uri(&self) -> String {
+        format!("data:{};base64,{}", MIME_TYPE, self.to_base64())
+    }
+
+    /// Makes a message of a base64 `data:` URI of any MIME type.
+    pub fn from_data_uri(uri: &str) -> Result<Self, SystemExclusiveError> {
+        let (header, data) = uri.strip_prefix("data:")
+            .and_then(|rest| rest.split_once(','))
+            .ok_or(SystemExclusiveError::InvalidMessage)?;
+        if !header.ends_with(";base64") {
+            return Err(SystemExclusiveError::UnsupportedFormat);
+        }
+        Message::from_base64(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn encode_and_decode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(decode("Zm9v YmE=").unwrap(), b"fooba");
+        assert_eq!(decode("Zm9vYmE").unwrap(), b"fooba");
+        assert_eq!(decode("Zm=9"), None);
+        assert_eq!(decode("Z"), None);
+        assert_eq!(decode("Zm9*"), None);
+    }
+
+    #[test]
+    fn data_uri() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        assert_eq!(message.to_base64(), "8H5/CQH3");
+        assert_eq!(message.to_data_uri(), "data:application/x-sysex;base64,8H5/CQH3");
+        assert_eq!(Message::from_data_uri(&message.to_data_uri()).unwrap(), message);
+        assert_eq!(Message::from_data_uri("data:;base64,8H5/CQH3").unwrap(), message);
+        assert!(matches!(Message::from_data_uri("data:text/plain,hello"), Err(SystemExclusiveError::UnsupportedFormat)));
+        assert!(Message::from_base64("!!").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(data in proptest::collection::vec(any::<u8>(), 0..64)) {
+            prop_assert_eq!(decode(&encode(&data)), Some(data));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! # behringer
+//!
+//! Identification helpers for Behringer System Exclusive messages.
+//! The messages are classified by their header bytes only,
+//! without decoding the data.
+//!
+//! The Deepmind synthesizers have the header `F0 00 20 32 20 0n 07 cc`,
+//! where `n` is the device ID and `cc` the command. The X32 family of
+//! mixers sends OSC commands as ASCII text after `F0 00 20 32 32`.
+
+use std::fmt;
+use crate::{Message, Manufacturer};
+
+/// Behringer manufacturer ID.
+pub const BEHRINGER: [u8; 3] = [0x00, 0x20, 0x32];
+
+/// Product byte of the Deepmind synthesizers.
+const DEEPMIND: u8 = 0x20;
+
+/// Model byte of the Deepmind 12.
+const DEEPMIND_MODEL: u8 = 0x07;
+
+/// Product byte of the X32 mixers.
+const X32: u8 = 0x32;
+
+/// Kind of a Behringer System Exclusive message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Kind {
+    /// Deepmind program or edit buffer dump request.
+    DeepmindRequest,
+    /// Deepmind program dump.
+    DeepmindProgramDump,
+    /// Deepmind edit buffer dump.
+    DeepmindEditBufferDump,
+    /// Other Deepmind message, like a global parameter dump.
+    Deepmind,
+    /// X32 OSC command.
+    X32,
+    /// Behringer message of unknown kind.
+    Unknown,
+}
+
+impl Kind {
+    /// Returns `true` if the message carries patch data.
+    pub fn is_patch_dump(&self) -> bool {
+        matches!(self, Kind::DeepmindProgramDump | Kind::DeepmindEditBufferDump)
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::DeepmindRequest => "Deepmind dump request",
+            Kind::DeepmindProgramDump => "Deepmind program dump",
+            Kind::DeepmindEditBufferDump => "Deepmind edit buffer dump",
+            Kind::Deepmind => "Deepmind",
+            Kind::X32 => "X32 OSC command",
+            Kind::Unknown => "Unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns `true` if the message is from Behringer.
+pub fn is_behringer(message: &Message) -> bool {
+    matches!(message, Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(BEHRINGER), .. })
+}
+
+/// Identifies the kind of a Behringer message from its header bytes.
+/// Returns `None` if the message is not from Behringer.
+pub fn identify(message: &Message) -> Option<Kind> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(BEHRINGER), payload } => payload,
+        _ => return None,
+    };
+
+    let kind = match payload.as_slice() {
+        [DEEPMIND, device, DEEPMIND_MODEL, command, ..] if device & 0xf0 == 0 => match command {
+            0x01 | 0x03 => Kind::DeepmindRequest,
+            0x02 => Kind::DeepmindProgramDump,
+            0x04 => Kind::DeepmindEditBufferDump,
+            _ => Kind::Deepmind,
+        },
+        [X32, b'/', ..] => Kind::X32,
+        _ => Kind::Unknown,
+    };
+    Some(kind)
+}
+
+/// Gets the OSC command text of an X32 message, like "/ch/01/mix/fader".
+pub fn x32_command(message: &Message) -> Option<String> {
+    if identify(message)? != Kind::X32 {
+        return None;
+    }
+    let text = &message.payload()[1..];
+    if text.iter().all(|b| (0x20..0x7f).contains(b)) {
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+    else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn behringer_message(payload: &[u8]) -> Message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(BEHRINGER), payload: payload.to_vec() }
+    }
+
+    #[test]
+    fn deepmind() {
+        let message = behringer_message(&[0x20, 0x00, 0x07, 0x02, 0x00, 0x05, 0x01, 0x02]);
+        assert_eq!(identify(&message), Some(Kind::DeepmindProgramDump));
+        assert!(identify(&message).unwrap().is_patch_dump());
+        let message = behringer_message(&[0x20, 0x01, 0x07, 0x03]);
+        assert_eq!(identify(&message), Some(Kind::DeepmindRequest));
+        assert_eq!(identify(&behringer_message(&[0x01, 0x02])), Some(Kind::Unknown));
+        assert_eq!(identify(&Message::from_bytes(&[0xF0, 0x43, 0x00, 0xF7]).unwrap()), None);
+    }
+
+    #[test]
+    fn x32() {
+        let message = Message::from_bytes(b"\xF0\x00\x20\x32\x32/ch/01/mix/fader ,f 0.75\xF7").unwrap();
+        assert!(is_behringer(&message));
+        assert_eq!(identify(&message), Some(Kind::X32));
+        assert_eq!(x32_command(&message), Some("/ch/01/mix/fader ,f 0.75".to_string()));
+    }
+}
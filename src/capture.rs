@@ -0,0 +1,277 @@
+//! # capture
+//!
+//! Capture logs of a MIDI session: every message with the time it was sent
+//! or received and its direction, so a session can be inspected and
+//! replayed later. Plain `.syx` files lose both.
+//!
+//! A capture log is a text file with one line per message. The fields are
+//! separated by tabs: the time in seconds from the start of the session,
+//! the direction (`in` for received, `out` for sent) and the message in hex
+//! (tabs shown as spaces here):
+//!
+//! ```text
+//! # syxpack capture
+//! 0.000000  out  F0 7E 7F 06 01 F7
+//! 0.012500  in   F0 7E 00 06 02 40 0A 00 02 00 01 00 00 00 F7
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored. New entries are
+//! appended, so a log can be written while the session goes on.
+//!
+//! A session is replayed with [`replay`], which sends the messages of one
+//! direction again at the recorded pace.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crate::{HexFormat, Message, Manufacturer};
+use crate::transfer::{send_timed, Connection, TransferError};
+
+/// Error type for reading capture logs.
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The log could not be read.
+    Io(io::Error),
+    /// A line of the log is not an entry. The line numbers start from one.
+    Parse { line: usize },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureError::Io(e) => write!(f, "{}", e),
+            CaptureError::Parse { line } => write!(f, "Invalid entry on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+/// The direction of a captured message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Direction {
+    /// Received from the device.
+    In,
+    /// Sent to the device.
+    Out,
+}
+
+impl Direction {
+    /// Gets the direction by name: `in` or `out`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "in" => Some(Direction::In),
+            "out" => Some(Direction::Out),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One message in a capture log.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CaptureEntry {
+    /// Time from the start of the session.
+    pub time: Duration,
+    pub direction: Direction,
+    pub message: Message,
+}
+
+impl fmt::Display for CaptureEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.6}\t{}\t{}", self.time.as_secs_f64(), self.direction, self.message.to_hex_string(&HexFormat::default()))
+    }
+}
+
+impl CaptureEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let seconds = fields.next()?.trim().parse::<f64>().ok()?;
+        let time = Duration::try_from_secs_f64(seconds).ok()?;
+        let direction = Direction::from_name(fields.next()?.trim())?;
+        let bytes = hex::decode(fields.next()?.replace(' ', "")).ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(CaptureEntry {
+            time,
+            direction,
+            message: Message::from_bytes(&bytes).ok()?,
+        })
+    }
+}
+
+/// Parses the entries of a capture log.
+pub fn parse(text: &str) -> Result<Vec<CaptureEntry>, CaptureError> {
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(CaptureEntry::parse(line).ok_or(CaptureError::Parse { line: index + 1 })?);
+    }
+    Ok(entries)
+}
+
+/// Loads the entries of a capture log file.
+pub fn load(path: &Path) -> Result<Vec<CaptureEntry>, CaptureError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+/// Gets the messages in one direction with their times, for sending
+/// them again with `transfer::send_timed`.
+pub fn schedule(entries: &[CaptureEntry], direction: Direction) -> Vec<(Duration, Message)> {
+    entries.iter()
+        .filter(|entry| entry.direction == direction)
+        .map(|entry| (entry.time, entry.message.clone()))
+        .collect()
+}
+
+/// Options for replaying a capture log.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ReplayOptions {
+    /// The direction of the messages to send.
+    pub direction: Direction,
+    /// Factor for the recorded times, so that 2.0 plays at half speed
+    /// and 0.0 sends the messages without delays.
+    pub scale: f64,
+    /// If given, only the messages of this manufacturer are sent.
+    pub manufacturer: Option<Manufacturer>,
+}
+
+impl ReplayOptions {
+    /// Makes options for replaying the messages of one direction at the recorded pace.
+    pub fn new(direction: Direction) -> Self {
+        ReplayOptions { direction, scale: 1.0, manufacturer: None }
+    }
+
+    /// Returns `true` if the entry is to be replayed with these options.
+    pub fn selects(&self, entry: &CaptureEntry) -> bool {
+        entry.direction == self.direction
+            && self.manufacturer.is_none_or(|wanted| entry.message.is_from(&wanted))
+    }
+}
+
+/// Gets the messages to replay with their times, starting from the first
+/// selected message, so that messages left out at the start do not cause
+/// a wait.
+pub fn replay_schedule(entries: &[CaptureEntry], options: &ReplayOptions) -> Vec<(Duration, Message)> {
+    let selected: Vec<&CaptureEntry> = entries.iter().filter(|entry| options.selects(entry)).collect();
+    let start = selected.first().map(|entry| entry.time).unwrap_or_default();
+    selected.iter().map(|entry| (entry.time.saturating_sub(start), entry.message.clone())).collect()
+}
+
+/// Sends the selected messages of a capture log over the connection,
+/// with the recorded timing scaled by the options.
+pub fn replay<C: Connection>(connection: &mut C, entries: &[CaptureEntry], options: &ReplayOptions) -> Result<(), TransferError> {
+    send_timed(connection, &replay_schedule(entries, options), options.scale)
+}
+
+/// Writes a capture log as the session goes on. The times are measured
+/// from when the writer was made.
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Makes a writer and writes the header of the log.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "# syxpack capture")?;
+        Ok(CaptureWriter { writer, start: Instant::now() })
+    }
+
+    /// Records a message sent or received now.
+    pub fn record(&mut self, direction: Direction, message: &Message) -> io::Result<()> {
+        let time = self.start.elapsed();
+        self.record_at(time, direction, message)
+    }
+
+    /// Records a message sent or received at the given time from the start of the session.
+    pub fn record_at(&mut self, time: Duration, direction: Direction, message: &Message) -> io::Result<()> {
+        let entry = CaptureEntry { time, direction, message: message.clone() };
+        writeln!(self.writer, "{}", entry)?;
+        self.writer.flush()
+    }
+
+    /// Consumes the capture writer and returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_parse() {
+        let request = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        let reply = Message::from_bytes(&[0xF0, 0x7E, 0x00, 0x06, 0x02, 0x40, 0x0A, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF7]).unwrap();
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.record_at(Duration::ZERO, Direction::Out, &request).unwrap();
+        writer.record_at(Duration::from_micros(12_500), Direction::In, &reply).unwrap();
+        writer.record(Direction::Out, &request).unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(text.starts_with("# syxpack capture\n0.000000\tout\tF0 7E 7F 06 01 F7\n0.012500\tin\tF0 7E 00 06 02"));
+
+        let entries = parse(&text).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], CaptureEntry { time: Duration::from_micros(12_500), direction: Direction::In, message: reply.clone() });
+        assert_eq!(schedule(&entries, Direction::In), vec![(Duration::from_micros(12_500), reply)]);
+        assert_eq!(schedule(&entries, Direction::Out).len(), 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(parse("0.5\tsideways\tF0 7D F7\n"), Err(CaptureError::Parse { line: 1 })));
+        assert!(matches!(parse("\n-1\tin\tF0 7D F7\n"), Err(CaptureError::Parse { line: 2 })));
+        assert!(matches!(parse("0\tin\tF0 7D\n"), Err(CaptureError::Parse { line: 1 })));
+        assert!(matches!(parse("1e30\tin\tF0 7D F7\n"), Err(CaptureError::Parse { line: 1 })));
+        assert!(parse("# only a comment\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn replay_session() {
+        let text = "# syxpack capture\n\
+            1.0\tout\tF0 41 10 F7\n\
+            1.5\tout\tF0 43 00 F7\n\
+            1.6\tin\tF0 43 01 F7\n\
+            2.5\tout\tF0 43 02 F7\n";
+        let entries = parse(text).unwrap();
+        let mut options = ReplayOptions::new(Direction::Out);
+        options.manufacturer = Some(Manufacturer::Standard(0x43));
+        let schedule = replay_schedule(&entries, &options);
+        assert_eq!(schedule.iter().map(|(time, _)| *time).collect::<Vec<_>>(), vec![Duration::ZERO, Duration::from_secs(1)]);
+
+        options.scale = 0.0;
+        let mut simulator = crate::simulator::Simulator::new();
+        replay(&mut simulator, &entries, &options).unwrap();
+        assert_eq!(simulator.received(), [entries[1].message.clone(), entries[3].message.clone()]);
+    }
+}
@@ -0,0 +1,177 @@
+//! # casio
+//!
+//! Casio CZ series (CZ-101, CZ-1000, CZ-3000, CZ-5000 and CZ-1) tone data
+//! messages and their handshake.
+//!
+//! The CZ transfers tone data in a handshake where the two sides exchange
+//! fragments of one System Exclusive message. A receive request from the
+//! host goes like this:
+//!
+//! ```text
+//! host: F0 44 00 00 7n 20 pp
+//! CZ:   F0 44 00 00 7n 30
+//! host: (256 bytes of tone data) F7
+//! CZ:   F0 44 00 00 7n 31 F7
+//! ```
+//!
+//! and a send request like this:
+//!
+//! ```text
+//! host: F0 44 00 00 7n 10 pp
+//! CZ:   F0 44 00 00 7n 30
+//! host: 7n 31
+//! CZ:   (256 bytes of tone data) F7
+//! host: F7
+//! ```
+//!
+//! where `n` is the MIDI channel and `pp` the program. Archived dumps are
+//! usually complete receive requests with the tone data, which
+//! [`identify`] recognizes. The tone data is 128 bytes split into nybbles,
+//! low nybble first.
+
+use nybble::{denybblify, NybbleOrder};
+use crate::{Message, Manufacturer};
+
+/// Casio manufacturer ID.
+pub const CASIO: u8 = 0x44;
+
+/// Size of the tone data in bytes, after joining the nybbles.
+pub const TONE_SIZE: usize = 128;
+
+/// Function byte of a send request, asking the CZ to send a tone.
+pub const SEND_REQUEST: u8 = 0x10;
+
+/// Function byte of a receive request, asking the CZ to receive a tone.
+pub const RECEIVE_REQUEST: u8 = 0x20;
+
+/// Function byte of the CZ's answer to a request.
+pub const READY: u8 = 0x30;
+
+/// Function byte acknowledging the other side in the handshake.
+pub const ACKNOWLEDGE: u8 = 0x31;
+
+/// Program number of the tone being edited, the sound data area.
+pub const EDIT_BUFFER: u8 = 0x60;
+
+/// A CZ message, or the start of one in the handshake.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CzMessage {
+    /// Send request for a program, possibly followed by the tone data.
+    SendRequest { channel: u8, program: u8, data: Vec<u8> },
+    /// Receive request for a program, possibly followed by the tone data.
+    ReceiveRequest { channel: u8, program: u8, data: Vec<u8> },
+    /// The CZ is ready, possibly followed by the tone data.
+    Ready { channel: u8, data: Vec<u8> },
+    /// Acknowledgement.
+    Acknowledge { channel: u8 },
+}
+
+/// Makes the header bytes of a CZ message for the channel (0 to 15).
+fn header(channel: u8) -> [u8; 5] {
+    [0xf0, CASIO, 0x00, 0x00, 0x70 | (channel & 0x0f)]
+}
+
+/// Makes the bytes the host sends to start a send request, asking the CZ
+/// to send a program. The message is not terminated, since the handshake continues.
+pub fn send_request_bytes(channel: u8, program: u8) -> Vec<u8> {
+    let mut bytes = header(channel).to_vec();
+    bytes.extend([SEND_REQUEST, program & 0x7f]);
+    bytes
+}
+
+/// Makes the bytes the host sends to start a receive request, before sending a program.
+pub fn receive_request_bytes(channel: u8, program: u8) -> Vec<u8> {
+    let mut bytes = header(channel).to_vec();
+    bytes.extend([RECEIVE_REQUEST, program & 0x7f]);
+    bytes
+}
+
+/// Makes the bytes the host sends in a send request to acknowledge that the CZ is ready.
+pub fn acknowledge_bytes(channel: u8) -> [u8; 2] {
+    [0x70 | (channel & 0x0f), ACKNOWLEDGE]
+}
+
+/// Gets a name for a program number, like "Preset 1" or "Internal 16".
+pub fn program_name(program: u8) -> String {
+    match program {
+        0x00..=0x0f => format!("Preset {}", program + 1),
+        0x20..=0x2f => format!("Internal {}", program - 0x20 + 1),
+        0x40..=0x4f => format!("Cartridge {}", program - 0x40 + 1),
+        EDIT_BUFFER => "Edit buffer".to_string(),
+        _ => format!("Program {:02X}", program),
+    }
+}
+
+/// Identifies a complete CZ message.
+pub fn identify(message: &Message) -> Option<CzMessage> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(CASIO), payload } => payload,
+        _ => return None,
+    };
+    let (channel, function) = match payload.get(..4)? {
+        [0x00, 0x00, status, function] if status & 0xf0 == 0x70 => (status & 0x0f, *function),
+        _ => return None,
+    };
+    match function {
+        SEND_REQUEST | RECEIVE_REQUEST => {
+            let program = *payload.get(4)?;
+            let data = payload[5..].to_vec();
+            if function == SEND_REQUEST {
+                Some(CzMessage::SendRequest { channel, program, data })
+            }
+            else {
+                Some(CzMessage::ReceiveRequest { channel, program, data })
+            }
+        },
+        READY => Some(CzMessage::Ready { channel, data: payload[4..].to_vec() }),
+        ACKNOWLEDGE => Some(CzMessage::Acknowledge { channel }),
+        _ => None,
+    }
+}
+
+/// Gets the tone data of a CZ message with its nybbles joined,
+/// if it carries a complete tone.
+pub fn tone_data(message: &Message) -> Option<Vec<u8>> {
+    let data = match identify(message)? {
+        CzMessage::SendRequest { data, .. } | CzMessage::ReceiveRequest { data, .. } | CzMessage::Ready { data, .. } => data,
+        CzMessage::Acknowledge { .. } => return None,
+    };
+    if data.len() != TONE_SIZE * 2 || data.iter().any(|b| b & 0xf0 != 0) {
+        return None;
+    }
+    Some(denybblify(data, NybbleOrder::LowFirst))
+}
+
+/// Makes a complete receive request with the tone data, as stored in archives.
+pub fn tone_message(channel: u8, program: u8, tone: &[u8; TONE_SIZE]) -> Message {
+    let mut payload = vec![0x00, 0x00, 0x70 | (channel & 0x0f), RECEIVE_REQUEST, program & 0x7f];
+    payload.extend(nybble::nybblify(tone.to_vec(), NybbleOrder::LowFirst));
+    Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(CASIO), payload }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_round_trip() {
+        let mut tone = [0u8; TONE_SIZE];
+        tone[0] = 0xA5;
+        tone[127] = 0x3C;
+        let message = tone_message(2, 0x21, &tone);
+        assert_eq!(message.byte_len(), 264);
+        assert!(matches!(identify(&message), Some(CzMessage::ReceiveRequest { channel: 2, program: 0x21, .. })));
+        assert_eq!(message.payload()[5..7], [0x05, 0x0A]);
+        assert_eq!(tone_data(&message), Some(tone.to_vec()));
+        assert_eq!(program_name(0x21), "Internal 2");
+    }
+
+    #[test]
+    fn handshake() {
+        assert_eq!(send_request_bytes(0, EDIT_BUFFER), vec![0xF0, 0x44, 0x00, 0x00, 0x70, 0x10, 0x60]);
+        assert_eq!(acknowledge_bytes(0), [0x70, 0x31]);
+        let message = Message::from_bytes(&[0xF0, 0x44, 0x00, 0x00, 0x71, 0x31, 0xF7]).unwrap();
+        assert_eq!(identify(&message), Some(CzMessage::Acknowledge { channel: 1 }));
+        assert_eq!(tone_data(&message), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! # catalog
+//!
+//! A collection index kept in an SQLite database, for librarians that
+//! open large collections often. Like an index file, the catalog records
+//! the digest, offset, manufacturer and size of each message and the
+//! size and modification time of each file, so that [`Catalog::update`]
+//! only reads the files that have changed. It also keeps the metadata
+//! sidecars of the files, which can be looked up by tag.
+//!
+//! The messages are in the `messages` table, the file stamps in `files`,
+//! and the sidecars in `metadata` and `tags`. Only available with the
+//! `catalog` feature.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::Manufacturer;
+use crate::index::{find_changes, FileStamp, Index, IndexEntry, IndexUpdate};
+use crate::metadata::{Metadata, MetadataError};
+use crate::scan::FileError;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        modified TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        digest TEXT NOT NULL,
+        path TEXT NOT NULL,
+        offset INTEGER NOT NULL,
+        manufacturer TEXT,
+        size INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_digest ON messages (digest);
+    CREATE INDEX IF NOT EXISTS messages_path ON messages (path);
+    CREATE TABLE IF NOT EXISTS metadata (
+        path TEXT PRIMARY KEY,
+        device TEXT,
+        source TEXT,
+        date TEXT,
+        notes TEXT
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        path TEXT NOT NULL,
+        tag TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+";
+
+/// Error type for catalogs.
+#[derive(Debug)]
+pub enum CatalogError {
+    /// The collection could not be read.
+    Io(io::Error),
+    /// The database could not be opened, read or written.
+    Sql(rusqlite::Error),
+    /// A row of the database is invalid.
+    Format(String),
+    /// The metadata sidecar of a file is invalid.
+    Metadata { path: PathBuf, error: MetadataError },
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::Io(e) => write!(f, "{}", e),
+            CatalogError::Sql(e) => write!(f, "{}", e),
+            CatalogError::Format(e) => write!(f, "Invalid catalog entry: {}", e),
+            CatalogError::Metadata { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CatalogError::Io(e) => Some(e),
+            CatalogError::Sql(e) => Some(e),
+            CatalogError::Format(_) => None,
+            CatalogError::Metadata { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for CatalogError {
+    fn from(e: io::Error) -> Self {
+        CatalogError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for CatalogError {
+    fn from(e: rusqlite::Error) -> Self {
+        CatalogError::Sql(e)
+    }
+}
+
+/// A collection index in an SQLite database.
+pub struct Catalog {
+    connection: Connection,
+}
+
+impl Catalog {
+    /// Opens the catalog in the database file, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, CatalogError> {
+        Catalog::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens a catalog that is kept in memory only.
+    pub fn open_in_memory() -> Result<Self, CatalogError> {
+        Catalog::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, CatalogError> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Catalog { connection })
+    }
+
+    /// Brings the catalog up to date with the files under `root` matching
+    /// the glob pattern, like `Index::update`. Only new and changed files
+    /// are read; the rows of removed files are deleted. The metadata
+    /// sidecars are read again for all the files, since they are small.
+    /// The changes are made in one transaction, so a failed update
+    /// leaves the catalog as it was.
+    pub fn update(&mut self, root: &Path, pattern: &str) -> Result<(IndexUpdate, Vec<FileError>), CatalogError> {
+        let changes = find_changes(root, pattern, &self.stamps()?)?;
+        let transaction = self.connection.transaction()?;
+        for path in &changes.stale {
+            let path = path_text(path);
+            transaction.execute("DELETE FROM messages WHERE path = ?1", params![path])?;
+            transaction.execute("DELETE FROM files WHERE path = ?1", params![path])?;
+        }
+        for (path, stamp, messages) in &changes.loaded {
+            let path = path_text(path);
+            transaction.execute(
+                "INSERT INTO files (path, size, modified) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (path) DO UPDATE SET size = excluded.size, modified = excluded.modified",
+                params![path, stamp.size as i64, stamp.modified.to_string()])?;
+            let mut index = Index::new();
+            index.add_file(Path::new(&path), messages);
+            for entry in index.entries() {
+                transaction.execute(
+                    "INSERT INTO messages (digest, path, offset, manufacturer, size) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        format!("{:x}", entry.digest),
+                        path,
+                        entry.offset as i64,
+                        entry.manufacturer.map(|m| m.to_hex()),
+                        entry.size as i64,
+                    ])?;
+            }
+        }
+
+        transaction.execute("DELETE FROM metadata", [])?;
+        transaction.execute("DELETE FROM tags", [])?;
+        let paths: Vec<String> = transaction.prepare("SELECT path FROM files ORDER BY path")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        for path in paths {
+            let metadata = Metadata::load_for(&root.join(&path))
+                .map_err(|error| CatalogError::Metadata { path: root.join(&path), error })?;
+            let Some(metadata) = metadata else {
+                continue;
+            };
+            transaction.execute(
+                "INSERT INTO metadata (path, device, source, date, notes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![path, metadata.device, metadata.source, metadata.date, metadata.notes])?;
+            for tag in &metadata.tags {
+                transaction.execute("INSERT INTO tags (path, tag) VALUES (?1, ?2)", params![path, tag])?;
+            }
+        }
+        transaction.commit()?;
+        Ok((changes.update, changes.errors))
+    }
+
+    /// Gets the stamps of the files in the catalog.
+    pub fn stamps(&self) -> Result<BTreeMap<PathBuf, FileStamp>, CatalogError> {
+        let mut statement = self.connection.prepare("SELECT path, size, modified FROM files")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?)))?;
+        let mut stamps = BTreeMap::new();
+        for row in rows {
+            let (path, size, modified) = row?;
+            let modified = modified.parse().map_err(|_| CatalogError::Format(format!("modification time {}", modified)))?;
+            stamps.insert(PathBuf::from(path), FileStamp { size: size as u64, modified });
+        }
+        Ok(stamps)
+    }
+
+    /// Gets the number of messages in the catalog.
+    pub fn message_count(&self) -> Result<usize, CatalogError> {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Finds the entries of messages with the digest.
+    pub fn lookup(&self, digest: &md5::Digest) -> Result<Vec<IndexEntry>, CatalogError> {
+        self.entries(Some(format!("{:x}", digest)))
+    }
+
+    /// Loads the whole catalog into an index, with the file stamps,
+    /// for searching with the `query` module.
+    pub fn to_index(&self) -> Result<Index, CatalogError> {
+        let mut index = Index::new();
+        for entry in self.entries(None)? {
+            index.push(entry);
+        }
+        for (path, stamp) in self.stamps()? {
+            index.insert_stamp(path, stamp);
+        }
+        Ok(index)
+    }
+
+    /// Gets the entries with the digest in hex, or all the entries.
+    fn entries(&self, digest: Option<String>) -> Result<Vec<IndexEntry>, CatalogError> {
+        let mut statement = self.connection.prepare(
+            "SELECT digest, path, offset, manufacturer, size FROM messages
+                WHERE ?1 IS NULL OR digest = ?1 ORDER BY rowid")?;
+        let rows = statement.query_map(params![digest], |row| Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, i64>(4)?,
+        )))?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (digest, path, offset, manufacturer, size) = row?;
+            let invalid = || CatalogError::Format(format!("message at {} in {}", offset, path));
+            let digest: [u8; 16] = hex::decode(&digest).ok().and_then(|d| d.try_into().ok()).ok_or_else(invalid)?;
+            let manufacturer = match manufacturer {
+                Some(id) => Some(Manufacturer::from_hex(&id).map_err(|_| invalid())?),
+                None => None,
+            };
+            entries.push(IndexEntry {
+                digest: md5::Digest(digest),
+                path: PathBuf::from(path),
+                offset: offset as u64,
+                manufacturer,
+                size: size as usize,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Gets the metadata of a file from its sidecar as it was at the last
+    /// update, or `None` if the file has no sidecar. The path is relative
+    /// to the root of the collection.
+    pub fn metadata(&self, path: &Path) -> Result<Option<Metadata>, CatalogError> {
+        let path = path_text(path);
+        let metadata = self.connection.query_row(
+            "SELECT device, source, date, notes FROM metadata WHERE path = ?1",
+            params![path],
+            |row| Ok(Metadata { device: row.get(0)?, source: row.get(1)?, date: row.get(2)?, tags: Vec::new(), notes: row.get(3)? }))
+            .optional()?;
+        let Some(mut metadata) = metadata else {
+            return Ok(None);
+        };
+        metadata.tags = self.connection.prepare("SELECT tag FROM tags WHERE path = ?1 ORDER BY rowid")?
+            .query_map(params![path], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(Some(metadata))
+    }
+
+    /// Finds the files whose sidecars have the tag, ignoring ASCII case.
+    pub fn files_with_tag(&self, tag: &str) -> Result<Vec<PathBuf>, CatalogError> {
+        let paths: Vec<String> = self.connection.prepare("SELECT DISTINCT path FROM tags WHERE lower(tag) = lower(?1) ORDER BY path")?
+            .query_map(params![tag], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(paths.into_iter().map(PathBuf::from).collect())
+    }
+}
+
+fn path_text(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::Message;
+    use crate::metadata::sidecar_path;
+
+    #[test]
+    fn update_and_lookup() {
+        let dir = std::env::temp_dir().join(format!("syxpack-catalog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7, 0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("b.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        let tags = Metadata { device: Some("DX7".to_string()), tags: vec!["Factory".to_string()], ..Default::default() };
+        tags.save(&sidecar_path(&dir.join("a.syx"))).unwrap();
+
+        let path = dir.join("catalog.db");
+        let mut catalog = Catalog::open(&path).unwrap();
+        let (update, errors) = catalog.update(&dir, "*.syx").unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(update.added.len(), 2);
+        assert_eq!(catalog.message_count().unwrap(), 3);
+
+        let message = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        let found: Vec<PathBuf> = catalog.lookup(&message.digest()).unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(found, vec![PathBuf::from("a.syx"), PathBuf::from("b.syx")]);
+        assert_eq!(catalog.metadata(Path::new("a.syx")).unwrap(), Some(tags));
+        assert_eq!(catalog.metadata(Path::new("b.syx")).unwrap(), None);
+        assert_eq!(catalog.files_with_tag("factory").unwrap(), vec![PathBuf::from("a.syx")]);
+
+        let (index, _) = Index::build(&dir, "*.syx").unwrap();
+        assert_eq!(catalog.to_index().unwrap(), index);
+        drop(catalog);
+
+        // Reopening keeps the stamps, so only the changed file is read
+        fs::write(dir.join("b.syx"), [0xF0, 0x41, 0x10, 0xF7]).unwrap();
+        let mut catalog = Catalog::open(&path).unwrap();
+        let (update, _) = catalog.update(&dir, "*.syx").unwrap();
+        assert_eq!(update, IndexUpdate { changed: vec![PathBuf::from("b.syx")], unchanged: 1, ..Default::default() });
+        assert_eq!(catalog.message_count().unwrap(), 3);
+        assert_eq!(catalog.lookup(&message.digest()).unwrap().len(), 1);
+
+        fs::write(sidecar_path(&dir.join("b.syx")), "not json").unwrap();
+        assert!(matches!(catalog.update(&dir, "*.syx"), Err(CatalogError::Metadata { .. })));
+        assert_eq!(catalog.metadata(Path::new("a.syx")).unwrap().map(|m| m.tags.len()), Some(1));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
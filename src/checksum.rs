@@ -0,0 +1,213 @@
+//! # checksum
+//!
+//! Checksum algorithms and locations, for verifying and fixing
+//! the checksums of edited messages.
+
+use crate::{Message, SystemExclusiveError, dx7, roland};
+
+/// Checksum algorithm. All of them are masked to seven bits.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ChecksumKind {
+    /// Two's complement of the sum, used by Roland and Yamaha.
+    TwosComplement,
+    /// Plain sum.
+    Sum,
+    /// Exclusive OR.
+    Xor,
+}
+
+impl ChecksumKind {
+    /// Gets the checksum kind by name: `roland` or `yamaha`, `sum` or `xor`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "roland" | "yamaha" => Some(ChecksumKind::TwosComplement),
+            "sum" => Some(ChecksumKind::Sum),
+            "xor" => Some(ChecksumKind::Xor),
+            _ => None,
+        }
+    }
+
+    /// Computes the checksum of the bytes.
+    pub fn compute(&self, data: &[u8]) -> u8 {
+        match self {
+            ChecksumKind::TwosComplement => data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)).wrapping_neg() & 0x7f,
+            ChecksumKind::Sum => data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) & 0x7f,
+            ChecksumKind::Xor => data.iter().fold(0u8, |acc, b| acc ^ b) & 0x7f,
+        }
+    }
+}
+
+/// Where the checksum of a message is and how it is computed.
+/// The offsets are into the payload.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ChecksumSpec {
+    pub kind: ChecksumKind,
+    /// Range of the payload bytes that the checksum covers.
+    pub range: std::ops::Range<usize>,
+    /// Offset of the checksum byte.
+    pub offset: usize,
+}
+
+impl ChecksumSpec {
+    /// Makes a spec for the common layout where the checksum is the last
+    /// payload byte and covers the bytes from `start` up to it.
+    pub fn trailing(kind: ChecksumKind, start: usize, payload_length: usize) -> Self {
+        let offset = payload_length.saturating_sub(1);
+        ChecksumSpec { kind, range: start.min(offset)..offset, offset }
+    }
+}
+
+/// A device profile that knows where the checksums of some messages are.
+pub trait ChecksumProfile {
+    /// Gets the checksum spec of the message, or `None` if the
+    /// profile does not recognize it.
+    fn checksum_spec(&self, message: &Message) -> Option<ChecksumSpec>;
+}
+
+impl Message {
+    /// Computes the checksum of the message according to the spec,
+    /// and whether it matches the checksum byte in the message.
+    pub fn verify_checksum(&self, spec: &ChecksumSpec) -> Result<bool, SystemExclusiveError> {
+        let payload = self.payload();
+        let data = payload.get(spec.range.clone()).ok_or(SystemExclusiveError::Truncated)?;
+        let checksum = payload.get(spec.offset).ok_or(SystemExclusiveError::Truncated)?;
+        Ok(spec.kind.compute(data) == *checksum)
+    }
+
+    /// Recomputes the checksum of the message according to the spec and
+    /// rewrites the checksum byte. Returns `true` if the byte changed.
+    pub fn fix_checksum(&mut self, spec: &ChecksumSpec) -> Result<bool, SystemExclusiveError> {
+        let payload = match self {
+            Message::Universal { payload, .. } | Message::ManufacturerSpecific { payload, .. } => payload,
+        };
+        let data = payload.get(spec.range.clone()).ok_or(SystemExclusiveError::Truncated)?;
+        let checksum = spec.kind.compute(data);
+        let old = payload.get_mut(spec.offset).ok_or(SystemExclusiveError::Truncated)?;
+        let changed = *old != checksum;
+        *old = checksum;
+        Ok(changed)
+    }
+
+    /// Verifies the checksum using a device profile. Returns `None`
+    /// if the profile does not recognize the message.
+    pub fn verify_checksum_with(&self, profile: &dyn ChecksumProfile) -> Option<bool> {
+        let spec = profile.checksum_spec(self)?;
+        self.verify_checksum(&spec).ok()
+    }
+
+    /// Fixes the checksum using a device profile. Returns `None`
+    /// if the profile does not recognize the message, otherwise
+    /// whether the checksum byte changed.
+    pub fn fix_checksum_with(&mut self, profile: &dyn ChecksumProfile) -> Option<bool> {
+        let spec = profile.checksum_spec(self)?;
+        self.fix_checksum(&spec).ok()
+    }
+}
+
+/// Gets the checksum spec of the message from the device profiles of
+/// this crate (DX7 dumps and Roland data sets), if one recognizes it.
+pub fn known_checksum_spec(message: &Message) -> Option<ChecksumSpec> {
+    let profiles: [&dyn ChecksumProfile; 2] = [&dx7::Profile, &roland::Profile];
+    profiles.iter().find_map(|profile| profile.checksum_spec(message))
+}
+
+/// Fixes the checksum of the message if `known_checksum_spec` knows
+/// where it is. Returns `None` if not, otherwise whether the checksum
+/// byte changed.
+pub fn fix_known_checksum(message: &mut Message) -> Option<bool> {
+    let spec = known_checksum_spec(message)?;
+    message.fix_checksum(&spec).ok()
+}
+
+/// Searches payloads of the same format for checksums: byte positions
+/// where every payload has the checksum of the bytes right before it,
+/// computed with one of the algorithms of `ChecksumKind`. For each
+/// position and algorithm the longest matching range is reported, and the
+/// results are ordered from the longest range, which is the most likely.
+///
+/// At least two payloads are needed, and the checksum byte must differ
+/// between them, since a byte that never changes matches by chance far
+/// too often. Payloads of different lengths are compared up to the length
+/// of the shortest one.
+pub fn locate_checksums(payloads: &[&[u8]]) -> Vec<ChecksumSpec> {
+    let length = payloads.iter().map(|p| p.len()).min().unwrap_or(0);
+    if payloads.len() < 2 || length < 2 {
+        return Vec::new();
+    }
+
+    // Running sums and XORs, so that the checksum of any range takes constant time
+    let prefixes: Vec<(Vec<u8>, Vec<u8>)> = payloads.iter()
+        .map(|payload| {
+            let mut sums = vec![0u8];
+            let mut xors = vec![0u8];
+            for b in &payload[..length] {
+                sums.push(sums[sums.len() - 1].wrapping_add(*b));
+                xors.push(xors[xors.len() - 1] ^ b);
+            }
+            (sums, xors)
+        })
+        .collect();
+    let checksum = |index: usize, kind: ChecksumKind, start: usize, end: usize| {
+        let (sums, xors) = &prefixes[index];
+        match kind {
+            ChecksumKind::TwosComplement => sums[end].wrapping_sub(sums[start]).wrapping_neg() & 0x7f,
+            ChecksumKind::Sum => sums[end].wrapping_sub(sums[start]) & 0x7f,
+            ChecksumKind::Xor => (xors[end] ^ xors[start]) & 0x7f,
+        }
+    };
+
+    let mut result = Vec::new();
+    for offset in 1..length {
+        if payloads.iter().all(|p| p[offset] == payloads[0][offset]) {
+            continue;
+        }
+        for kind in [ChecksumKind::TwosComplement, ChecksumKind::Sum, ChecksumKind::Xor] {
+            let start = (0..offset).find(|start| {
+                payloads.iter().enumerate().all(|(index, p)| checksum(index, kind, *start, offset) == p[offset])
+            });
+            if let Some(start) = start {
+                result.push(ChecksumSpec { kind, range: start..offset, offset });
+            }
+        }
+    }
+    result.sort_by(|a, b| b.range.len().cmp(&a.range.len()).then(a.offset.cmp(&b.offset)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_and_fix() {
+        // Roland data set with a stale checksum
+        let mut message = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x01, 0x41, 0xF7]).unwrap();
+        let spec = ChecksumSpec::trailing(ChecksumKind::TwosComplement, 3, message.payload().len());
+        assert_eq!(spec.range, 3..7);
+        assert!(!message.verify_checksum(&spec).unwrap());
+        assert!(message.fix_checksum(&spec).unwrap());
+        assert_eq!(message.payload()[7], 0x40);
+        assert!(message.verify_checksum(&spec).unwrap());
+        assert!(!message.fix_checksum(&spec).unwrap());
+
+        let spec = ChecksumSpec { kind: ChecksumKind::Sum, range: 0..20, offset: 20 };
+        assert!(matches!(message.verify_checksum(&spec), Err(SystemExclusiveError::Truncated)));
+    }
+
+    #[test]
+    fn locate() {
+        // Dumps with a header, data and a Roland checksum of the data
+        let dumps: Vec<Vec<u8>> = [[0x01, 0x02, 0x03, 0x04], [0x10, 0x00, 0x7F, 0x05], [0x33, 0x21, 0x42, 0x00]].iter()
+            .map(|data| {
+                let mut payload = vec![0x10, 0x42, 0x12];
+                payload.extend(data);
+                payload.push(ChecksumKind::TwosComplement.compute(data));
+                payload
+            })
+            .collect();
+        let payloads: Vec<&[u8]> = dumps.iter().map(|d| d.as_slice()).collect();
+        let found = locate_checksums(&payloads);
+        assert_eq!(found[0], ChecksumSpec { kind: ChecksumKind::TwosComplement, range: 3..7, offset: 7 });
+        assert!(locate_checksums(&payloads[..1]).is_empty());
+    }
+}
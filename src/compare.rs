@@ -0,0 +1,168 @@
+//! # compare
+//!
+//! Comparing two collections of dumps, like backups of the same device
+//! taken at different times.
+//!
+//! A message's slot is the path of its file relative to the root of the
+//! collection together with its index in that file. Messages in the same
+//! slot with different digests are modified. A message in a slot that only
+//! one collection has counts as unchanged if a message with the same digest
+//! is anywhere in the other collection, since it has only been moved;
+//! otherwise it is only in that collection.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::scan::{load_directory, FileMessages, ScanError};
+
+/// One message in a compared collection.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Entry {
+    /// Path of the file relative to the root of the collection.
+    pub path: PathBuf,
+    /// Index of the message in its file.
+    pub index: usize,
+    pub digest: md5::Digest,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} #{} {:x}", self.path.display(), self.index, self.digest)
+    }
+}
+
+/// The differences between two collections.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Comparison {
+    pub only_in_a: Vec<Entry>,
+    pub only_in_b: Vec<Entry>,
+    /// Messages in the same slot with different digests, from A and B.
+    pub modified: Vec<(Entry, Entry)>,
+    /// The number of messages found in both collections.
+    pub unchanged: usize,
+}
+
+impl Comparison {
+    /// Returns `true` if the collections contain the same messages.
+    pub fn is_identical(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.modified.is_empty()
+    }
+
+    /// Formats the comparison as a JSON object with the arrays
+    /// `only_in_a`, `only_in_b` and `modified`, and the count `unchanged`.
+    pub fn to_json(&self) -> String {
+        let only_in_a: Vec<String> = self.only_in_a.iter().map(entry_json).collect();
+        let only_in_b: Vec<String> = self.only_in_b.iter().map(entry_json).collect();
+        let modified: Vec<String> = self.modified.iter()
+            .map(|(a, b)| format!("{{\"a\":{},\"b\":{}}}", entry_json(a), entry_json(b)))
+            .collect();
+        format!("{{\"only_in_a\":[{}],\"only_in_b\":[{}],\"modified\":[{}],\"unchanged\":{}}}",
+            only_in_a.join(","), only_in_b.join(","), modified.join(","), self.unchanged)
+    }
+}
+
+impl fmt::Display for Comparison {
+    /// Writes one line per difference, prefixed with `-` for messages only
+    /// in A, `+` for messages only in B and `~` for modified messages,
+    /// followed by a summary line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.only_in_a {
+            writeln!(f, "- {}", entry)?;
+        }
+        for entry in &self.only_in_b {
+            writeln!(f, "+ {}", entry)?;
+        }
+        for (a, b) in &self.modified {
+            writeln!(f, "~ {} #{} {:x} -> {:x}", a.path.display(), a.index, a.digest, b.digest)?;
+        }
+        writeln!(f, "{} only in A, {} only in B, {} modified, {} unchanged",
+            self.only_in_a.len(), self.only_in_b.len(), self.modified.len(), self.unchanged)
+    }
+}
+
+fn entry_json(entry: &Entry) -> String {
+    let path = entry.path.display().to_string().replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{{\"path\":\"{}\",\"index\":{},\"digest\":\"{:x}\"}}", path, entry.index, entry.digest)
+}
+
+fn slots(files: &[FileMessages], root: &Path) -> BTreeMap<(PathBuf, usize), md5::Digest> {
+    let mut result = BTreeMap::new();
+    for (path, messages) in files {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        for (index, message) in messages.iter().enumerate() {
+            result.insert((relative.to_path_buf(), index), message.digest());
+        }
+    }
+    result
+}
+
+/// Compares two collections of loaded files. The paths of the files
+/// are made relative to `root_a` and `root_b` to find the slots.
+pub fn compare_files(a: &[FileMessages], root_a: &Path, b: &[FileMessages], root_b: &Path) -> Comparison {
+    let slots_a = slots(a, root_a);
+    let slots_b = slots(b, root_b);
+    let digests_a: HashSet<md5::Digest> = slots_a.values().copied().collect();
+    let digests_b: HashSet<md5::Digest> = slots_b.values().copied().collect();
+
+    let entry = |(path, index): &(PathBuf, usize), digest: &md5::Digest| Entry { path: path.clone(), index: *index, digest: *digest };
+    let mut comparison = Comparison::default();
+    for (slot, digest) in &slots_a {
+        match slots_b.get(slot) {
+            Some(other) if other == digest => comparison.unchanged += 1,
+            Some(other) => comparison.modified.push((entry(slot, digest), entry(slot, other))),
+            None if digests_b.contains(digest) => comparison.unchanged += 1,
+            None => comparison.only_in_a.push(entry(slot, digest)),
+        }
+    }
+    for (slot, digest) in &slots_b {
+        if !slots_a.contains_key(slot) && !digests_a.contains(digest) {
+            comparison.only_in_b.push(entry(slot, digest));
+        }
+    }
+    comparison
+}
+
+/// Loads the `.syx` and `.mid` files in two directories and compares them.
+pub fn compare_directories(a: &Path, b: &Path) -> Result<Comparison, ScanError> {
+    Ok(compare_files(&load_directory(a)?, a, &load_directory(b)?, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+
+    fn message(value: u8) -> Message {
+        Message::from_bytes(&[0xF0, 0x43, 0x00, value, 0xF7]).unwrap()
+    }
+
+    #[test]
+    fn compare_collections() {
+        let a = vec![
+            (PathBuf::from("a/bank.syx"), vec![message(1), message(2), message(3)]),
+            (PathBuf::from("a/old.syx"), vec![message(4)]),
+        ];
+        let b = vec![
+            (PathBuf::from("b/bank.syx"), vec![message(1), message(5)]),
+            (PathBuf::from("b/moved.syx"), vec![message(3)]),
+            (PathBuf::from("b/new.syx"), vec![message(6)]),
+        ];
+        let comparison = compare_files(&a, Path::new("a"), &b, Path::new("b"));
+        assert_eq!(comparison.modified.len(), 1);
+        assert_eq!(comparison.modified[0].0.index, 1);
+        assert_eq!(comparison.modified[0].1.digest, message(5).digest());
+        assert_eq!(comparison.only_in_a.iter().map(|e| e.path.clone()).collect::<Vec<_>>(), vec![PathBuf::from("old.syx")]);
+        assert_eq!(comparison.only_in_b.iter().map(|e| e.path.clone()).collect::<Vec<_>>(), vec![PathBuf::from("new.syx")]);
+        assert_eq!(comparison.unchanged, 2);
+        assert!(!comparison.is_identical());
+
+        let text = comparison.to_string();
+        assert!(text.starts_with("- old.syx #0 "));
+        assert!(text.ends_with("1 only in A, 1 only in B, 1 modified, 2 unchanged\n"));
+        let json = comparison.to_json();
+        assert!(json.starts_with("{\"only_in_a\":[{\"path\":\"old.syx\",\"index\":0,\"digest\":\""));
+        assert!(json.ends_with("\"unchanged\":2}"));
+
+        assert!(compare_files(&a, Path::new("a"), &a, Path::new("a")).is_identical());
+    }
+}
@@ -0,0 +1,162 @@
+//! # device_id
+//!
+//! Reading the device ID or MIDI channel of manufacturer-specific messages,
+//! for picking the messages addressed to one device out of a mixed capture.
+//!
+//! There is no standard place for the device ID in manufacturer-specific
+//! messages. [`DeviceIdField`] describes where it is, and [`preset`] knows
+//! the usual place for some manufacturers.
+
+use crate::{Message, Manufacturer, casio, dx7, ensoniq, kawai, korg, proteus, roland};
+
+/// The location of the device ID in the payload of a message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DeviceIdField {
+    /// Offset of the byte from the start of the payload, after the manufacturer ID.
+    pub offset: usize,
+    /// Mask for the bits of the byte that hold the device ID.
+    pub mask: u8,
+}
+
+impl DeviceIdField {
+    /// Creates a new device ID field.
+    pub const fn new(offset: usize, mask: u8) -> Self {
+        DeviceIdField { offset, mask }
+    }
+
+    /// Reads the device ID of a message.
+    pub fn read(&self, message: &Message) -> Option<u8> {
+        message.payload().get(self.offset).map(|b| b & self.mask)
+    }
+
+    /// Writes the device ID to a message, keeping the bits of the byte
+    /// outside the mask. Returns `false` without changing the message if
+    /// the ID does not fit the mask or the payload is too short.
+    pub fn write(&self, message: &mut Message, id: u8) -> bool {
+        if id & !self.mask != 0 {
+            return false;
+        }
+        let payload = match message {
+            Message::Universal { payload, .. } | Message::ManufacturerSpecific { payload, .. } => payload,
+        };
+        match payload.get_mut(self.offset) {
+            Some(b) => {
+                *b = (*b & !self.mask) | (id & self.mask);
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+/// Gets the usual location of the device ID for a manufacturer:
+///
+/// - Roland: the device ID byte after the manufacturer ID
+/// - Yamaha, Korg and Kawai: the MIDI channel in the low four bits
+///   of the byte after the manufacturer ID
+/// - Ensoniq and E-mu: the channel or device ID byte after the product ID
+/// - Casio: the MIDI channel in the low four bits of the third byte
+pub fn preset(manufacturer: &Manufacturer) -> Option<DeviceIdField> {
+    match manufacturer {
+        Manufacturer::Standard(roland::ROLAND) => Some(DeviceIdField::new(0, 0x7f)),
+        Manufacturer::Standard(dx7::YAMAHA) | Manufacturer::Standard(korg::KORG) | Manufacturer::Standard(kawai::KAWAI) =>
+            Some(DeviceIdField::new(0, 0x0f)),
+        Manufacturer::Standard(ensoniq::ENSONIQ) => Some(DeviceIdField::new(1, 0x0f)),
+        Manufacturer::Standard(proteus::EMU) => Some(DeviceIdField::new(1, 0x7f)),
+        Manufacturer::Standard(casio::CASIO) => Some(DeviceIdField::new(2, 0x0f)),
+        _ => None,
+    }
+}
+
+impl Message {
+    /// Gets the device ID of the message: the target device of universal
+    /// messages, or the device ID or channel at the location given by
+    /// `preset` for manufacturer-specific messages. Returns `None` if the
+    /// location is not known or the payload is too short.
+    pub fn device_id(&self) -> Option<u8> {
+        match self {
+            Message::Universal { target, .. } => Some(*target),
+            Message::ManufacturerSpecific { manufacturer, .. } => preset(manufacturer)?.read(self),
+        }
+    }
+
+    /// Changes the device ID of the message from `from` to `to`, where
+    /// `Message::device_id` finds it. Returns `true` if the message had
+    /// the device ID `from` and was changed, which is needed when sending
+    /// dumps to a device set to another device ID or channel. Returns
+    /// `false` without changing the message if `to` does not fit the field,
+    /// like channel 0x13 for a channel in four bits.
+    pub fn remap_device_id(&mut self, from: u8, to: u8) -> bool {
+        if self.device_id() != Some(from) {
+            return false;
+        }
+        match self {
+            Message::Universal { target, .. } if to <= 0x7f => {
+                *target = to;
+                true
+            },
+            Message::Universal { .. } => false,
+            Message::ManufacturerSpecific { manufacturer, .. } => match preset(manufacturer) {
+                Some(field) => field.write(self, to),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Selects the messages whose device ID, as read by `Message::device_id`
+/// or from the given field, is `id`.
+pub fn filter_by_device_id(messages: &[Message], id: u8, field: Option<DeviceIdField>) -> Vec<Message> {
+    messages.iter()
+        .filter(|message| match field {
+            Some(field) => field.read(message),
+            None => message.device_id(),
+        } == Some(id))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_and_filter() {
+        let messages = vec![
+            Message::from_bytes(&[0xF0, 0x43, 0x03, 0x00, 0x01, 0x1B, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x7E, 0x03, 0x06, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x01, 0x03, 0xF7]).unwrap(),
+        ];
+        assert_eq!(messages[0].device_id(), Some(3));
+        assert_eq!(messages[1].device_id(), Some(0x10));
+        assert_eq!(messages[3].device_id(), None);
+
+        let for_device = filter_by_device_id(&messages, 3, None);
+        assert_eq!(for_device, vec![messages[0].clone(), messages[2].clone()]);
+        let custom = filter_by_device_id(&messages, 3, Some(DeviceIdField::new(0, 0x7f)));
+        assert_eq!(custom, vec![messages[0].clone(), messages[3].clone()]);
+    }
+
+    #[test]
+    fn remap() {
+        let mut kawai = Message::from_bytes(&[0xF0, 0x40, 0x02, 0x20, 0x00, 0x03, 0xF7]).unwrap();
+        assert!(kawai.remap_device_id(2, 5));
+        assert_eq!(kawai.payload()[0], 0x05);
+        assert!(!kawai.remap_device_id(2, 6));
+        assert!(!kawai.remap_device_id(5, 0x13));
+        assert_eq!(kawai.payload()[0], 0x05);
+
+        let mut roland = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0xF7]).unwrap();
+        assert!(roland.remap_device_id(0x10, 0x11));
+        assert_eq!(roland.device_id(), Some(0x11));
+
+        let mut universal = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        assert!(universal.remap_device_id(0x7F, 0x00));
+        assert_eq!(universal.to_bytes(), vec![0xF0, 0x7E, 0x00, 0x09, 0x01, 0xF7]);
+        assert!(!universal.remap_device_id(0x00, 0x80));
+
+        let mut unknown = Message::from_bytes(&[0xF0, 0x01, 0x03, 0xF7]).unwrap();
+        assert!(!unknown.remap_device_id(3, 4));
+    }
+}
@@ -0,0 +1,285 @@
+//! # diff
+//!
+//! Finding the differences between two payloads, such as two patch dumps
+//! of the same device, to show which parameters differ, and saving them
+//! as a patch file that makes the same change to other dumps.
+//!
+//! A patch file is a text file with one line per changed range. The fields
+//! are separated by tabs: the offset in the payload, the old bytes and the
+//! new bytes in hex (tabs shown as spaces here):
+//!
+//! ```text
+//! # syxpack patch
+//! 118  20 31  21 31
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::{format_hex, HexFormat, Message};
+use crate::checksum::{fix_known_checksum, known_checksum_spec};
+
+/// Error type for reading and applying patches.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The patch file could not be read.
+    Io(io::Error),
+    /// A line of the patch file is not a change. The line numbers start from one.
+    Parse { line: usize },
+    /// The payload does not have the old bytes of a change at its offset.
+    Mismatch { offset: usize },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Io(e) => write!(f, "{}", e),
+            PatchError::Parse { line } => write!(f, "Invalid change on line {}", line),
+            PatchError::Mismatch { offset } => write!(f, "Payload does not match the patch at offset {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatchError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PatchError {
+    fn from(e: io::Error) -> Self {
+        PatchError::Io(e)
+    }
+}
+
+/// A range of bytes that differs between two payloads.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DiffSpan {
+    /// Offset of the range from the start of the payloads.
+    pub offset: usize,
+    /// The bytes in the first payload. Shorter than `new` if the
+    /// first payload ends within the range.
+    pub old: Vec<u8>,
+    /// The bytes in the second payload. Shorter than `old` if the
+    /// second payload ends within the range.
+    pub new: Vec<u8>,
+}
+
+impl DiffSpan {
+    /// Gets the byte range of the span.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset.saturating_add(self.old.len().max(self.new.len()))
+    }
+
+    /// Splits the span to leave out the byte at `offset`, if the byte
+    /// is within both the old and the new bytes.
+    fn without(self, offset: usize) -> Vec<DiffSpan> {
+        let index = match offset.checked_sub(self.offset) {
+            Some(index) if index < self.old.len() && index < self.new.len() => index,
+            _ => return vec![self],
+        };
+        let before = DiffSpan { offset: self.offset, old: self.old[..index].to_vec(), new: self.new[..index].to_vec() };
+        let after = DiffSpan { offset: offset + 1, old: self.old[index + 1..].to_vec(), new: self.new[index + 1..].to_vec() };
+        [before, after].into_iter().filter(|span| !span.old.is_empty() || !span.new.is_empty()).collect()
+    }
+}
+
+/// Compares two payloads byte by byte and returns the ranges that differ,
+/// in order. Adjacent differing bytes are joined into one span. If one
+/// payload is longer, its extra bytes are one more span.
+pub fn diff_payloads(a: &[u8], b: &[u8]) -> Vec<DiffSpan> {
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    let common = a.len().min(b.len());
+    for offset in 0..common {
+        if a[offset] == b[offset] {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(span) if span.range().end == offset => {
+                span.old.push(a[offset]);
+                span.new.push(b[offset]);
+            },
+            _ => spans.push(DiffSpan { offset, old: vec![a[offset]], new: vec![b[offset]] }),
+        }
+    }
+
+    if a.len() != b.len() {
+        let old = a[common..].to_vec();
+        let new = b[common..].to_vec();
+        match spans.last_mut() {
+            Some(span) if span.range().end == common => {
+                span.old.extend(old);
+                span.new.extend(new);
+            },
+            _ => spans.push(DiffSpan { offset: common, old, new }),
+        }
+    }
+    spans
+}
+
+/// Changes to a payload, found by comparing two dumps.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Patch {
+    /// The changed ranges, in order.
+    pub spans: Vec<DiffSpan>,
+}
+
+impl Patch {
+    /// Makes the patch that changes payload `a` into payload `b`.
+    pub fn from_payloads(a: &[u8], b: &[u8]) -> Self {
+        Patch { spans: diff_payloads(a, b) }
+    }
+
+    /// Makes the patch that changes the payload of message `a` into that of `b`.
+    /// If the messages are of a format with a known checksum, the checksum
+    /// byte is left out, so that the patch applies to other dumps too.
+    pub fn from_messages(a: &Message, b: &Message) -> Self {
+        let mut patch = Patch::from_payloads(a.payload(), b.payload());
+        if let (Some(spec_a), Some(spec_b)) = (known_checksum_spec(a), known_checksum_spec(b)) {
+            if spec_a.offset == spec_b.offset {
+                patch.spans = patch.spans.into_iter().flat_map(|span| span.without(spec_a.offset)).collect();
+            }
+        }
+        patch
+    }
+
+    /// Returns `true` if the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Applies the patch to a payload. Every changed range must have
+    /// its old bytes in the payload, and the ranges must be in order
+    /// without overlapping.
+    pub fn apply(&self, payload: &[u8]) -> Result<Vec<u8>, PatchError> {
+        let mut previous_end = 0;
+        for span in &self.spans {
+            let end = span.offset.checked_add(span.old.len())
+                .ok_or(PatchError::Mismatch { offset: span.offset })?;
+            if span.offset < previous_end || payload.get(span.offset..end) != Some(span.old.as_slice()) {
+                return Err(PatchError::Mismatch { offset: span.offset });
+            }
+            previous_end = end;
+        }
+        let mut result = payload.to_vec();
+        for span in self.spans.iter().rev() {
+            result.splice(span.offset..span.offset + span.old.len(), span.new.iter().copied());
+        }
+        Ok(result)
+    }
+
+    /// Applies the patch to the payload of a message, and fixes the
+    /// checksum if the message is of a format with a known checksum
+    /// (see `checksum::fix_known_checksum`).
+    pub fn apply_to_message(&self, message: &Message) -> Result<Message, PatchError> {
+        let mut result = message.clone();
+        let patched = self.apply(message.payload())?;
+        match &mut result {
+            Message::Universal { payload, .. } | Message::ManufacturerSpecific { payload, .. } => *payload = patched,
+        }
+        fix_known_checksum(&mut result);
+        Ok(result)
+    }
+
+    /// Parses a patch file.
+    pub fn parse(text: &str) -> Result<Self, PatchError> {
+        let mut spans = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let span = parse_span(line).ok_or(PatchError::Parse { line: index + 1 })?;
+            spans.push(span);
+        }
+        Ok(Patch { spans })
+    }
+
+    /// Loads a patch file.
+    pub fn load(path: &Path) -> Result<Self, PatchError> {
+        Patch::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the patch to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+fn parse_span(line: &str) -> Option<DiffSpan> {
+    let mut fields = line.split('\t');
+    let offset = fields.next()?.trim().parse().ok()?;
+    let old = hex::decode(fields.next()?.replace(' ', "")).ok()?;
+    let new = hex::decode(fields.next()?.replace(' ', "")).ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(DiffSpan { offset, old, new })
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# syxpack patch")?;
+        let format = HexFormat::default();
+        for span in &self.spans {
+            writeln!(f, "{}\t{}\t{}", span.offset, format_hex(&span.old, &format), format_hex(&span.new, &format))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans() {
+        assert!(diff_payloads(&[1, 2, 3], &[1, 2, 3]).is_empty());
+
+        let spans = diff_payloads(&[1, 2, 3, 4, 5, 6], &[1, 9, 9, 4, 5, 7, 8]);
+        assert_eq!(spans, vec![
+            DiffSpan { offset: 1, old: vec![2, 3], new: vec![9, 9] },
+            DiffSpan { offset: 5, old: vec![6], new: vec![7, 8] },
+        ]);
+        assert_eq!(spans[1].range(), 5..7);
+
+        assert_eq!(diff_payloads(&[1, 2], &[1]), vec![DiffSpan { offset: 1, old: vec![2], new: vec![] }]);
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let patch = Patch::from_payloads(&[1, 2, 3, 4], &[1, 5, 3, 4, 6]);
+        let text = patch.to_string();
+        assert_eq!(text, "# syxpack patch\n1\t02\t05\n4\t\t06\n");
+        assert_eq!(Patch::parse(&text).unwrap(), patch);
+        assert_eq!(patch.apply(&[0, 2, 0, 0]).unwrap(), vec![0, 5, 0, 0, 6]);
+        assert!(matches!(patch.apply(&[0, 1, 0, 0]), Err(PatchError::Mismatch { offset: 1 })));
+
+        let overlapping = Patch::parse("0\t01 02 03\t\n2\t03\t\n").unwrap();
+        assert!(matches!(overlapping.apply(&[1, 2, 3]), Err(PatchError::Mismatch { offset: 2 })));
+        let unsorted = Patch::parse("2\t03\t04\n0\t01\t05\n").unwrap();
+        assert!(matches!(unsorted.apply(&[1, 2, 3]), Err(PatchError::Mismatch { offset: 0 })));
+        let overflowing = Patch::parse("18446744073709551615\t01\t02\n").unwrap();
+        assert!(matches!(overflowing.apply(&[1, 2, 3]), Err(PatchError::Mismatch { .. })));
+        assert!(matches!(Patch::parse("1\t02\n"), Err(PatchError::Parse { line: 1 })));
+    }
+
+    #[test]
+    fn patch_fixes_checksum() {
+        // Roland data sets that differ in one data byte and the checksum
+        let a = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x01, 0x30, 0x00, 0x0F, 0xF7]).unwrap();
+        let b = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x01, 0x30, 0x01, 0x0E, 0xF7]).unwrap();
+        let patch = Patch::from_messages(&a, &b);
+        assert_eq!(patch.to_string(), "# syxpack patch\n6\t00\t01\n");
+
+        // Another data set with the same data byte at a different address
+        let message = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x01, 0x31, 0x00, 0x0E, 0xF7]).unwrap();
+        let patched = patch.apply_to_message(&message).unwrap();
+        assert_eq!(patched.payload()[6..], [0x01, 0x0D]);
+    }
+}
@@ -0,0 +1,419 @@
+//! # dx7
+//!
+//! Helpers for Yamaha DX7 voice dumps, both single voices
+//! and packed 32-voice banks.
+
+use crate::{Message, Manufacturer, SystemExclusiveError, Section, SectionKind, SectionProfile};
+use crate::checksum::{ChecksumKind, ChecksumProfile, ChecksumSpec};
+use crate::patch::{Patch, single_message};
+
+/// Yamaha manufacturer ID.
+pub const YAMAHA: u8 = 0x43;
+
+/// Size of an unpacked voice (VCED format).
+pub const VOICE_SIZE: usize = 155;
+
+/// Size of a packed voice (VMEM format).
+pub const PACKED_VOICE_SIZE: usize = 128;
+
+/// Number of voices in a bank dump.
+pub const VOICE_COUNT: usize = 32;
+
+/// Size of the bank dump data.
+pub const BANK_DATA_SIZE: usize = VOICE_COUNT * PACKED_VOICE_SIZE;
+
+/// Length of the voice name in characters.
+pub const NAME_LENGTH: usize = 10;
+
+/// Format number of a single voice dump.
+const VOICE_FORMAT: u8 = 0x00;
+
+/// Format number of a 32-voice bank dump.
+const BANK_FORMAT: u8 = 0x09;
+
+const OPERATOR_COUNT: usize = 6;
+const OPERATOR_SIZE: usize = 21;
+const PACKED_OPERATOR_SIZE: usize = 17;
+
+/// Computes the DX7 checksum of the data bytes: the two's complement
+/// of the sum of the bytes, masked to seven bits.
+pub fn checksum(data: &[u8]) -> u8 {
+    let sum = data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+    sum.wrapping_neg() & 0x7f
+}
+
+/// A DX7 voice in unpacked (VCED) format.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Voice {
+    pub data: [u8; VOICE_SIZE],
+}
+
+impl Voice {
+    /// Creates a voice from unpacked voice data.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SystemExclusiveError> {
+        let data: [u8; VOICE_SIZE] = data.try_into()
+            .map_err(|_| SystemExclusiveError::InvalidMessage)?;
+        Ok(Voice { data })
+    }
+
+    /// Creates a voice by unpacking packed (VMEM) voice data.
+    pub fn from_packed(packed: &[u8]) -> Result<Self, SystemExclusiveError> {
+        let packed: &[u8; PACKED_VOICE_SIZE] = packed.try_into()
+            .map_err(|_| SystemExclusiveError::InvalidMessage)?;
+        Ok(Voice { data: unpack_voice(packed) })
+    }
+
+    /// Gets the voice name, with trailing spaces removed.
+    pub fn name(&self) -> String {
+        voice_name(&self.data[VOICE_SIZE - NAME_LENGTH..])
+    }
+
+    /// Converts the voice into packed (VMEM) format.
+    pub fn to_packed(&self) -> [u8; PACKED_VOICE_SIZE] {
+        pack_voice(&self.data)
+    }
+}
+
+/// Makes a voice name from the name bytes, as ASCII with
+/// trailing spaces removed.
+fn voice_name(data: &[u8]) -> String {
+    let name: String = data.iter()
+        .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+        .collect();
+    name.trim_end().to_string()
+}
+
+/// Packs an unpacked (VCED) voice into the VMEM format used in bank dumps.
+pub fn pack_voice(data: &[u8; VOICE_SIZE]) -> [u8; PACKED_VOICE_SIZE] {
+    let mut result = [0u8; PACKED_VOICE_SIZE];
+
+    for op in 0..OPERATOR_COUNT {
+        let s = &data[op * OPERATOR_SIZE..(op + 1) * OPERATOR_SIZE];
+        let d = &mut result[op * PACKED_OPERATOR_SIZE..(op + 1) * PACKED_OPERATOR_SIZE];
+        d[..11].copy_from_slice(&s[..11]);  // EG, breakpoint, depths
+        d[11] = ((s[12] & 0x03) << 2) | (s[11] & 0x03);  // curves
+        d[12] = ((s[20] & 0x0f) << 3) | (s[13] & 0x07);  // detune, rate scaling
+        d[13] = ((s[15] & 0x07) << 2) | (s[14] & 0x03);  // key velocity sensitivity, AMS
+        d[14] = s[16];  // output level
+        d[15] = ((s[18] & 0x1f) << 1) | (s[17] & 0x01);  // frequency coarse, oscillator mode
+        d[16] = s[19];  // frequency fine
+    }
+
+    let s = &data[OPERATOR_COUNT * OPERATOR_SIZE..];
+    let d = &mut result[OPERATOR_COUNT * PACKED_OPERATOR_SIZE..];
+    d[..8].copy_from_slice(&s[..8]);  // pitch EG
+    d[8] = s[8] & 0x1f;  // algorithm
+    d[9] = ((s[10] & 0x01) << 3) | (s[9] & 0x07);  // oscillator key sync, feedback
+    d[10..14].copy_from_slice(&s[11..15]);  // LFO speed, delay, PMD, AMD
+    d[14] = ((s[17] & 0x07) << 4) | ((s[16] & 0x07) << 1) | (s[15] & 0x01);  // PMS, waveform, sync
+    d[15] = s[18];  // transpose
+    d[16..].copy_from_slice(&s[19..]);  // name
+
+    result
+}
+
+/// Unpacks a packed (VMEM) voice into the VCED format used in single voice dumps.
+pub fn unpack_voice(data: &[u8; PACKED_VOICE_SIZE]) -> [u8; VOICE_SIZE] {
+    let mut result = [0u8; VOICE_SIZE];
+
+    for op in 0..OPERATOR_COUNT {
+        let s = &data[op * PACKED_OPERATOR_SIZE..(op + 1) * PACKED_OPERATOR_SIZE];
+        let d = &mut result[op * OPERATOR_SIZE..(op + 1) * OPERATOR_SIZE];
+        d[..11].copy_from_slice(&s[..11]);
+        d[11] = s[11] & 0x03;
+        d[12] = (s[11] >> 2) & 0x03;
+        d[13] = s[12] & 0x07;
+        d[14] = s[13] & 0x03;
+        d[15] = (s[13] >> 2) & 0x07;
+        d[16] = s[14];
+        d[17] = s[15] & 0x01;
+        d[18] = (s[15] >> 1) & 0x1f;
+        d[19] = s[16];
+        d[20] = (s[12] >> 3) & 0x0f;
+    }
+
+    let s = &data[OPERATOR_COUNT * PACKED_OPERATOR_SIZE..];
+    let d = &mut result[OPERATOR_COUNT * OPERATOR_SIZE..];
+    d[..8].copy_from_slice(&s[..8]);
+    d[8] = s[8] & 0x1f;
+    d[9] = s[9] & 0x07;
+    d[10] = (s[9] >> 3) & 0x01;
+    d[11..15].copy_from_slice(&s[10..14]);
+    d[15] = s[14] & 0x01;
+    d[16] = (s[14] >> 1) & 0x07;
+    d[17] = (s[14] >> 4) & 0x07;
+    d[18] = s[15];
+    d[19..].copy_from_slice(&s[16..]);
+
+    result
+}
+
+/// A DX7 voice dump, either a single voice or a bank of 32 voices.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Dump {
+    Voice { channel: u8, voice: Voice },
+    Bank { channel: u8, voices: Vec<Voice> },
+}
+
+impl Dump {
+    /// Parses a DX7 voice dump from a System Exclusive message,
+    /// verifying the checksum.
+    pub fn from_message(message: &Message) -> Result<Self, SystemExclusiveError> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(YAMAHA), payload } => payload,
+            Message::ManufacturerSpecific { .. } => return Err(SystemExclusiveError::InvalidManufacturer),
+            _ => return Err(SystemExclusiveError::InvalidMessage),
+        };
+
+        // Sub-status and channel, format, byte count MSB and LSB
+        if payload.len() < 5 || payload[0] & 0xf0 != 0x00 {
+            return Err(SystemExclusiveError::InvalidMessage);
+        }
+        let channel = payload[0] & 0x0f;
+        let format = payload[1];
+        let byte_count = ((payload[2] as usize) << 7) | payload[3] as usize;
+
+        let expected_count = match format {
+            VOICE_FORMAT => VOICE_SIZE,
+            BANK_FORMAT => BANK_DATA_SIZE,
+            _ => return Err(SystemExclusiveError::InvalidMessage),
+        };
+        if byte_count != expected_count || payload.len() != 4 + byte_count + 1 {
+            return Err(SystemExclusiveError::InvalidMessage);
+        }
+
+        let data = &payload[4..4 + byte_count];
+        if checksum(data) != payload[4 + byte_count] {
+            return Err(SystemExclusiveError::InvalidChecksum);
+        }
+
+        if format == VOICE_FORMAT {
+            Ok(Dump::Voice { channel, voice: Voice::from_bytes(data)? })
+        }
+        else {
+            let mut voices = Vec::new();
+            for chunk in data.chunks(PACKED_VOICE_SIZE) {
+                voices.push(Voice::from_packed(chunk)?);
+            }
+            Ok(Dump::Bank { channel, voices })
+        }
+    }
+
+    /// Makes a System Exclusive message from this dump.
+    pub fn to_message(&self) -> Message {
+        let (channel, format, data) = match self {
+            Dump::Voice { channel, voice } => (*channel, VOICE_FORMAT, voice.data.to_vec()),
+            Dump::Bank { channel, voices } => {
+                let mut data = Vec::new();
+                for voice in voices {
+                    data.extend(voice.to_packed());
+                }
+                (*channel, BANK_FORMAT, data)
+            }
+        };
+
+        let mut payload = vec![
+            channel & 0x0f,
+            format,
+            ((data.len() >> 7) & 0x7f) as u8,
+            (data.len() & 0x7f) as u8,
+        ];
+        payload.extend(&data);
+        payload.push(checksum(&data));
+
+        Message::ManufacturerSpecific {
+            manufacturer: Manufacturer::Standard(YAMAHA),
+            payload,
+        }
+    }
+
+    /// Gets the names of the voices in this dump.
+    pub fn voice_names(&self) -> Vec<String> {
+        match self {
+            Dump::Voice { voice, .. } => vec![voice.name()],
+            Dump::Bank { voices, .. } => voices.iter().map(|v| v.name()).collect(),
+        }
+    }
+}
+
+/// Section profile for DX7 voice dumps, annotating the voice names
+/// and the checksum.
+pub struct Profile;
+
+impl SectionProfile for Profile {
+    fn payload_sections(&self, message: &Message) -> Option<Vec<Section>> {
+        let dump = Dump::from_message(message).ok()?;
+
+        // Initiator, manufacturer, sub-status, format and byte count
+        let data_offset = 6;
+        let mut sections = Vec::new();
+        let data_size = match dump {
+            Dump::Voice { .. } => {
+                sections.push(Section::labeled(SectionKind::Name, "Voice name",
+                    data_offset + VOICE_SIZE - NAME_LENGTH, NAME_LENGTH));
+                VOICE_SIZE
+            },
+            Dump::Bank { .. } => {
+                for i in 0..VOICE_COUNT {
+                    sections.push(Section::labeled(SectionKind::Name, &format!("Voice {} name", i + 1),
+                        data_offset + (i + 1) * PACKED_VOICE_SIZE - NAME_LENGTH, NAME_LENGTH));
+                }
+                BANK_DATA_SIZE
+            },
+        };
+        sections.push(Section::new(SectionKind::Checksum, data_offset + data_size, 1));
+        Some(sections)
+    }
+}
+
+impl ChecksumProfile for Profile {
+    fn checksum_spec(&self, message: &Message) -> Option<ChecksumSpec> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(YAMAHA), payload } => payload,
+            _ => return None,
+        };
+        let data_size = match *payload.get(1)? {
+            VOICE_FORMAT => VOICE_SIZE,
+            BANK_FORMAT => BANK_DATA_SIZE,
+            _ => return None,
+        };
+        if payload[0] & 0xf0 != 0x00 || payload.len() != 4 + data_size + 1 {
+            return None;
+        }
+        Some(ChecksumSpec { kind: ChecksumKind::TwosComplement, range: 4..4 + data_size, offset: 4 + data_size })
+    }
+}
+
+impl Patch for Dump {
+    /// Gets the voice name of a single voice dump. Banks have no name.
+    fn name(&self) -> Option<String> {
+        match self {
+            Dump::Voice { voice, .. } => Some(voice.name()),
+            Dump::Bank { .. } => None,
+        }
+    }
+
+    fn to_messages(&self) -> Vec<Message> {
+        vec![self.to_message()]
+    }
+
+    fn from_messages(messages: &[Message]) -> Result<Self, SystemExclusiveError> {
+        Dump::from_message(single_message(messages)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn make_voice(name: &str) -> Voice {
+        let mut data = [0u8; VOICE_SIZE];
+        for op in 0..OPERATOR_COUNT {
+            let d = &mut data[op * OPERATOR_SIZE..(op + 1) * OPERATOR_SIZE];
+            d[..8].copy_from_slice(&[99, 99, 99, 99, 99, 99, 99, 0]);
+            d[11] = 3;   // right curve
+            d[12] = 2;
+            d[13] = 7;   // rate scaling
+            d[14] = 1;
+            d[15] = 5;
+            d[16] = 90;  // output level
+            d[17] = 1;   // fixed frequency
+            d[18] = 31;
+            d[19] = 50;
+            d[20] = 14;  // detune
+        }
+        let g = &mut data[OPERATOR_COUNT * OPERATOR_SIZE..];
+        g[8] = 31;   // algorithm 32
+        g[9] = 7;    // feedback
+        g[10] = 1;
+        g[15] = 1;
+        g[16] = 5;   // LFO waveform
+        g[17] = 7;
+        g[18] = 24;  // transpose
+        let name = format!("{:<10}", name);
+        g[19..].copy_from_slice(name.as_bytes());
+        Voice { data }
+    }
+
+    #[test]
+    fn pack_unpack_round_trip() {
+        let voice = make_voice("E.PIANO 1");
+        let packed = voice.to_packed();
+        assert_eq!(Voice::from_packed(&packed).unwrap(), voice);
+    }
+
+    #[test]
+    fn single_voice_dump() {
+        let dump = Dump::Voice { channel: 0, voice: make_voice("BRASS 1") };
+        let message = dump.to_message();
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 163);
+        assert_eq!(bytes[..6], [0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B]);
+
+        let parsed = Dump::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(parsed.voice_names(), vec!["BRASS 1"]);
+        assert_eq!(parsed, dump);
+    }
+
+    #[test]
+    fn bank_dump() {
+        let voices: Vec<Voice> = (0..VOICE_COUNT).map(|i| make_voice(&format!("VOICE {}", i + 1))).collect();
+        let dump = Dump::Bank { channel: 2, voices };
+        let bytes = dump.to_message().to_bytes();
+        assert_eq!(bytes.len(), 4104);
+        assert_eq!(bytes[..6], [0xF0, 0x43, 0x02, 0x09, 0x20, 0x00]);
+
+        let parsed = Dump::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(parsed.voice_names()[31], "VOICE 32");
+        assert_eq!(parsed, dump);
+    }
+
+    #[test]
+    fn voice_sections() {
+        let message = Dump::Voice { channel: 0, voice: make_voice("BRASS 1") }.to_message();
+        let bytes = message.to_bytes();
+        let sections = message.sections_with(&Profile);
+        let name = sections.iter().find(|s| s.kind == SectionKind::Name).unwrap();
+        assert_eq!(&bytes[name.range()], b"BRASS 1   ");
+        let checksum = sections.iter().find(|s| s.kind == SectionKind::Checksum).unwrap();
+        assert_eq!(checksum.offset, bytes.len() - 2);
+
+        // The sections cover the whole message without gaps
+        let mut position = 0;
+        for section in &sections {
+            assert_eq!(section.offset, position);
+            position += section.length;
+        }
+        assert_eq!(position, bytes.len());
+    }
+
+    #[test]
+    fn fix_stale_checksum() {
+        let mut bytes = Dump::Voice { channel: 0, voice: make_voice("STRINGS") }.to_message().to_bytes();
+        bytes[20] ^= 0x01;
+        let mut message = Message::from_bytes(&bytes).unwrap();
+        assert_eq!(message.verify_checksum_with(&Profile), Some(false));
+        assert_eq!(message.fix_checksum_with(&Profile), Some(true));
+        assert!(Dump::from_message(&message).is_ok());
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let dump = Dump::Voice { channel: 0, voice: make_voice("STRINGS") };
+        let mut bytes = dump.to_message().to_bytes();
+        let index = bytes.len() - 2;
+        bytes[index] ^= 0x01;
+        let message = Message::from_bytes(&bytes).unwrap();
+        assert!(matches!(Dump::from_message(&message), Err(SystemExclusiveError::InvalidChecksum)));
+    }
+
+    proptest! {
+        #[test]
+        fn unpack_pack_property(packed in proptest::collection::vec(0x00u8..0x80, PACKED_VOICE_SIZE)) {
+            let packed: [u8; PACKED_VOICE_SIZE] = packed.try_into().unwrap();
+            let voice = unpack_voice(&packed);
+            prop_assert_eq!(unpack_voice(&pack_voice(&voice)), voice);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! # ensoniq
+//!
+//! Classification of Ensoniq System Exclusive dumps from the ESQ-1 and SQ-80,
+//! based on their message types and length signatures.
+
+use std::fmt;
+use nybble::{denybblify, NybbleOrder};
+use crate::{Message, Manufacturer};
+
+/// Ensoniq manufacturer ID.
+pub const ENSONIQ: u8 = 0x0f;
+
+/// Product ID shared by the ESQ-1 and the SQ-80.
+pub const ESQ1: u8 = 0x02;
+
+/// Size of one program in the dump, as nybbles.
+pub const PROGRAM_SIZE: usize = 204;
+
+/// Number of programs in an all-program dump.
+pub const PROGRAM_COUNT: usize = 40;
+
+/// Length of the program name in characters.
+pub const NAME_LENGTH: usize = 6;
+
+const SINGLE_PROGRAM_DUMP: u8 = 0x01;
+const ALL_PROGRAMS_DUMP: u8 = 0x02;
+
+/// Kind of an ESQ-1/SQ-80 dump.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Kind {
+    SingleProgram,
+    AllPrograms,
+}
+
+impl Kind {
+    /// Gets the expected length of the complete message in bytes.
+    pub fn message_length(&self) -> usize {
+        let data_length = match self {
+            Kind::SingleProgram => PROGRAM_SIZE,
+            Kind::AllPrograms => PROGRAM_SIZE * PROGRAM_COUNT,
+        };
+        // Initiator, manufacturer, product, channel, message type, terminator
+        5 + data_length + 1
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::SingleProgram => "Single program dump",
+            Kind::AllPrograms => "All programs dump",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Identifies an ESQ-1/SQ-80 program dump, checking that the
+/// message type and length signature agree. Returns the MIDI channel
+/// and the kind of dump.
+pub fn identify(message: &Message) -> Option<(u8, Kind)> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(ENSONIQ), payload } => payload,
+        _ => return None,
+    };
+
+    if payload.len() < 3 || payload[0] != ESQ1 {
+        return None;
+    }
+    let channel = payload[1] & 0x0f;
+    let kind = match payload[2] {
+        SINGLE_PROGRAM_DUMP => Kind::SingleProgram,
+        ALL_PROGRAMS_DUMP => Kind::AllPrograms,
+        _ => return None,
+    };
+    if payload.len() + 3 != kind.message_length() {
+        return None;
+    }
+    Some((channel, kind))
+}
+
+/// Gets the program names from an ESQ-1/SQ-80 program dump.
+/// Returns an empty vector if the message is not a program dump.
+pub fn program_names(message: &Message) -> Vec<String> {
+    if identify(message).is_none() {
+        return Vec::new();
+    }
+    let payload = match message {
+        Message::ManufacturerSpecific { payload, .. } => payload,
+        _ => return Vec::new(),
+    };
+
+    payload[3..].chunks(PROGRAM_SIZE)
+        .map(|program| {
+            let data = denybblify(program[..NAME_LENGTH * 2].to_vec(), NybbleOrder::LowFirst);
+            let name: String = data.iter()
+                .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+                .collect();
+            name.trim_end().to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nybble::nybblify;
+
+    fn program_data(name: &str) -> Vec<u8> {
+        let mut data = format!("{:<6}", name).into_bytes();
+        data.resize(PROGRAM_SIZE / 2, 0x00);
+        nybblify(data, NybbleOrder::LowFirst)
+    }
+
+    #[test]
+    fn single_program() {
+        let mut bytes = vec![0xF0, 0x0F, 0x02, 0x03, 0x01];
+        bytes.extend(program_data("BRASS"));
+        bytes.push(0xF7);
+        assert_eq!(bytes.len(), 210);
+
+        let message = Message::from_bytes(&bytes).unwrap();
+        assert_eq!(identify(&message), Some((3, Kind::SingleProgram)));
+        assert_eq!(program_names(&message), vec!["BRASS"]);
+    }
+
+    #[test]
+    fn all_programs() {
+        let mut bytes = vec![0xF0, 0x0F, 0x02, 0x00, 0x02];
+        for i in 0..PROGRAM_COUNT {
+            bytes.extend(program_data(&format!("P{}", i)));
+        }
+        bytes.push(0xF7);
+        assert_eq!(bytes.len(), 8166);
+
+        let message = Message::from_bytes(&bytes).unwrap();
+        assert_eq!(identify(&message), Some((0, Kind::AllPrograms)));
+        assert_eq!(program_names(&message)[39], "P39");
+    }
+
+    #[test]
+    fn wrong_length() {
+        let bytes = vec![0xF0, 0x0F, 0x02, 0x00, 0x01, 0x00, 0x00, 0xF7];
+        let message = Message::from_bytes(&bytes).unwrap();
+        assert_eq!(identify(&message), None);
+    }
+}
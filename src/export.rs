@@ -0,0 +1,88 @@
+//! # export
+//!
+//! Exporting messages as array literals of C or Rust source code,
+//! for embedding canned messages in firmware.
+
+use std::fmt;
+use crate::Message;
+
+/// Language of the exported source code.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Language {
+    /// A `const unsigned char` array.
+    C,
+    /// A `const` array of `u8`.
+    Rust,
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Language::C => "C",
+            Language::Rust => "Rust",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Options for exporting a message as source code.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ExportOptions {
+    pub language: Language,
+    /// Name of the array. It is used as it is, so it must be a valid identifier.
+    pub name: String,
+    /// Number of bytes on each line.
+    pub bytes_per_line: usize,
+}
+
+impl ExportOptions {
+    /// Makes options with twelve bytes on each line.
+    pub fn new(language: Language, name: &str) -> Self {
+        ExportOptions { language, name: name.to_string(), bytes_per_line: 12 }
+    }
+}
+
+/// Formats the data as an array literal, like
+/// `const unsigned char GM_ON[6] = { ... };`.
+pub fn to_source(data: &[u8], options: &ExportOptions) -> String {
+    let (start, end) = match options.language {
+        Language::C => (format!("const unsigned char {}[{}] = {{", options.name, data.len()), "};"),
+        Language::Rust => (format!("pub const {}: [u8; {}] = [", options.name, data.len()), "];"),
+    };
+    let mut result = start;
+    result.push('\n');
+    for line in data.chunks(options.bytes_per_line.max(1)) {
+        let bytes: Vec<String> = line.iter().map(|b| format!("0x{:02X},", b)).collect();
+        result.push_str("    ");
+        result.push_str(&bytes.join(" "));
+        result.push('\n');
+    }
+    result.push_str(end);
+    result.push('\n');
+    result
+}
+
+impl Message {
+    /// Formats the bytes of the message as an array literal of source code.
+    pub fn to_source(&self, options: &ExportOptions) -> String {
+        to_source(&self.to_bytes(), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_and_rust() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        let mut options = ExportOptions::new(Language::C, "gm_on");
+        options.bytes_per_line = 4;
+        assert_eq!(message.to_source(&options),
+            "const unsigned char gm_on[6] = {\n    0xF0, 0x7E, 0x7F, 0x09,\n    0x01, 0xF7,\n};\n");
+
+        let options = ExportOptions::new(Language::Rust, "GM_ON");
+        assert_eq!(message.to_source(&options),
+            "pub const GM_ON: [u8; 6] = [\n    0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7,\n];\n");
+    }
+}
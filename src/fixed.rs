@@ -0,0 +1,119 @@
+//! # fixed
+//!
+//! A message type with a fixed capacity that keeps its bytes in an array
+//! instead of on the heap, for embedded targets and other places where
+//! allocation is not wanted. Parsing, serializing and getting the payload
+//! of a [`FixedMessage`] do not allocate; converting it to an owned
+//! `Message` does.
+
+use std::fmt;
+use crate::{check_framing, Message, Manufacturer, SystemExclusiveError, NON_REAL_TIME, REAL_TIME};
+
+/// A message of at most `N` bytes, including the initiator and terminator.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FixedMessage<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedMessage<N> {
+    /// Makes a message of the data, checked like `Message::from_bytes`.
+    /// Fails with `SystemExclusiveError::MessageTooLong` if the data
+    /// does not fit in `N` bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SystemExclusiveError> {
+        if data.len() > N {
+            return Err(SystemExclusiveError::MessageTooLong);
+        }
+        check_framing(data)?;
+        let mut bytes = [0u8; N];
+        bytes[..data.len()].copy_from_slice(data);
+        Ok(FixedMessage { bytes, len: data.len() })
+    }
+
+    /// Gets the bytes of the message.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Gets the length of the message in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets the maximum length of the message in bytes.
+    pub const fn capacity() -> usize {
+        N
+    }
+
+    /// Gets the manufacturer, or `None` for universal messages.
+    pub fn manufacturer(&self) -> Option<Manufacturer> {
+        match self.bytes[1] {
+            NON_REAL_TIME | REAL_TIME => None,
+            0x00 => Some(Manufacturer::Extended([0x00, self.bytes[2], self.bytes[3]])),
+            b => Some(Manufacturer::Standard(b)),
+        }
+    }
+
+    /// Gets the payload, after the manufacturer ID or the sub-IDs,
+    /// like `Message::payload`.
+    pub fn payload(&self) -> &[u8] {
+        let start = match self.bytes[1] {
+            NON_REAL_TIME | REAL_TIME => 5,
+            0x00 => 4,
+            _ => 2,
+        };
+        &self.bytes[start..self.len - 1]
+    }
+
+    /// Makes an owned message of this message.
+    pub fn to_message(&self) -> Message {
+        Message::from_framed(self.as_bytes())
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedMessage<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FixedMessage").field(&self.as_bytes()).finish()
+    }
+}
+
+impl<const N: usize> From<&FixedMessage<N>> for Message {
+    fn from(message: &FixedMessage<N>) -> Self {
+        message.to_message()
+    }
+}
+
+impl<const N: usize> TryFrom<&Message> for FixedMessage<N> {
+    type Error = SystemExclusiveError;
+
+    fn try_from(message: &Message) -> Result<Self, Self::Error> {
+        if message.byte_len() > N {
+            return Err(SystemExclusiveError::MessageTooLong);
+        }
+        FixedMessage::from_bytes(&message.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_convert() {
+        let bytes = [0xF0, 0x43, 0x00, 0x01, 0x1B, 0xF7];
+        let fixed = FixedMessage::<16>::from_bytes(&bytes).unwrap();
+        assert_eq!(fixed.as_bytes(), bytes);
+        assert_eq!(fixed.manufacturer(), Some(Manufacturer::Standard(0x43)));
+        assert_eq!(fixed.payload(), [0x00, 0x01, 0x1B]);
+
+        let message = fixed.to_message();
+        assert_eq!(message, Message::from_bytes(&bytes).unwrap());
+        assert_eq!(FixedMessage::<16>::try_from(&message).unwrap(), fixed);
+        assert!(matches!(FixedMessage::<4>::try_from(&message), Err(SystemExclusiveError::MessageTooLong)));
+
+        let universal = FixedMessage::<8>::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert_eq!(universal.manufacturer(), None);
+        assert!(universal.payload().is_empty());
+        assert!(FixedMessage::<8>::from_bytes(&[0xF0, 0x43]).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! # fuzzing
+//!
+//! `Arbitrary` implementations for generating structured random messages
+//! in fuzz targets and property tests. The generated messages are always
+//! valid: the IDs and payload bytes are 7-bit, so `to_bytes` followed by
+//! `from_bytes` gives the same message back. Only available with the
+//! `arbitrary` feature.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use crate::{Message, Manufacturer, UniversalKind, DEVELOPMENT};
+
+fn u7(u: &mut Unstructured<'_>) -> Result<u8> {
+    Ok(u8::arbitrary(u)? & 0x7f)
+}
+
+/// Generates payload bytes, masked to 7 bits.
+fn payload(u: &mut Unstructured<'_>) -> Result<Vec<u8>> {
+    u.arbitrary_iter::<u8>()?.map(|b| b.map(|b| b & 0x7f)).collect()
+}
+
+impl<'a> Arbitrary<'a> for UniversalKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if bool::arbitrary(u)? { UniversalKind::RealTime } else { UniversalKind::NonRealTime })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        bool::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Manufacturer {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if bool::arbitrary(u)? {
+            Ok(Manufacturer::Extended([0x00, u7(u)?, u7(u)?]))
+        }
+        else {
+            Ok(Manufacturer::Standard(u.int_in_range(0x01..=DEVELOPMENT)?))
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if bool::arbitrary(u)? {
+            Ok(Message::Universal {
+                kind: UniversalKind::arbitrary(u)?,
+                target: u7(u)?,
+                sub_id1: u7(u)?,
+                sub_id2: u7(u)?,
+                payload: payload(u)?,
+            })
+        }
+        else {
+            Ok(Message::ManufacturerSpecific { manufacturer: Manufacturer::arbitrary(u)?, payload: payload(u)? })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_messages_round_trip() {
+        let noise: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let mut u = Unstructured::new(&noise);
+        let mut count = 0;
+        while !u.is_empty() {
+            let message = Message::arbitrary(&mut u).unwrap();
+            assert_eq!(Message::from_bytes(&message.to_bytes()).unwrap(), message);
+            count += 1;
+        }
+        assert!(count > 1);
+    }
+}
@@ -0,0 +1,233 @@
+//! # guess
+//!
+//! Guessing the device a System Exclusive message is for, to answer
+//! "what synth is this dump for?" about anonymous `.syx` files.
+//!
+//! The identifiers of the device modules are tried first. Messages they
+//! do not recognize are compared against a table of header and length
+//! signatures, and as a last resort the manufacturer alone is reported.
+//! Each guess has a confidence from 0 to 100.
+
+use std::cmp::Reverse;
+use std::fmt;
+use crate::{Message, Manufacturer};
+use crate::{alesis, behringer, casio, dx7, ensoniq, kawai, korg, novation, proteus, roland, sequential};
+
+/// Confidence of a message recognized by a device module, with its
+/// checksum or length verified.
+const VERIFIED: u8 = 95;
+
+/// Confidence of a message recognized from its header bytes.
+const HEADER: u8 = 75;
+
+/// Confidence of a message matching a signature of the table.
+const SIGNATURE: u8 = 60;
+
+/// Confidence of a guess from the manufacturer ID only.
+const MANUFACTURER: u8 = 10;
+
+/// A guess of the device a message is for.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DeviceGuess {
+    /// The device model, or the manufacturer if the model is not known.
+    pub device: String,
+    /// Confidence from 0 to 100.
+    pub confidence: u8,
+    /// What the guess is based on.
+    pub reason: String,
+}
+
+impl DeviceGuess {
+    fn new(device: &str, confidence: u8, reason: &str) -> Self {
+        DeviceGuess { device: device.to_string(), confidence, reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for DeviceGuess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}%): {}", self.device, self.confidence, self.reason)
+    }
+}
+
+/// Header and length signature of a dump format.
+struct Signature {
+    manufacturer: Manufacturer,
+    /// Payload bytes after the manufacturer ID, with `None` for any value.
+    header: &'static [Option<u8>],
+    /// Length of the complete message, or `None` for any length.
+    length: Option<usize>,
+    device: &'static str,
+    reason: &'static str,
+}
+
+impl Signature {
+    fn matches(&self, message: &Message) -> bool {
+        let Message::ManufacturerSpecific { manufacturer, payload } = message else {
+            return false;
+        };
+        *manufacturer == self.manufacturer &&
+            payload.len() >= self.header.len() &&
+            self.header.iter().zip(payload).all(|(expected, b)| expected.is_none_or(|e| e == *b)) &&
+            self.length.is_none_or(|length| length == message.byte_len())
+    }
+}
+
+/// Signatures of formats without a device module. The channel nybbles
+/// are left out of the headers.
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        manufacturer: Manufacturer::Standard(dx7::YAMAHA),
+        header: &[None, Some(0x03), Some(0x00), Some(0x5d)],
+        length: Some(101),
+        device: "Yamaha TX81Z/DX21",
+        reason: "VCED voice dump length",
+    },
+    Signature {
+        manufacturer: Manufacturer::Standard(dx7::YAMAHA),
+        header: &[None, Some(0x04), Some(0x20), Some(0x00)],
+        length: Some(4104),
+        device: "Yamaha TX81Z/DX21",
+        reason: "VMEM bank dump length",
+    },
+    Signature {
+        manufacturer: Manufacturer::Standard(roland::ROLAND),
+        header: &[None, Some(0x14)],
+        length: None,
+        device: "Roland D-50",
+        reason: "Model ID in the header",
+    },
+    Signature {
+        manufacturer: Manufacturer::Standard(roland::ROLAND),
+        header: &[None, Some(0x16)],
+        length: None,
+        device: "Roland MT-32/D-110",
+        reason: "Model ID in the header",
+    },
+    Signature {
+        manufacturer: Manufacturer::Standard(roland::ROLAND),
+        header: &[None, Some(0x42)],
+        length: None,
+        device: "Roland GS sound module",
+        reason: "Model ID in the header",
+    },
+    Signature {
+        manufacturer: Manufacturer::Standard(roland::ROLAND),
+        header: &[None, Some(roland::JV1080)],
+        length: None,
+        device: "Roland JV-1080",
+        reason: "Model ID in the header",
+    },
+];
+
+/// Guesses the device a message is for. The guesses are sorted by
+/// confidence, the most likely first. Universal messages are not
+/// for any particular device, and get no guesses.
+pub fn guess_device(message: &Message) -> Vec<DeviceGuess> {
+    let manufacturer = match message {
+        Message::ManufacturerSpecific { manufacturer, .. } => *manufacturer,
+        _ => return Vec::new(),
+    };
+
+    let mut guesses = identified(message);
+    guesses.extend(SIGNATURES.iter()
+        .filter(|signature| signature.matches(message))
+        .map(|signature| DeviceGuess::new(signature.device, SIGNATURE, signature.reason)));
+    if guesses.is_empty() {
+        guesses.push(DeviceGuess::new(&manufacturer.name(), MANUFACTURER, "Manufacturer ID only"));
+    }
+    guesses.sort_by_key(|guess| Reverse(guess.confidence));
+    guesses
+}
+
+/// Gets the guesses of the device modules that recognize the message.
+fn identified(message: &Message) -> Vec<DeviceGuess> {
+    let mut guesses = Vec::new();
+    if let Ok(dump) = dx7::Dump::from_message(message) {
+        let reason = match dump {
+            dx7::Dump::Voice { .. } => "Voice dump with a valid checksum",
+            dx7::Dump::Bank { .. } => "Bank dump with a valid checksum",
+        };
+        guesses.push(DeviceGuess::new("Yamaha DX7", VERIFIED, reason));
+    }
+    if let Some(header) = kawai::identify(message) {
+        let device = format!("Kawai {}", header.machine);
+        guesses.push(DeviceGuess::new(&device, HEADER, &header.function.to_string()));
+    }
+    if let Some(dump) = korg::identify(message) {
+        let device = format!("Korg {}", dump.model);
+        guesses.push(DeviceGuess::new(&device, HEADER, &dump.kind.to_string()));
+    }
+    if casio::identify(message).is_some() {
+        let (confidence, reason) = match casio::tone_data(message) {
+            Some(_) => (VERIFIED, "Tone data of the expected length"),
+            None => (HEADER, "CZ handshake header"),
+        };
+        guesses.push(DeviceGuess::new("Casio CZ series", confidence, reason));
+    }
+    if let Some((_, kind)) = ensoniq::identify(message) {
+        guesses.push(DeviceGuess::new("Ensoniq ESQ-1/SQ-80", VERIFIED, &kind.to_string()));
+    }
+    if let Ok(dump) = alesis::QsDump::from_message(message) {
+        let confidence = if dump.is_program() { VERIFIED } else { HEADER };
+        guesses.push(DeviceGuess::new("Alesis QS series", confidence, "QS dump header"));
+    }
+    if let Ok(dump) = sequential::Dump::from_message(message) {
+        let device = format!("Sequential {}", dump.product());
+        guesses.push(DeviceGuess::new(&device, HEADER, "Product ID in the header"));
+    }
+    match proteus::ProteusMessage::from_message(message) {
+        Ok(proteus::ProteusMessage::PresetData { .. }) =>
+            guesses.push(DeviceGuess::new("E-mu Proteus", VERIFIED, "Preset data with a valid checksum")),
+        Ok(_) => guesses.push(DeviceGuess::new("E-mu Proteus", HEADER, "Proteus product ID")),
+        Err(_) => {},
+    }
+    match behringer::identify(message) {
+        Some(behringer::Kind::X32) => guesses.push(DeviceGuess::new("Behringer X32", HEADER, "X32 OSC command")),
+        Some(behringer::Kind::Unknown) | None => {},
+        Some(kind) => guesses.push(DeviceGuess::new("Behringer Deepmind 12", HEADER, &kind.to_string())),
+    }
+    match novation::identify(message) {
+        Some(novation::Kind::Unknown) | None => {},
+        Some(kind) => guesses.push(DeviceGuess::new("Novation", HEADER, &kind.to_string())),
+    }
+    guesses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guess_identified_devices() {
+        let mut bytes = vec![0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B];
+        bytes.extend([0x00; dx7::VOICE_SIZE]);
+        bytes.extend([0x00, 0xF7]);
+        let guesses = guess_device(&Message::from_bytes(&bytes).unwrap());
+        assert_eq!(guesses, vec![DeviceGuess::new("Yamaha DX7", VERIFIED, "Voice dump with a valid checksum")]);
+
+        let k4 = Message::from_bytes(&[0xF0, 0x40, 0x01, 0x20, 0x00, 0x04, 0x00, 0x00, 0x01, 0xF7]).unwrap();
+        assert_eq!(guess_device(&k4)[0].device, "Kawai K4");
+
+        let gs_reset = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]).unwrap();
+        assert_eq!(guess_device(&gs_reset)[0].to_string(), "Roland GS sound module (60%): Model ID in the header");
+    }
+
+    #[test]
+    fn guess_from_signatures_and_manufacturer() {
+        let mut bytes = vec![0xF0, 0x43, 0x02, 0x03, 0x00, 0x5D];
+        bytes.extend([0x00; 93]);
+        bytes.extend([0x00, 0xF7]);
+        let guesses = guess_device(&Message::from_bytes(&bytes).unwrap());
+        assert_eq!(guesses.len(), 1);
+        assert_eq!((guesses[0].device.as_str(), guesses[0].confidence), ("Yamaha TX81Z/DX21", SIGNATURE));
+
+        let yamaha = Message::from_bytes(&[0xF0, 0x43, 0x10, 0x4C, 0x00, 0xF7]).unwrap();
+        let guesses = guess_device(&yamaha);
+        assert_eq!(guesses.len(), 1);
+        assert_eq!(guesses[0].confidence, MANUFACTURER);
+        assert_eq!(guesses[0].device, Manufacturer::Standard(dx7::YAMAHA).name());
+
+        let identity_request = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert!(guess_device(&identity_request).is_empty());
+    }
+}
@@ -0,0 +1,443 @@
+//! # index
+//!
+//! Digest index files for collections of dumps, for finding duplicates and
+//! checking whether a dump is already archived without scanning the whole
+//! collection again.
+//!
+//! An index is a text file with one line per message. The fields are
+//! separated by tabs: the MD5 digest of the message in hex, the offset of
+//! the message in its file, the manufacturer ID in hex (`-` for universal
+//! messages), the size of the message in bytes and the path of the file
+//! relative to the root of the collection (tabs shown as spaces here):
+//!
+//! ```text
+//! # syxpack index
+//! 5d41402abc4b2a76b9719d911017c592  0  43  4104  yamaha/dx7/rom1a.syx
+//! ```
+//!
+//! The offsets are those of the messages in a `.syx` file. For messages
+//! from a Standard MIDI File they are offsets in the messages as they would
+//! be saved to a `.syx` file.
+//!
+//! An index built from a directory also has a `file` line for each file,
+//! with its size, its modification time in nanoseconds since the Unix epoch
+//! and its path, so that [`Index::update`] only reads the files that have
+//! changed since they were indexed:
+//!
+//! ```text
+//! file  4104  1700000000000000000  yamaha/dx7/rom1a.syx
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::{Message, Manufacturer};
+use crate::scan::{find_files, read_messages, FileError, FileMessages, ScanError};
+
+/// Error type for reading index files.
+#[derive(Debug)]
+pub enum IndexError {
+    /// The index could not be read.
+    Io(io::Error),
+    /// A line of the index is invalid. The line numbers start from one.
+    Parse { line: usize },
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexError::Io(e) => write!(f, "{}", e),
+            IndexError::Parse { line } => write!(f, "Invalid index entry on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IndexError {
+    fn from(e: io::Error) -> Self {
+        IndexError::Io(e)
+    }
+}
+
+/// One message in an index.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct IndexEntry {
+    pub digest: md5::Digest,
+    /// Path of the file, relative to the root of the collection.
+    pub path: PathBuf,
+    /// Offset of the message in the file.
+    pub offset: u64,
+    /// The manufacturer, or `None` for universal messages.
+    pub manufacturer: Option<Manufacturer>,
+    /// Size of the message in bytes.
+    pub size: usize,
+}
+
+/// The size and modification time of an indexed file, for finding
+/// the files that have changed since they were indexed.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct FileStamp {
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u128,
+}
+
+impl FileStamp {
+    /// Gets the stamp of a file as it is now.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+        Ok(FileStamp { size: metadata.len(), modified })
+    }
+}
+
+/// The files that `Index::update` found to be new, changed or removed,
+/// relative to the root of the collection.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct IndexUpdate {
+    pub added: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// Number of files that were not read again.
+    pub unchanged: usize,
+}
+
+impl IndexUpdate {
+    /// Returns `true` if any file was added, changed or removed.
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.changed.is_empty() || !self.removed.is_empty()
+    }
+}
+
+/// The files found by `find_changes`.
+pub(crate) struct Changes {
+    pub update: IndexUpdate,
+    /// Files whose entries are out of date, relative to the root.
+    pub stale: HashSet<PathBuf>,
+    /// Files read again, relative to the root, with their stamps.
+    pub loaded: Vec<(PathBuf, FileStamp, Vec<Message>)>,
+    pub errors: Vec<FileError>,
+}
+
+/// Compares the files under `root` matching the glob pattern to their
+/// known stamps, and reads the files that are new or have changed.
+pub(crate) fn find_changes(root: &Path, pattern: &str, known: &BTreeMap<PathBuf, FileStamp>) -> io::Result<Changes> {
+    let (paths, mut errors) = find_files(root, pattern)?;
+    let mut update = IndexUpdate::default();
+    let mut found = HashSet::new();
+    let mut stale = HashSet::new();
+    let mut loaded = Vec::new();
+    for path in paths {
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        found.insert(relative.clone());
+        // The stamp is taken before reading, so that a change while
+        // reading is found by the next update.
+        let stamp = FileStamp::of(&path);
+        let known = known.get(&relative).copied();
+        if let (Ok(stamp), Some(known)) = (&stamp, known) {
+            if *stamp == known {
+                update.unchanged += 1;
+                continue;
+            }
+        }
+        stale.insert(relative.clone());
+        match stamp.map_err(ScanError::Io).and_then(|stamp| Ok((stamp, read_messages(&path)?))) {
+            Ok((stamp, messages)) => {
+                if known.is_some() { update.changed.push(relative.clone()) } else { update.added.push(relative.clone()) }
+                loaded.push((relative, stamp, messages));
+            },
+            Err(e) => errors.push((path, e)),
+        }
+    }
+    update.removed = known.keys().filter(|path| !found.contains(*path)).cloned().collect();
+    stale.extend(update.removed.iter().cloned());
+    Ok(Changes { update, stale, loaded, errors })
+}
+
+/// An index of the messages in a collection, looked up by digest.
+#[derive(Clone, Default, Debug)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+    by_digest: HashMap<md5::Digest, Vec<usize>>,
+    stamps: BTreeMap<PathBuf, FileStamp>,
+}
+
+impl Index {
+    /// Makes an empty index.
+    pub fn new() -> Self {
+        Index::default()
+    }
+
+    /// Indexes all the files under `root` matching the glob pattern,
+    /// like `scan::load_glob`. Files that cannot be read or parsed
+    /// are returned as errors.
+    pub fn build(root: &Path, pattern: &str) -> io::Result<(Self, Vec<FileError>)> {
+        let mut index = Index::new();
+        let (_, errors) = index.update(root, pattern)?;
+        Ok((index, errors))
+    }
+
+    /// Brings the index up to date with the files under `root` matching
+    /// the glob pattern. Only new files and files whose size or modification
+    /// time has changed are read; the entries of removed files are dropped.
+    /// Files that cannot be read or parsed are returned as errors, and
+    /// their entries are dropped too.
+    pub fn update(&mut self, root: &Path, pattern: &str) -> io::Result<(IndexUpdate, Vec<FileError>)> {
+        let changes = find_changes(root, pattern, &self.stamps)?;
+        self.remove_files(&changes.stale);
+        for (path, stamp, messages) in changes.loaded {
+            self.add_file(&path, &messages);
+            self.stamps.insert(path, stamp);
+        }
+        Ok((changes.update, changes.errors))
+    }
+
+    /// Removes the entries and stamps of the files.
+    fn remove_files(&mut self, paths: &HashSet<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        self.stamps.retain(|path, _| !paths.contains(path));
+        let entries = std::mem::take(&mut self.entries);
+        self.by_digest.clear();
+        for entry in entries.into_iter().filter(|entry| !paths.contains(&entry.path)) {
+            self.push(entry);
+        }
+    }
+
+    /// Gets the stamps of the files indexed by `build` or `update`.
+    pub fn stamps(&self) -> &BTreeMap<PathBuf, FileStamp> {
+        &self.stamps
+    }
+
+    /// Records the stamp of an indexed file.
+    #[cfg(feature = "catalog")]
+    pub(crate) fn insert_stamp(&mut self, path: PathBuf, stamp: FileStamp) {
+        self.stamps.insert(path, stamp);
+    }
+
+    /// Indexes the messages of files that are already loaded.
+    pub fn from_files(files: &[FileMessages]) -> Self {
+        let mut index = Index::new();
+        for (path, messages) in files {
+            index.add_file(path, messages);
+        }
+        index
+    }
+
+    /// Adds the messages of one file to the index.
+    pub fn add_file(&mut self, path: &Path, messages: &[Message]) {
+        let mut offset = 0;
+        for message in messages {
+            let manufacturer = match message {
+                Message::ManufacturerSpecific { manufacturer, .. } => Some(*manufacturer),
+                Message::Universal { .. } => None,
+            };
+            let size = message.byte_len();
+            self.push(IndexEntry { digest: message.digest(), path: path.to_path_buf(), offset, manufacturer, size });
+            offset += size as u64;
+        }
+    }
+
+    /// Adds an entry to the index.
+    pub fn push(&mut self, entry: IndexEntry) {
+        self.by_digest.entry(entry.digest).or_default().push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// Gets all the entries, in the order they were added.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Gets the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the entries of messages with the digest.
+    pub fn lookup(&self, digest: &md5::Digest) -> Vec<&IndexEntry> {
+        self.by_digest.get(digest)
+            .map(|indices| indices.iter().map(|i| &self.entries[*i]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the message is already in the index.
+    pub fn contains(&self, message: &Message) -> bool {
+        self.by_digest.contains_key(&message.digest())
+    }
+
+    /// Gets the groups of entries with the same digest, for messages
+    /// that occur more than once. The groups are in the order of their
+    /// first entry.
+    pub fn duplicates(&self) -> Vec<Vec<&IndexEntry>> {
+        let mut groups: Vec<&Vec<usize>> = self.by_digest.values().filter(|indices| indices.len() > 1).collect();
+        groups.sort_by_key(|indices| indices[0]);
+        groups.into_iter()
+            .map(|indices| indices.iter().map(|i| &self.entries[*i]).collect())
+            .collect()
+    }
+
+    /// Parses an index from the text of an index file.
+    pub fn parse(text: &str) -> Result<Self, IndexError> {
+        let mut index = Index::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = IndexError::Parse { line: number + 1 };
+            if let Some(stamp) = line.strip_prefix("file\t") {
+                let (path, stamp) = parse_stamp(stamp).ok_or(invalid)?;
+                index.stamps.insert(path, stamp);
+                continue;
+            }
+            let entry = parse_entry(line).ok_or(invalid)?;
+            index.push(entry);
+        }
+        Ok(index)
+    }
+
+    /// Loads an index file.
+    pub fn load(path: &Path) -> Result<Self, IndexError> {
+        Index::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the index to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl PartialEq for Index {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries && self.stamps == other.stamps
+    }
+}
+
+impl Eq for Index {}
+
+fn parse_entry(line: &str) -> Option<IndexEntry> {
+    let mut fields = line.splitn(5, '\t');
+    let digest: [u8; 16] = hex::decode(fields.next()?).ok()?.try_into().ok()?;
+    let offset = fields.next()?.parse().ok()?;
+    let manufacturer = match fields.next()? {
+        "-" => None,
+        id => Some(Manufacturer::from_hex(id).ok()?),
+    };
+    let size = fields.next()?.parse().ok()?;
+    let path = PathBuf::from(fields.next()?);
+    Some(IndexEntry { digest: md5::Digest(digest), path, offset, manufacturer, size })
+}
+
+fn parse_stamp(fields: &str) -> Option<(PathBuf, FileStamp)> {
+    let mut fields = fields.splitn(3, '\t');
+    let size = fields.next()?.parse().ok()?;
+    let modified = fields.next()?.parse().ok()?;
+    Some((PathBuf::from(fields.next()?), FileStamp { size, modified }))
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# syxpack index")?;
+        for (path, stamp) in &self.stamps {
+            writeln!(f, "file\t{}\t{}\t{}", stamp.size, stamp.modified, path.display())?;
+        }
+        for entry in &self.entries {
+            let manufacturer = entry.manufacturer.map(|m| m.to_hex()).unwrap_or_else(|| "-".to_string());
+            writeln!(f, "{:x}\t{}\t{}\t{}\t{}", entry.digest, entry.offset, manufacturer, entry.size, entry.path.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_and_lookup() {
+        let dir = std::env::temp_dir().join(format!("syxpack-index-{}", std::process::id()));
+        fs::create_dir_all(dir.join("yamaha")).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7, 0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("yamaha/b.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+
+        let (index, errors) = Index::build(&dir, "**/*.syx").unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.entries()[1].offset, 5);
+        assert_eq!(index.entries()[1].manufacturer, None);
+
+        let message = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        assert!(index.contains(&message));
+        let found: Vec<&Path> = index.lookup(&message.digest()).iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(found, vec![Path::new("a.syx"), Path::new("yamaha/b.syx")]);
+        assert_eq!(index.duplicates().len(), 1);
+
+        let path = dir.join("index.txt");
+        index.save(&path).unwrap();
+        assert_eq!(Index::load(&path).unwrap(), index);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn incremental_update() {
+        let dir = std::env::temp_dir().join(format!("syxpack-index-update-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("b.syx"), [0xF0, 0x41, 0x10, 0xF7]).unwrap();
+        fs::write(dir.join("c.syx"), [0xF0, 0x7D, 0x01, 0xF7]).unwrap();
+        let (mut index, _) = Index::build(&dir, "*.syx").unwrap();
+        assert_eq!(index.stamps().len(), 3);
+
+        let path = dir.join("index.txt");
+        index.save(&path).unwrap();
+        let mut loaded = Index::load(&path).unwrap();
+        assert_eq!(loaded, index);
+        let (update, errors) = loaded.update(&dir, "*.syx").unwrap();
+        assert!(errors.is_empty());
+        assert!(!update.has_changes());
+        assert_eq!(update.unchanged, 3);
+
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0x02, 0xF7]).unwrap();
+        fs::remove_file(dir.join("b.syx")).unwrap();
+        fs::write(dir.join("d.syx"), [0xF0, 0x7D, 0x01, 0xF7]).unwrap();
+        let (update, _) = index.update(&dir, "*.syx").unwrap();
+        assert_eq!(update, IndexUpdate {
+            added: vec![PathBuf::from("d.syx")],
+            changed: vec![PathBuf::from("a.syx")],
+            removed: vec![PathBuf::from("b.syx")],
+            unchanged: 1,
+        });
+        assert_eq!(index.len(), 3);
+        assert!(index.contains(&Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, 0x02, 0xF7]).unwrap()));
+        assert!(!index.contains(&Message::from_bytes(&[0xF0, 0x41, 0x10, 0xF7]).unwrap()));
+        assert_eq!(index.duplicates().len(), 1);
+        let (rebuilt, _) = Index::build(&dir, "*.syx").unwrap();
+        assert_eq!(index.stamps(), rebuilt.stamps());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_entry() {
+        assert!(matches!(Index::parse("# syxpack index\n00\t0\t43\t5\ta.syx\n"), Err(IndexError::Parse { line: 2 })));
+        assert!(matches!(Index::parse("file\t5\ta.syx\n"), Err(IndexError::Parse { line: 1 })));
+    }
+}
@@ -0,0 +1,161 @@
+//! # kawai
+//!
+//! Classification of Kawai K1, K4 and K5/K5m System Exclusive messages
+//! by their machine ID and function code, so that mixed Kawai archives
+//! can be sorted by model.
+//!
+//! The messages have the header `F0 40 0n ff 00 mm`, where `n` is the
+//! MIDI channel, `ff` the function code, `00` the synthesizer group
+//! and `mm` the machine ID.
+
+use std::fmt;
+use crate::{Message, Manufacturer};
+
+/// Kawai manufacturer ID.
+pub const KAWAI: u8 = 0x40;
+
+/// Group byte of the synthesizers.
+const SYNTHESIZER_GROUP: u8 = 0x00;
+
+/// A Kawai synthesizer model.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Machine {
+    K5,
+    K1,
+    K4,
+}
+
+impl Machine {
+    /// Gets the model from its machine ID.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x02 => Some(Machine::K5),
+            0x03 => Some(Machine::K1),
+            0x04 => Some(Machine::K4),
+            _ => None,
+        }
+    }
+
+    /// Gets the machine ID of the model.
+    pub fn id(&self) -> u8 {
+        match self {
+            Machine::K5 => 0x02,
+            Machine::K1 => 0x03,
+            Machine::K4 => 0x04,
+        }
+    }
+}
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Machine::K5 => "K5/K5m",
+            Machine::K1 => "K1",
+            Machine::K4 => "K4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Function code of a Kawai message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Function {
+    OneDataRequest,
+    BlockDataRequest,
+    AllDataRequest,
+    ParameterSend,
+    OneDataDump,
+    BlockDataDump,
+    AllDataDump,
+    WriteComplete,
+    WriteError,
+    WriteErrorProtect,
+    WriteErrorNoCard,
+}
+
+impl Function {
+    /// Gets the function from its code.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x00 => Some(Function::OneDataRequest),
+            0x01 => Some(Function::BlockDataRequest),
+            0x04 => Some(Function::AllDataRequest),
+            0x10 => Some(Function::ParameterSend),
+            0x20 => Some(Function::OneDataDump),
+            0x21 => Some(Function::BlockDataDump),
+            0x22 => Some(Function::AllDataDump),
+            0x40 => Some(Function::WriteComplete),
+            0x41 => Some(Function::WriteError),
+            0x42 => Some(Function::WriteErrorProtect),
+            0x43 => Some(Function::WriteErrorNoCard),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the message carries patch data.
+    pub fn is_dump(&self) -> bool {
+        matches!(self, Function::OneDataDump | Function::BlockDataDump | Function::AllDataDump)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Function::OneDataRequest => "One data dump request",
+            Function::BlockDataRequest => "Block data dump request",
+            Function::AllDataRequest => "All data dump request",
+            Function::ParameterSend => "Parameter send",
+            Function::OneDataDump => "One data dump",
+            Function::BlockDataDump => "Block data dump",
+            Function::AllDataDump => "All data dump",
+            Function::WriteComplete => "Write complete",
+            Function::WriteError => "Write error",
+            Function::WriteErrorProtect => "Write error (protected)",
+            Function::WriteErrorNoCard => "Write error (no card)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The header of a Kawai synthesizer message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Header {
+    pub channel: u8,
+    pub function: Function,
+    pub machine: Machine,
+}
+
+/// Identifies the model and function of a Kawai K1, K4 or K5 message.
+pub fn identify(message: &Message) -> Option<Header> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(KAWAI), payload } => payload,
+        _ => return None,
+    };
+    match payload.get(..4)? {
+        [channel, function, SYNTHESIZER_GROUP, machine] if channel & 0xf0 == 0 => Some(Header {
+            channel: *channel,
+            function: Function::from_code(*function)?,
+            machine: Machine::from_id(*machine)?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identify_models() {
+        let k1 = Message::from_bytes(&[0xF0, 0x40, 0x00, 0x21, 0x00, 0x03, 0x00, 0x00, 0x01, 0xF7]).unwrap();
+        let k4 = Message::from_bytes(&[0xF0, 0x40, 0x01, 0x20, 0x00, 0x04, 0x00, 0x00, 0x01, 0xF7]).unwrap();
+        let k5 = Message::from_bytes(&[0xF0, 0x40, 0x02, 0x20, 0x00, 0x02, 0x00, 0x00, 0x01, 0xF7]).unwrap();
+        assert_eq!(identify(&k1), Some(Header { channel: 0, function: Function::BlockDataDump, machine: Machine::K1 }));
+        assert_eq!(identify(&k4).map(|h| h.machine), Some(Machine::K4));
+        assert_eq!(identify(&k5).map(|h| (h.channel, h.machine.to_string())), Some((2, "K5/K5m".to_string())));
+        assert!(identify(&k5).unwrap().function.is_dump());
+
+        let unknown = Message::from_bytes(&[0xF0, 0x40, 0x00, 0x20, 0x00, 0x0A, 0xF7]).unwrap();
+        assert_eq!(identify(&unknown), None);
+    }
+}
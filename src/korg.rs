@@ -0,0 +1,157 @@
+//! # korg
+//!
+//! Classification of Korg Trinity and Triton program and combination dumps,
+//! with their bank selection bytes.
+//!
+//! The dump data is in the Korg 7-to-8 packing. This module only locates
+//! the packed data with `packed_data`; unpacking it is left to a separate
+//! Korg codec crate, as the codec was removed from this crate in 0.17.
+
+use std::fmt;
+use crate::{Message, Manufacturer};
+
+/// Korg manufacturer ID.
+pub const KORG: u8 = 0x42;
+
+/// Model ID of the Trinity.
+pub const TRINITY: u8 = 0x3f;
+
+/// Model ID of the Triton.
+pub const TRITON: u8 = 0x50;
+
+const CURRENT_PROGRAM_DUMP: u8 = 0x40;
+const PROGRAM_DUMP: u8 = 0x4c;
+const CURRENT_COMBINATION_DUMP: u8 = 0x49;
+const COMBINATION_DUMP: u8 = 0x4d;
+
+/// A Korg workstation model.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Model {
+    Trinity,
+    Triton,
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Model::Trinity => "Trinity",
+            Model::Triton => "Triton",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Kind of a program or combination dump.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Kind {
+    /// The program in the edit buffer.
+    CurrentProgram,
+    /// Programs from a bank.
+    Programs,
+    /// The combination in the edit buffer.
+    CurrentCombination,
+    /// Combinations from a bank.
+    Combinations,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::CurrentProgram => "Current program dump",
+            Kind::Programs => "Program dump",
+            Kind::CurrentCombination => "Current combination dump",
+            Kind::Combinations => "Combination dump",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The header of a Trinity or Triton dump.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Dump {
+    pub model: Model,
+    pub channel: u8,
+    pub kind: Kind,
+    /// The bank, for dumps from a bank (0 is bank A).
+    pub bank: Option<u8>,
+    /// The number of the first program or combination, for dumps from a bank.
+    pub number: Option<u8>,
+}
+
+impl Dump {
+    /// Gets the offset of the packed data in the payload.
+    pub fn data_offset(&self) -> usize {
+        // Channel, model, function, and for banks the bank and number
+        match self.kind {
+            Kind::CurrentProgram | Kind::CurrentCombination => 3,
+            Kind::Programs | Kind::Combinations => 5,
+        }
+    }
+}
+
+/// Gets the letter of a bank, like 'A' for 0.
+pub fn bank_letter(bank: u8) -> Option<char> {
+    if bank < 26 {
+        Some((b'A' + bank) as char)
+    }
+    else {
+        None
+    }
+}
+
+/// Identifies a Trinity or Triton program or combination dump from its header.
+pub fn identify(message: &Message) -> Option<Dump> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(KORG), payload } => payload,
+        _ => return None,
+    };
+
+    let (channel, model, function) = match payload.get(..3)? {
+        [format, model, function] if format & 0xf0 == 0x30 => (format & 0x0f, *model, *function),
+        _ => return None,
+    };
+    let model = match model {
+        TRINITY => Model::Trinity,
+        TRITON => Model::Triton,
+        _ => return None,
+    };
+    let kind = match function {
+        CURRENT_PROGRAM_DUMP => Kind::CurrentProgram,
+        PROGRAM_DUMP => Kind::Programs,
+        CURRENT_COMBINATION_DUMP => Kind::CurrentCombination,
+        COMBINATION_DUMP => Kind::Combinations,
+        _ => return None,
+    };
+    let (bank, number) = match kind {
+        Kind::Programs | Kind::Combinations => (Some(*payload.get(3)?), Some(*payload.get(4)?)),
+        _ => (None, None),
+    };
+    Some(Dump { model, channel, kind, bank, number })
+}
+
+/// Gets the packed data of a Trinity or Triton dump.
+pub fn packed_data(message: &Message) -> Option<&[u8]> {
+    let dump = identify(message)?;
+    message.payload().get(dump.data_offset()..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identify_dumps() {
+        let message = Message::from_bytes(&[0xF0, 0x42, 0x32, 0x50, 0x4C, 0x01, 0x10, 0x01, 0x02, 0xF7]).unwrap();
+        let dump = identify(&message).unwrap();
+        assert_eq!(dump, Dump { model: Model::Triton, channel: 2, kind: Kind::Programs, bank: Some(1), number: Some(0x10) });
+        assert_eq!(dump.bank.and_then(bank_letter), Some('B'));
+        assert_eq!(packed_data(&message), Some(&[0x01, 0x02][..]));
+
+        let message = Message::from_bytes(&[0xF0, 0x42, 0x30, 0x3F, 0x49, 0x05, 0xF7]).unwrap();
+        assert_eq!(identify(&message).map(|d| (d.model, d.kind)), Some((Model::Trinity, Kind::CurrentCombination)));
+        assert_eq!(packed_data(&message), Some(&[0x05][..]));
+
+        let message = Message::from_bytes(&[0xF0, 0x42, 0x30, 0x50, 0x12, 0xF7]).unwrap();
+        assert_eq!(identify(&message), None);
+    }
+}
@@ -1,10 +1,76 @@
 //! # syxpack
 //!
 //! `syxpack` is a collection of helpers for processing MIDI System Exclusive messages.
+//!
+//! ## Panics
+//!
+//! The functions that parse, split or decode messages do not panic or read
+//! out of bounds on any input. Malformed data gives an error or `None`.
+//! This is checked by property tests over arbitrary input and by running
+//! the parsers over the seed corpus of the fuzz targets, also under Miri.
+//! Functions that panic on invalid arguments, as opposed to invalid input
+//! data, say so.
 
 use std::fmt;
-use std::collections::HashMap;
-use lazy_static::lazy_static;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub mod alesis;
+pub mod analyze;
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod bank;
+pub mod base64;
+pub mod behringer;
+pub mod capture;
+pub mod casio;
+#[cfg(feature = "catalog")]
+pub mod catalog;
+pub mod checksum;
+pub mod compare;
+pub mod device_id;
+pub mod diff;
+pub mod dx7;
+pub mod ensoniq;
+pub mod export;
+pub mod fixed;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+pub mod guess;
+pub mod index;
+pub mod kawai;
+pub mod korg;
+pub mod lint;
+#[cfg(feature = "metadata")]
+pub mod metadata;
+pub mod novation;
+pub mod patch;
+pub mod proteus;
+pub mod query;
+pub mod repair;
+pub mod roland;
+pub mod rtpmidi;
+pub mod scan;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod sequential;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod simulator;
+pub mod smf;
+pub mod stats;
+pub mod stream;
+pub mod template;
+pub mod timing;
+pub mod transfer;
+pub mod universal;
+pub mod usbmidi;
+pub mod watch;
 
 /// Manufacturer specific SysEx message initiator.
 pub const INITIATOR: u8 = 0xf0;
@@ -12,6 +78,13 @@ pub const INITIATOR: u8 = 0xf0;
 /// Manufacturer specific SysEx message terminator.
 pub const TERMINATOR: u8 = 0xf7;
 
+/// Default size limit for messages, in bytes. Real-world messages are
+/// much shorter; anything longer is most likely missing its terminator.
+pub const DEFAULT_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Device ID for addressing all devices ("all call") in universal messages.
+pub const ALL_CALL: u8 = 0x7f;
+
 /// Development/non-commercial SysEx manufacturer ID.
 pub const DEVELOPMENT: u8 = 0x7d;
 
@@ -21,25 +94,64 @@ pub const NON_REAL_TIME: u8 = 0x7e;
 /// Universal real-time SysEx message indicator.
 pub const REAL_TIME: u8 = 0x7f;
 
-/// Error type for System Exclusive messages.
+/// Error type for System Exclusive messages. New variants may be added
+/// as protocol support grows, so matches need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum SystemExclusiveError {
     InvalidMessage,
     InvalidManufacturer,
+    /// The checksum in the message does not match the computed checksum.
+    InvalidChecksum,
+    MessageTooLong,
+    /// Reading or writing the message failed.
+    Io(io::Error),
+    /// The message ends before all of its data.
+    Truncated,
+    /// A data byte has the high bit set.
+    InvalidDataByte { offset: usize, value: u8 },
+    /// The message is valid, but its format is not supported.
+    UnsupportedFormat,
 }
 
 impl fmt::Display for SystemExclusiveError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", match &self {
-            SystemExclusiveError::InvalidMessage => "Invalid System Exclusive message",
-            SystemExclusiveError::InvalidManufacturer => "Invalid manufacturer identifier"
-        })
+        match &self {
+            SystemExclusiveError::InvalidMessage => write!(f, "Invalid System Exclusive message"),
+            SystemExclusiveError::InvalidManufacturer => write!(f, "Invalid manufacturer identifier"),
+            SystemExclusiveError::InvalidChecksum => write!(f, "Invalid checksum"),
+            SystemExclusiveError::MessageTooLong => write!(f, "Message exceeds the size limit"),
+            SystemExclusiveError::Io(e) => write!(f, "{}", e),
+            SystemExclusiveError::Truncated => write!(f, "Message is truncated"),
+            SystemExclusiveError::InvalidDataByte { offset, value } =>
+                write!(f, "Invalid data byte {:02X} at offset {}", value, offset),
+            SystemExclusiveError::UnsupportedFormat => write!(f, "Unsupported message format"),
+        }
+    }
+}
+
+impl std::error::Error for SystemExclusiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SystemExclusiveError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SystemExclusiveError {
+    fn from(e: io::Error) -> Self {
+        SystemExclusiveError::Io(e)
     }
 }
 
 /// MIDI manufacturer. The ID is either a single byte for standard IDs,
 /// or three bytes for extended IDs.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+///
+/// Manufacturers are ordered like the official ID listing:
+/// all standard IDs first, then the extended IDs in numeric order.
+/// (The variant order below is significant for the derived ordering.)
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum Manufacturer {
     Standard(u8),
     Extended([u8; 3]),
@@ -51,6 +163,9 @@ impl Manufacturer {
         if data.len() != 1 && data.len() != 3 {
             return Err(SystemExclusiveError::InvalidManufacturer);
         }
+        if (data[0] == 0x00) != (data.len() == 3) {
+            return Err(SystemExclusiveError::InvalidManufacturer);
+        }
         if data[0] == 0x00 {
             Ok(Manufacturer::Extended([data[0], data[1], data[2]]))
         }
@@ -59,8 +174,33 @@ impl Manufacturer {
         }
     }
 
+    /// Creates a new manufacturer from a hex string, such as "41" or "00203C"
+    /// as produced by `to_hex`. Case is ignored, and the bytes may be separated
+    /// by spaces, colons, dashes or commas ("00 20 3C", "00:20:3c").
+    pub fn from_hex(s: &str) -> Result<Self, SystemExclusiveError> {
+        let digits: String = s.chars()
+            .filter(|c| !c.is_whitespace() && !matches!(c, ':' | '-' | ','))
+            .collect();
+        let data = hex::decode(digits).map_err(|_| SystemExclusiveError::InvalidManufacturer)?;
+        if data.iter().any(|b| b & 0x80 != 0) {
+            return Err(SystemExclusiveError::InvalidManufacturer);
+        }
+        Manufacturer::from_bytes(&data)
+    }
+
+    /// Reads the manufacturer straight from a buffer starting with the
+    /// System Exclusive initiator, without parsing the whole message.
+    /// Returns `None` for universal messages and invalid data.
+    pub fn peek(data: &[u8]) -> Option<Self> {
+        match data {
+            [INITIATOR, 0x00, b1, b2, ..] => Some(Manufacturer::Extended([0x00, *b1, *b2])),
+            [INITIATOR, b @ 0x01..=DEVELOPMENT, ..] => Some(Manufacturer::Standard(*b)),
+            _ => None,
+        }
+    }
+
     /// Creates a new manufacturer with default ID.
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Manufacturer::Standard(0x40)
     }
 
@@ -77,8 +217,13 @@ impl Manufacturer {
         hex::encode(self.to_bytes()).to_uppercase()
     }
 
+    /// Gets the manufacturer SysEx bytes as a hex string in the given format.
+    pub fn to_hex_string(&self, format: &HexFormat) -> String {
+        format_hex(&self.to_bytes(), format)
+    }
+
     /// Returns `true` if this manufacturer represents development / non-commercial.
-    pub fn is_development(&self) -> bool {
+    pub const fn is_development(&self) -> bool {
         match self {
             Manufacturer::Standard(b) => *b == DEVELOPMENT,
             Manufacturer::Extended(_) => false
@@ -91,67 +236,133 @@ impl Manufacturer {
             return "Development / Non-commercial".to_string()
         }
 
-        let hex_id = self.to_hex();
-        if let Some(n) = MANUFACTURER_NAMES.get(&*hex_id) {
-            n.to_string()
+        #[cfg(feature = "manufacturer-names")]
+        {
+            let mut buffer = [0u8; 6];
+            if let Some(n) = MANUFACTURER_NAMES.get(self.hex_key(&mut buffer)) {
+                return n.to_string();
+            }
         }
-        else {
-            "Unknown manufacturer".to_string()
+
+        "Unknown manufacturer".to_string()
+    }
+
+    /// Returns `true` if the manufacturer is in the registry of names.
+    /// Without the `manufacturer-names` feature only the development ID is known.
+    pub fn is_known(&self) -> bool {
+        #[cfg(feature = "manufacturer-names")]
+        {
+            let mut buffer = [0u8; 6];
+            self.is_development() || MANUFACTURER_NAMES.contains_key(self.hex_key(&mut buffer))
+        }
+        #[cfg(not(feature = "manufacturer-names"))]
+        {
+            self.is_development()
+        }
+    }
+
+    /// Formats the ID in uppercase hex into the buffer without allocating,
+    /// for looking up the manufacturer name.
+    #[cfg(feature = "manufacturer-names")]
+    fn hex_key<'a>(&self, buffer: &'a mut [u8; 6]) -> &'a str {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        let (bytes, length): ([u8; 3], usize) = match self {
+            Manufacturer::Standard(b) => ([*b, 0, 0], 1),
+            Manufacturer::Extended(bytes) => (*bytes, 3),
+        };
+        for (i, b) in bytes[..length].iter().enumerate() {
+            buffer[i * 2] = DIGITS[(b >> 4) as usize];
+            buffer[i * 2 + 1] = DIGITS[(b & 0x0f) as usize];
         }
+        std::str::from_utf8(&buffer[..length * 2]).unwrap_or_default()
     }
 
-    /// Gets the group of this manufacturer based on the identifier.
+    /// Gets the group of this manufacturer based on the identifier, by the
+    /// ranges assigned by the MMA and AMEI: 01-1F and 00 00 01-00 1F 7F are
+    /// North American, 20-3F and 00 20 00-00 3F 7F European & Other, and
+    /// 40-5F and 00 40 00-00 5F 7F Japanese. Other IDs are not assigned.
     pub fn group(&self) -> ManufacturerGroup {
         if self.is_development() {
             return ManufacturerGroup::Development
         }
 
-        match self {
-            Manufacturer::Standard(b) => {
-                if (0x01..0x40).contains(b) {
-                    ManufacturerGroup::NorthAmerican
-                }
-                else if (0x40..0x60).contains(b) {
-                    ManufacturerGroup::Japanese
-                }
-                else {
-                    ManufacturerGroup::EuropeanAndOther
-                }
-            },
-            Manufacturer::Extended(bs) => {
-                if (bs[1] & (1 << 6)) != 0 {  // 0x4x
-                    ManufacturerGroup::Japanese
-                }
-                else if (bs[1] & (1 << 5)) != 0 { // 0x2x
-                    ManufacturerGroup::EuropeanAndOther
-                }
-                else {
-                    ManufacturerGroup::NorthAmerican
-                }
-            }
+        let range = match self {
+            Manufacturer::Standard(0x00) | Manufacturer::Extended([_, 0x00, 0x00]) => return ManufacturerGroup::Unassigned,
+            Manufacturer::Standard(b) => *b,
+            Manufacturer::Extended(bs) => bs[1],
+        };
+        match range {
+            0x00..=0x1f => ManufacturerGroup::NorthAmerican,
+            0x20..=0x3f => ManufacturerGroup::EuropeanAndOther,
+            0x40..=0x5f => ManufacturerGroup::Japanese,
+            _ => ManufacturerGroup::Unassigned,
         }
     }
 }
 
+impl Default for Manufacturer {
+    fn default() -> Self {
+        Manufacturer::new()
+    }
+}
+
 impl fmt::Display for Manufacturer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.name())
     }
 }
 
-/// Finds a manufacturer by initial match of name.
+/// Finds a manufacturer by initial match of name. Without the
+/// `manufacturer-names` feature there are no names, and this always fails.
+#[cfg(feature = "manufacturer-names")]
 pub fn find_manufacturer(name: &str) -> Result<Manufacturer, SystemExclusiveError> {
-    for (key, value) in &*MANUFACTURER_NAMES {
+    for (key, value) in MANUFACTURER_NAMES.entries() {
         if value.to_lowercase().starts_with(&name.to_lowercase()) {
             let id_bytes = hex::decode(key).unwrap();
             return Ok(Manufacturer::from_bytes(&id_bytes).unwrap());
         }
     }
-    return Err(SystemExclusiveError::InvalidManufacturer);
+    Err(SystemExclusiveError::InvalidManufacturer)
+}
+
+/// Finds a manufacturer by initial match of name. Without the
+/// `manufacturer-names` feature there are no names, and this always fails.
+#[cfg(not(feature = "manufacturer-names"))]
+pub fn find_manufacturer(_name: &str) -> Result<Manufacturer, SystemExclusiveError> {
+    Err(SystemExclusiveError::InvalidManufacturer)
+}
+
+/// Searches for manufacturers by hex ID or by name. A query that is a valid
+/// manufacturer ID in hex (like "43" or "00 20 3C") matches that ID, and any
+/// query matches the manufacturers whose name contains it, ignoring case.
+/// The results are in manufacturer ID order, without duplicates.
+pub fn search_manufacturers(query: &str) -> Vec<Manufacturer> {
+    let mut result = Vec::new();
+    if let Ok(manufacturer) = Manufacturer::from_hex(query) {
+        result.push(manufacturer);
+    }
+
+    #[cfg(feature = "manufacturer-names")]
+    {
+        let query = query.trim().to_lowercase();
+        if !query.is_empty() {
+            for (key, value) in MANUFACTURER_NAMES.entries() {
+                if value.to_lowercase().contains(&query) {
+                    if let Ok(manufacturer) = Manufacturer::from_hex(key) {
+                        result.push(manufacturer);
+                    }
+                }
+            }
+        }
+    }
+
+    result.sort();
+    result.dedup();
+    result
 }
 
 /// The kind of a Universal System Exclusive message.
-#[derive(Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum UniversalKind {
     NonRealTime,
     RealTime,
@@ -168,105 +379,792 @@ impl fmt::Display for UniversalKind {
 }
 
 /// A MIDI System Exclusive message.
-#[derive(Debug)]
+///
+/// The alternate `Debug` format (`{:#?}`) shows the manufacturer name,
+/// the sub-ID names and only the start of the payload in hex, to keep
+/// large dumps readable in logs.
+#[derive(Clone, Eq, PartialEq)]
 pub enum Message {
     Universal { kind: UniversalKind, target: u8, sub_id1: u8, sub_id2: u8, payload: Vec<u8> },
     ManufacturerSpecific { manufacturer: Manufacturer, payload: Vec<u8> },
 }
 
+/// Number of payload bytes shown by the alternate `Debug` format of `Message`.
+const DEBUG_PREVIEW_LENGTH: usize = 16;
+
+/// Formats the start of a payload in hex, with its length.
+fn payload_preview(payload: &[u8]) -> String {
+    let preview = format_hex(&payload[..payload.len().min(DEBUG_PREVIEW_LENGTH)], &HexFormat::default());
+    let ellipsis = if payload.len() > DEBUG_PREVIEW_LENGTH { " ..." } else { "" };
+    format!("{}{} ({} bytes)", preview, ellipsis, payload.len())
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() {
+            return match self {
+                Message::Universal { kind, target, sub_id1, sub_id2, payload } => f.debug_struct("Universal")
+                    .field("kind", kind)
+                    .field("target", target)
+                    .field("sub_id1", sub_id1)
+                    .field("sub_id2", sub_id2)
+                    .field("payload", payload)
+                    .finish(),
+                Message::ManufacturerSpecific { manufacturer, payload } => f.debug_struct("ManufacturerSpecific")
+                    .field("manufacturer", manufacturer)
+                    .field("payload", payload)
+                    .finish(),
+            };
+        }
+
+        match self {
+            Message::Universal { kind, target, sub_id1, sub_id2, payload } => {
+                let name = universal_sub_id_description(*kind, *sub_id1, *sub_id2).unwrap_or_else(|| "Unknown".to_string());
+                f.debug_struct("Universal")
+                    .field("kind", &format_args!("{}", kind))
+                    .field("target", &format_args!("{:02X}h", target))
+                    .field("sub_ids", &format_args!("{:02X}h {:02X}h", sub_id1, sub_id2))
+                    .field("name", &format_args!("{}", name))
+                    .field("payload", &format_args!("{}", payload_preview(payload)))
+                    .finish()
+            },
+            Message::ManufacturerSpecific { manufacturer, payload } => f.debug_struct("ManufacturerSpecific")
+                .field("manufacturer", &format_args!("{} ({})", manufacturer.to_hex(), manufacturer.name()))
+                .field("group", &format_args!("{}", manufacturer.group()))
+                .field("payload", &format_args!("{}", payload_preview(payload)))
+                .finish(),
+        }
+    }
+}
+
+/// The kind of a System Exclusive message, determined from its first bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum MessageKind {
+    UniversalNonRealTime,
+    UniversalRealTime,
+    ManufacturerStandard,
+    ManufacturerExtended,
+    Development,
+    Invalid,
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MessageKind::UniversalNonRealTime => "Universal Non-Real-time",
+            MessageKind::UniversalRealTime => "Universal Real-time",
+            MessageKind::ManufacturerStandard => "Manufacturer-specific (standard ID)",
+            MessageKind::ManufacturerExtended => "Manufacturer-specific (extended ID)",
+            MessageKind::Development => "Development / Non-commercial",
+            MessageKind::Invalid => "Invalid",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Classifies a System Exclusive message by looking only at its first
+/// few bytes, without parsing the payload. The terminator is not checked.
+pub fn classify(data: &[u8]) -> MessageKind {
+    match data {
+        [INITIATOR, NON_REAL_TIME, _, _, _, ..] => MessageKind::UniversalNonRealTime,
+        [INITIATOR, REAL_TIME, _, _, _, ..] => MessageKind::UniversalRealTime,
+        [INITIATOR, DEVELOPMENT, ..] => MessageKind::Development,
+        [INITIATOR, 0x00, _, _, ..] => MessageKind::ManufacturerExtended,
+        [INITIATOR, 0x01..=0x7c, ..] => MessageKind::ManufacturerStandard,
+        _ => MessageKind::Invalid,
+    }
+}
+
+/// The first bytes of a System Exclusive message, read without parsing
+/// the payload, for deciding cheaply whether a message is of interest.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MessageHeader {
+    pub kind: MessageKind,
+    /// The manufacturer of manufacturer-specific and development messages.
+    pub manufacturer: Option<Manufacturer>,
+    /// The target device ID of universal messages.
+    pub target: Option<u8>,
+    /// The sub-IDs #1 and #2 of universal messages.
+    pub sub_ids: Option<(u8, u8)>,
+    /// Length of the message in bytes, from initiator to terminator.
+    pub length: usize,
+}
+
+impl MessageHeader {
+    /// Reads the header of the message starting at the beginning of the data.
+    /// The rest of the data is taken to be the message, and is not checked.
+    pub fn read(data: &[u8]) -> Self {
+        let kind = classify(data);
+        let (target, sub_ids) = match data {
+            [INITIATOR, NON_REAL_TIME | REAL_TIME, target, sub_id1, sub_id2, ..] => (Some(*target), Some((*sub_id1, *sub_id2))),
+            _ => (None, None),
+        };
+        MessageHeader { kind, manufacturer: Manufacturer::peek(data), target, sub_ids, length: data.len() }
+    }
+}
+
+/// Finds the messages in the data whose headers satisfy the predicate,
+/// without copying or parsing them. Bytes before an initiator and
+/// messages without a terminator are skipped, as are messages whose
+/// header is not valid.
+pub fn filter_messages<F>(data: &[u8], mut predicate: F) -> Vec<&[u8]>
+where
+    F: FnMut(&MessageHeader) -> bool,
+{
+    data.split_inclusive(|&n| n == TERMINATOR)
+        .filter(|part| part.last() == Some(&TERMINATOR))
+        .filter_map(|part| part.iter().rposition(|&n| n == INITIATOR).map(|start| &part[start..]))
+        .filter(|message| {
+            let header = MessageHeader::read(message);
+            header.kind != MessageKind::Invalid && predicate(&header)
+        })
+        .collect()
+}
+
 /// Returns the number of System Exclusive messages in this vector,
 /// based on the count of terminator bytes.
-pub fn message_count(data: &Vec<u8>) -> usize {
+pub fn message_count(data: &[u8]) -> usize {
     data.iter().filter(|&n| *n == TERMINATOR).count()
 }
 
+/// Splits the vector by the terminator byte like `split_messages`, but fails with
+/// `SystemExclusiveError::MessageTooLong` if any part is longer than `limit` bytes.
+pub fn split_messages_with_limit(data: Vec<u8>, limit: usize) -> Result<Vec<Vec<u8>>, SystemExclusiveError> {
+    if data.split_inclusive(|&n| n == TERMINATOR).any(|part| part.len() > limit) {
+        return Err(SystemExclusiveError::MessageTooLong);
+    }
+    Ok(split_messages(data))
+}
+
+/// Progress of a long-running operation, reported to a callback.
+/// The callback returns `ControlFlow::Break(())` to cancel the operation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Progress {
+    /// Number of units (bytes, messages or files) completed so far.
+    pub done: usize,
+    /// Total number of units, if known.
+    pub total: Option<usize>,
+}
+
+impl Progress {
+    /// Creates a new progress report.
+    pub fn new(done: usize, total: Option<usize>) -> Self {
+        Progress { done, total }
+    }
+
+    /// Gets the completed fraction between 0.0 and 1.0, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some(self.done as f64 / total as f64),
+            None => None,
+        }
+    }
+}
+
 /// Splits the vector by the terminator byte, including it.
 pub fn split_messages(data: Vec<u8>) -> Vec<Vec<u8>> {
+    split_messages_with_progress(data, |_| ControlFlow::Continue(()))
+        .unwrap_or_default()
+}
+
+/// Splits the vector by the terminator byte like `split_messages`,
+/// reporting the number of bytes processed after each message.
+/// Returns `None` if the callback cancels the operation.
+pub fn split_messages_with_progress<F>(data: Vec<u8>, mut progress: F) -> Option<Vec<Vec<u8>>>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
     let mut parts: Vec<Vec<u8>> = Vec::new();
+    let mut done = 0;
     for part in data.split_inclusive(|&n| n == TERMINATOR) {
         parts.push(part.to_vec());
+        done += part.len();
+        if progress(Progress::new(done, Some(data.len()))).is_break() {
+            return None;
+        }
+    }
+    Some(parts)
+}
+
+/// Computes the MD5 digests of the messages, reporting the number
+/// of messages processed after each one.
+/// Returns `None` if the callback cancels the operation.
+pub fn digest_messages<F>(messages: &[Message], mut progress: F) -> Option<Vec<md5::Digest>>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    let mut digests = Vec::with_capacity(messages.len());
+    for message in messages {
+        digests.push(message.digest());
+        if progress(Progress::new(digests.len(), Some(messages.len()))).is_break() {
+            return None;
+        }
     }
-    parts
+    Some(digests)
+}
+
+/// Checks that the data is framed like a message: it starts with the
+/// initiator, ends with the terminator and is long enough for its kind.
+pub(crate) fn check_framing(data: &[u8]) -> Result<(), SystemExclusiveError> {
+    if data.first() != Some(&INITIATOR) {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+
+    if data.last() != Some(&TERMINATOR) {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+
+    // The shortest messages have a one-byte manufacturer ID and no payload.
+    let minimum_length = match data.get(1) {
+        Some(&NON_REAL_TIME) | Some(&REAL_TIME) => 6,
+        Some(0x00) => 5,
+        _ => 3,
+    };
+    if data.len() < minimum_length {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+    if data[1] & 0x80 != 0 {
+        return Err(SystemExclusiveError::InvalidDataByte { offset: 1, value: data[1] });
+    }
+
+    Ok(())
 }
 
 impl Message {
     /// Creates a new SysEx message based on the initial data bytes.
     pub fn from_bytes(data: &[u8]) -> Result<Self, SystemExclusiveError> {
-        if data[0] != INITIATOR {
-            return Err(SystemExclusiveError::InvalidMessage);
-        }
+        check_framing(data)?;
+        Ok(Message::from_framed(data))
+    }
 
+    /// Makes a message of data that has passed `check_framing`.
+    pub(crate) fn from_framed(data: &[u8]) -> Self {
         let last_byte_index = data.len() - 1;
-        if data[last_byte_index] != TERMINATOR {
-            return Err(SystemExclusiveError::InvalidMessage);
-        }
-
-        if data.len() < 5 {   // too short
-            return Err(SystemExclusiveError::InvalidMessage);
-        }
-
         match data[1] {
-            DEVELOPMENT => Ok(Message::ManufacturerSpecific {
+            DEVELOPMENT => Message::ManufacturerSpecific {
                 manufacturer: Manufacturer::Standard(data[1]),
                 payload: data[2..last_byte_index].to_vec()
-            }),
-            NON_REAL_TIME => Ok(Message::Universal {
+            },
+            NON_REAL_TIME => Message::Universal {
                 kind: UniversalKind::NonRealTime,
                 target: data[2],
                 sub_id1: data[3],
                 sub_id2: data[4],
                 payload: data[5..last_byte_index].to_vec()
-            }),
-            REAL_TIME => Ok(Message::Universal {
+            },
+            REAL_TIME => Message::Universal {
                 kind: UniversalKind::RealTime,
                 target: data[2],
                 sub_id1: data[3],
                 sub_id2: data[4],
                 payload: data[5..last_byte_index].to_vec()
-            }),
-            0x00 => Ok(Message::ManufacturerSpecific {
+            },
+            0x00 => Message::ManufacturerSpecific {
                 manufacturer: Manufacturer::Extended([data[1], data[2], data[3]]),
                 payload: data[4..last_byte_index].to_vec()
-            }),
-            _ => Ok(Message::ManufacturerSpecific {
+            },
+            _ => Message::ManufacturerSpecific {
                 manufacturer: Manufacturer::Standard(data[1]),
                 payload: data[2..last_byte_index].to_vec()
-            }),
+            },
         }
     }
 
+    /// Creates a new SysEx message like `from_bytes`, but fails with
+    /// `SystemExclusiveError::MessageTooLong` if the data is longer than `limit` bytes.
+    pub fn from_bytes_with_limit(data: &[u8], limit: usize) -> Result<Self, SystemExclusiveError> {
+        if data.len() > limit {
+            return Err(SystemExclusiveError::MessageTooLong);
+        }
+        Message::from_bytes(data)
+    }
+
+    /// Reads one message from a reader. Bytes before the initiator are skipped,
+    /// and reading stops at the terminator. Real-time status bytes inside
+    /// the message are ignored. Returns `None` if the reader ends before
+    /// a message starts. The reader is read one byte at a time, so wrap
+    /// unbuffered readers in `io::BufReader`. To know where the messages
+    /// are in the input, use `MessageReader`.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Option<Self>> {
+        Ok(MessageReader::new(r).next_message()?.map(|(_, message)| message))
+    }
+
     /// Converts the message into bytes for MIDI messaging.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut result = Vec::<u8>::new();
+        let mut result = Vec::with_capacity(self.byte_len());
+        self.extend_bytes(&mut result);
+        result
+    }
+
+    /// Gets the bytes of the message in canonical form. For any data that
+    /// `from_bytes` accepts, this is byte-identical to that data: parsing and
+    /// serializing never alters a dump, including the shortest messages,
+    /// empty payloads, development IDs and payload bytes with the high bit set.
+    /// A message built in code has only one byte form, so this is the same as `to_bytes`.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
 
+    /// Gets the length of the message in bytes, including the
+    /// initiator and the terminator.
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Message::Universal { payload, .. } => 5 + payload.len() + 1,
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(_), payload } => 2 + payload.len() + 1,
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(_), payload } => 4 + payload.len() + 1,
+        }
+    }
+
+    /// Appends the message bytes to `out`. Use `byte_len` to reserve
+    /// space when serializing many messages into one buffer.
+    pub fn extend_bytes(&self, out: &mut Vec<u8>) {
+        out.reserve(self.byte_len());
         match self {
             Message::Universal { kind, target, sub_id1, sub_id2, payload } => {
-                result.push(INITIATOR);
-                result.push(match kind {
+                out.push(INITIATOR);
+                out.push(match kind {
                     UniversalKind::NonRealTime => NON_REAL_TIME,
                     UniversalKind::RealTime => REAL_TIME,
                 });
-                result.push(*target);
-                result.push(*sub_id1);
-                result.push(*sub_id2);
-                result.extend(payload);
-                result.push(TERMINATOR);
+                out.push(*target);
+                out.push(*sub_id1);
+                out.push(*sub_id2);
+                out.extend(payload);
+                out.push(TERMINATOR);
             },
             Message::ManufacturerSpecific { manufacturer, payload } => {
-                result.push(INITIATOR);
-                result.extend(manufacturer.to_bytes());
-                result.extend(payload);
-                result.push(TERMINATOR);
+                out.push(INITIATOR);
+                match manufacturer {
+                    Manufacturer::Standard(b) => out.push(*b),
+                    Manufacturer::Extended(bytes) => out.extend(bytes),
+                }
+                out.extend(payload);
+                out.push(TERMINATOR);
             }
         }
+    }
+
+    /// Writes the message bytes to a writer, without making
+    /// an intermediate copy of the message.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Message::Universal { kind, target, sub_id1, sub_id2, payload } => {
+                let kind_byte = match kind {
+                    UniversalKind::NonRealTime => NON_REAL_TIME,
+                    UniversalKind::RealTime => REAL_TIME,
+                };
+                w.write_all(&[INITIATOR, kind_byte, *target, *sub_id1, *sub_id2])?;
+                w.write_all(payload)?;
+            },
+            Message::ManufacturerSpecific { manufacturer, payload } => {
+                w.write_all(&[INITIATOR])?;
+                match manufacturer {
+                    Manufacturer::Standard(b) => w.write_all(&[*b])?,
+                    Manufacturer::Extended(bytes) => w.write_all(bytes)?,
+                }
+                w.write_all(payload)?;
+            },
+        }
+        w.write_all(&[TERMINATOR])
+    }
 
+    /// Returns `true` if this is a universal message, real-time or non-real-time.
+    pub fn is_universal(&self) -> bool {
+        matches!(self, Message::Universal { .. })
+    }
+
+    /// Returns `true` if this is a manufacturer-specific message,
+    /// including development messages.
+    pub fn is_manufacturer_specific(&self) -> bool {
+        matches!(self, Message::ManufacturerSpecific { .. })
+    }
+
+    /// Returns `true` if this is a manufacturer-specific message of the manufacturer.
+    pub fn is_from(&self, manufacturer: &Manufacturer) -> bool {
+        matches!(self, Message::ManufacturerSpecific { manufacturer: m, .. } if m == manufacturer)
+    }
+
+    /// Returns `true` if this is a universal message addressed to all devices.
+    pub fn is_broadcast(&self) -> bool {
+        matches!(self, Message::Universal { target: ALL_CALL, .. })
+    }
+
+    /// Returns `true` if this is a universal message addressed to the device
+    /// with the given ID, either directly or by broadcast. Manufacturer-specific
+    /// messages have no standard device ID, so for them this returns `false`.
+    pub fn targets_device(&self, id: u8) -> bool {
+        match self {
+            Message::Universal { target, .. } => *target == id || *target == ALL_CALL,
+            Message::ManufacturerSpecific { .. } => false,
+        }
+    }
+
+    /// Decodes this message into a fully decoded universal message.
+    /// Returns an error if this is not a universal message, or if it is
+    /// a universal message type that is not supported.
+    pub fn decode_universal(&self) -> Result<universal::UniversalMessage, SystemExclusiveError> {
+        universal::UniversalMessage::from_message(self)
+    }
+
+    /// Divides the message bytes into labeled sections.
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![Section::new(SectionKind::Initiator, 0, 1)];
+        let payload_offset = match self {
+            Message::Universal { .. } => {
+                sections.push(Section::new(SectionKind::UniversalKind, 1, 1));
+                sections.push(Section::new(SectionKind::DeviceId, 2, 1));
+                sections.push(Section::new(SectionKind::SubId1, 3, 1));
+                sections.push(Section::new(SectionKind::SubId2, 4, 1));
+                5
+            },
+            Message::ManufacturerSpecific { manufacturer, .. } => {
+                let length = manufacturer.to_bytes().len();
+                sections.push(Section::new(SectionKind::Manufacturer, 1, length));
+                1 + length
+            },
+        };
+        let payload_length = self.payload().len();
+        if payload_length > 0 {
+            sections.push(Section::new(SectionKind::Payload, payload_offset, payload_length));
+        }
+        sections.push(Section::new(SectionKind::Terminator, payload_offset + payload_length, 1));
+        sections
+    }
+
+    /// Divides the message bytes into labeled sections, letting the profile
+    /// divide the payload further.
+    pub fn sections_with(&self, profile: &dyn SectionProfile) -> Vec<Section> {
+        let sections = self.sections();
+        let inner = match profile.payload_sections(self) {
+            Some(inner) => inner,
+            None => return sections,
+        };
+
+        let mut result = Vec::new();
+        for section in sections {
+            if section.kind != SectionKind::Payload {
+                result.push(section);
+                continue;
+            }
+            let end = section.offset + section.length;
+            let mut position = section.offset;
+            for part in &inner {
+                if part.offset < position || part.offset + part.length > end {
+                    continue;
+                }
+                if part.offset > position {
+                    result.push(Section::new(SectionKind::Payload, position, part.offset - position));
+                }
+                result.push(part.clone());
+                position = part.offset + part.length;
+            }
+            if position < end {
+                result.push(Section::new(SectionKind::Payload, position, end - position));
+            }
+        }
         result
     }
 
+    /// Gets the payload of the message.
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            Message::Universal { payload, .. } | Message::ManufacturerSpecific { payload, .. } => payload,
+        }
+    }
+
+    /// Gets the message bytes as a hex string in the given format.
+    pub fn to_hex_string(&self, format: &HexFormat) -> String {
+        format_hex(&self.to_bytes(), format)
+    }
+
     /// Compute the MD5 digest for this message.
     pub fn digest(&self) -> md5::Digest {
         md5::compute(self.to_bytes())
     }
+
+    /// Makes a copy of this message with the bytes masked by `options`
+    /// set to zero, for comparing messages while ignoring them.
+    pub fn normalized(&self, options: &CompareOptions) -> Message {
+        let mut message = self.clone();
+        let payload = match &mut message {
+            Message::Universal { target, payload, .. } => {
+                if options.ignore_device_id {
+                    *target = 0x00;
+                }
+                payload
+            },
+            Message::ManufacturerSpecific { payload, .. } => payload,
+        };
+
+        for offset in &options.ignored_offsets {
+            if let Some(b) = payload.get_mut(*offset) {
+                *b = 0x00;
+            }
+        }
+        if options.ignore_checksum {
+            if let Some(b) = payload.last_mut() {
+                *b = 0x00;
+            }
+        }
+        message
+    }
+
+    /// Compares this message with another one, ignoring the bytes masked by `options`.
+    pub fn eq_ignoring(&self, other: &Message, options: &CompareOptions) -> bool {
+        self.normalized(options) == other.normalized(options)
+    }
+}
+
+/// The kind of a byte range in a message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SectionKind {
+    Initiator,
+    Manufacturer,
+    UniversalKind,
+    DeviceId,
+    SubId1,
+    SubId2,
+    Payload,
+    Name,
+    Checksum,
+    Terminator,
+}
+
+impl fmt::Display for SectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SectionKind::Initiator => "Initiator",
+            SectionKind::Manufacturer => "Manufacturer",
+            SectionKind::UniversalKind => "Universal kind",
+            SectionKind::DeviceId => "Device ID",
+            SectionKind::SubId1 => "Sub-ID #1",
+            SectionKind::SubId2 => "Sub-ID #2",
+            SectionKind::Payload => "Payload",
+            SectionKind::Name => "Name",
+            SectionKind::Checksum => "Checksum",
+            SectionKind::Terminator => "Terminator",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reads messages from a stream, keeping track of the position of each
+/// message in it. Positions are `u64`, so that captures larger than
+/// 4 GB work on all platforms.
+pub struct MessageReader<R> {
+    reader: R,
+    position: u64,
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Makes a reader that starts at position zero.
+    pub fn new(reader: R) -> Self {
+        MessageReader { reader, position: 0 }
+    }
+
+    /// Gets the number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reads the next message like `Message::read_from`, and returns it
+    /// with the position of its initiator byte.
+    pub fn next_message(&mut self) -> io::Result<Option<(u64, Message)>> {
+        let mut byte = [0u8; 1];
+        loop {
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            self.position += 1;
+            if byte[0] == INITIATOR {
+                break;
+            }
+        }
+
+        let offset = self.position - 1;
+        let mut data = vec![INITIATOR];
+        loop {
+            self.reader.read_exact(&mut byte)?;
+            self.position += 1;
+            match byte[0] {
+                TERMINATOR => break,
+                0xf8..=0xff => continue,
+                b if b & 0x80 != 0 => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("unexpected status byte {:02X} in message", b)));
+                },
+                b => data.push(b),
+            }
+            if data.len() >= DEFAULT_SIZE_LIMIT {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, SystemExclusiveError::MessageTooLong));
+            }
+        }
+        data.push(TERMINATOR);
+
+        Message::from_bytes(&data)
+            .map(|message| Some((offset, message)))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+    type Item = io::Result<(u64, Message)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
+
+/// A labeled byte range in a message. The offset is from the start
+/// of the message, counting the initiator byte.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub label: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Section {
+    /// Makes a section labeled with the name of its kind.
+    pub fn new(kind: SectionKind, offset: usize, length: usize) -> Self {
+        Section { kind, label: kind.to_string(), offset, length }
+    }
+
+    /// Makes a section with a specific label.
+    pub fn labeled(kind: SectionKind, label: &str, offset: usize, length: usize) -> Self {
+        Section { kind, label: label.to_string(), offset, length }
+    }
+
+    /// Gets the byte range of this section.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.length
+    }
+
+    /// Gets the byte range of this section in a file, for a message
+    /// starting at `message_offset`, like a position from `MessageReader`.
+    pub fn absolute_range(&self, message_offset: u64) -> std::ops::Range<u64> {
+        let start = message_offset + self.offset as u64;
+        start..start + self.length as u64
+    }
+}
+
+/// A device profile that knows more about the payload of some messages,
+/// such as where their name and checksum fields are.
+pub trait SectionProfile {
+    /// Gets the sections inside the payload of the message, in order
+    /// and not overlapping, or `None` if the profile does not apply.
+    /// The parts of the payload not covered are labeled as payload.
+    fn payload_sections(&self, message: &Message) -> Option<Vec<Section>>;
+}
+
+/// Options for comparing messages while ignoring some of their bytes.
+#[derive(Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct CompareOptions {
+    /// Ignore the target device ID of universal messages.
+    pub ignore_device_id: bool,
+    /// Ignore the last byte of the payload, where most formats put the checksum.
+    pub ignore_checksum: bool,
+    /// Payload offsets of other bytes to ignore, such as the device ID or
+    /// MIDI channel byte of manufacturer-specific messages.
+    pub ignored_offsets: Vec<usize>,
+}
+
+/// Formatting options for hex strings. The default is uppercase
+/// with bytes separated by spaces, all on one line.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct HexFormat {
+    /// String to put between bytes on the same line.
+    pub separator: String,
+    /// Use uppercase hex digits.
+    pub uppercase: bool,
+    /// Number of bytes on each line, or `None` for a single line.
+    pub bytes_per_line: Option<usize>,
+    /// Append an "h" to each byte, as in "F0h".
+    pub suffix: bool,
+}
+
+impl HexFormat {
+    /// Continuous uppercase hex digits, as produced by `Manufacturer::to_hex`.
+    pub fn compact() -> Self {
+        HexFormat { separator: String::new(), ..Default::default() }
+    }
+
+    /// Uppercase bytes with an "h" suffix, as often found in manuals.
+    pub fn manual() -> Self {
+        HexFormat { suffix: true, ..Default::default() }
+    }
+}
+
+impl Default for HexFormat {
+    fn default() -> Self {
+        HexFormat {
+            separator: " ".to_string(),
+            uppercase: true,
+            bytes_per_line: None,
+            suffix: false,
+        }
+    }
+}
+
+/// Formats the bytes as a hex string. Lines are separated by newlines.
+pub fn format_hex(data: &[u8], format: &HexFormat) -> String {
+    let format_byte = |b: &u8| {
+        let mut s = if format.uppercase { format!("{:02X}", b) } else { format!("{:02x}", b) };
+        if format.suffix {
+            s.push('h');
+        }
+        s
+    };
+    let line_length = match format.bytes_per_line {
+        Some(n) if n > 0 => n,
+        _ => data.len().max(1),
+    };
+
+    data.chunks(line_length)
+        .map(|line| line.iter().map(format_byte).collect::<Vec<String>>().join(&format.separator))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Writes the message to a file. The data is first written to a temporary
+/// file in the same directory and then renamed, so that an interrupted write
+/// never leaves a partial file behind.
+pub fn write_file(path: &Path, message: &Message) -> io::Result<()> {
+    write_messages(path, std::slice::from_ref(message))
+}
+
+/// Writes the messages one after another to a file, atomically like `write_file`.
+pub fn write_messages(path: &Path, messages: &[Message]) -> io::Result<()> {
+    let temp_path = temporary_path(path)?;
+    let result = (|| {
+        let mut writer = io::BufWriter::new(fs::File::create(&temp_path)?);
+        for message in messages {
+            message.write_to(&mut writer)?;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Counter that keeps the temporary file names of the threads of a process apart.
+static TEMPORARY_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Makes a temporary file path next to `path`, unique within the process.
+fn temporary_path(path: &Path) -> io::Result<PathBuf> {
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    let count = TEMPORARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    temp_name.push(format!(".{}.{}.tmp", std::process::id(), count));
+    Ok(path.with_file_name(temp_name))
 }
 
 /// Group of manufacturer.
@@ -276,6 +1174,8 @@ pub enum ManufacturerGroup {
     NorthAmerican,
     EuropeanAndOther,
     Japanese,
+    /// IDs outside the assigned ranges, including the reserved 00 00 00.
+    Unassigned,
 }
 
 impl fmt::Display for ManufacturerGroup {
@@ -285,546 +1185,774 @@ impl fmt::Display for ManufacturerGroup {
             ManufacturerGroup::EuropeanAndOther => "European & Other",
             ManufacturerGroup::Japanese => "Japanese",
             ManufacturerGroup::NorthAmerican => "North American",
+            ManufacturerGroup::Unassigned => "Unassigned",
         };
         write!(f, "{}", name)
     }
 }
 
-lazy_static! {
-    static ref MANUFACTURER_NAMES: HashMap<&'static str, &'static str> = {
-        HashMap::from([
-            ("01", "Sequential Circuits"),
-            ("02", "IDP"),
-            ("03", "Voyetra Turtle Beach, Inc."),
-            ("04", "Moog Music"),
-            ("05", "Passport Designs"),
-            ("06", "Lexicon Inc."),
-            ("07", "Kurzweil / Young Chang"),
-            ("08", "Fender"),
-            ("09", "MIDI9"),
-            ("0A", "AKG Acoustics"),
-            ("0B", "Voyce Music"),
-            ("0C", "WaveFrame (Timeline)"),
-            ("0D", "ADA Signal Processors, Inc."),
-            ("0E", "Garfield Electronics"),
-            ("0F", "Ensoniq"),
-            ("10", "Oberheim / Gibson Labs"),
-            ("11", "Apple"),
-            ("12", "Grey Matter Response"),
-            ("13", "Digidesign Inc."),
-            ("14", "Palmtree Instruments"),
-            ("15", "JLCooper Electronics"),
-            ("16", "Lowrey Organ Company"),
-            ("17", "Adams-Smith"),
-            ("18", "E-mu"),
-            ("19", "Harmony Systems"),
-            ("1A", "ART"),
-            ("1B", "Baldwin"),
-            ("1C", "Eventide"),
-            ("1D", "Inventronics"),
-            ("1E", "Key Concepts"),
-            ("1F", "Clarity"),
-            ("20", "Passac"),
-            ("21", "Proel Labs (SIEL)"),
-            ("22", "Synthaxe (UK)"),
-            ("23", "Stepp"),
-            ("24", "Hohner"),
-            ("25", "Twister"),
-            ("26", "Ketron s.r.l."),
-            ("27", "Jellinghaus MS"),
-            ("28", "Southworth Music Systems"),
-            ("29", "PPG (Germany)"),
-            ("2A", "JEN"),
-            ("2B", "Solid State Logic Organ Systems"),
-            ("2C", "Audio Veritrieb-P. Struven"),
-            ("2D", "Neve"),
-            ("2E", "Soundtracs Ltd."),
-            ("2F", "Elka"),
-            ("30", "Dynacord"),
-            ("31", "Viscount International Spa (Intercontinental Electronics)"),
-            ("32", "Drawmer"),
-            ("33", "Clavia Digital Instruments"),
-            ("34", "Audio Architecture"),
-            ("35", "Generalmusic Corp SpA"),
-            ("36", "Cheetah Marketing"),
-            ("37", "C.T.M."),
-            ("38", "Simmons UK"),
-            ("39", "Soundcraft Electronics"),
-            ("3A", "Steinberg Media Technologies GmbH"),
-            ("3B", "Wersi Gmbh"),
-            ("3C", "AVAB Niethammer AB"),
-            ("3D", "Digigram"),
-            ("3E", "Waldorf Electronics GmbH"),
-            ("3F", "Quasimidi"),
-
-            ("000001", "Time/Warner Interactive"),
-            ("000002", "Advanced Gravis Comp. Tech Ltd."),
-            ("000003", "Media Vision"),
-            ("000004", "Dornes Research Group"),
-            ("000005", "K-Muse"),
-            ("000006", "Stypher"),
-            ("000007", "Digital Music Corp."),
-            ("000008", "IOTA Systems"),
-            ("000009", "New England Digital"),
-            ("00000A", "Artisyn"),
-            ("00000B", "IVL Technologies Ltd."),
-            ("00000C", "Southern Music Systems"),
-            ("00000D", "Lake Butler Sound Company"),
-            ("00000E", "Alesis Studio Electronics"),
-            ("00000F", "Sound Creation"),
-            ("000010", "DOD Electronics Corp."),
-            ("000011", "Studer-Editech"),
-            ("000012", "Sonus"),
-            ("000013", "Temporal Acuity Products"),
-            ("000014", "Perfect Fretworks"),
-            ("000015", "KAT Inc."),
-            ("000016", "Opcode Systems"),
-            ("000017", "Rane Corporation"),
-            ("000018", "Anadi Electronique"),
-            ("000019", "KMX"),
-            ("00001A", "Allen & Heath Brenell"),
-            ("00001B", "Peavey Electronics"),
-            ("00001C", "360 Systems"),
-            ("00001D", "Spectrum Design and Development"),
-            ("00001E", "Marquis Music"),
-            ("00001F", "Zeta Systems"),
-            ("000020", "Axxes (Brian Parsonett)"),
-            ("000021", "Orban"),
-            ("000022", "Indian Valley Mfg."),
-            ("000023", "Triton"),
-            ("000024", "KTI"),
-            ("000025", "Breakway Technologies"),
-            ("000026", "Leprecon / CAE Inc."),
-            ("000027", "Harrison Systems Inc."),
-            ("000028", "Future Lab/Mark Kuo"),
-            ("000029", "Rocktron Corporation"),
-            ("00002A", "PianoDisc"),
-            ("00002B", "Cannon Research Group"),
-            ("00002C", "Reserved"),
-            ("00002D", "Rodgers Instrument LLC"),
-            ("00002E", "Blue Sky Logic"),
-            ("00002F", "Encore Electronics"),
-            ("000030", "Uptown"),
-            ("000031", "Voce"),
-            ("000032", "CTI Audio, Inc. (Musically Intel. Devs.)"),
-            ("000033", "S3 Incorporated"),
-            ("000034", "Broderbund / Red Orb"),
-            ("000035", "Allen Organ Co."),
-            ("000036", "Reserved"),
-            ("000037", "Music Quest"),
-            ("000038", "Aphex"),
-            ("000039", "Gallien Krueger"),
-            ("00003A", "IBM"),
-            ("00003B", "Mark Of The Unicorn"),
-            ("00003C", "Hotz Corporation"),
-            ("00003D", "ETA Lighting"),
-            ("00003E", "NSI Corporation"),
-            ("00003F", "Ad Lib, Inc."),
-            ("000040", "Richmond Sound Design"),
-            ("000041", "Microsoft"),
-            ("000042", "Mindscape (Software Toolworks)"),
-            ("000043", "Russ Jones Marketing / Niche"),
-            ("000044", "Intone"),
-            ("000045", "Advanced Remote Technologies"),
-            ("000046", "White Instruments"),
-            ("000047", "GT Electronics/Groove Tubes"),
-            ("000048", "Pacific Research & Engineering"),
-            ("000049", "Timeline Vista, Inc."),
-            ("00004A", "Mesa Boogie Ltd."),
-            ("00004B", "FSLI"),
-            ("00004C", "Sequoia Development Group"),
-            ("00004D", "Studio Electronics"),
-            ("00004E", "Euphonix, Inc"),
-            ("00004F", "InterMIDI, Inc."),
-            ("000050", "MIDI Solutions Inc."),
-            ("000051", "3DO Company"),
-            ("000052", "Lightwave Research / High End Systems"),
-            ("000053", "Micro-W Corporation"),
-            ("000054", "Spectral Synthesis, Inc."),
-            ("000055", "Lone Wolf"),
-            ("000056", "Studio Technologies Inc."),
-            ("000057", "Peterson Electro-Musical Product, Inc."),
-            ("000058", "Atari Corporation"),
-            ("000059", "Marion Systems Corporation"),
-            ("00005A", "Design Event"),
-            ("00005B", "Winjammer Software Ltd."),
-            ("00005C", "AT&T Bell Laboratories"),
-            ("00005D", "Reserved"),
-            ("00005E", "Symetrix"),
-            ("00005F", "MIDI the World"),
-            ("000060", "Spatializer"),
-            ("000061", "Micros ‘N MIDI"),
-            ("000062", "Accordians International"),
-            ("000063", "EuPhonics (now 3Com)"),
-            ("000064", "Musonix"),
-            ("000065", "Turtle Beach Systems (Voyetra)"),
-            ("000066", "Loud Technologies / Mackie"),
-            ("000067", "Compuserve"),
-            ("000068", "BEC Technologies"),
-            ("000069", "QRS Music Inc"),
-            ("00006A", "P.G. Music"),
-            ("00006B", "Sierra Semiconductor"),
-            ("00006C", "EpiGraf"),
-            ("00006D", "Electronics Diversified Inc"),
-            ("00006E", "Tune 1000"),
-            ("00006F", "Advanced Micro Devices"),
-            ("000070", "Mediamation"),
-            ("000071", "Sabine Musical Mfg. Co. Inc."),
-            ("000072", "Woog Labs"),
-            ("000073", "Micropolis Corp"),
-            ("000074", "Ta Horng Musical Instrument"),
-            ("000075", "e-Tek Labs (Forte Tech)"),
-            ("000076", "Electro-Voice"),
-            ("000077", "Midisoft Corporation"),
-            ("000078", "QSound Labs"),
-            ("000079", "Westrex"),
-            ("00007A", "Nvidia"),
-            ("00007B", "ESS Technology"),
-            ("00007C", "Media Trix Peripherals"),
-            ("00007D", "Brooktree Corp"),
-            ("00007E", "Otari Corp"),
-            ("00007F", "Key Electronics, Inc."),
-            ("000100", "Shure Incorporated"),
-            ("000101", "AuraSound"),
-            ("000102", "Crystal Semiconductor"),
-            ("000103", "Conexant (Rockwell)"),
-            ("000104", "Silicon Graphics"),
-            ("000105", "M-Audio (Midiman)"),
-            ("000106", "PreSonus"),
-            // 000107?
-            ("000108", "Topaz Enterprises"),
-            ("000109", "Cast Lighting"),
-            ("00010A", "Microsoft Consumer Division"),
-            ("00010B", "Sonic Foundry"),
-            ("00010C", "Line 6 (Fast Forward) (Yamaha)"),
-            ("00010D", "Beatnik Inc"),
-            ("00010E", "Van Koevering Company"),
-            ("00010F", "Altech Systems"),
-            ("000110", "S & S Research"),
-            ("000111", "VLSI Technology"),
-            ("000112", "Chromatic Research"),
-            ("000113", "Sapphire"),
-            ("000114", "IDRC"),
-            ("000115", "Justonic Tuning"),
-            ("000116", "TorComp Research Inc."),
-            ("000117", "Newtek Inc."),
-            ("000118", "Sound Sculpture"),
-            ("000119", "Walker Technical"),
-            ("00011A", "Digital Harmony (PAVO)"),
-            ("00011B", "InVision Interactive"),
-            ("00011C", "T-Square Design"),
-            ("00011D", "Nemesys Music Technology"),
-            ("00011E", "DBX Professional (Harman Intl)"),
-            ("00011F", "Syndyne Corporation"),
-            ("000120", "Bitheadz"),
-            ("000121", "BandLab Technologies"),
-            ("000122", "Analog Devices"),
-            ("000123", "National Semiconductor"),
-            ("000124", "Boom Theory / Adinolfi Alternative Percussion"),
-            ("000125", "Virtual DSP Corporation"),
-            ("000126", "Antares Systems"),
-            ("000127", "Angel Software"),
-            ("000128", "St Louis Music"),
-            ("000129", "Passport Music Software LLC (Gvox)"),
-            ("00012A", "Ashley Audio Inc."),
-            ("00012B", "Vari-Lite Inc."),
-            ("00012C", "Summit Audio Inc."),
-            ("00012D", "Aureal Semiconductor Inc."),
-            ("00012E", "SeaSound LLC"),
-            ("00012F", "U.S. Robotics"),
-            ("000130", "Aurisis Research"),
-            ("000131", "Nearfield Research"),
-            ("000132", "FM7 Inc"),
-            ("000133", "Swivel Systems"),
-            ("000134", "Hyperactive Audio Systems"),
-            ("000135", "MidiLite (Castle Studios Productions)"),
-            ("000136", "Radikal Technologies"),
-            ("000137", "Roger Linn Design"),
-            ("000138", "TC-Helicon Vocal Technologies"),
-            ("000139", "Event Electronics"),
-            ("00013A", "Sonic Network Inc"),
-            ("00013B", "Realtime Music Solutions"),
-            ("00013C", "Apogee Digital"),
-            ("00013D", "Classical Organs, Inc."),
-            ("00013E", "Microtools Inc."),
-            ("00013F", "Numark Industries"),
-            ("000140", "Frontier Design Group, LLC"),
-            ("000141", "Recordare LLC"),
-            ("000142", "Starr Labs"),
-            ("000143", "Voyager Sound Inc."),
-            ("000144", "Manifold Labs"),
-            ("000145", "Aviom Inc."),
-            ("000146", "Mixmeister Technology"),
-            ("000147", "Notation Software"),
-            ("000148", "Mercurial Communications"),
-            ("000149", "Wave Arts"),
-            ("00014A", "Logic Sequencing Devices"),
-            ("00014B", "Axess Electronics"),
-            ("00014C", "Muse Research"),
-            ("00014D", "Open Labs"),
-            ("00014E", "Guillemot Corp"),
-            ("00014F", "Samson Technologies"),
-            ("000150", "Electronic Theatre Controls"),
-            ("000151", "Blackberry (RIM)"),
-            ("000152", "Mobileer"),
-            ("000153", "Synthogy"),
-            ("000154", "Lynx Studio Technology Inc."),
-            ("000155", "Damage Control Engineering LLC"),
-            ("000156", "Yost Engineering, Inc."),
-            ("000157", "Brooks & Forsman Designs LLC / DrumLite"),
-            ("000158", "Infinite Response"),
-            ("000159", "Garritan Corp"),
-            ("00015A", "Plogue Art et Technologie, Inc"),
-            ("00015B", "RJM Music Technology"),
-            ("00015C", "Custom Solutions Software"),
-            ("00015D", "Sonarcana LLC / Highly Liquid"),
-            ("00015E", "Centrance"),
-            ("00015F", "Kesumo LLC"),
-            ("000160", "Stanton (Gibson Brands)"),
-            ("000161", "Livid Instruments"),
-            ("000162", "First Act / 745 Media"),
-            ("000163", "Pygraphics, Inc."),
-            ("000164", "Panadigm Innovations Ltd"),
-            ("000165", "Avedis Zildjian Co"),
-            ("000166", "Auvital Music Corp"),
-            ("000167", "You Rock Guitar (was: Inspired Instruments)"),
-            ("000168", "Chris Grigg Designs"),
-            ("000169", "Slate Digital LLC"),
-            ("00016A", "Mixware"),
-            ("00016B", "Social Entropy"),
-            ("00016C", "Source Audio LLC"),
-            ("00016D", "Ernie Ball / Music Man"),
-            ("00016E", "Fishman"),
-            ("00016F", "Custom Audio Electronics"),
-            ("000170", "American Audio/DJ"),
-            ("000171", "Mega Control Systems"),
-            ("000172", "Kilpatrick Audio"),
-            ("000173", "iConnectivity"),
-            ("000174", "Fractal Audio"),
-            ("000175", "NetLogic Microsystems"),
-            ("000176", "Music Computing"),
-            ("000177", "Nektar Technology Inc"),
-            ("000178", "Zenph Sound Innovations"),
-            ("000179", "DJTechTools.com"),
-            ("00017A", "Rezonance Labs"),
-            ("00017B", "Decibel Eleven"),
-            ("00017C", "CNMAT"),
-            ("00017D", "Media Overkill"),
-            ("00017E", "Confusion Studios"),
-            ("00017F", "moForte Inc"),
-            ("000200", "Miselu Inc"),
-            ("000201", "Amelia's Compass LLC"),
-            ("000202", "Zivix LLC"),
-            ("000203", "Artiphon"),
-            ("000204", "Synclavier Digital"),
-            ("000205", "Light & Sound Control Devices LLC"),
-            ("000206", "Retronyms Inc"),
-            ("000207", "JS Technologies"),
-            ("000208", "Quicco Sound"),
-            ("000209", "A-Designs Audio"),
-            ("00020A", "McCarthy Music Corp"),
-            ("00020B", "Denon DJ"),
-            ("00020C", "Keith Robert Murray"),
-            ("00020D", "Google"),
-            ("00020E", "ISP Technologies"),
-            ("00020F", "Abstrakt Instruments LLC"),
-            ("000210", "Meris LLC"),
-            ("000211", "Sensorpoint LLC"),
-            ("000212", "Hi-Z Labs"),
-            ("000213", "Imitone"),
-            ("000214", "Intellijel Designs Inc."),
-            ("000215", "Dasz Instruments Inc."),
-            ("000216", "Remidi"),
-            ("000217", "Disaster Area Designs LLC"),
-            ("000218", "Universal Audio"),
-            ("000219", "Carter Duncan Corp"),
-            ("00021A", "Essential Technology"),
-            ("00021B", "Cantux Research LLC"),
-            ("00021C", "Hummel Technologies"),
-            ("00021D", "Sensel Inc"),
-            ("00021E", "DBML Group"),
-            ("00021F", "Madrona Labs"),
-            ("000220", "Mesa Boogie"),
-            ("000221", "Effigy Labs"),
-            ("000222", "Amenote"),
-            ("000223", "Red Panda LLC"),
-            ("000224", "OnSong LLC"),
-            ("000225", "Jamboxx Inc."),
-            ("000226", "Electro-Harmonix"),
-            ("000227", "RnD64 Inc"),
-            ("000228", "Neunaber Technology LLC"),
-            ("000229", "Kaom Inc."),
-            ("00022A", "Hallowell EMC"),
-            ("00022B", "Sound Devices, LLC"),
-            ("00022C", "Spectrasonics, Inc"),
-            ("00022D", "Second Sound, LLC"),
-            ("00022E", "8eo (Horn)"),
-            ("00022F", "VIDVOX LLC"),
-            ("000230", "Matthews Effects"),
-            ("000231", "Bright Blue Beetle"),
-            ("000232", "Audio Impressions"),
-            ("000233", " Looperlative"),
-            ("000234", "Steinway"),
-            ("000235", "Ingenious Arts and Technologies LLC"),
-            ("000236", "DCA Audio"),
-            ("000237", "Buchla USA"),
-            ("000238", "Sinicon"),
-            ("000239", "Isla Instruments"),
-            ("00023A", "Soundiron LLC"),
-            ("00023B", "Sonoclast, LLC"),
-            ("00023C", "Copper and Cedar"),
-            ("00023D", "Whirled Notes"),
-            ("00023E", "Cejetvole, LLC"),
-            ("00023F", "DAWn Audio LLC"),
-            ("000240", "Space Brain Circuits"),
-            ("000241", "Caedence"),
-            ("000242", "HCN Designs, LLC (The MIDI Maker)"),
-            ("000243", "PTZOptics"),
-            ("000244", "Noise Engineering"),
-            ("000245", "Synthesia LLC"),
-            ("000246", "Jeff Whitehead Lutherie LLC"),
-            ("000247", "Wampler Pedals Inc."),
-            ("000248", "Tapis Magique"),
-            ("000249", "Leaf Secrets"),
-            ("00024A", "Groove Synthesis"),
-            ("00024B", "Audiocipher Technologies LLC"),
-            ("00024C", "Mellotron Inc."),
-            ("00024D", "Hologram Electronics LLC"),
-            ("00024E", "iCON Americas, LLC"),
-            ("00024F", "Singular Sound"),
-            ("000250", "Genovation Inc"),
-            ("000251", "Method Red"),
-            ("000252", "Brain Inventions"),
-            ("000253", "Synervoz Communications Inc."),
-            ("000254", "Hypertriangle Inc"),
-
-            // European & Other Group
-            ("002000", "Dream SAS"),
-            ("002001", "Strand Lighting"),
-            ("002002", "Amek Div of Harman Industries"),
-            ("002003", "Casa Di Risparmio Di Loreto"),
-            ("002004", "Böhm electronic GmbH"),
-            ("002005", "Syntec Digital Audio"),
-            ("002006", "Trident Audio Developments"),
-            ("002007", "Real World Studio"),
-            ("002008", "Evolution Synthesis, Ltd"),
-            ("002009", "Yes Technology"),
-            ("00200A", "Audiomatica"),
-            ("00200B", "Bontempi SpA (Sigma)"),
-            ("00200C", "F.B.T. Elettronica SpA"),
-            ("00200D", "MidiTemp GmbH"),
-            ("00200E", "LA Audio (Larking Audio)"),
-            ("00200F", "Zero 88 Lighting Limited"),
-            ("002010", "Micon Audio Electronics GmbH"),
-            ("002011", "Forefront Technology"),
-            ("002012", "Studio Audio and Video Ltd."),
-            ("002013", "Kenton Electronics"),
-            ("002014", "Celco/ Electrosonic"),
-            ("002015", "ADB"),
-            ("002016", "Marshall Products Limited"),
-            ("002017", "DDA"),
-            ("002018", "BSS Audio Ltd."),
-            ("002019", "MA Lighting Technology"),
-            ("00201A", "Fatar SRL c/o Music Industries"),
-            ("00201B", "QSC Audio Products Inc."),
-            ("00201C", "Artisan Clasic Organ Inc."),
-            ("00201D", "Orla Spa"),
-            ("00201E", "Pinnacle Audio (Klark Teknik PLC)"),
-            ("00201F", "TC Electronics"),
-            ("002020", "Doepfer Musikelektronik GmbH"),
-            ("002021", "Creative ATC / E-mu"),
-            ("002022", "Seyddo/Minami"),
-            ("002023", "LG Electronics (Goldstar)"),
-            ("002024", "Midisoft sas di M.Cima & C"),
-            ("002025", "Samick Musical Inst. Co. Ltd."),
-            ("002026", "Penny and Giles (Bowthorpe PLC)"),
-            ("002027", "Acorn Computer"),
-            ("002028", "LSC Electronics Pty. Ltd."),
-            ("002029", "Focusrite/Novation"),
-            ("00202A", "Samkyung Mechatronics"),
-            ("00202B", "Medeli Electronics Co."),
-            ("00202C", "Charlie Lab SRL"),
-            ("00202D", "Blue Chip Music Technology"),
-            ("00202E", "BEE OH Corp"),
-            ("00202F", "LG Semicon America"),
-            ("002030", "TESI"),
-            ("002031", "EMAGIC"),
-            ("002032", "Behringer GmbH"),
-            ("002033", "Access Music Electronics"),
-            ("002034", "Synoptic"),
-            ("002035", "Hanmesoft"),
-            ("002036", "Terratec Electronic GmbH"),
-            ("002037", "Proel SpA"),
-            ("002038", "IBK MIDI"),
-            ("002039", "IRCAM"),
-            ("00203A", "Propellerhead Software"),
-            ("00203B", "Red Sound Systems Ltd"),
-            ("00203C", "Elektron ESI AB"),
-            ("00203D", "Sintefex Audio"),
-            ("00203E", "MAM (Music and More)"),
-            ("00203F", "Amsaro GmbH"),
-            ("002040", "CDS Advanced Technology BV (Lanbox)"),
-            ("002041", "Mode Machines (Touched By Sound GmbH)"),
-            ("002042", "DSP Arts"),
-            ("002043", "Phil Rees Music Tech"),
-            ("002044", "Stamer Musikanlagen GmbH"),
-            ("002045", "Musical Muntaner S.A. dba Soundart"),
-            ("002046", "C-Mexx Software"),
-
-            ("00206B", "Arturia"),
-            ("002076", "Teenage Engineering"),
-
-            ("002103", "PreSonus Software Ltd"),
-
-            ("002109", "Native Instruments"),
-
-            ("002110", "ROLI Ltd"),
-
-            ("00211A", "IK Multimedia"),
-
-            ("00211D", "Ableton"),
-
-            ("40", "Kawai Musical Instruments MFG. CO. Ltd"),
-            ("41", "Roland Corporation"),
-            ("42", "Korg Inc."),
-            ("43", "Yamaha"),
-            ("44", "Casio Computer Co. Ltd"),
-            // 0x45 is not assigned
-            ("46", "Kamiya Studio Co. Ltd"),
-            ("47", "Akai Electric Co. Ltd."),
-            ("48", "Victor Company of Japan, Ltd."),
-            ("4B", "Fujitsu Limited"),
-            ("4C", "Sony Corporation"),
-            ("4E", "Teac Corporation"),
-            ("50", "Matsushita Electric Industrial Co. , Ltd"),
-            ("51", "Fostex Corporation"),
-            ("52", "Zoom Corporation"),
-            ("54", "Matsushita Communication Industrial Co., Ltd."),
-            ("55", "Suzuki Musical Instruments MFG. Co., Ltd."),
-            ("56", "Fuji Sound Corporation Ltd."),
-            ("57", "Acoustic Technical Laboratory, Inc."),
-            // 58h is not assigned
-            ("59", "Faith, Inc."),
-            ("5A", "Internet Corporation"),
-            // 5Bh is not assigned
-            ("5C", "Seekers Co. Ltd."),
-            // 5Dh and 5Eh are not assigned
-            ("5F", "SD Card Association"),
-
-            ("004000", "Crimson Technology Inc."),
-            ("004001", "Softbank Mobile Corp"),
-            ("004003", "D&M Holdings Inc."),
-            ("004004", "Xing Inc."),
-            ("004005", "Alpha Theta Corporation"),
-            ("004006", "Pioneer Corporation"),
-            ("004007", "Slik Corporation"),
-        ])
+/// Assignment of a Universal System Exclusive sub-ID #1, with the names
+/// of its sub-ID #2 values. Sub-IDs #1 whose sub-ID #2 carries data
+/// (like a packet or sample number) have no sub-ID #2 names.
+#[derive(Debug)]
+pub struct SubIdAssignment {
+    pub sub_id1: u8,
+    pub name: &'static str,
+    pub sub_id2_names: &'static [(u8, &'static str)],
+}
+
+/// Sub-ID assignments of Universal Non-Real-time messages.
+pub static NON_REAL_TIME_SUB_IDS: &[SubIdAssignment] = &[
+    SubIdAssignment { sub_id1: 0x01, name: "Sample Dump Header", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x02, name: "Sample Data Packet", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x03, name: "Sample Dump Request", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x04, name: "MIDI Time Code", sub_id2_names: &[
+        (0x00, "Special"),
+        (0x01, "Punch In Points"),
+        (0x02, "Punch Out Points"),
+        (0x03, "Delete Punch In Point"),
+        (0x04, "Delete Punch Out Point"),
+        (0x05, "Event Start Point"),
+        (0x06, "Event Stop Point"),
+        (0x07, "Event Start Points with Additional Info"),
+        (0x08, "Event Stop Points with Additional Info"),
+        (0x09, "Delete Event Start Point"),
+        (0x0a, "Delete Event Stop Point"),
+        (0x0b, "Cue Points"),
+        (0x0c, "Cue Points with Additional Info"),
+        (0x0d, "Delete Cue Point"),
+        (0x0e, "Event Name in Additional Info"),
+    ] },
+    SubIdAssignment { sub_id1: 0x05, name: "Sample Dump Extensions", sub_id2_names: &[
+        (0x01, "Loop Points Transmission"),
+        (0x02, "Loop Points Request"),
+        (0x03, "Sample Name Transmission"),
+        (0x04, "Sample Name Request"),
+        (0x05, "Extended Dump Header"),
+        (0x06, "Extended Loop Points Transmission"),
+        (0x07, "Extended Loop Points Request"),
+    ] },
+    SubIdAssignment { sub_id1: 0x06, name: "General Information", sub_id2_names: &[
+        (0x01, "Identity Request"),
+        (0x02, "Identity Reply"),
+    ] },
+    SubIdAssignment { sub_id1: 0x07, name: "File Dump", sub_id2_names: &[
+        (0x01, "Header"),
+        (0x02, "Data Packet"),
+        (0x03, "Request"),
+    ] },
+    SubIdAssignment { sub_id1: 0x08, name: "MIDI Tuning Standard", sub_id2_names: &[
+        (0x00, "Bulk Dump Request"),
+        (0x01, "Bulk Dump Reply"),
+        (0x03, "Tuning Dump Request"),
+        (0x04, "Key-Based Tuning Dump"),
+        (0x05, "Scale/Octave Tuning Dump, 1 Byte Format"),
+        (0x06, "Scale/Octave Tuning Dump, 2 Byte Format"),
+        (0x07, "Single Note Tuning Change with Bank Select"),
+        (0x08, "Scale/Octave Tuning, 1 Byte Format"),
+        (0x09, "Scale/Octave Tuning, 2 Byte Format"),
+    ] },
+    SubIdAssignment { sub_id1: 0x09, name: "General MIDI", sub_id2_names: &[
+        (0x01, "General MIDI 1 System On"),
+        (0x02, "General MIDI System Off"),
+        (0x03, "General MIDI 2 System On"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0a, name: "Downloadable Sounds", sub_id2_names: &[
+        (0x01, "Turn DLS On"),
+        (0x02, "Turn DLS Off"),
+        (0x03, "Turn DLS Voice Allocation Off"),
+        (0x04, "Turn DLS Voice Allocation On"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0b, name: "File Reference Message", sub_id2_names: &[
+        (0x01, "Open File"),
+        (0x02, "Select or Reselect Contents"),
+        (0x03, "Open File and Select Contents"),
+        (0x04, "Close File"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0c, name: "MIDI Visual Control", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x0d, name: "MIDI Capability Inquiry", sub_id2_names: &[
+        (0x20, "Profile Inquiry"),
+        (0x21, "Reply to Profile Inquiry"),
+        (0x22, "Set Profile On"),
+        (0x23, "Set Profile Off"),
+        (0x24, "Profile Enabled Report"),
+        (0x25, "Profile Disabled Report"),
+        (0x30, "Inquiry: Property Exchange Capabilities"),
+        (0x31, "Reply to Property Exchange Capabilities"),
+        (0x34, "Inquiry: Get Property Data"),
+        (0x35, "Reply to Get Property Data"),
+        (0x36, "Inquiry: Set Property Data"),
+        (0x37, "Reply to Set Property Data"),
+        (0x38, "Subscription"),
+        (0x39, "Reply to Subscription"),
+        (0x3f, "Notify"),
+        (0x70, "Discovery"),
+        (0x71, "Reply to Discovery"),
+        (0x72, "Inquiry: Endpoint Information"),
+        (0x73, "Reply to Endpoint Information"),
+        (0x7d, "ACK"),
+        (0x7e, "Invalidate MUID"),
+        (0x7f, "NAK"),
+    ] },
+    SubIdAssignment { sub_id1: 0x7b, name: "End of File", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x7c, name: "Wait", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x7d, name: "Cancel", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x7e, name: "NAK", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x7f, name: "ACK", sub_id2_names: &[] },
+];
+
+/// Sub-ID assignments of Universal Real-time messages.
+pub static REAL_TIME_SUB_IDS: &[SubIdAssignment] = &[
+    SubIdAssignment { sub_id1: 0x01, name: "MIDI Time Code", sub_id2_names: &[
+        (0x01, "Full Message"),
+        (0x02, "User Bits"),
+    ] },
+    SubIdAssignment { sub_id1: 0x02, name: "MIDI Show Control", sub_id2_names: &[
+        (0x00, "MSC Extensions"),
+    ] },
+    SubIdAssignment { sub_id1: 0x03, name: "Notation Information", sub_id2_names: &[
+        (0x01, "Bar Number"),
+        (0x02, "Time Signature (Immediate)"),
+        (0x42, "Time Signature (Delayed)"),
+    ] },
+    SubIdAssignment { sub_id1: 0x04, name: "Device Control", sub_id2_names: &[
+        (0x01, "Master Volume"),
+        (0x02, "Master Balance"),
+        (0x03, "Master Fine Tuning"),
+        (0x04, "Master Coarse Tuning"),
+        (0x05, "Global Parameter Control"),
+    ] },
+    SubIdAssignment { sub_id1: 0x05, name: "Real Time MTC Cueing", sub_id2_names: &[
+        (0x00, "Special"),
+        (0x01, "Punch In Points"),
+        (0x02, "Punch Out Points"),
+        (0x05, "Event Start Points"),
+        (0x06, "Event Stop Points"),
+        (0x07, "Event Start Points with Additional Info"),
+        (0x08, "Event Stop Points with Additional Info"),
+        (0x0b, "Cue Points"),
+        (0x0c, "Cue Points with Additional Info"),
+        (0x0e, "Event Name in Additional Info"),
+    ] },
+    SubIdAssignment { sub_id1: 0x06, name: "MIDI Machine Control Commands", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x07, name: "MIDI Machine Control Responses", sub_id2_names: &[] },
+    SubIdAssignment { sub_id1: 0x08, name: "MIDI Tuning Standard", sub_id2_names: &[
+        (0x02, "Single Note Tuning Change"),
+        (0x07, "Single Note Tuning Change with Bank Select"),
+        (0x08, "Scale/Octave Tuning, 1 Byte Format"),
+        (0x09, "Scale/Octave Tuning, 2 Byte Format"),
+    ] },
+    SubIdAssignment { sub_id1: 0x09, name: "Controller Destination Setting", sub_id2_names: &[
+        (0x01, "Channel Pressure"),
+        (0x02, "Polyphonic Key Pressure"),
+        (0x03, "Controller"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0a, name: "Key-Based Instrument Control", sub_id2_names: &[
+        (0x01, "Key-Based Instrument Control"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0b, name: "Scalable Polyphony MIDI MIP Message", sub_id2_names: &[
+        (0x01, "MIP Message"),
+    ] },
+    SubIdAssignment { sub_id1: 0x0c, name: "Mobile Phone Control Message", sub_id2_names: &[
+        (0x00, "Mobile Phone Control Message"),
+    ] },
+];
+
+/// Finds the sub-ID #1 assignment of a universal message kind.
+fn find_sub_id(kind: UniversalKind, sub_id1: u8) -> Option<&'static SubIdAssignment> {
+    let table = match kind {
+        UniversalKind::NonRealTime => NON_REAL_TIME_SUB_IDS,
+        UniversalKind::RealTime => REAL_TIME_SUB_IDS,
     };
+    table.iter().find(|a| a.sub_id1 == sub_id1)
+}
+
+/// Gets the name of the sub-ID #1 of a universal message, like "General Information".
+pub fn universal_sub_id1_name(kind: UniversalKind, sub_id1: u8) -> Option<&'static str> {
+    find_sub_id(kind, sub_id1).map(|a| a.name)
+}
+
+/// Gets the name of a universal message from its sub-IDs, like "Identity Reply".
+/// If the sub-ID #2 of the message carries data instead of identifying
+/// the message, the name of the sub-ID #1 is returned.
+pub fn universal_sub_id_name(kind: UniversalKind, sub_id1: u8, sub_id2: u8) -> Option<&'static str> {
+    let assignment = find_sub_id(kind, sub_id1)?;
+    if assignment.sub_id2_names.is_empty() {
+        return Some(assignment.name);
+    }
+    assignment.sub_id2_names.iter()
+        .find(|(id, _)| *id == sub_id2)
+        .map(|(_, name)| *name)
+}
+
+/// Gets a full description of a universal message from its sub-IDs,
+/// like "General Information — Identity Reply".
+pub fn universal_sub_id_description(kind: UniversalKind, sub_id1: u8, sub_id2: u8) -> Option<String> {
+    let assignment = find_sub_id(kind, sub_id1)?;
+    if assignment.sub_id2_names.is_empty() {
+        return Some(assignment.name.to_string());
+    }
+    let name = universal_sub_id_name(kind, sub_id1, sub_id2)?;
+    Some(format!("{} — {}", assignment.name, name))
 }
 
+/// Manufacturer names keyed by the ID in uppercase hex.
+#[cfg(feature = "manufacturer-names")]
+static MANUFACTURER_NAMES: phf::Map<&'static str, &'static str> = phf::phf_map! {
+    "01" => "Sequential Circuits",
+    "02" => "IDP",
+    "03" => "Voyetra Turtle Beach, Inc.",
+    "04" => "Moog Music",
+    "05" => "Passport Designs",
+    "06" => "Lexicon Inc.",
+    "07" => "Kurzweil / Young Chang",
+    "08" => "Fender",
+    "09" => "MIDI9",
+    "0A" => "AKG Acoustics",
+    "0B" => "Voyce Music",
+    "0C" => "WaveFrame (Timeline)",
+    "0D" => "ADA Signal Processors, Inc.",
+    "0E" => "Garfield Electronics",
+    "0F" => "Ensoniq",
+    "10" => "Oberheim / Gibson Labs",
+    "11" => "Apple",
+    "12" => "Grey Matter Response",
+    "13" => "Digidesign Inc.",
+    "14" => "Palmtree Instruments",
+    "15" => "JLCooper Electronics",
+    "16" => "Lowrey Organ Company",
+    "17" => "Adams-Smith",
+    "18" => "E-mu",
+    "19" => "Harmony Systems",
+    "1A" => "ART",
+    "1B" => "Baldwin",
+    "1C" => "Eventide",
+    "1D" => "Inventronics",
+    "1E" => "Key Concepts",
+    "1F" => "Clarity",
+    "20" => "Passac",
+    "21" => "Proel Labs (SIEL)",
+    "22" => "Synthaxe (UK)",
+    "23" => "Stepp",
+    "24" => "Hohner",
+    "25" => "Twister",
+    "26" => "Ketron s.r.l.",
+    "27" => "Jellinghaus MS",
+    "28" => "Southworth Music Systems",
+    "29" => "PPG (Germany)",
+    "2A" => "JEN",
+    "2B" => "Solid State Logic Organ Systems",
+    "2C" => "Audio Veritrieb-P. Struven",
+    "2D" => "Neve",
+    "2E" => "Soundtracs Ltd.",
+    "2F" => "Elka",
+    "30" => "Dynacord",
+    "31" => "Viscount International Spa (Intercontinental Electronics)",
+    "32" => "Drawmer",
+    "33" => "Clavia Digital Instruments",
+    "34" => "Audio Architecture",
+    "35" => "Generalmusic Corp SpA",
+    "36" => "Cheetah Marketing",
+    "37" => "C.T.M.",
+    "38" => "Simmons UK",
+    "39" => "Soundcraft Electronics",
+    "3A" => "Steinberg Media Technologies GmbH",
+    "3B" => "Wersi Gmbh",
+    "3C" => "AVAB Niethammer AB",
+    "3D" => "Digigram",
+    "3E" => "Waldorf Electronics GmbH",
+    "3F" => "Quasimidi",
+
+    "000001" => "Time/Warner Interactive",
+    "000002" => "Advanced Gravis Comp. Tech Ltd.",
+    "000003" => "Media Vision",
+    "000004" => "Dornes Research Group",
+    "000005" => "K-Muse",
+    "000006" => "Stypher",
+    "000007" => "Digital Music Corp.",
+    "000008" => "IOTA Systems",
+    "000009" => "New England Digital",
+    "00000A" => "Artisyn",
+    "00000B" => "IVL Technologies Ltd.",
+    "00000C" => "Southern Music Systems",
+    "00000D" => "Lake Butler Sound Company",
+    "00000E" => "Alesis Studio Electronics",
+    "00000F" => "Sound Creation",
+    "000010" => "DOD Electronics Corp.",
+    "000011" => "Studer-Editech",
+    "000012" => "Sonus",
+    "000013" => "Temporal Acuity Products",
+    "000014" => "Perfect Fretworks",
+    "000015" => "KAT Inc.",
+    "000016" => "Opcode Systems",
+    "000017" => "Rane Corporation",
+    "000018" => "Anadi Electronique",
+    "000019" => "KMX",
+    "00001A" => "Allen & Heath Brenell",
+    "00001B" => "Peavey Electronics",
+    "00001C" => "360 Systems",
+    "00001D" => "Spectrum Design and Development",
+    "00001E" => "Marquis Music",
+    "00001F" => "Zeta Systems",
+    "000020" => "Axxes (Brian Parsonett)",
+    "000021" => "Orban",
+    "000022" => "Indian Valley Mfg.",
+    "000023" => "Triton",
+    "000024" => "KTI",
+    "000025" => "Breakway Technologies",
+    "000026" => "Leprecon / CAE Inc.",
+    "000027" => "Harrison Systems Inc.",
+    "000028" => "Future Lab/Mark Kuo",
+    "000029" => "Rocktron Corporation",
+    "00002A" => "PianoDisc",
+    "00002B" => "Cannon Research Group",
+    "00002C" => "Reserved",
+    "00002D" => "Rodgers Instrument LLC",
+    "00002E" => "Blue Sky Logic",
+    "00002F" => "Encore Electronics",
+    "000030" => "Uptown",
+    "000031" => "Voce",
+    "000032" => "CTI Audio, Inc. (Musically Intel. Devs.)",
+    "000033" => "S3 Incorporated",
+    "000034" => "Broderbund / Red Orb",
+    "000035" => "Allen Organ Co.",
+    "000036" => "Reserved",
+    "000037" => "Music Quest",
+    "000038" => "Aphex",
+    "000039" => "Gallien Krueger",
+    "00003A" => "IBM",
+    "00003B" => "Mark Of The Unicorn",
+    "00003C" => "Hotz Corporation",
+    "00003D" => "ETA Lighting",
+    "00003E" => "NSI Corporation",
+    "00003F" => "Ad Lib, Inc.",
+    "000040" => "Richmond Sound Design",
+    "000041" => "Microsoft",
+    "000042" => "Mindscape (Software Toolworks)",
+    "000043" => "Russ Jones Marketing / Niche",
+    "000044" => "Intone",
+    "000045" => "Advanced Remote Technologies",
+    "000046" => "White Instruments",
+    "000047" => "GT Electronics/Groove Tubes",
+    "000048" => "Pacific Research & Engineering",
+    "000049" => "Timeline Vista, Inc.",
+    "00004A" => "Mesa Boogie Ltd.",
+    "00004B" => "FSLI",
+    "00004C" => "Sequoia Development Group",
+    "00004D" => "Studio Electronics",
+    "00004E" => "Euphonix, Inc",
+    "00004F" => "InterMIDI, Inc.",
+    "000050" => "MIDI Solutions Inc.",
+    "000051" => "3DO Company",
+    "000052" => "Lightwave Research / High End Systems",
+    "000053" => "Micro-W Corporation",
+    "000054" => "Spectral Synthesis, Inc.",
+    "000055" => "Lone Wolf",
+    "000056" => "Studio Technologies Inc.",
+    "000057" => "Peterson Electro-Musical Product, Inc.",
+    "000058" => "Atari Corporation",
+    "000059" => "Marion Systems Corporation",
+    "00005A" => "Design Event",
+    "00005B" => "Winjammer Software Ltd.",
+    "00005C" => "AT&T Bell Laboratories",
+    "00005D" => "Reserved",
+    "00005E" => "Symetrix",
+    "00005F" => "MIDI the World",
+    "000060" => "Spatializer",
+    "000061" => "Micros ‘N MIDI",
+    "000062" => "Accordians International",
+    "000063" => "EuPhonics (now 3Com)",
+    "000064" => "Musonix",
+    "000065" => "Turtle Beach Systems (Voyetra)",
+    "000066" => "Loud Technologies / Mackie",
+    "000067" => "Compuserve",
+    "000068" => "BEC Technologies",
+    "000069" => "QRS Music Inc",
+    "00006A" => "P.G. Music",
+    "00006B" => "Sierra Semiconductor",
+    "00006C" => "EpiGraf",
+    "00006D" => "Electronics Diversified Inc",
+    "00006E" => "Tune 1000",
+    "00006F" => "Advanced Micro Devices",
+    "000070" => "Mediamation",
+    "000071" => "Sabine Musical Mfg. Co. Inc.",
+    "000072" => "Woog Labs",
+    "000073" => "Micropolis Corp",
+    "000074" => "Ta Horng Musical Instrument",
+    "000075" => "e-Tek Labs (Forte Tech)",
+    "000076" => "Electro-Voice",
+    "000077" => "Midisoft Corporation",
+    "000078" => "QSound Labs",
+    "000079" => "Westrex",
+    "00007A" => "Nvidia",
+    "00007B" => "ESS Technology",
+    "00007C" => "Media Trix Peripherals",
+    "00007D" => "Brooktree Corp",
+    "00007E" => "Otari Corp",
+    "00007F" => "Key Electronics, Inc.",
+    "000100" => "Shure Incorporated",
+    "000101" => "AuraSound",
+    "000102" => "Crystal Semiconductor",
+    "000103" => "Conexant (Rockwell)",
+    "000104" => "Silicon Graphics",
+    "000105" => "M-Audio (Midiman)",
+    "000106" => "PreSonus",
+    // 000107?
+    "000108" => "Topaz Enterprises",
+    "000109" => "Cast Lighting",
+    "00010A" => "Microsoft Consumer Division",
+    "00010B" => "Sonic Foundry",
+    "00010C" => "Line 6 (Fast Forward) (Yamaha)",
+    "00010D" => "Beatnik Inc",
+    "00010E" => "Van Koevering Company",
+    "00010F" => "Altech Systems",
+    "000110" => "S & S Research",
+    "000111" => "VLSI Technology",
+    "000112" => "Chromatic Research",
+    "000113" => "Sapphire",
+    "000114" => "IDRC",
+    "000115" => "Justonic Tuning",
+    "000116" => "TorComp Research Inc.",
+    "000117" => "Newtek Inc.",
+    "000118" => "Sound Sculpture",
+    "000119" => "Walker Technical",
+    "00011A" => "Digital Harmony (PAVO)",
+    "00011B" => "InVision Interactive",
+    "00011C" => "T-Square Design",
+    "00011D" => "Nemesys Music Technology",
+    "00011E" => "DBX Professional (Harman Intl)",
+    "00011F" => "Syndyne Corporation",
+    "000120" => "Bitheadz",
+    "000121" => "BandLab Technologies",
+    "000122" => "Analog Devices",
+    "000123" => "National Semiconductor",
+    "000124" => "Boom Theory / Adinolfi Alternative Percussion",
+    "000125" => "Virtual DSP Corporation",
+    "000126" => "Antares Systems",
+    "000127" => "Angel Software",
+    "000128" => "St Louis Music",
+    "000129" => "Passport Music Software LLC (Gvox)",
+    "00012A" => "Ashley Audio Inc.",
+    "00012B" => "Vari-Lite Inc.",
+    "00012C" => "Summit Audio Inc.",
+    "00012D" => "Aureal Semiconductor Inc.",
+    "00012E" => "SeaSound LLC",
+    "00012F" => "U.S. Robotics",
+    "000130" => "Aurisis Research",
+    "000131" => "Nearfield Research",
+    "000132" => "FM7 Inc",
+    "000133" => "Swivel Systems",
+    "000134" => "Hyperactive Audio Systems",
+    "000135" => "MidiLite (Castle Studios Productions)",
+    "000136" => "Radikal Technologies",
+    "000137" => "Roger Linn Design",
+    "000138" => "TC-Helicon Vocal Technologies",
+    "000139" => "Event Electronics",
+    "00013A" => "Sonic Network Inc",
+    "00013B" => "Realtime Music Solutions",
+    "00013C" => "Apogee Digital",
+    "00013D" => "Classical Organs, Inc.",
+    "00013E" => "Microtools Inc.",
+    "00013F" => "Numark Industries",
+    "000140" => "Frontier Design Group, LLC",
+    "000141" => "Recordare LLC",
+    "000142" => "Starr Labs",
+    "000143" => "Voyager Sound Inc.",
+    "000144" => "Manifold Labs",
+    "000145" => "Aviom Inc.",
+    "000146" => "Mixmeister Technology",
+    "000147" => "Notation Software",
+    "000148" => "Mercurial Communications",
+    "000149" => "Wave Arts",
+    "00014A" => "Logic Sequencing Devices",
+    "00014B" => "Axess Electronics",
+    "00014C" => "Muse Research",
+    "00014D" => "Open Labs",
+    "00014E" => "Guillemot Corp",
+    "00014F" => "Samson Technologies",
+    "000150" => "Electronic Theatre Controls",
+    "000151" => "Blackberry (RIM)",
+    "000152" => "Mobileer",
+    "000153" => "Synthogy",
+    "000154" => "Lynx Studio Technology Inc.",
+    "000155" => "Damage Control Engineering LLC",
+    "000156" => "Yost Engineering, Inc.",
+    "000157" => "Brooks & Forsman Designs LLC / DrumLite",
+    "000158" => "Infinite Response",
+    "000159" => "Garritan Corp",
+    "00015A" => "Plogue Art et Technologie, Inc",
+    "00015B" => "RJM Music Technology",
+    "00015C" => "Custom Solutions Software",
+    "00015D" => "Sonarcana LLC / Highly Liquid",
+    "00015E" => "Centrance",
+    "00015F" => "Kesumo LLC",
+    "000160" => "Stanton (Gibson Brands)",
+    "000161" => "Livid Instruments",
+    "000162" => "First Act / 745 Media",
+    "000163" => "Pygraphics, Inc.",
+    "000164" => "Panadigm Innovations Ltd",
+    "000165" => "Avedis Zildjian Co",
+    "000166" => "Auvital Music Corp",
+    "000167" => "You Rock Guitar (was: Inspired Instruments)",
+    "000168" => "Chris Grigg Designs",
+    "000169" => "Slate Digital LLC",
+    "00016A" => "Mixware",
+    "00016B" => "Social Entropy",
+    "00016C" => "Source Audio LLC",
+    "00016D" => "Ernie Ball / Music Man",
+    "00016E" => "Fishman",
+    "00016F" => "Custom Audio Electronics",
+    "000170" => "American Audio/DJ",
+    "000171" => "Mega Control Systems",
+    "000172" => "Kilpatrick Audio",
+    "000173" => "iConnectivity",
+    "000174" => "Fractal Audio",
+    "000175" => "NetLogic Microsystems",
+    "000176" => "Music Computing",
+    "000177" => "Nektar Technology Inc",
+    "000178" => "Zenph Sound Innovations",
+    "000179" => "DJTechTools.com",
+    "00017A" => "Rezonance Labs",
+    "00017B" => "Decibel Eleven",
+    "00017C" => "CNMAT",
+    "00017D" => "Media Overkill",
+    "00017E" => "Confusion Studios",
+    "00017F" => "moForte Inc",
+    "000200" => "Miselu Inc",
+    "000201" => "Amelia's Compass LLC",
+    "000202" => "Zivix LLC",
+    "000203" => "Artiphon",
+    "000204" => "Synclavier Digital",
+    "000205" => "Light & Sound Control Devices LLC",
+    "000206" => "Retronyms Inc",
+    "000207" => "JS Technologies",
+    "000208" => "Quicco Sound",
+    "000209" => "A-Designs Audio",
+    "00020A" => "McCarthy Music Corp",
+    "00020B" => "Denon DJ",
+    "00020C" => "Keith Robert Murray",
+    "00020D" => "Google",
+    "00020E" => "ISP Technologies",
+    "00020F" => "Abstrakt Instruments LLC",
+    "000210" => "Meris LLC",
+    "000211" => "Sensorpoint LLC",
+    "000212" => "Hi-Z Labs",
+    "000213" => "Imitone",
+    "000214" => "Intellijel Designs Inc.",
+    "000215" => "Dasz Instruments Inc.",
+    "000216" => "Remidi",
+    "000217" => "Disaster Area Designs LLC",
+    "000218" => "Universal Audio",
+    "000219" => "Carter Duncan Corp",
+    "00021A" => "Essential Technology",
+    "00021B" => "Cantux Research LLC",
+    "00021C" => "Hummel Technologies",
+    "00021D" => "Sensel Inc",
+    "00021E" => "DBML Group",
+    "00021F" => "Madrona Labs",
+    "000220" => "Mesa Boogie",
+    "000221" => "Effigy Labs",
+    "000222" => "Amenote",
+    "000223" => "Red Panda LLC",
+    "000224" => "OnSong LLC",
+    "000225" => "Jamboxx Inc.",
+    "000226" => "Electro-Harmonix",
+    "000227" => "RnD64 Inc",
+    "000228" => "Neunaber Technology LLC",
+    "000229" => "Kaom Inc.",
+    "00022A" => "Hallowell EMC",
+    "00022B" => "Sound Devices, LLC",
+    "00022C" => "Spectrasonics, Inc",
+    "00022D" => "Second Sound, LLC",
+    "00022E" => "8eo (Horn)",
+    "00022F" => "VIDVOX LLC",
+    "000230" => "Matthews Effects",
+    "000231" => "Bright Blue Beetle",
+    "000232" => "Audio Impressions",
+    "000233" => " Looperlative",
+    "000234" => "Steinway",
+    "000235" => "Ingenious Arts and Technologies LLC",
+    "000236" => "DCA Audio",
+    "000237" => "Buchla USA",
+    "000238" => "Sinicon",
+    "000239" => "Isla Instruments",
+    "00023A" => "Soundiron LLC",
+    "00023B" => "Sonoclast, LLC",
+    "00023C" => "Copper and Cedar",
+    "00023D" => "Whirled Notes",
+    "00023E" => "Cejetvole, LLC",
+    "00023F" => "DAWn Audio LLC",
+    "000240" => "Space Brain Circuits",
+    "000241" => "Caedence",
+    "000242" => "HCN Designs, LLC (The MIDI Maker)",
+    "000243" => "PTZOptics",
+    "000244" => "Noise Engineering",
+    "000245" => "Synthesia LLC",
+    "000246" => "Jeff Whitehead Lutherie LLC",
+    "000247" => "Wampler Pedals Inc.",
+    "000248" => "Tapis Magique",
+    "000249" => "Leaf Secrets",
+    "00024A" => "Groove Synthesis",
+    "00024B" => "Audiocipher Technologies LLC",
+    "00024C" => "Mellotron Inc.",
+    "00024D" => "Hologram Electronics LLC",
+    "00024E" => "iCON Americas, LLC",
+    "00024F" => "Singular Sound",
+    "000250" => "Genovation Inc",
+    "000251" => "Method Red",
+    "000252" => "Brain Inventions",
+    "000253" => "Synervoz Communications Inc.",
+    "000254" => "Hypertriangle Inc",
+
+    // European & Other Group
+    "002000" => "Dream SAS",
+    "002001" => "Strand Lighting",
+    "002002" => "Amek Div of Harman Industries",
+    "002003" => "Casa Di Risparmio Di Loreto",
+    "002004" => "Böhm electronic GmbH",
+    "002005" => "Syntec Digital Audio",
+    "002006" => "Trident Audio Developments",
+    "002007" => "Real World Studio",
+    "002008" => "Evolution Synthesis, Ltd",
+    "002009" => "Yes Technology",
+    "00200A" => "Audiomatica",
+    "00200B" => "Bontempi SpA (Sigma)",
+    "00200C" => "F.B.T. Elettronica SpA",
+    "00200D" => "MidiTemp GmbH",
+    "00200E" => "LA Audio (Larking Audio)",
+    "00200F" => "Zero 88 Lighting Limited",
+    "002010" => "Micon Audio Electronics GmbH",
+    "002011" => "Forefront Technology",
+    "002012" => "Studio Audio and Video Ltd.",
+    "002013" => "Kenton Electronics",
+    "002014" => "Celco/ Electrosonic",
+    "002015" => "ADB",
+    "002016" => "Marshall Products Limited",
+    "002017" => "DDA",
+    "002018" => "BSS Audio Ltd.",
+    "002019" => "MA Lighting Technology",
+    "00201A" => "Fatar SRL c/o Music Industries",
+    "00201B" => "QSC Audio Products Inc.",
+    "00201C" => "Artisan Clasic Organ Inc.",
+    "00201D" => "Orla Spa",
+    "00201E" => "Pinnacle Audio (Klark Teknik PLC)",
+    "00201F" => "TC Electronics",
+    "002020" => "Doepfer Musikelektronik GmbH",
+    "002021" => "Creative ATC / E-mu",
+    "002022" => "Seyddo/Minami",
+    "002023" => "LG Electronics (Goldstar)",
+    "002024" => "Midisoft sas di M.Cima & C",
+    "002025" => "Samick Musical Inst. Co. Ltd.",
+    "002026" => "Penny and Giles (Bowthorpe PLC)",
+    "002027" => "Acorn Computer",
+    "002028" => "LSC Electronics Pty. Ltd.",
+    "002029" => "Focusrite/Novation",
+    "00202A" => "Samkyung Mechatronics",
+    "00202B" => "Medeli Electronics Co.",
+    "00202C" => "Charlie Lab SRL",
+    "00202D" => "Blue Chip Music Technology",
+    "00202E" => "BEE OH Corp",
+    "00202F" => "LG Semicon America",
+    "002030" => "TESI",
+    "002031" => "EMAGIC",
+    "002032" => "Behringer GmbH",
+    "002033" => "Access Music Electronics",
+    "002034" => "Synoptic",
+    "002035" => "Hanmesoft",
+    "002036" => "Terratec Electronic GmbH",
+    "002037" => "Proel SpA",
+    "002038" => "IBK MIDI",
+    "002039" => "IRCAM",
+    "00203A" => "Propellerhead Software",
+    "00203B" => "Red Sound Systems Ltd",
+    "00203C" => "Elektron ESI AB",
+    "00203D" => "Sintefex Audio",
+    "00203E" => "MAM (Music and More)",
+    "00203F" => "Amsaro GmbH",
+    "002040" => "CDS Advanced Technology BV (Lanbox)",
+    "002041" => "Mode Machines (Touched By Sound GmbH)",
+    "002042" => "DSP Arts",
+    "002043" => "Phil Rees Music Tech",
+    "002044" => "Stamer Musikanlagen GmbH",
+    "002045" => "Musical Muntaner S.A. dba Soundart",
+    "002046" => "C-Mexx Software",
+
+    "00206B" => "Arturia",
+    "002076" => "Teenage Engineering",
+
+    "002103" => "PreSonus Software Ltd",
+
+    "002109" => "Native Instruments",
+
+    "002110" => "ROLI Ltd",
+
+    "00211A" => "IK Multimedia",
+
+    "00211D" => "Ableton",
+
+    "40" => "Kawai Musical Instruments MFG. CO. Ltd",
+    "41" => "Roland Corporation",
+    "42" => "Korg Inc.",
+    "43" => "Yamaha",
+    "44" => "Casio Computer Co. Ltd",
+    // 0x45 is not assigned
+    "46" => "Kamiya Studio Co. Ltd",
+    "47" => "Akai Electric Co. Ltd.",
+    "48" => "Victor Company of Japan, Ltd.",
+    "4B" => "Fujitsu Limited",
+    "4C" => "Sony Corporation",
+    "4E" => "Teac Corporation",
+    "50" => "Matsushita Electric Industrial Co. , Ltd",
+    "51" => "Fostex Corporation",
+    "52" => "Zoom Corporation",
+    "54" => "Matsushita Communication Industrial Co., Ltd.",
+    "55" => "Suzuki Musical Instruments MFG. Co., Ltd.",
+    "56" => "Fuji Sound Corporation Ltd.",
+    "57" => "Acoustic Technical Laboratory, Inc.",
+    // 58h is not assigned
+    "59" => "Faith, Inc.",
+    "5A" => "Internet Corporation",
+    // 5Bh is not assigned
+    "5C" => "Seekers Co. Ltd.",
+    // 5Dh and 5Eh are not assigned
+    "5F" => "SD Card Association",
+
+    "004000" => "Crimson Technology Inc.",
+    "004001" => "Softbank Mobile Corp",
+    "004003" => "D&M Holdings Inc.",
+    "004004" => "Xing Inc.",
+    "004005" => "Alpha Theta Corporation",
+    "004006" => "Pioneer Corporation",
+    "004007" => "Slik Corporation",
+};
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    fn data_bytes(max_length: usize) -> impl Strategy<Value = Vec<u8>> {
+        proptest::collection::vec(0x00u8..0x80, 0..max_length)
+    }
+
+    fn arbitrary_manufacturer() -> impl Strategy<Value = Manufacturer> {
+        prop_oneof![
+            (0x01u8..=DEVELOPMENT).prop_map(Manufacturer::Standard),
+            (0x00u8..0x80, 0x00u8..0x80).prop_map(|(b1, b2)| Manufacturer::Extended([0x00, b1, b2])),
+        ]
+    }
+
+    fn arbitrary_message() -> impl Strategy<Value = Message> {
+        prop_oneof![
+            (arbitrary_manufacturer(), data_bytes(64))
+                .prop_map(|(manufacturer, payload)| Message::ManufacturerSpecific { manufacturer, payload }),
+            (any::<bool>(), 0x00u8..0x80, 0x00u8..0x80, 0x00u8..0x80, data_bytes(64))
+                .prop_map(|(real_time, target, sub_id1, sub_id2, payload)| Message::Universal {
+                    kind: if real_time { UniversalKind::RealTime } else { UniversalKind::NonRealTime },
+                    target, sub_id1, sub_id2, payload
+                }),
+        ]
+    }
 
     #[test]
     fn new_message_manufacturer_standard() {
@@ -911,19 +2039,546 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "manufacturer-names")]
     fn manufacturer_display_name() {
         let manufacturer = Manufacturer::Standard(0x43);
         assert_eq!(format!("{}", manufacturer), "Yamaha");
     }
 
     #[test]
+    #[cfg(feature = "manufacturer-names")]
     fn find_manufacturer_name_success() {
         let manuf = find_manufacturer("yama").unwrap();
         assert_eq!(manuf.name(), "Yamaha");
     }
 
+    #[test]
+    #[cfg(feature = "manufacturer-names")]
+    fn known_manufacturers() {
+        assert!(Manufacturer::Standard(0x43).is_known());
+        assert!(Manufacturer::Extended([0x00, 0x20, 0x29]).is_known());
+        assert!(Manufacturer::Standard(DEVELOPMENT).is_known());
+        assert!(!Manufacturer::Standard(0x60).is_known());
+        assert!(!Manufacturer::Extended([0x00, 0x7F, 0x7F]).is_known());
+    }
+
+    #[test]
+    #[cfg(not(feature = "manufacturer-names"))]
+    fn manufacturer_names_omitted() {
+        assert_eq!(Manufacturer::Standard(0x43).name(), "Unknown manufacturer");
+        assert!(!Manufacturer::Standard(0x43).is_known());
+        assert!(find_manufacturer("yama").is_err());
+    }
+
     #[test]
     fn find_manufacturer_name_failure() {
         assert!(find_manufacturer("humppaurku").is_err());
     }
+
+    #[test]
+    fn classify_kinds() {
+        assert_eq!(classify(&[0xF0, 0x7E, 0x00, 0x06, 0x01, 0xF7]), MessageKind::UniversalNonRealTime);
+        assert_eq!(classify(&[0xF0, 0x7F, 0x7F, 0x04, 0x01]), MessageKind::UniversalRealTime);
+        assert_eq!(classify(&[0xF0, 0x43, 0xF7]), MessageKind::ManufacturerStandard);
+        assert_eq!(classify(&[0xF0, 0x00, 0x20, 0x29]), MessageKind::ManufacturerExtended);
+        assert_eq!(classify(&[0xF0, 0x7D, 0xF7]), MessageKind::Development);
+        assert_eq!(classify(&[0xF0, 0x00, 0x20]), MessageKind::Invalid);
+        assert_eq!(classify(&[0x90, 0x40, 0x7F]), MessageKind::Invalid);
+        assert_eq!(classify(&[]), MessageKind::Invalid);
+    }
+
+    #[test]
+    fn manufacturer_groups() {
+        let cases = [
+            (Manufacturer::Standard(0x01), ManufacturerGroup::NorthAmerican),
+            (Manufacturer::Standard(0x1F), ManufacturerGroup::NorthAmerican),
+            (Manufacturer::Standard(0x20), ManufacturerGroup::EuropeanAndOther),
+            (Manufacturer::Standard(0x41), ManufacturerGroup::Japanese),
+            (Manufacturer::Standard(0x60), ManufacturerGroup::Unassigned),
+            (Manufacturer::Standard(0x7D), ManufacturerGroup::Development),
+            (Manufacturer::Extended([0x00, 0x00, 0x01]), ManufacturerGroup::NorthAmerican),
+            (Manufacturer::Extended([0x00, 0x21, 0x09]), ManufacturerGroup::EuropeanAndOther),
+            (Manufacturer::Extended([0x00, 0x40, 0x00]), ManufacturerGroup::Japanese),
+            (Manufacturer::Extended([0x00, 0x5F, 0x7F]), ManufacturerGroup::Japanese),
+            (Manufacturer::Extended([0x00, 0x60, 0x00]), ManufacturerGroup::Unassigned),
+            (Manufacturer::Extended([0x00, 0x00, 0x00]), ManufacturerGroup::Unassigned),
+        ];
+        for (manufacturer, group) in cases {
+            assert_eq!(manufacturer.group(), group, "{:?}", manufacturer);
+        }
+    }
+
+    #[test]
+    fn kind_predicates() {
+        let yamaha = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0xF7]).unwrap();
+        let universal = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert!(yamaha.is_manufacturer_specific() && !yamaha.is_universal());
+        assert!(universal.is_universal() && !universal.is_manufacturer_specific());
+        assert!(yamaha.is_from(&Manufacturer::Standard(0x43)));
+        assert!(!yamaha.is_from(&Manufacturer::Standard(0x41)));
+        assert!(!universal.is_from(&Manufacturer::Standard(0x7E)));
+    }
+
+    #[test]
+    fn filter_by_header() {
+        let data = [
+            0x00, 0xF0, 0x43, 0x00, 0x09, 0xF7,  // stray byte before the initiator
+            0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7,
+            0xF0, 0x00, 0x20, 0x29, 0x01, 0xF7,
+            0xF0, 0xF7,
+            0xF0, 0x43, 0x10,  // no terminator
+        ];
+        let yamaha = filter_messages(&data, |header| header.manufacturer == Some(Manufacturer::Standard(0x43)));
+        assert_eq!(yamaha, vec![&data[1..6]]);
+        let identity = filter_messages(&data, |header| header.sub_ids == Some((0x06, 0x01)));
+        assert_eq!(identity, vec![&data[6..12]]);
+        assert_eq!(filter_messages(&data, |_| true).len(), 3);
+        assert_eq!(filter_messages(&data, |header| header.length > 5).len(), 2);
+
+        let header = MessageHeader::read(&data[6..12]);
+        assert_eq!((header.kind, header.target, header.manufacturer), (MessageKind::UniversalNonRealTime, Some(0x7F), None));
+    }
+
+    #[test]
+    fn peek_manufacturer() {
+        assert_eq!(Manufacturer::peek(&[0xF0, 0x43, 0x00, 0xF7]), Some(Manufacturer::Standard(0x43)));
+        assert_eq!(Manufacturer::peek(&[0xF0, 0x00, 0x20, 0x29, 0xF7]), Some(Manufacturer::Extended([0x00, 0x20, 0x29])));
+        assert_eq!(Manufacturer::peek(&[0xF0, 0x7E, 0x00, 0x06, 0x01, 0xF7]), None);
+        assert_eq!(Manufacturer::peek(&[0xF0, 0x00]), None);
+    }
+
+    #[test]
+    fn compare_ignoring_channel() {
+        // Kawai K4 one patch dumps on channels 1 and 3
+        let a = Message::from_bytes(&[0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, 0x3F, 0x12, 0xF7]).unwrap();
+        let b = Message::from_bytes(&[0xF0, 0x40, 0x02, 0x20, 0x00, 0x04, 0x00, 0x3F, 0x12, 0xF7]).unwrap();
+        assert_ne!(a, b);
+        let options = CompareOptions { ignored_offsets: vec![0], ..Default::default() };
+        assert!(a.eq_ignoring(&b, &options));
+    }
+
+    #[test]
+    fn compare_ignoring_target_and_checksum() {
+        let a = Message::from_bytes(&[0xF0, 0x7E, 0x00, 0x06, 0x02, 0x43, 0x10, 0xF7]).unwrap();
+        let b = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x02, 0x43, 0x11, 0xF7]).unwrap();
+        let options = CompareOptions { ignore_device_id: true, ignore_checksum: true, ..Default::default() };
+        assert!(a.eq_ignoring(&b, &options));
+        assert!(!a.eq_ignoring(&b, &CompareOptions::default()));
+    }
+
+    #[test]
+    fn write_messages_to_file() {
+        let dir = std::env::temp_dir().join(format!("syxpack-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bank.syx");
+        let messages = vec![
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(0x43), payload: vec![0x01] },
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(0x41), payload: vec![] },
+        ];
+        write_messages(&path, &messages).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0xF0, 0x43, 0x01, 0xF7, 0xF0, 0x41, 0xF7]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);  // no temporary file left
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_messages_from_threads() {
+        let dir = std::env::temp_dir().join(format!("syxpack-threads-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bank.syx");
+        let contents: Vec<Vec<u8>> = (0..2u8).map(|i| vec![0xF0, 0x43, i, 0xF7]).collect();
+        std::thread::scope(|scope| {
+            for data in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    let messages = vec![Message::from_bytes(data).unwrap(); 1000];
+                    for _ in 0..20 {
+                        write_messages(path, &messages).unwrap();
+                    }
+                });
+            }
+        });
+        let written = fs::read(&path).unwrap();
+        assert!(contents.iter().any(|data| written == data.repeat(1000)));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hex_formats() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert_eq!(message.to_hex_string(&HexFormat::default()), "F0 7E 7F 06 01 F7");
+        assert_eq!(message.to_hex_string(&HexFormat::compact()), "F07E7F0601F7");
+        assert_eq!(message.to_hex_string(&HexFormat::manual()), "F0h 7Eh 7Fh 06h 01h F7h");
+
+        let format = HexFormat { separator: ",".to_string(), uppercase: false, bytes_per_line: Some(4), suffix: false };
+        assert_eq!(message.to_hex_string(&format), "f0,7e,7f,06\n01,f7");
+
+        let manufacturer = Manufacturer::Extended([0x00, 0x20, 0x29]);
+        assert_eq!(manufacturer.to_hex_string(&HexFormat::compact()), manufacturer.to_hex());
+    }
+
+    #[test]
+    fn size_limit() {
+        let data = vec![0xF0, 0x43, 0x00, 0x01, 0x02, 0xF7];
+        assert!(Message::from_bytes_with_limit(&data, 6).is_ok());
+        assert!(matches!(Message::from_bytes_with_limit(&data, 5), Err(SystemExclusiveError::MessageTooLong)));
+
+        let mut swallowed = vec![0xF0, 0x43];
+        swallowed.extend(vec![0x00; 100]);  // missing terminator
+        swallowed.extend([0xF0, 0x43, 0x00, 0x01, 0xF7]);
+        assert!(matches!(split_messages_with_limit(swallowed.clone(), 64), Err(SystemExclusiveError::MessageTooLong)));
+        assert_eq!(split_messages_with_limit(swallowed, DEFAULT_SIZE_LIMIT).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invalid_input_does_not_panic() {
+        assert!(Message::from_bytes(&[]).is_err());
+        assert!(Message::from_bytes(&[0xF0]).is_err());
+        assert!(Message::from_bytes(&[0xF0, 0xF7]).is_err());
+        assert!(Manufacturer::from_bytes(&[0x00]).is_err());
+        assert!(Manufacturer::from_bytes(&[0x43, 0x00, 0x00]).is_err());
+        assert!(Manufacturer::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn short_messages() {
+        for data in [
+            vec![0xF0, 0x43, 0xF7],
+            vec![0xF0, DEVELOPMENT, 0xF7],
+            vec![0xF0, 0x00, 0x20, 0x29, 0xF7],
+            vec![0xF0, NON_REAL_TIME, 0x7F, 0x09, 0x01, 0xF7],
+        ] {
+            assert_eq!(Message::from_bytes(&data).unwrap().to_bytes(), data);
+        }
+        assert!(Message::from_bytes(&[0xF0, NON_REAL_TIME, 0x7F, 0x09, 0xF7]).is_err());
+        assert!(Message::from_bytes(&[0xF0, 0x00, 0x20, 0xF7]).is_err());
+        assert!(Message::from_bytes(&[0xF0, 0x90, 0xF7]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn message_round_trip(message in arbitrary_message()) {
+            let bytes = message.to_bytes();
+            prop_assert_eq!(Message::from_bytes(&bytes).unwrap(), message);
+        }
+
+        #[test]
+        fn from_bytes_never_panics(data in proptest::collection::vec(any::<u8>(), 0..32)) {
+            let _ = Message::from_bytes(&data);
+        }
+
+        #[test]
+        fn parsing_never_panics(data in proptest::collection::vec(any::<u8>(), 0..300)) {
+            exercise_parsers(&data);
+        }
+
+        #[test]
+        fn decoding_never_panics(data in proptest::collection::vec(any::<u8>(), 0..64), header in 0usize..6) {
+            // Start with a valid header to get past the framing checks
+            let headers: [&[u8]; 6] = [&[0xF0, 0x7E], &[0xF0, 0x7F], &[0xF0, 0x41], &[0xF0, 0x43], &[0xF0, 0x00, 0x20, 0x32], &[0xF0, 0x40]];
+            let mut bytes = headers[header].to_vec();
+            bytes.extend(data.iter().map(|b| b & 0x7f));
+            bytes.push(TERMINATOR);
+            exercise_parsers(&bytes);
+        }
+
+        #[test]
+        fn canonical_bytes_match_input(data in proptest::collection::vec(any::<u8>(), 0..32)) {
+            let mut data = data;
+            data.insert(0, INITIATOR);
+            data.push(TERMINATOR);
+            if let Ok(message) = Message::from_bytes(&data) {
+                prop_assert_eq!(message.canonical_bytes(), data);
+            }
+        }
+    }
+
+    /// Runs the parsing, splitting and decoding functions over the data.
+    fn exercise_parsers(data: &[u8]) {
+        let _ = classify(data);
+        let _ = message_count(data);
+        let _ = split_messages(data.to_vec());
+        let _ = split_messages_with_limit(data.to_vec(), 16);
+        let _ = Manufacturer::from_bytes(data);
+        let _ = Manufacturer::peek(data);
+        let _ = Manufacturer::from_hex(&String::from_utf8_lossy(data));
+        let _ = scan::parse_messages(data);
+        let _ = smf::timed_sysex_events(data);
+        let _ = MessageReader::new(data).count();
+        let _ = stream::StreamParser::with_limit(8).push(data);
+        let _ = fixed::FixedMessage::<64>::from_bytes(data);
+        let _ = analyze::analyze(data);
+        let _ = alesis::unpack_data(data);
+        let _ = sequential::unpack_data(data);
+        let _ = dx7::checksum(data);
+        let _ = universal::xor_checksum(data);
+        let _ = format_hex(data, &HexFormat { bytes_per_line: Some(0), ..Default::default() });
+        if let Ok(message) = Message::from_bytes(data) {
+            let _ = message.decode_universal();
+            let _ = message.sections();
+            let _ = message.lint();
+            let _ = message.device_id();
+            let _ = format!("{:#?}", message);
+            let _ = behringer::x32_command(&message);
+            let _ = casio::tone_data(&message);
+            let _ = kawai::identify(&message);
+            let _ = korg::packed_data(&message);
+            let _ = novation::identify(&message);
+            let _ = roland::JV1080_MAP.describe(&message);
+            let _ = roland::DataSet::from_message(&message, 4);
+            let _ = dx7::Dump::from_message(&message);
+            let _ = sequential::Dump::from_message(&message).map(|d| d.name());
+            let _ = alesis::QsDump::from_message(&message);
+            let _ = ensoniq::program_names(&message);
+            let _ = proteus::ProteusMessage::from_message(&message);
+        }
+    }
+
+    /// Runs the parsers over the seed files of the fuzz targets.
+    #[test]
+    fn panic_free_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+        let mut count = 0;
+        for target in fs::read_dir(corpus).unwrap() {
+            for entry in fs::read_dir(target.unwrap().path()).unwrap() {
+                exercise_parsers(&fs::read(entry.unwrap().path()).unwrap());
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn manufacturer_from_hex() {
+        assert_eq!(Manufacturer::from_hex("41").unwrap(), Manufacturer::Standard(0x41));
+        assert_eq!(Manufacturer::from_hex("00203C").unwrap(), Manufacturer::Extended([0x00, 0x20, 0x3C]));
+        assert_eq!(Manufacturer::from_hex("00 20 3c").unwrap(), Manufacturer::Extended([0x00, 0x20, 0x3C]));
+        assert_eq!(Manufacturer::from_hex("00:20:29").unwrap().to_hex(), "002029");
+        assert!(Manufacturer::from_hex("0020").is_err());
+        assert!(Manufacturer::from_hex("4").is_err());
+        assert!(Manufacturer::from_hex("zz").is_err());
+        assert!(Manufacturer::from_hex("F0").is_err());
+    }
+
+    #[test]
+    fn manufacturer_order() {
+        let mut manufacturers = vec![
+            Manufacturer::Extended([0x00, 0x20, 0x29]),
+            Manufacturer::Standard(0x43),
+            Manufacturer::Extended([0x00, 0x00, 0x0E]),
+            Manufacturer::Standard(0x01),
+            Manufacturer::Standard(DEVELOPMENT),
+        ];
+        manufacturers.sort();
+        assert_eq!(manufacturers, vec![
+            Manufacturer::Standard(0x01),
+            Manufacturer::Standard(0x43),
+            Manufacturer::Standard(DEVELOPMENT),
+            Manufacturer::Extended([0x00, 0x00, 0x0E]),
+            Manufacturer::Extended([0x00, 0x20, 0x29]),
+        ]);
+    }
+
+    #[test]
+    fn sub_id_names() {
+        assert_eq!(universal_sub_id_name(UniversalKind::NonRealTime, 0x06, 0x02), Some("Identity Reply"));
+        assert_eq!(universal_sub_id1_name(UniversalKind::NonRealTime, 0x06), Some("General Information"));
+        assert_eq!(universal_sub_id_description(UniversalKind::NonRealTime, 0x06, 0x02),
+            Some("General Information — Identity Reply".to_string()));
+        assert_eq!(universal_sub_id_name(UniversalKind::NonRealTime, 0x7f, 0x05), Some("ACK"));
+        assert_eq!(universal_sub_id_name(UniversalKind::RealTime, 0x04, 0x01), Some("Master Volume"));
+        assert_eq!(universal_sub_id_name(UniversalKind::RealTime, 0x06, 0x01), Some("MIDI Machine Control Commands"));
+        assert_eq!(universal_sub_id_name(UniversalKind::NonRealTime, 0x06, 0x03), None);
+        assert_eq!(universal_sub_id_name(UniversalKind::RealTime, 0x50, 0x01), None);
+    }
+
+    #[test]
+    fn broadcast() {
+        let all = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert!(all.is_broadcast());
+        assert!(all.targets_device(0x10));
+
+        let one = Message::from_bytes(&[0xF0, 0x7E, 0x10, 0x06, 0x01, 0xF7]).unwrap();
+        assert!(!one.is_broadcast());
+        assert!(one.targets_device(0x10));
+        assert!(!one.targets_device(0x11));
+
+        let kawai = Message::from_bytes(&[0xF0, 0x40, 0x7F, 0x20, 0xF7]).unwrap();
+        assert!(!kawai.is_broadcast());
+        assert!(!kawai.targets_device(0x7F));
+    }
+
+    #[test]
+    fn message_sections() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        let kinds: Vec<SectionKind> = message.sections().iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, vec![SectionKind::Initiator, SectionKind::UniversalKind, SectionKind::DeviceId,
+            SectionKind::SubId1, SectionKind::SubId2, SectionKind::Terminator]);
+
+        let message = Message::from_bytes(&[0xF0, 0x00, 0x20, 0x29, 0x01, 0x02, 0xF7]).unwrap();
+        let sections = message.sections();
+        assert_eq!(sections[1], Section::new(SectionKind::Manufacturer, 1, 3));
+        assert_eq!(sections[2].range(), 4..6);
+        assert_eq!(sections[3].offset, 6);
+    }
+
+    #[test]
+    fn write_to_writer() {
+        let messages = [
+            Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x00, 0x20, 0x29, 0x01, 0x02, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x43, 0xF7]).unwrap(),
+        ];
+        for message in &messages {
+            let mut output = Vec::new();
+            message.write_to(&mut output).unwrap();
+            assert_eq!(output, message.to_bytes());
+        }
+    }
+
+    #[test]
+    fn read_from_reader() {
+        let data: &[u8] = &[0x90, 0x40, 0x7F, 0xF0, 0x43, 0xF8, 0x01, 0xF7, 0x00, 0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7, 0xFE];
+        let mut reader = data;
+        let first = Message::read_from(&mut reader).unwrap().unwrap();
+        assert_eq!(first.to_bytes(), vec![0xF0, 0x43, 0x01, 0xF7]);
+        let second = Message::read_from(&mut reader).unwrap().unwrap();
+        assert!(second.is_broadcast());
+        assert!(Message::read_from(&mut reader).unwrap().is_none());
+
+        let mut truncated: &[u8] = &[0xF0, 0x43, 0x01];
+        assert_eq!(Message::read_from(&mut truncated).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let mut interrupted: &[u8] = &[0xF0, 0x43, 0x90, 0xF7];
+        assert_eq!(Message::read_from(&mut interrupted).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn byte_len_and_extend_bytes() {
+        let messages = [
+            Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x00, 0x20, 0x29, 0x01, 0x02, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x43, 0xF7]).unwrap(),
+        ];
+        let total: usize = messages.iter().map(|m| m.byte_len()).sum();
+        let mut buffer = Vec::with_capacity(total);
+        for message in &messages {
+            assert_eq!(message.byte_len(), message.to_bytes().len());
+            message.extend_bytes(&mut buffer);
+        }
+        assert_eq!(buffer.len(), total);
+        assert_eq!(buffer[..6], [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]);
+    }
+
+    #[test]
+    fn error_trait() {
+        let error: Box<dyn std::error::Error> = Box::new(Message::from_bytes(&[0xF0, 0x80, 0xF7]).unwrap_err());
+        assert_eq!(error.to_string(), "Invalid data byte 80 at offset 1");
+
+        let io_error = SystemExclusiveError::from(io::Error::new(io::ErrorKind::UnexpectedEof, "end of data"));
+        assert!(std::error::Error::source(&io_error).is_some());
+        assert_eq!(io_error.to_string(), "end of data");
+    }
+
+    #[test]
+    #[cfg(feature = "manufacturer-names")]
+    fn search_manufacturers_by_name_and_id() {
+        assert!(search_manufacturers("yamaha").contains(&Manufacturer::Standard(0x43)));
+        assert_eq!(search_manufacturers("43")[0], Manufacturer::Standard(0x43));
+        assert!(search_manufacturers("no such maker").is_empty());
+        assert!(search_manufacturers(" ").is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "manufacturer-names"))]
+    fn search_manufacturers_by_id_only() {
+        assert_eq!(search_manufacturers("00 20 3C"), vec![Manufacturer::Extended([0x00, 0x20, 0x3c])]);
+        assert!(search_manufacturers("yamaha").is_empty());
+    }
+
+    #[test]
+    fn canonical_corpus() {
+        let corpus: &[&[u8]] = &[
+            &[0xF0, 0x43, 0xF7],  // shortest message
+            &[0xF0, DEVELOPMENT, 0xF7],  // development ID, empty payload
+            &[0xF0, DEVELOPMENT, 0x01, 0x02, 0xF7],
+            &[0xF0, 0x00, 0x20, 0x29, 0xF7],  // extended ID, empty payload
+            &[0xF0, 0x00, 0x20, 0x29, 0x02, 0x11, 0x78, 0xF7],
+            &[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7],  // GM System On
+            &[0xF0, 0x7E, 0x00, 0x06, 0x02, 0x40, 0x0A, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF7],  // identity reply
+            &[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x40, 0xF7],  // master volume
+            &[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7],  // GS reset
+            &[0xF0, 0x43, 0x00, 0x80, 0xFF, 0xF7],  // high-bit payload bytes are kept
+        ];
+        for data in corpus {
+            assert_eq!(&Message::from_bytes(data).unwrap().canonical_bytes(), data);
+        }
+    }
+
+    /// Checks that the dumps in `testdata/dumps` survive parsing and
+    /// serializing unchanged, message by message and as whole files.
+    #[test]
+    fn canonical_dumps() {
+        let dumps = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/dumps");
+        let mut count = 0;
+        for entry in fs::read_dir(dumps).unwrap() {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            let mut canonical = Vec::new();
+            for part in split_messages(data.clone()) {
+                let message = Message::from_bytes(&part).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+                assert_eq!(message.canonical_bytes(), part, "{}", path.display());
+                canonical.extend(message.canonical_bytes());
+            }
+            assert_eq!(canonical, data, "{}", path.display());
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn debug_format() {
+        let message = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B, 0xF7]).unwrap();
+        assert_eq!(format!("{:?}", message), "ManufacturerSpecific { manufacturer: Standard(67), payload: [0, 0, 1, 27] }");
+
+        let mut bytes = vec![0xF0, 0x7E, 0x00, 0x02, 0x01];
+        bytes.extend(vec![0x55; 100]);
+        bytes.push(0xF7);
+        let pretty = format!("{:#?}", Message::from_bytes(&bytes).unwrap());
+        assert!(pretty.contains("name: Sample Data Packet,\n"), "{}", pretty);
+        assert!(pretty.contains("payload: 55 55 55 55 55 55 55 55 55 55 55 55 55 55 55 55 ... (100 bytes)"), "{}", pretty);
+    }
+
+    #[test]
+    fn reader_positions() {
+        let data = [0x00, 0xF0, 0x43, 0x00, 0xF7, 0xFE, 0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7];
+        let mut reader = MessageReader::new(&data[..]);
+        let positions: Vec<u64> = reader.by_ref().map(|result| result.unwrap().0).collect();
+        assert_eq!(positions, vec![1, 6]);
+        assert_eq!(reader.position(), data.len() as u64);
+
+        let section = Section::new(SectionKind::Payload, 2, 2);
+        assert_eq!(section.absolute_range(5_000_000_000), 5_000_000_002..5_000_000_004);
+    }
+
+    #[test]
+    fn split_with_progress() {
+        let data = vec![0xF0, 0x43, 0xF7, 0xF0, 0x40, 0x00, 0xF7];
+        let mut reports = Vec::new();
+        let parts = split_messages_with_progress(data, |p| {
+            reports.push(p);
+            ControlFlow::Continue(())
+        }).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(reports, vec![Progress::new(3, Some(7)), Progress::new(7, Some(7))]);
+    }
+
+    #[test]
+    fn digest_cancelled() {
+        let message = Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(0x43), payload: vec![] };
+        let messages = vec![message.clone(), message];
+        assert!(digest_messages(&messages, |_| ControlFlow::Break(())).is_none());
+        assert_eq!(digest_messages(&messages, |_| ControlFlow::Continue(())).unwrap().len(), 2);
+    }
 }
+
@@ -0,0 +1,198 @@
+//! # lint
+//!
+//! Checks for anomalies in messages that are not hard errors,
+//! so that tools can show them as warnings.
+
+use std::fmt;
+use crate::{Message, Manufacturer, SystemExclusiveError, dx7};
+
+/// Shortest run of one repeated byte at the end of a payload
+/// that is reported as possible padding of a truncated dump.
+pub const PADDING_RUN_LENGTH: usize = 32;
+
+/// How serious a diagnostic is.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Severity {
+    Info,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The kind of anomaly found.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DiagnosticKind {
+    /// A payload byte has the high bit set.
+    HighBitByte,
+    /// The length does not match the length of a known format.
+    SuspiciousLength,
+    /// The checksum of a known format does not match.
+    ChecksumMismatch,
+    /// The manufacturer ID is not assigned to anyone.
+    UnassignedManufacturer,
+    /// The payload ends with a long run of one byte, as if padded.
+    PaddingRun,
+}
+
+/// An anomaly found in a message.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    /// Offset from the start of the message, if the anomaly is at a specific byte.
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, kind: DiagnosticKind, offset: Option<usize>, message: String) -> Self {
+        Diagnostic { severity, kind, offset, message }
+    }
+
+    /// Gets the offset of the anomaly in a file, for a message starting
+    /// at `message_offset`, like a position from `crate::MessageReader`.
+    pub fn absolute_offset(&self, message_offset: u64) -> Option<u64> {
+        self.offset.map(|offset| message_offset + offset as u64)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{}: {} (at offset {})", self.severity, self.message, offset),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+impl Message {
+    /// Checks the message for anomalies.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut result = Vec::new();
+        let payload = self.payload();
+        let payload_offset = self.byte_len() - payload.len() - 1;
+
+        for (i, b) in payload.iter().enumerate() {
+            if b & 0x80 != 0 {
+                result.push(Diagnostic::new(Severity::Warning, DiagnosticKind::HighBitByte, Some(payload_offset + i),
+                    format!("data byte {:02X} has the high bit set", b)));
+            }
+        }
+
+        match self {
+            Message::Universal { .. } => match self.decode_universal() {
+                Err(SystemExclusiveError::InvalidChecksum) => result.push(Diagnostic::new(Severity::Warning,
+                    DiagnosticKind::ChecksumMismatch, None, "checksum does not match".to_string())),
+                Err(SystemExclusiveError::InvalidMessage) | Err(SystemExclusiveError::Truncated) =>
+                    result.push(Diagnostic::new(Severity::Warning, DiagnosticKind::SuspiciousLength, None,
+                        format!("length {} does not match the universal message type", self.byte_len()))),
+                _ => {},
+            },
+            Message::ManufacturerSpecific { manufacturer, .. } => {
+                if !manufacturer.is_development() && !is_assigned(manufacturer) {
+                    result.push(Diagnostic::new(Severity::Warning, DiagnosticKind::UnassignedManufacturer, Some(1),
+                        format!("manufacturer ID {} is not assigned", manufacturer.to_hex())));
+                }
+                lint_dx7(self, &mut result);
+            },
+        }
+
+        if let Some(last) = payload.last() {
+            let run = payload.iter().rev().take_while(|b| *b == last).count();
+            if run >= PADDING_RUN_LENGTH {
+                result.push(Diagnostic::new(Severity::Info, DiagnosticKind::PaddingRun, Some(payload_offset + payload.len() - run),
+                    format!("payload ends with {} bytes of {:02X}, the dump may be truncated or padded", run, last)));
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(feature = "manufacturer-names")]
+fn is_assigned(manufacturer: &Manufacturer) -> bool {
+    manufacturer.name() != "Unknown manufacturer"
+}
+
+#[cfg(not(feature = "manufacturer-names"))]
+fn is_assigned(_manufacturer: &Manufacturer) -> bool {
+    // Without the name table there is no way to tell.
+    true
+}
+
+/// Checks the length and checksum of messages that look like DX7 voice dumps.
+fn lint_dx7(message: &Message, result: &mut Vec<Diagnostic>) {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(dx7::YAMAHA), payload } => payload,
+        _ => return,
+    };
+    let expected = match payload.get(..4) {
+        Some([sub_status, 0x00, 0x01, 0x1b]) if sub_status & 0xf0 == 0 => dx7::VOICE_SIZE,
+        Some([sub_status, 0x09, 0x20, 0x00]) if sub_status & 0xf0 == 0 => dx7::BANK_DATA_SIZE,
+        _ => return,
+    };
+    if payload.len() != 4 + expected + 1 {
+        result.push(Diagnostic::new(Severity::Warning, DiagnosticKind::SuspiciousLength, None,
+            format!("DX7 dump has {} data bytes, expected {}", payload.len().saturating_sub(5), expected)));
+    }
+    else if message.verify_checksum_with(&dx7::Profile) == Some(false) {
+        result.push(Diagnostic::new(Severity::Warning, DiagnosticKind::ChecksumMismatch, Some(message.byte_len() - 2),
+            "DX7 checksum does not match".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(message: &Message) -> Vec<DiagnosticKind> {
+        message.lint().iter().map(|d| d.kind).collect()
+    }
+
+    #[test]
+    fn clean_message() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        assert!(message.lint().is_empty());
+    }
+
+    #[test]
+    fn high_bit_and_padding() {
+        let mut payload = vec![0x10, 0x90];
+        payload.extend([0x00; PADDING_RUN_LENGTH]);
+        let message = Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(0x41), payload };
+        let diagnostics = message.lint();
+        assert_eq!(diagnostics[0].kind, DiagnosticKind::HighBitByte);
+        assert_eq!(diagnostics[0].offset, Some(3));
+        assert_eq!(diagnostics[1].kind, DiagnosticKind::PaddingRun);
+        assert_eq!(diagnostics[1].offset, Some(4));
+        assert_eq!(diagnostics[1].absolute_offset(1 << 32), Some((1 << 32) + 4));
+    }
+
+    #[test]
+    fn known_formats() {
+        // DX7 single voice dump that is missing its last data bytes
+        let mut bytes = vec![0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B];
+        bytes.extend([0x01; 100]);
+        bytes.extend([0x00, 0xF7]);
+        assert_eq!(kinds(&Message::from_bytes(&bytes).unwrap()), vec![DiagnosticKind::SuspiciousLength]);
+
+        // Identity reply with one byte missing
+        let bytes = [0xF0, 0x7E, 0x00, 0x06, 0x02, 0x40, 0x0A, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0xF7];
+        assert_eq!(kinds(&Message::from_bytes(&bytes).unwrap()), vec![DiagnosticKind::SuspiciousLength]);
+    }
+
+    #[test]
+    #[cfg(feature = "manufacturer-names")]
+    fn unassigned_manufacturer() {
+        let message = Message::from_bytes(&[0xF0, 0x00, 0x7F, 0x7F, 0x01, 0xF7]).unwrap();
+        assert_eq!(kinds(&message), vec![DiagnosticKind::UnassignedManufacturer]);
+    }
+}
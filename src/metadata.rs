@@ -0,0 +1,202 @@
+//! # metadata
+//!
+//! Metadata sidecar files that describe where a dump came from, so that
+//! it stays with the file instead of a separate spreadsheet.
+//!
+//! The sidecar of `bank.syx` is `bank.syx.meta.json`. All the fields are
+//! optional:
+//!
+//! ```json
+//! {
+//!   "device": "DX7",
+//!   "source": "ROM cartridge 1A",
+//!   "date": "1983-05-01",
+//!   "tags": ["factory", "brass"],
+//!   "notes": "Dumped from the original cartridge"
+//! }
+//! ```
+//!
+//! The date is kept as it is written. The scanned files of a collection can be
+//! filtered by tag with [`filter_by_tag`], and counted by tag with [`tag_stats`].
+//! Only available with the `metadata` feature.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::scan::FileMessages;
+use crate::stats::SysexStats;
+
+/// The file name extension of metadata sidecars.
+pub const EXTENSION: &str = "meta.json";
+
+/// Error type for reading metadata sidecars.
+#[derive(Debug)]
+pub enum MetadataError {
+    /// The sidecar could not be read or written.
+    Io(io::Error),
+    /// The sidecar is not valid metadata.
+    Format(String),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataError::Io(e) => write!(f, "{}", e),
+            MetadataError::Format(e) => write!(f, "Invalid metadata: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetadataError::Io(e) => Some(e),
+            MetadataError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MetadataError {
+    fn from(e: io::Error) -> Self {
+        MetadataError::Io(e)
+    }
+}
+
+/// Gets the path of the metadata sidecar of a file, like `bank.syx.meta.json` for `bank.syx`.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(EXTENSION);
+    PathBuf::from(name)
+}
+
+/// The description of a dump file.
+#[derive(Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The name of the device the dump is for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Where the dump came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// When the dump was made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl Metadata {
+    /// Parses the metadata from the text of a sidecar.
+    pub fn parse(text: &str) -> Result<Self, MetadataError> {
+        serde_json::from_str(text).map_err(|e| MetadataError::Format(e.to_string()))
+    }
+
+    /// Gets the text of the sidecar.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Loads the metadata from a sidecar file.
+    pub fn load(path: &Path) -> Result<Self, MetadataError> {
+        Metadata::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the metadata to a sidecar file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json() + "\n")
+    }
+
+    /// Loads the metadata of a dump file from its sidecar,
+    /// or `None` if the file has no sidecar.
+    pub fn load_for(path: &Path) -> Result<Option<Self>, MetadataError> {
+        let sidecar = sidecar_path(path);
+        if sidecar.exists() { Metadata::load(&sidecar).map(Some) } else { Ok(None) }
+    }
+
+    /// Returns `true` if the metadata has the tag, ignoring ASCII case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Keeps the scanned files whose sidecars have the tag.
+/// Files without a sidecar are left out.
+pub fn filter_by_tag(files: Vec<FileMessages>, tag: &str) -> Result<Vec<FileMessages>, MetadataError> {
+    let mut result = Vec::new();
+    for (path, messages) in files {
+        if Metadata::load_for(&path)?.is_some_and(|metadata| metadata.has_tag(tag)) {
+            result.push((path, messages));
+        }
+    }
+    Ok(result)
+}
+
+/// Computes the statistics of the messages of the scanned files for each
+/// tag in their sidecars. Tags are compared in lowercase.
+pub fn tag_stats(files: &[FileMessages]) -> Result<BTreeMap<String, SysexStats>, MetadataError> {
+    let mut result: BTreeMap<String, SysexStats> = BTreeMap::new();
+    for (path, messages) in files {
+        let Some(metadata) = Metadata::load_for(path)? else {
+            continue;
+        };
+        let mut tags: Vec<String> = metadata.tags.iter().map(|tag| tag.to_lowercase()).collect();
+        tags.sort();
+        tags.dedup();
+        for tag in tags {
+            result.entry(tag).or_default().extend(messages);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+    use crate::scan::load_directory;
+
+    #[test]
+    fn parse_and_save() {
+        assert_eq!(sidecar_path(Path::new("dumps/bank.syx")), PathBuf::from("dumps/bank.syx.meta.json"));
+        let metadata = Metadata::parse(r#"{ "device": "DX7", "tags": ["Factory"] }"#).unwrap();
+        assert_eq!(metadata.device.as_deref(), Some("DX7"));
+        assert!(metadata.has_tag("factory"));
+        assert!(!metadata.has_tag("brass"));
+        assert_eq!(Metadata::parse(&metadata.to_json()).unwrap(), metadata);
+        assert!(!metadata.to_json().contains("notes"));
+        assert!(matches!(Metadata::parse(r#"{ "tags": "factory" }"#), Err(MetadataError::Format(_))));
+    }
+
+    #[test]
+    fn tagged_files() {
+        let dir = std::env::temp_dir().join(format!("syxpack-metadata-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("b.syx"), [0xF0, 0x41, 0x10, 0xF7, 0xF0, 0x41, 0x11, 0xF7]).unwrap();
+        fs::write(dir.join("c.syx"), [0xF0, 0x7D, 0xF7]).unwrap();
+        let tagged = |tags: &[&str]| Metadata { tags: tags.iter().map(|t| t.to_string()).collect(), ..Default::default() };
+        tagged(&["factory", "brass"]).save(&sidecar_path(&dir.join("a.syx"))).unwrap();
+        tagged(&["Factory"]).save(&sidecar_path(&dir.join("b.syx"))).unwrap();
+
+        let files = load_directory(&dir).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(Metadata::load_for(&dir.join("c.syx")).unwrap(), None);
+        let brass = filter_by_tag(files.clone(), "BRASS").unwrap();
+        assert_eq!(brass.len(), 1);
+        assert_eq!(brass[0].1, vec![Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7]).unwrap()]);
+
+        let stats = tag_stats(&files).unwrap();
+        assert_eq!(stats.keys().collect::<Vec<_>>(), ["brass", "factory"]);
+        assert_eq!(stats["factory"].messages, 3);
+
+        fs::write(sidecar_path(&dir.join("c.syx")), "not json").unwrap();
+        assert!(matches!(tag_stats(&files), Err(MetadataError::Format(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//! # novation
+//!
+//! Identification helpers for Novation System Exclusive messages.
+//! The messages are classified by their header bytes only,
+//! without decoding the data.
+
+use std::fmt;
+use crate::{Message, Manufacturer};
+
+/// Novation (Focusrite/Novation) manufacturer ID.
+pub const NOVATION: [u8; 3] = [0x00, 0x20, 0x29];
+
+/// Length of the payload above which a synth message is considered a patch dump.
+const MINIMUM_PATCH_DUMP_LENGTH: usize = 64;
+
+/// Kind of a Novation System Exclusive message.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Kind {
+    /// Bootloader message carrying a firmware update chunk.
+    FirmwareUpdate,
+    /// Synth patch dump (Circuit, Peak, Summit and others).
+    PatchDump,
+    /// Components protocol and device control message.
+    Components,
+    /// Novation message of unknown kind.
+    Unknown,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::FirmwareUpdate => "Firmware update",
+            Kind::PatchDump => "Patch dump",
+            Kind::Components => "Components",
+            Kind::Unknown => "Unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns `true` if the message is from Novation.
+pub fn is_novation(message: &Message) -> bool {
+    matches!(message, Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(NOVATION), .. })
+}
+
+/// Identifies the kind of a Novation message from its header bytes.
+/// Returns `None` if the message is not from Novation.
+pub fn identify(message: &Message) -> Option<Kind> {
+    let payload = match message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(NOVATION), payload } => payload,
+        _ => return None,
+    };
+
+    // The first payload byte is the product type, the second the product
+    // or command byte.
+    let kind = match (payload.first(), payload.get(1)) {
+        (Some(0x00), Some(0x70..=0x7f)) => Kind::FirmwareUpdate,
+        (Some(0x01), Some(_)) if payload.len() >= MINIMUM_PATCH_DUMP_LENGTH => Kind::PatchDump,
+        (Some(0x02), Some(_)) => Kind::Components,
+        _ => Kind::Unknown,
+    };
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn novation_message(header: &[u8], length: usize) -> Message {
+        let mut payload = header.to_vec();
+        payload.resize(length, 0x00);
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Extended(NOVATION), payload }
+    }
+
+    #[test]
+    fn firmware_update() {
+        let message = novation_message(&[0x00, 0x72, 0x01], 200);
+        assert_eq!(identify(&message), Some(Kind::FirmwareUpdate));
+    }
+
+    #[test]
+    fn patch_dump() {
+        let message = novation_message(&[0x01, 0x60, 0x00], 350);  // Circuit
+        assert_eq!(identify(&message), Some(Kind::PatchDump));
+    }
+
+    #[test]
+    fn components() {
+        let message = novation_message(&[0x02, 0x0C, 0x0E, 0x01], 4);  // Launchpad X
+        assert_eq!(identify(&message), Some(Kind::Components));
+    }
+
+    #[test]
+    fn not_novation() {
+        let message = Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(0x43), payload: vec![] };
+        assert_eq!(identify(&message), None);
+        assert!(!is_novation(&message));
+    }
+}
@@ -0,0 +1,62 @@
+//! # patch
+//!
+//! A common interface for patches of specific synthesizers, so that
+//! tools can operate on them without knowing the device.
+
+use crate::{Message, SystemExclusiveError};
+
+/// A patch (voice, program, preset) that is transferred
+/// as one or more System Exclusive messages.
+pub trait Patch: Sized {
+    /// Gets the name of the patch, if it has one.
+    fn name(&self) -> Option<String>;
+
+    /// Makes the System Exclusive messages that transfer this patch.
+    fn to_messages(&self) -> Vec<Message>;
+
+    /// Parses a patch from the messages that make it up.
+    fn from_messages(messages: &[Message]) -> Result<Self, SystemExclusiveError>;
+}
+
+/// Gets the only message of a patch that is transferred as a single message.
+pub fn single_message(messages: &[Message]) -> Result<&Message, SystemExclusiveError> {
+    match messages {
+        [message] => Ok(message),
+        _ => Err(SystemExclusiveError::InvalidMessage),
+    }
+}
+
+/// Parses every message that is a complete patch on its own,
+/// skipping the messages that are not.
+pub fn single_message_patches<P: Patch>(messages: &[Message]) -> Vec<P> {
+    messages.iter()
+        .filter_map(|message| P::from_messages(std::slice::from_ref(message)).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dx7, sequential};
+
+    fn names<P: Patch>(patches: &[P]) -> Vec<Option<String>> {
+        patches.iter().map(|patch| patch.name()).collect()
+    }
+
+    #[test]
+    fn generic_patches() {
+        let mut data = vec![b' '; 1024];
+        data[107..111].copy_from_slice(b"Lead");
+        let program = sequential::Dump::Program { product: sequential::Product::Ob6, bank: 0, program: 1, data };
+
+        let mut messages = program.to_messages();
+        messages.push(Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap());
+
+        let programs: Vec<sequential::Dump> = single_message_patches(&messages);
+        assert_eq!(names(&programs), vec![Some("Lead".to_string())]);
+
+        let voices: Vec<dx7::Dump> = single_message_patches(&messages);
+        assert!(voices.is_empty());
+        assert!(sequential::Dump::from_messages(&[]).is_err());
+    }
+}
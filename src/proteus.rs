@@ -0,0 +1,195 @@
+//! # proteus
+//!
+//! Helpers for E-mu Proteus family parameter change and preset dump messages.
+//! Parameter numbers and values are 14-bit quantities transmitted
+//! least significant byte first.
+
+use crate::{Message, Manufacturer, SystemExclusiveError};
+
+/// E-mu manufacturer ID.
+pub const EMU: u8 = 0x18;
+
+/// Proteus family product ID.
+pub const PROTEUS: u8 = 0x04;
+
+/// Number of parameters in a preset.
+pub const PRESET_PARAMETER_COUNT: usize = 128;
+
+/// Length of the preset name in characters, stored in the first parameters.
+pub const NAME_LENGTH: usize = 12;
+
+const PRESET_REQUEST: u8 = 0x00;
+const PRESET_DATA: u8 = 0x01;
+const PARAMETER_REQUEST: u8 = 0x02;
+const PARAMETER_VALUE: u8 = 0x03;
+
+/// Decodes a 14-bit value from its least and most significant bytes.
+pub fn decode_value(lsb: u8, msb: u8) -> u16 {
+    ((msb as u16 & 0x7f) << 7) | (lsb as u16 & 0x7f)
+}
+
+/// Encodes a 14-bit value into its least and most significant bytes.
+pub fn encode_value(value: u16) -> [u8; 2] {
+    [(value & 0x7f) as u8, ((value >> 7) & 0x7f) as u8]
+}
+
+/// Interprets a 14-bit value as a two's complement signed value.
+pub fn signed_value(value: u16) -> i16 {
+    let value = value & 0x3fff;
+    if value & 0x2000 != 0 {
+        value as i16 - 0x4000
+    }
+    else {
+        value as i16
+    }
+}
+
+/// Computes the preset data checksum: the sum of the data bytes, masked to seven bits.
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b)) & 0x7f
+}
+
+/// A Proteus family System Exclusive message.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ProteusMessage {
+    PresetRequest { device: u8, preset: u16 },
+    PresetData { device: u8, preset: u16, values: Vec<u16> },
+    ParameterRequest { device: u8, parameter: u16 },
+    ParameterValue { device: u8, parameter: u16, value: u16 },
+}
+
+impl ProteusMessage {
+    /// Parses a Proteus message from a System Exclusive message.
+    /// Preset data checksums are verified.
+    pub fn from_message(message: &Message) -> Result<Self, SystemExclusiveError> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(EMU), payload } => payload,
+            Message::ManufacturerSpecific { .. } => return Err(SystemExclusiveError::InvalidManufacturer),
+            _ => return Err(SystemExclusiveError::InvalidMessage),
+        };
+
+        // Product ID, device ID, command, 14-bit number
+        if payload.len() < 5 || payload[0] != PROTEUS {
+            return Err(SystemExclusiveError::InvalidMessage);
+        }
+        let device = payload[1];
+        let number = decode_value(payload[3], payload[4]);
+        let data = &payload[5..];
+
+        match payload[2] {
+            PRESET_REQUEST => Ok(ProteusMessage::PresetRequest { device, preset: number }),
+            PRESET_DATA => {
+                if data.len() != PRESET_PARAMETER_COUNT * 2 + 1 {
+                    return Err(SystemExclusiveError::InvalidMessage);
+                }
+                let (values, sum) = data.split_at(PRESET_PARAMETER_COUNT * 2);
+                if checksum(values) != sum[0] {
+                    return Err(SystemExclusiveError::InvalidChecksum);
+                }
+                Ok(ProteusMessage::PresetData {
+                    device,
+                    preset: number,
+                    values: values.chunks(2).map(|v| decode_value(v[0], v[1])).collect(),
+                })
+            },
+            PARAMETER_REQUEST => Ok(ProteusMessage::ParameterRequest { device, parameter: number }),
+            PARAMETER_VALUE if data.len() == 2 => Ok(ProteusMessage::ParameterValue {
+                device,
+                parameter: number,
+                value: decode_value(data[0], data[1]),
+            }),
+            _ => Err(SystemExclusiveError::InvalidMessage),
+        }
+    }
+
+    /// Makes a System Exclusive message from this Proteus message.
+    pub fn to_message(&self) -> Message {
+        let mut payload = vec![PROTEUS];
+        match self {
+            ProteusMessage::PresetRequest { device, preset } => {
+                payload.extend([*device, PRESET_REQUEST]);
+                payload.extend(encode_value(*preset));
+            },
+            ProteusMessage::PresetData { device, preset, values } => {
+                payload.extend([*device, PRESET_DATA]);
+                payload.extend(encode_value(*preset));
+                let data: Vec<u8> = values.iter().flat_map(|v| encode_value(*v)).collect();
+                payload.extend(&data);
+                payload.push(checksum(&data));
+            },
+            ProteusMessage::ParameterRequest { device, parameter } => {
+                payload.extend([*device, PARAMETER_REQUEST]);
+                payload.extend(encode_value(*parameter));
+            },
+            ProteusMessage::ParameterValue { device, parameter, value } => {
+                payload.extend([*device, PARAMETER_VALUE]);
+                payload.extend(encode_value(*parameter));
+                payload.extend(encode_value(*value));
+            },
+        }
+
+        Message::ManufacturerSpecific {
+            manufacturer: Manufacturer::Standard(EMU),
+            payload,
+        }
+    }
+
+    /// Gets the preset name, if this is a preset data message.
+    pub fn preset_name(&self) -> Option<String> {
+        match self {
+            ProteusMessage::PresetData { values, .. } => {
+                let name: String = values.iter().take(NAME_LENGTH)
+                    .map(|v| if (0x20..0x7f).contains(v) { *v as u8 as char } else { ' ' })
+                    .collect();
+                Some(name.trim_end().to_string())
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn value_encoding() {
+        assert_eq!(encode_value(300), [0x2C, 0x02]);
+        assert_eq!(decode_value(0x2C, 0x02), 300);
+        assert_eq!(signed_value(0x3fff), -1);
+        assert_eq!(signed_value(0x0040), 64);
+    }
+
+    #[test]
+    fn parameter_value() {
+        let bytes = vec![0xF0, 0x18, 0x04, 0x00, 0x03, 0x2C, 0x02, 0x7F, 0x7F, 0xF7];
+        let message = ProteusMessage::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(message, ProteusMessage::ParameterValue { device: 0, parameter: 300, value: 0x3fff });
+        assert_eq!(message.to_message().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn preset_data() {
+        let mut values = vec![0u16; PRESET_PARAMETER_COUNT];
+        for (i, c) in "Rock Organ".chars().enumerate() {
+            values[i] = c as u16;
+        }
+        values[100] = 1000;
+        let original = ProteusMessage::PresetData { device: 1, preset: 64, values };
+        let bytes = original.to_message().to_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 5 + 256 + 1 + 1);
+
+        let parsed = ProteusMessage::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(parsed.preset_name(), Some("Rock Organ".to_string()));
+        assert_eq!(parsed, original);
+    }
+
+    proptest! {
+        #[test]
+        fn value_property(value in 0u16..0x4000) {
+            let [lsb, msb] = encode_value(value);
+            prop_assert_eq!(decode_value(lsb, msb), value);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! # query
+//!
+//! Searching the messages of an indexed collection. A [`Query`] is matched
+//! against the entries of an `index::Index`, and the messages found are
+//! returned as [`Hit`]s, which give the file and offset of each message
+//! and load it only when asked.
+//!
+//! The manufacturer, digest and payload length are matched against the
+//! index alone. A byte pattern needs the files of the matching entries to
+//! be read, and a tag needs their metadata sidecars (with the `metadata`
+//! feature), so those are checked last.
+
+#[cfg(feature = "metadata")]
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use crate::{Message, Manufacturer};
+use crate::index::{Index, IndexEntry};
+use crate::scan::{read_messages, ScanError};
+use crate::smf;
+#[cfg(feature = "metadata")]
+use crate::metadata::{Metadata, MetadataError};
+
+/// Error type for searching a collection.
+#[derive(Debug)]
+pub enum QueryError {
+    /// A file of the collection could not be read or parsed.
+    Scan(ScanError),
+    /// A file does not have the message given in the index,
+    /// so the index is out of date.
+    Stale { path: PathBuf, offset: u64 },
+    /// A metadata sidecar could not be read.
+    #[cfg(feature = "metadata")]
+    Metadata(MetadataError),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::Scan(e) => write!(f, "{}", e),
+            QueryError::Stale { path, offset } => write!(f, "No indexed message at offset {} of '{}'", offset, path.display()),
+            #[cfg(feature = "metadata")]
+            QueryError::Metadata(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QueryError::Scan(e) => Some(e),
+            #[cfg(feature = "metadata")]
+            QueryError::Metadata(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ScanError> for QueryError {
+    fn from(e: ScanError) -> Self {
+        QueryError::Scan(e)
+    }
+}
+
+impl From<io::Error> for QueryError {
+    fn from(e: io::Error) -> Self {
+        QueryError::Scan(ScanError::Io(e))
+    }
+}
+
+#[cfg(feature = "metadata")]
+impl From<MetadataError> for QueryError {
+    fn from(e: MetadataError) -> Self {
+        QueryError::Metadata(e)
+    }
+}
+
+/// The conditions for finding messages. A message is found when it meets
+/// all the conditions that are given; the default query finds every message.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Query {
+    /// Only manufacturer-specific messages of this manufacturer.
+    pub manufacturer: Option<Manufacturer>,
+    /// Only messages with this digest.
+    pub digest: Option<md5::Digest>,
+    /// Only messages with a payload length in this range.
+    pub payload_length: Option<RangeInclusive<usize>>,
+    /// Only messages containing these bytes, anywhere from initiator to terminator.
+    pub pattern: Option<Vec<u8>>,
+    /// Only messages of files with this tag in their metadata sidecars, ignoring ASCII case.
+    #[cfg(feature = "metadata")]
+    pub tag: Option<String>,
+}
+
+impl Query {
+    /// Makes a query that finds every message.
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Returns `true` if the indexed message meets the conditions that can be
+    /// checked without reading the file: manufacturer, digest and payload length.
+    pub fn matches_entry(&self, entry: &IndexEntry) -> bool {
+        self.manufacturer.is_none_or(|wanted| entry.manufacturer == Some(wanted))
+            && self.digest.is_none_or(|wanted| entry.digest == wanted)
+            && self.payload_length.as_ref().is_none_or(|range| range.contains(&payload_length(entry)))
+    }
+
+    /// Returns `true` if the message meets the conditions of the query
+    /// that are about the message itself, that is all but the tag.
+    pub fn matches(&self, message: &Message) -> bool {
+        let manufacturer = match message {
+            Message::ManufacturerSpecific { manufacturer, .. } => Some(*manufacturer),
+            Message::Universal { .. } => None,
+        };
+        self.manufacturer.is_none_or(|wanted| manufacturer == Some(wanted))
+            && self.digest.is_none_or(|wanted| message.digest() == wanted)
+            && self.payload_length.as_ref().is_none_or(|range| range.contains(&message.payload().len()))
+            && self.pattern.as_ref().is_none_or(|pattern| contains(&message.to_bytes(), pattern))
+    }
+}
+
+/// Gets the payload length of an indexed message from its size.
+fn payload_length(entry: &IndexEntry) -> usize {
+    let header = match entry.manufacturer {
+        Some(manufacturer) => 1 + manufacturer.to_bytes().len(),
+        None => 5,
+    };
+    entry.size.saturating_sub(header + 1)
+}
+
+fn contains(data: &[u8], pattern: &[u8]) -> bool {
+    pattern.is_empty() || data.windows(pattern.len()).any(|window| window == pattern)
+}
+
+/// A message found in a collection.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Hit {
+    /// Path of the file, relative to the root of the collection.
+    pub path: PathBuf,
+    /// Offset of the message in the file, as in the index.
+    pub offset: u64,
+    /// Size of the message in bytes.
+    pub size: usize,
+}
+
+impl Hit {
+    fn of(entry: &IndexEntry) -> Self {
+        Hit { path: entry.path.clone(), offset: entry.offset, size: entry.size }
+    }
+
+    /// Loads the message from its file under the root of the collection.
+    pub fn load(&self, root: &Path) -> Result<Message, QueryError> {
+        self.load_from(&fs::read(root.join(&self.path))?)
+    }
+
+    /// Gets the message from the contents of its file.
+    fn load_from(&self, data: &[u8]) -> Result<Message, QueryError> {
+        let stale = || QueryError::Stale { path: self.path.clone(), offset: self.offset };
+        if smf::is_smf(data) {
+            // The offsets are those of the messages saved to a .syx file
+            let events = smf::sysex_events(data).map_err(ScanError::Parse)?;
+            let mut offset = 0;
+            for event in events {
+                if offset == self.offset {
+                    return Message::from_bytes(&event.data).map_err(|_| stale());
+                }
+                offset += event.data.len() as u64;
+            }
+            return Err(stale());
+        }
+        let start = usize::try_from(self.offset).map_err(|_| stale())?;
+        let end = start.checked_add(self.size).ok_or_else(stale)?;
+        let bytes = data.get(start..end).ok_or_else(stale)?;
+        Message::from_bytes(bytes).map_err(|_| stale())
+    }
+}
+
+/// The contents of the file read last, so that the consecutive
+/// entries of a file are loaded with one read.
+#[derive(Default)]
+struct FileCache {
+    path: PathBuf,
+    data: Option<Vec<u8>>,
+}
+
+impl FileCache {
+    fn load(&mut self, root: &Path, hit: &Hit) -> Result<Message, QueryError> {
+        if self.data.is_none() || self.path != hit.path {
+            self.data = Some(fs::read(root.join(&hit.path))?);
+            self.path = hit.path.clone();
+        }
+        hit.load_from(self.data.as_deref().unwrap_or_default())
+    }
+}
+
+/// Finds the messages of the collection under `root` described by the
+/// index that match the query. Files are only read if the query has a
+/// pattern or a tag, and then only the files of the entries that match
+/// the rest of the query.
+pub fn search(index: &Index, root: &Path, query: &Query) -> Result<Vec<Hit>, QueryError> {
+    let mut cache = FileCache::default();
+    #[cfg(feature = "metadata")]
+    let mut tagged: HashMap<&Path, bool> = HashMap::new();
+    let mut hits = Vec::new();
+    for entry in index.entries() {
+        if !query.matches_entry(entry) {
+            continue;
+        }
+        #[cfg(feature = "metadata")]
+        if let Some(tag) = &query.tag {
+            let has_tag = match tagged.get(entry.path.as_path()) {
+                Some(has_tag) => *has_tag,
+                None => {
+                    let has_tag = Metadata::load_for(&root.join(&entry.path))?.is_some_and(|m| m.has_tag(tag));
+                    tagged.insert(&entry.path, has_tag);
+                    has_tag
+                },
+            };
+            if !has_tag {
+                continue;
+            }
+        }
+        let hit = Hit::of(entry);
+        if let Some(pattern) = &query.pattern {
+            if !contains(&cache.load(root, &hit)?.to_bytes(), pattern) {
+                continue;
+            }
+        }
+        hits.push(hit);
+    }
+    Ok(hits)
+}
+
+/// Loads the messages of the hits. Consecutive hits in the same file
+/// are loaded with one read.
+pub fn load_hits(hits: &[Hit], root: &Path) -> Result<Vec<Message>, QueryError> {
+    let mut cache = FileCache::default();
+    hits.iter().map(|hit| cache.load(root, hit)).collect()
+}
+
+/// Finds the messages in the files that match the query, without an index.
+/// The tag is not checked.
+pub fn search_files(paths: &[PathBuf], query: &Query) -> Result<Vec<(PathBuf, Message)>, QueryError> {
+    let mut result = Vec::new();
+    for path in paths {
+        for message in read_messages(path)? {
+            if query.matches(&message) {
+                result.push((path.clone(), message));
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_index() {
+        let dir = std::env::temp_dir().join(format!("syxpack-query-{}", std::process::id()));
+        fs::create_dir_all(dir.join("yamaha")).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7, 0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("yamaha/b.syx"), [0xF0, 0x43, 0x00, 0x09, 0x20, 0x00, 0x01, 0xF7, 0xF0, 0x00, 0x20, 0x29, 0x01, 0xF7]).unwrap();
+        let (index, _) = Index::build(&dir, "**/*.syx").unwrap();
+
+        let mut query = Query::new();
+        assert_eq!(search(&index, &dir, &query).unwrap().len(), 4);
+        query.manufacturer = Some(Manufacturer::Standard(0x43));
+        let hits = search(&index, &dir, &query).unwrap();
+        assert_eq!(hits.iter().map(|h| h.path.as_path()).collect::<Vec<_>>(), [Path::new("a.syx"), Path::new("yamaha/b.syx")]);
+        query.payload_length = Some(3..=10);
+        let hits = search(&index, &dir, &query).unwrap();
+        assert_eq!(hits, vec![Hit { path: PathBuf::from("yamaha/b.syx"), offset: 0, size: 8 }]);
+        assert_eq!(hits[0].load(&dir).unwrap().payload(), [0x00, 0x09, 0x20, 0x00, 0x01]);
+
+        let mut query = Query::new();
+        query.pattern = Some(vec![0x00, 0x01]);
+        let messages = load_hits(&search(&index, &dir, &query).unwrap(), &dir).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| query.matches(m)));
+
+        let universal = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        let query = Query { digest: Some(universal.digest()), ..Default::default() };
+        assert_eq!(search(&index, &dir, &query).unwrap()[0].offset, 5);
+        assert_eq!(search_files(&[dir.join("a.syx"), dir.join("yamaha/b.syx")], &query).unwrap(), vec![(dir.join("a.syx"), universal)]);
+
+        // The file has changed since it was indexed
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0xF7]).unwrap();
+        let stale = Hit { path: PathBuf::from("a.syx"), offset: 5, size: 6 };
+        assert!(matches!(stale.load(&dir), Err(QueryError::Stale { offset: 5, .. })));
+        let corrupt = Hit { path: PathBuf::from("a.syx"), offset: 1, size: usize::MAX };
+        assert!(matches!(corrupt.load(&dir), Err(QueryError::Stale { offset: 1, .. })));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "metadata")]
+    #[test]
+    fn search_by_tag() {
+        let dir = std::env::temp_dir().join(format!("syxpack-query-tag-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("b.syx"), [0xF0, 0x41, 0x10, 0xF7]).unwrap();
+        let metadata = Metadata { tags: vec!["Factory".to_string()], ..Default::default() };
+        metadata.save(&crate::metadata::sidecar_path(&dir.join("b.syx"))).unwrap();
+        let (index, _) = Index::build(&dir, "*.syx").unwrap();
+
+        let query = Query { tag: Some("factory".to_string()), ..Default::default() };
+        let hits = search(&index, &dir, &query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, PathBuf::from("b.syx"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
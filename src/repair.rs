@@ -0,0 +1,206 @@
+//! # repair
+//!
+//! Repairing damaged dumps, as often found in archive downloads: bytes
+//! outside of messages are removed, real-time bytes inside messages are
+//! dropped, and messages cut short by another message, a status byte or
+//! the end of the data get a terminator. Checksums of the formats known
+//! to `checksum::known_checksum_spec` can be fixed too.
+//!
+//! Every change is reported as an [`Edit`] with its offset in the
+//! original data, so that the repair can be checked afterwards.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::{Message, INITIATOR, TERMINATOR, write_messages};
+use crate::checksum::fix_known_checksum;
+
+/// The kind of a change made while repairing.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum EditKind {
+    /// Bytes outside of messages were removed.
+    RemovedGarbage,
+    /// A real-time byte inside a message was removed.
+    RemovedRealTime,
+    /// A terminator was added to a message that did not have one.
+    AddedTerminator,
+    /// A message too short to be valid was left out.
+    DroppedMessage,
+    /// The checksum of a message was fixed.
+    FixedChecksum,
+}
+
+/// A change made while repairing.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Edit {
+    pub kind: EditKind,
+    /// Offset of the change in the original data.
+    pub offset: usize,
+    /// Number of bytes removed, or one for the other changes.
+    pub length: usize,
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            EditKind::RemovedGarbage => write!(f, "{}: removed {} bytes outside of messages", self.offset, self.length),
+            EditKind::RemovedRealTime => write!(f, "{}: removed a real-time byte", self.offset),
+            EditKind::AddedTerminator => write!(f, "{}: added a terminator", self.offset),
+            EditKind::DroppedMessage => write!(f, "{}: dropped an invalid message of {} bytes", self.offset, self.length),
+            EditKind::FixedChecksum => write!(f, "{}: fixed the checksum", self.offset),
+        }
+    }
+}
+
+/// Options for repairing.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct RepairOptions {
+    /// Fix the checksums of messages in known formats.
+    pub fix_checksums: bool,
+}
+
+/// The result of a repair.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Repair {
+    pub messages: Vec<Message>,
+    /// The changes made, in the order of their offsets.
+    pub edits: Vec<Edit>,
+}
+
+impl Repair {
+    /// Returns `true` if nothing needed to be changed.
+    pub fn is_clean(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+/// Repairs the data of a `.syx` file.
+pub fn repair(data: &[u8], options: &RepairOptions) -> Repair {
+    let mut repair = Repair::default();
+    let mut current: Option<(usize, Vec<u8>)> = None;
+    let mut garbage: Option<usize> = None;
+
+    for (offset, b) in data.iter().copied().enumerate() {
+        if b >= 0xf8 && current.is_some() {
+            repair.edits.push(Edit { kind: EditKind::RemovedRealTime, offset, length: 1 });
+            continue;
+        }
+        match b {
+            INITIATOR => {
+                if let Some(start) = garbage.take() {
+                    repair.edits.push(Edit { kind: EditKind::RemovedGarbage, offset: start, length: offset - start });
+                }
+                if let Some((start, bytes)) = current.take() {
+                    finish(&mut repair, start, bytes, offset, options);
+                }
+                current = Some((offset, vec![INITIATOR]));
+            },
+            TERMINATOR if current.is_some() => {
+                if let Some((start, mut bytes)) = current.take() {
+                    bytes.push(TERMINATOR);
+                    finish(&mut repair, start, bytes, offset + 1, options);
+                }
+            },
+            0x00..=0x7f if current.is_some() => {
+                if let Some((_, bytes)) = current.as_mut() {
+                    bytes.push(b);
+                }
+            },
+            _ => {
+                // Any other status byte ends the message
+                if let Some((start, bytes)) = current.take() {
+                    finish(&mut repair, start, bytes, offset, options);
+                }
+                garbage.get_or_insert(offset);
+            },
+        }
+    }
+    if let Some(start) = garbage {
+        repair.edits.push(Edit { kind: EditKind::RemovedGarbage, offset: start, length: data.len() - start });
+    }
+    if let Some((start, bytes)) = current {
+        finish(&mut repair, start, bytes, data.len(), options);
+    }
+    repair.edits.sort_by_key(|edit| edit.offset);
+    repair
+}
+
+/// Adds a message that started at `start` and ended before `end` in the
+/// original data, adding the terminator if it is missing.
+fn finish(repair: &mut Repair, start: usize, mut bytes: Vec<u8>, end: usize, options: &RepairOptions) {
+    if bytes.last() != Some(&TERMINATOR) {
+        repair.edits.push(Edit { kind: EditKind::AddedTerminator, offset: end, length: 1 });
+        bytes.push(TERMINATOR);
+    }
+    let Ok(mut message) = Message::from_bytes(&bytes) else {
+        repair.edits.push(Edit { kind: EditKind::DroppedMessage, offset: start, length: end - start });
+        return;
+    };
+    if options.fix_checksums && fix_known_checksum(&mut message) == Some(true) {
+        repair.edits.push(Edit { kind: EditKind::FixedChecksum, offset: start, length: 1 });
+    }
+    repair.messages.push(message);
+}
+
+/// Repairs a `.syx` file and writes the repaired copy to `output`.
+/// Returns the repair with the edits made.
+pub fn repair_file(input: &Path, output: &Path, options: &RepairOptions) -> io::Result<Repair> {
+    let repair = repair(&fs::read(input)?, options);
+    write_messages(output, &repair.messages)?;
+    Ok(repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_damage() {
+        let data = [
+            0x0D, 0x0A,  // garbage before the first message
+            0xF0, 0x43, 0xF8, 0x00, 0x09, 0xF7,  // clock inside
+            0xF0, 0x41, 0x10,  // cut short by the next message
+            0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7,
+            0xF0, 0x42, 0x30,  // cut short by the end of the data
+        ];
+        let repair = repair(&data, &RepairOptions::default());
+        let bytes: Vec<Vec<u8>> = repair.messages.iter().map(|m| m.to_bytes()).collect();
+        assert_eq!(bytes, vec![
+            vec![0xF0, 0x43, 0x00, 0x09, 0xF7],
+            vec![0xF0, 0x41, 0x10, 0xF7],
+            vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7],
+            vec![0xF0, 0x42, 0x30, 0xF7],
+        ]);
+        assert_eq!(repair.edits, vec![
+            Edit { kind: EditKind::RemovedGarbage, offset: 0, length: 2 },
+            Edit { kind: EditKind::RemovedRealTime, offset: 4, length: 1 },
+            Edit { kind: EditKind::AddedTerminator, offset: 11, length: 1 },
+            Edit { kind: EditKind::AddedTerminator, offset: 20, length: 1 },
+        ]);
+        assert_eq!(repair.edits[0].to_string(), "0: removed 2 bytes outside of messages");
+
+        let clean = super::repair(&[0xF0, 0x43, 0x00, 0xF7], &RepairOptions::default());
+        assert!(clean.is_clean());
+    }
+
+    #[test]
+    fn status_bytes_and_short_messages() {
+        let data = [0xF0, 0x43, 0x00, 0x90, 0x40, 0x7F, 0xF0, 0xF7, 0xF7];
+        let repair = repair(&data, &RepairOptions::default());
+        assert_eq!(repair.messages, vec![Message::from_bytes(&[0xF0, 0x43, 0x00, 0xF7]).unwrap()]);
+        let kinds: Vec<EditKind> = repair.edits.iter().map(|edit| edit.kind).collect();
+        assert_eq!(kinds, vec![EditKind::AddedTerminator, EditKind::RemovedGarbage, EditKind::DroppedMessage, EditKind::RemovedGarbage]);
+    }
+
+    #[test]
+    fn fix_checksums() {
+        // Roland DT1 with a wrong checksum
+        let data = [0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x00, 0xF7];
+        let options = RepairOptions { fix_checksums: true };
+        let repair = repair(&data, &options);
+        assert_eq!(repair.edits, vec![Edit { kind: EditKind::FixedChecksum, offset: 0, length: 1 }]);
+        assert_eq!(repair.messages[0].to_bytes()[9], 0x41);
+        assert!(super::repair(&data, &RepairOptions::default()).is_clean());
+    }
+}
@@ -0,0 +1,288 @@
+//! # roland
+//!
+//! Roland data set (DT1) messages and address maps that give names to
+//! the parameters at each address, with a map for the JV-1080 family
+//! (JV-1080, JV-2080, XP-50, XP-60 and XP-80).
+//!
+//! Roland addresses are written as bytes of seven bits each, like
+//! `03 00 10 4A`. Here they are kept as those bytes packed into a `u32`,
+//! so that `0x0300104a` is the address above.
+
+use std::fmt;
+use crate::{Message, Manufacturer, SystemExclusiveError};
+use crate::checksum::{ChecksumKind, ChecksumProfile, ChecksumSpec};
+
+/// Roland manufacturer ID.
+pub const ROLAND: u8 = 0x41;
+
+/// Command ID of data set messages.
+pub const DATA_SET: u8 = 0x12;
+
+/// Command ID of data request messages.
+pub const DATA_REQUEST: u8 = 0x11;
+
+/// Model ID of the JV-1080 family.
+pub const JV1080: u8 = 0x6a;
+
+/// A decoded data set (DT1) message.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DataSet {
+    pub device: u8,
+    pub model: u8,
+    /// The address as its seven-bit bytes packed into a `u32`.
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl DataSet {
+    /// Decodes a data set message with a one-byte model ID and an address
+    /// of `address_size` bytes (3 for GS devices, 4 for the JV-1080 family),
+    /// and checks its checksum.
+    pub fn from_message(message: &Message, address_size: usize) -> Result<Self, SystemExclusiveError> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(ROLAND), payload } => payload,
+            _ => return Err(SystemExclusiveError::InvalidManufacturer),
+        };
+        if !(1..=4).contains(&address_size) {
+            return Err(SystemExclusiveError::UnsupportedFormat);
+        }
+        // Device, model, command, address, checksum
+        if payload.len() < 3 + address_size + 1 {
+            return Err(SystemExclusiveError::Truncated);
+        }
+        if payload[2] != DATA_SET {
+            return Err(SystemExclusiveError::UnsupportedFormat);
+        }
+        let checksum_offset = payload.len() - 1;
+        if ChecksumKind::TwosComplement.compute(&payload[3..checksum_offset]) != payload[checksum_offset] {
+            return Err(SystemExclusiveError::InvalidChecksum);
+        }
+        let address = payload[3..3 + address_size].iter().fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        Ok(DataSet {
+            device: payload[0],
+            model: payload[1],
+            address,
+            data: payload[3 + address_size..checksum_offset].to_vec(),
+        })
+    }
+
+    /// Makes a data set message with an address of `address_size` bytes.
+    pub fn to_message(&self, address_size: usize) -> Message {
+        let mut payload = vec![self.device, self.model, DATA_SET];
+        payload.extend((0..address_size).rev().map(|i| ((self.address >> (8 * i)) & 0x7f) as u8));
+        payload.extend(&self.data);
+        payload.push(ChecksumKind::TwosComplement.compute(&payload[3..]));
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(ROLAND), payload }
+    }
+}
+
+/// Converts an address to a linear number, for address arithmetic.
+fn linear(address: u32) -> u32 {
+    (0..4).fold(0, |acc, i| (acc << 7) | ((address >> (8 * (3 - i))) & 0x7f))
+}
+
+/// How the value of a parameter is stored.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Encoding {
+    /// One byte.
+    Byte,
+    /// A number split into the given number of four-bit nybbles, most significant first.
+    Nybbles(usize),
+    /// ASCII text of the given length.
+    Text(usize),
+}
+
+impl Encoding {
+    /// Gets the number of bytes the parameter takes.
+    pub fn size(&self) -> usize {
+        match self {
+            Encoding::Byte => 1,
+            Encoding::Nybbles(size) | Encoding::Text(size) => *size,
+        }
+    }
+}
+
+/// A named parameter at an offset in a block.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Parameter {
+    pub offset: u32,
+    pub name: &'static str,
+    pub encoding: Encoding,
+}
+
+const fn byte(offset: u32, name: &'static str) -> Parameter {
+    Parameter { offset, name, encoding: Encoding::Byte }
+}
+
+/// A block of parameters at a base address, like the common
+/// parameters of a patch or one of its tones.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Block {
+    pub base: u32,
+    pub name: &'static str,
+    pub parameters: &'static [Parameter],
+}
+
+/// A parameter value read from a data set.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParameterValue {
+    /// The full name, with the name of the block, like "Patch Tone 1 Cutoff Frequency".
+    pub name: String,
+    pub value: String,
+}
+
+impl fmt::Display for ParameterValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.name, self.value)
+    }
+}
+
+/// A map from addresses to parameter names for one model.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct AddressMap {
+    pub model: u8,
+    /// Number of address bytes in the data set messages.
+    pub address_size: usize,
+    pub blocks: &'static [Block],
+}
+
+impl AddressMap {
+    /// Finds the block and parameter at an address.
+    pub fn lookup(&self, address: u32) -> Option<(&Block, &Parameter)> {
+        let address = linear(address);
+        self.blocks.iter().find_map(|block| {
+            let offset = address.checked_sub(linear(block.base))?;
+            block.parameters.iter().find(|p| p.offset == offset).map(|p| (block, p))
+        })
+    }
+
+    /// Names the parameters set by a data set message of this model.
+    /// Bytes at addresses without a parameter are skipped.
+    pub fn describe(&self, message: &Message) -> Result<Vec<ParameterValue>, SystemExclusiveError> {
+        let data_set = DataSet::from_message(message, self.address_size)?;
+        if data_set.model != self.model {
+            return Err(SystemExclusiveError::UnsupportedFormat);
+        }
+
+        let start = linear(data_set.address);
+        let mut result = Vec::new();
+        let mut index = 0;
+        while index < data_set.data.len() {
+            let address = start + index as u32;
+            let packed = (0..4).fold(0u32, |acc, i| (acc << 8) | ((address >> (7 * (3 - i))) & 0x7f));
+            let (block, parameter) = match self.lookup(packed) {
+                Some(found) => found,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let size = parameter.encoding.size();
+            let bytes = match data_set.data.get(index..index + size) {
+                Some(bytes) => bytes,
+                None => break,
+            };
+            let value = match parameter.encoding {
+                Encoding::Byte => bytes[0].to_string(),
+                Encoding::Nybbles(_) => bytes.iter().fold(0u32, |acc, b| (acc << 4) | (*b & 0x0f) as u32).to_string(),
+                Encoding::Text(_) => bytes.iter().map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' }).collect(),
+            };
+            result.push(ParameterValue { name: format!("{} {}", block.name, parameter.name), value });
+            index += size;
+        }
+        Ok(result)
+    }
+}
+
+/// Patch common parameters of the JV-1080 family.
+const JV1080_PATCH_COMMON: &[Parameter] = &[
+    Parameter { offset: 0x00, name: "Name", encoding: Encoding::Text(12) },
+    byte(0x0c, "EFX Type"),
+    byte(0x21, "Chorus Level"),
+    byte(0x22, "Chorus Rate"),
+    byte(0x23, "Chorus Depth"),
+    byte(0x27, "Reverb Type"),
+    byte(0x28, "Reverb Level"),
+    byte(0x29, "Reverb Time"),
+    Parameter { offset: 0x2c, name: "Tempo", encoding: Encoding::Nybbles(2) },
+    byte(0x2e, "Level"),
+    byte(0x2f, "Pan"),
+];
+
+/// Patch tone parameters of the JV-1080 family.
+const JV1080_PATCH_TONE: &[Parameter] = &[
+    byte(0x00, "Switch"),
+    byte(0x01, "Wave Group Type"),
+    byte(0x02, "Wave Group ID"),
+    Parameter { offset: 0x03, name: "Wave Number", encoding: Encoding::Nybbles(2) },
+    byte(0x05, "Wave Gain"),
+    byte(0x08, "Velocity Range Lower"),
+    byte(0x09, "Velocity Range Upper"),
+    byte(0x49, "Filter Type"),
+    byte(0x4a, "Cutoff"),
+    byte(0x4b, "Cutoff Keyfollow"),
+    byte(0x4e, "Resonance"),
+    byte(0x5c, "Level"),
+    byte(0x5f, "Pan"),
+];
+
+/// Address map of the temporary patch of the JV-1080 family.
+pub static JV1080_MAP: AddressMap = AddressMap {
+    model: JV1080,
+    address_size: 4,
+    blocks: &[
+        Block { base: 0x03000000, name: "Patch Common", parameters: JV1080_PATCH_COMMON },
+        Block { base: 0x03001000, name: "Patch Tone 1", parameters: JV1080_PATCH_TONE },
+        Block { base: 0x03001200, name: "Patch Tone 2", parameters: JV1080_PATCH_TONE },
+        Block { base: 0x03001400, name: "Patch Tone 3", parameters: JV1080_PATCH_TONE },
+        Block { base: 0x03001600, name: "Patch Tone 4", parameters: JV1080_PATCH_TONE },
+    ],
+};
+
+/// Checksum profile for Roland data set messages.
+pub struct Profile;
+
+impl ChecksumProfile for Profile {
+    fn checksum_spec(&self, message: &Message) -> Option<ChecksumSpec> {
+        match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(ROLAND), payload }
+                if payload.len() > 4 && payload[2] == DATA_SET =>
+                Some(ChecksumSpec::trailing(ChecksumKind::TwosComplement, 3, payload.len())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_set_round_trip() {
+        // GS reset
+        let message = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]).unwrap();
+        let data_set = DataSet::from_message(&message, 3).unwrap();
+        assert_eq!(data_set, DataSet { device: 0x10, model: 0x42, address: 0x40007f, data: vec![0x00] });
+        assert_eq!(data_set.to_message(3), message);
+        assert_eq!(message.verify_checksum_with(&Profile), Some(true));
+
+        let mut bytes = message.to_bytes();
+        bytes[9] = 0x40;
+        assert!(matches!(DataSet::from_message(&Message::from_bytes(&bytes).unwrap(), 3),
+            Err(SystemExclusiveError::InvalidChecksum)));
+    }
+
+    #[test]
+    fn describe_jv1080() {
+        let message = DataSet { device: 0x10, model: JV1080, address: 0x0300104a, data: vec![84, 100] }.to_message(4);
+        let values: Vec<String> = JV1080_MAP.describe(&message).unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(values, vec!["Patch Tone 1 Cutoff = 84", "Patch Tone 1 Cutoff Keyfollow = 100"]);
+
+        let mut data = b"Jupiter Pad ".to_vec();
+        data.push(0x04);
+        let message = DataSet { device: 0x10, model: JV1080, address: 0x03000000, data }.to_message(4);
+        let values = JV1080_MAP.describe(&message).unwrap();
+        assert_eq!(values[0].to_string(), "Patch Common Name = Jupiter Pad ");
+        assert_eq!(values[1].to_string(), "Patch Common EFX Type = 4");
+    }
+}
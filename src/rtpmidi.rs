@@ -0,0 +1,202 @@
+//! # rtpmidi
+//!
+//! Segmented System Exclusive commands of RTP-MIDI (AppleMIDI, RFC 6295),
+//! for sending messages over network MIDI in packets of limited size.
+//!
+//! A message that fits in one command is sent as it is, from F0 to F7.
+//! Longer messages are split into segments marked by their first and last
+//! bytes:
+//!
+//! - first segment: F0 ... F0
+//! - middle segments: F7 ... F0
+//! - last segment: F7 ... F7
+//!
+//! A segment ending in F4 cancels the message being sent.
+//!
+//! Only the SysEx commands are handled here, not the rest of the RTP
+//! packet. The delta times and other commands of the command section
+//! are left to the caller.
+
+use std::fmt;
+use crate::{Message, SystemExclusiveError, INITIATOR, TERMINATOR};
+
+/// The byte that ends a segment that cancels the message.
+pub const CANCEL: u8 = 0xf4;
+
+/// Smallest segment size: the two marker bytes and one data byte.
+pub const MIN_SEGMENT_SIZE: usize = 3;
+
+/// The kind of a SysEx command, by its marker bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Segment {
+    /// A whole message, F0 ... F7.
+    Complete,
+    /// The first segment of a message, F0 ... F0.
+    First,
+    /// A middle segment, F7 ... F0.
+    Middle,
+    /// The last segment, F7 ... F7.
+    Last,
+    /// A segment that cancels the message, ending in F4.
+    Cancel,
+}
+
+impl Segment {
+    /// Gets the kind of a SysEx command from its marker bytes,
+    /// or `None` if the bytes are not a SysEx command.
+    pub fn of(data: &[u8]) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+        match (data[0], data[data.len() - 1]) {
+            (INITIATOR, TERMINATOR) => Some(Segment::Complete),
+            (INITIATOR, INITIATOR) => Some(Segment::First),
+            (TERMINATOR, INITIATOR) => Some(Segment::Middle),
+            (TERMINATOR, TERMINATOR) => Some(Segment::Last),
+            (INITIATOR | TERMINATOR, CANCEL) => Some(Segment::Cancel),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Segment::Complete => "complete",
+            Segment::First => "first",
+            Segment::Middle => "middle",
+            Segment::Last => "last",
+            Segment::Cancel => "cancel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Splits a message into SysEx commands of at most `max_size` bytes,
+/// including the marker bytes. Sizes below `MIN_SEGMENT_SIZE` are raised
+/// to it.
+pub fn segment(message: &Message, max_size: usize) -> Vec<Vec<u8>> {
+    let data = message.to_bytes();
+    let max_size = max_size.max(MIN_SEGMENT_SIZE);
+    if data.len() <= max_size {
+        return vec![data];
+    }
+
+    let inner = &data[1..data.len() - 1];
+    let chunks: Vec<&[u8]> = inner.chunks(max_size - 2).collect();
+    let last = chunks.len() - 1;
+    chunks.iter().enumerate()
+        .map(|(index, chunk)| {
+            let start = if index == 0 { INITIATOR } else { TERMINATOR };
+            let end = if index == last { TERMINATOR } else { INITIATOR };
+            let mut segment = vec![start];
+            segment.extend(*chunk);
+            segment.push(end);
+            segment
+        })
+        .collect()
+}
+
+/// Joins segmented SysEx commands back into messages.
+#[derive(Clone, Default, Debug)]
+pub struct Reassembler {
+    pending: Option<Vec<u8>>,
+}
+
+impl Reassembler {
+    /// Makes a reassembler with no message in progress.
+    pub fn new() -> Self {
+        Reassembler::default()
+    }
+
+    /// Returns `true` if a segmented message has been started but not finished.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Handles one SysEx command. Returns the message when it is complete,
+    /// or `None` if more segments are needed or the message was cancelled.
+    ///
+    /// A middle or last segment without a first segment, or a first
+    /// segment while another message is in progress, fails with
+    /// `SystemExclusiveError::Truncated` and drops the message in progress.
+    /// A complete message also drops the message in progress.
+    pub fn push(&mut self, command: &[u8]) -> Result<Option<Message>, SystemExclusiveError> {
+        let kind = Segment::of(command).ok_or(SystemExclusiveError::InvalidMessage)?;
+        let inner = &command[1..command.len() - 1];
+        match kind {
+            Segment::Complete => {
+                self.pending = None;
+                Message::from_bytes(command).map(Some)
+            },
+            Segment::First => {
+                if self.pending.is_some() {
+                    self.pending = None;
+                    return Err(SystemExclusiveError::Truncated);
+                }
+                let mut data = vec![INITIATOR];
+                data.extend(inner);
+                self.pending = Some(data);
+                Ok(None)
+            },
+            Segment::Middle => {
+                let data = self.pending.as_mut().ok_or(SystemExclusiveError::Truncated)?;
+                data.extend(inner);
+                Ok(None)
+            },
+            Segment::Last => {
+                let mut data = self.pending.take().ok_or(SystemExclusiveError::Truncated)?;
+                data.extend(inner);
+                data.push(TERMINATOR);
+                Message::from_bytes(&data).map(Some)
+            },
+            Segment::Cancel => {
+                self.pending = None;
+                Ok(None)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_and_reassemble() {
+        let message = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x20, 0x00, 0x01, 0x02, 0xF7]).unwrap();
+        let segments = segment(&message, 4);
+        assert_eq!(segments, vec![
+            vec![0xF0, 0x43, 0x00, 0xF0],
+            vec![0xF7, 0x09, 0x20, 0xF0],
+            vec![0xF7, 0x00, 0x01, 0xF0],
+            vec![0xF7, 0x02, 0xF7],
+        ]);
+        let kinds: Vec<Segment> = segments.iter().filter_map(|s| Segment::of(s)).collect();
+        assert_eq!(kinds, vec![Segment::First, Segment::Middle, Segment::Middle, Segment::Last]);
+
+        let mut reassembler = Reassembler::new();
+        let results: Vec<Option<Message>> = segments.iter().map(|s| reassembler.push(s).unwrap()).collect();
+        assert_eq!(results.last().unwrap().as_ref(), Some(&message));
+        assert!(results[..3].iter().all(|r| r.is_none()));
+        assert!(!reassembler.is_pending());
+
+        assert_eq!(segment(&message, 100), vec![message.to_bytes()]);
+        assert_eq!(reassembler.push(&message.to_bytes()).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn cancel_and_errors() {
+        let mut reassembler = Reassembler::new();
+        reassembler.push(&[0xF0, 0x43, 0xF0]).unwrap();
+        assert!(reassembler.is_pending());
+        assert_eq!(reassembler.push(&[0xF7, 0x01, 0xF4]).unwrap(), None);
+        assert!(!reassembler.is_pending());
+
+        assert!(matches!(reassembler.push(&[0xF7, 0x01, 0xF7]), Err(SystemExclusiveError::Truncated)));
+        reassembler.push(&[0xF0, 0x43, 0xF0]).unwrap();
+        assert!(matches!(reassembler.push(&[0xF0, 0x41, 0xF0]), Err(SystemExclusiveError::Truncated)));
+        assert!(!reassembler.is_pending());
+        assert!(matches!(reassembler.push(&[0x90, 0x40]), Err(SystemExclusiveError::InvalidMessage)));
+    }
+}
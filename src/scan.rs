@@ -0,0 +1,428 @@
+//! # scan
+//!
+//! Loading System Exclusive messages from files and directories.
+//! Raw `.syx` files and Standard MIDI Files (`.mid`) are supported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use crate::{Message, Manufacturer, Progress, UniversalKind, SystemExclusiveError, DEFAULT_SIZE_LIMIT, INITIATOR, TERMINATOR, split_messages_with_limit, smf};
+
+/// Error type for loading messages from a file.
+#[derive(Debug)]
+pub enum ScanError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file contents could not be parsed.
+    Parse(SystemExclusiveError),
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::Io(e) => write!(f, "{}", e),
+            ScanError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Io(e) => Some(e),
+            ScanError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ScanError {
+    fn from(e: io::Error) -> Self {
+        ScanError::Io(e)
+    }
+}
+
+impl From<SystemExclusiveError> for ScanError {
+    fn from(e: SystemExclusiveError) -> Self {
+        ScanError::Parse(e)
+    }
+}
+
+/// Messages loaded from one file.
+pub type FileMessages = (PathBuf, Vec<Message>);
+
+/// Error encountered while loading one file.
+pub type FileError = (PathBuf, ScanError);
+
+/// Returns `true` if the path has an extension of a supported file type.
+pub fn is_supported_file(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ["syx", "mid", "midi"].contains(&ext.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Parses all the messages in the data of a raw System Exclusive file.
+/// Messages longer than `DEFAULT_SIZE_LIMIT` are rejected. Bytes after
+/// the last message that do not start another one, like a newline added
+/// by an editor, are ignored; `repair::repair` reports them.
+pub fn parse_messages(data: &[u8]) -> Result<Vec<Message>, SystemExclusiveError> {
+    parse_messages_with_limit(data, DEFAULT_SIZE_LIMIT)
+}
+
+/// Parses all the messages in the data of a raw System Exclusive file,
+/// rejecting messages longer than `limit` bytes.
+pub fn parse_messages_with_limit(data: &[u8], limit: usize) -> Result<Vec<Message>, SystemExclusiveError> {
+    let mut parts = split_messages_with_limit(data.to_vec(), limit)?;
+    if parts.len() > 1 && parts.last().is_some_and(|part| !part.ends_with(&[TERMINATOR]) && !part.contains(&INITIATOR)) {
+        parts.pop();
+    }
+    parts
+        .iter()
+        .map(|part| Message::from_bytes(part))
+        .collect()
+}
+
+/// Reads all the messages from a `.syx` file or a Standard MIDI File.
+pub fn read_messages(path: &Path) -> Result<Vec<Message>, ScanError> {
+    let data = fs::read(path)?;
+    if smf::is_smf(&data) {
+        let events = smf::sysex_events(&data)?;
+        let messages = events.iter()
+            .map(|event| Message::from_bytes(&event.data))
+            .collect::<Result<Vec<Message>, SystemExclusiveError>>()?;
+        Ok(messages)
+    }
+    else {
+        Ok(parse_messages(&data)?)
+    }
+}
+
+/// Lists the supported files directly inside the directory, sorted by path.
+fn supported_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_file() && is_supported_file(&entry_path) {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Loads the messages from all the `.syx` and `.mid` files in the directory.
+/// Stops at the first file that cannot be read or parsed.
+pub fn load_directory(path: &Path) -> Result<Vec<FileMessages>, ScanError> {
+    let mut result = Vec::new();
+    for file_path in supported_files(path)? {
+        let messages = read_messages(&file_path)?;
+        result.push((file_path, messages));
+    }
+    Ok(result)
+}
+
+/// Loads the messages from all the `.syx` and `.mid` files in the directory,
+/// collecting the errors of files that cannot be read or parsed instead of stopping.
+pub fn load_directory_collecting_errors(path: &Path) -> Result<(Vec<FileMessages>, Vec<FileError>), ScanError> {
+    let mut loaded = Vec::new();
+    let mut errors = Vec::new();
+    for file_path in supported_files(path)? {
+        match read_messages(&file_path) {
+            Ok(messages) => loaded.push((file_path, messages)),
+            Err(e) => errors.push((file_path, e)),
+        }
+    }
+    Ok((loaded, errors))
+}
+
+/// The result of loading many files: the messages of the files that
+/// could be loaded, and the errors of the ones that could not.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub files: Vec<FileMessages>,
+    pub errors: Vec<FileError>,
+}
+
+impl ScanReport {
+    /// Gets the manufacturers of the loaded messages that are not in the
+    /// registry of names, with the files where each was seen.
+    pub fn unknown_manufacturers(&self) -> BTreeMap<Manufacturer, Vec<&Path>> {
+        let mut result: BTreeMap<Manufacturer, Vec<&Path>> = BTreeMap::new();
+        for (path, messages) in &self.files {
+            for message in messages {
+                if let Message::ManufacturerSpecific { manufacturer, .. } = message {
+                    if !manufacturer.is_known() {
+                        let paths = result.entry(*manufacturer).or_default();
+                        if paths.last() != Some(&path.as_path()) {
+                            paths.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Matches one path component against a pattern component
+/// containing `*` and `?` wildcards, ignoring ASCII case.
+fn matches_component(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches_component(&pattern[1..], name) ||
+                (!name.is_empty() && matches_component(pattern, &name[1..]))
+        },
+        (Some(b'?'), Some(_)) => matches_component(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p.eq_ignore_ascii_case(n) => matches_component(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn matches_components(pattern: &[&str], components: &[String]) -> bool {
+    match pattern.first() {
+        None => components.is_empty(),
+        Some(&"**") => {
+            matches_components(&pattern[1..], components) ||
+                (!components.is_empty() && matches_components(pattern, &components[1..]))
+        },
+        Some(p) => {
+            !components.is_empty() &&
+                matches_component(p.as_bytes(), components[0].as_bytes()) &&
+                matches_components(&pattern[1..], &components[1..])
+        },
+    }
+}
+
+/// Matches a relative path against a glob pattern. The pattern components
+/// are separated by `/`, and can contain `*` (any characters), `?` (any one
+/// character) or be `**` (any number of directories). Case is ignored,
+/// since patch archives tend to mix `.syx` and `.SYX`.
+pub fn glob_matches(pattern: &str, path: &Path) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let components: Vec<String> = path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    matches_components(&pattern, &components)
+}
+
+/// Finds the files under `root` whose path relative to `root` matches the
+/// glob pattern, descending into subdirectories. Directories that cannot be
+/// read are reported as errors. The files are sorted by path.
+pub fn find_files(root: &Path, pattern: &str) -> io::Result<(Vec<PathBuf>, Vec<FileError>)> {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    let mut is_root = true;
+
+    while let Some(directory) = directories.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if is_root => return Err(e),
+            Err(e) => {
+                errors.push((directory, ScanError::Io(e)));
+                continue;
+            }
+        };
+        is_root = false;
+
+        for entry in entries {
+            // Symbolic links to directories are not followed, to avoid loops.
+            let (entry_path, is_dir) = match entry.and_then(|e| Ok((e.path(), e.file_type()?.is_dir()))) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    errors.push((directory.clone(), ScanError::Io(e)));
+                    continue;
+                }
+            };
+            if is_dir {
+                directories.push(entry_path);
+            }
+            else if let Ok(relative) = entry_path.strip_prefix(root) {
+                if glob_matches(pattern, relative) {
+                    files.push(entry_path);
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok((files, errors))
+}
+
+/// Loads the messages from all the files under `root` matching the glob
+/// pattern, such as `**/*.syx` or `**/*.mid`. Files that cannot be read or
+/// parsed are reported in the errors instead of stopping the scan.
+pub fn load_glob(root: &Path, pattern: &str) -> io::Result<ScanReport> {
+    let report = load_glob_with_progress(root, pattern, |_| ControlFlow::Continue(()))?;
+    Ok(report.unwrap_or_default())
+}
+
+/// Loads the messages like `load_glob`, reporting the number of files
+/// processed after each one. Returns `Ok(None)` if the callback cancels the scan.
+pub fn load_glob_with_progress<F>(root: &Path, pattern: &str, mut progress: F) -> io::Result<Option<ScanReport>>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    let (paths, errors) = find_files(root, pattern)?;
+    let mut report = ScanReport { files: Vec::new(), errors };
+    let total = paths.len();
+    for (index, path) in paths.into_iter().enumerate() {
+        match read_messages(&path) {
+            Ok(messages) => report.files.push((path, messages)),
+            Err(e) => report.errors.push((path, e)),
+        }
+        if progress(Progress::new(index + 1, Some(total))).is_break() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(report))
+}
+
+/// The header row written by `write_csv`.
+pub const CSV_HEADER: &str = "file,index,manufacturer_id,manufacturer,kind,sub_ids,payload_length,digest";
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        value.to_string()
+    }
+}
+
+/// Writes one CSV row per message, with the file, the index of the message
+/// in the file, the manufacturer ID and name, the kind, the universal sub-IDs,
+/// the payload length and the MD5 digest, for cataloguing large collections.
+/// The first row is `CSV_HEADER`.
+pub fn write_csv<W: Write>(w: &mut W, files: &[FileMessages]) -> io::Result<()> {
+    writeln!(w, "{}", CSV_HEADER)?;
+    for (path, messages) in files {
+        for (index, message) in messages.iter().enumerate() {
+            let (manufacturer_id, manufacturer, kind, sub_ids) = match message {
+                Message::Universal { kind, sub_id1, sub_id2, .. } => {
+                    let kind = match kind {
+                        UniversalKind::NonRealTime => "universal-non-real-time",
+                        UniversalKind::RealTime => "universal-real-time",
+                    };
+                    (String::new(), String::new(), kind, format!("{:02X} {:02X}", sub_id1, sub_id2))
+                },
+                Message::ManufacturerSpecific { manufacturer, .. } =>
+                    (manufacturer.to_hex(), manufacturer.name(), "manufacturer", String::new()),
+            };
+            writeln!(w, "{},{},{},{},{},{},{},{:x}",
+                csv_field(&path.to_string_lossy()), index, manufacturer_id, csv_field(&manufacturer),
+                kind, sub_ids, message.payload().len(), message.digest())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_bytes() {
+        let messages = parse_messages(&[0xF0, 0x43, 0x00, 0xF7, 0xF0, 0x41, 0x10, 0xF7, 0x0D, 0x0A]).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(parse_messages(&[0xF0, 0x43, 0x00, 0xF7, 0xF0, 0x41]).is_err());
+        assert!(parse_messages(&[0x0A]).is_err());
+    }
+
+    #[test]
+    fn error_source() {
+        let error: Box<dyn std::error::Error> = Box::new(ScanError::from(io::Error::other("gone")));
+        assert_eq!(error.source().map(|e| e.to_string()), Some("gone".to_string()));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_matches("**/*.syx", Path::new("a.syx")));
+        assert!(glob_matches("**/*.syx", Path::new("yamaha/dx7/ROM1A.SYX")));
+        assert!(!glob_matches("**/*.syx", Path::new("yamaha/dx7/rom1a.mid")));
+        assert!(glob_matches("*/dx?/*.syx", Path::new("yamaha/dx7/rom1a.syx")));
+        assert!(!glob_matches("*.syx", Path::new("yamaha/rom1a.syx")));
+        assert!(glob_matches("yamaha/**", Path::new("yamaha/dx7/rom1a.syx")));
+    }
+
+    #[test]
+    fn load_recursive() {
+        let dir = std::env::temp_dir().join(format!("syxpack-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("roland/d50")).unwrap();
+        fs::write(dir.join("top.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("roland/d50/bank.syx"), [0xF0, 0x41, 0x00, 0x01, 0xF7]).unwrap();
+        fs::write(dir.join("roland/broken.syx"), [0x41, 0xF7]).unwrap();
+        fs::write(dir.join("roland/readme.txt"), b"text").unwrap();
+
+        let report = load_glob(&dir, "**/*.syx").unwrap();
+        let paths: Vec<&PathBuf> = report.files.iter().map(|f| &f.0).collect();
+        assert_eq!(paths, vec![&dir.join("roland/d50/bank.syx"), &dir.join("top.syx")]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, dir.join("roland/broken.syx"));
+
+        let cancelled = load_glob_with_progress(&dir, "**/*.syx", |_| ControlFlow::Break(())).unwrap();
+        assert!(cancelled.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn csv_rows() {
+        let files = vec![(PathBuf::from("dumps/a, b.syx"), vec![
+            Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap(),
+        ])];
+        let mut out = Vec::new();
+        write_csv(&mut out, &files).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("\"dumps/a, b.syx\",0,43,"));
+        assert!(lines[1].ends_with(&format!(",manufacturer,,2,{:x}", md5::compute([0xF0, 0x43, 0x00, 0x01, 0xF7]))));
+        assert!(lines[2].starts_with("\"dumps/a, b.syx\",1,,,universal-non-real-time,09 01,0,"));
+    }
+
+    #[cfg(feature = "manufacturer-names")]
+    #[test]
+    fn unknown_manufacturers() {
+        let unknown = Message::from_bytes(&[0xF0, 0x60, 0x01, 0xF7]).unwrap();
+        let known = Message::from_bytes(&[0xF0, 0x43, 0x01, 0xF7]).unwrap();
+        let report = ScanReport {
+            files: vec![
+                (PathBuf::from("a.syx"), vec![unknown.clone(), known.clone(), unknown.clone()]),
+                (PathBuf::from("b.syx"), vec![known]),
+                (PathBuf::from("c.syx"), vec![unknown]),
+            ],
+            errors: Vec::new(),
+        };
+        let unknown = report.unknown_manufacturers();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[&Manufacturer::Standard(0x60)], vec![Path::new("a.syx"), Path::new("c.syx")]);
+    }
+
+    #[test]
+    fn load_files() {
+        let dir = std::env::temp_dir().join(format!("syxpack-scan-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7, 0xF0, 0x41, 0x10, 0x02, 0xF7]).unwrap();
+        fs::write(dir.join("b.SYX"), [0x00, 0x43, 0xF7]).unwrap();
+        fs::write(dir.join("notes.txt"), b"not a dump").unwrap();
+
+        assert!(load_directory(&dir).is_err());
+
+        let (loaded, errors) = load_directory_collecting_errors(&dir).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, dir.join("a.syx"));
+        assert_eq!(loaded[0].1.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, dir.join("b.SYX"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,488 @@
+//! # schema
+//!
+//! Payload layout descriptions loaded at runtime from TOML or JSON,
+//! for decoding messages of devices without a built-in profile.
+//!
+//! A schema in TOML looks like this:
+//!
+//! ```toml
+//! name = "Example Synth Patch"
+//! manufacturer = "43"
+//! header = [0x00, 0x01]
+//!
+//! [[fields]]
+//! name = "Patch name"
+//! offset = 2
+//! width = 10
+//! encoding = "ascii"
+//!
+//! [[fields]]
+//! name = "Waveform"
+//! offset = 12
+//! values = { "0" = "Sine", "1" = "Saw" }
+//! ```
+//!
+//! Offsets are from the start of the payload, after the manufacturer ID.
+//! Only available with the `schema` feature.
+//!
+//! Single fields can also be given as short specifications like
+//! `patch=0x10:2:nybbles` (see [`Field::from_spec`]) and extracted from
+//! many files at once with [`extract_fields`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::{Message, Manufacturer, Section, SectionKind, SectionProfile};
+use crate::scan::FileMessages;
+
+/// Error type for loading schemas and decoding payloads with them.
+#[derive(Debug)]
+pub enum SchemaError {
+    /// The schema file could not be read.
+    Io(io::Error),
+    /// The schema could not be parsed.
+    Parse(String),
+    /// The message does not match the schema.
+    Mismatch,
+    /// A field extends past the end of the payload.
+    OutOfRange { field: String },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::Io(e) => write!(f, "{}", e),
+            SchemaError::Parse(e) => write!(f, "Invalid schema: {}", e),
+            SchemaError::Mismatch => write!(f, "Message does not match the schema"),
+            SchemaError::OutOfRange { field } => write!(f, "Field '{}' is out of range", field),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SchemaError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SchemaError {
+    fn from(e: io::Error) -> Self {
+        SchemaError::Io(e)
+    }
+}
+
+/// Encoding of a field in the payload.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    /// Unsigned value in 7-bit bytes, most significant byte first.
+    #[default]
+    Unsigned,
+    /// Unsigned value in 7-bit bytes, least significant byte first.
+    UnsignedLsb,
+    /// Unsigned value in 4-bit nybbles, most significant nybble first.
+    Nybbles,
+    /// Unsigned value in 4-bit nybbles, least significant nybble first.
+    NybblesLsb,
+    /// Printable ASCII text.
+    Ascii,
+    /// Raw bytes, shown in hex.
+    Raw,
+}
+
+impl Encoding {
+    /// Gets the encoding by name, as in schema files: `unsigned`,
+    /// `unsigned-lsb`, `nybbles`, `nybbles-lsb`, `ascii` or `raw`.
+    /// `nibbles` is accepted for `nybbles`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unsigned" => Some(Encoding::Unsigned),
+            "unsigned-lsb" => Some(Encoding::UnsignedLsb),
+            "nybbles" | "nibbles" => Some(Encoding::Nybbles),
+            "nybbles-lsb" | "nibbles-lsb" => Some(Encoding::NybblesLsb),
+            "ascii" => Some(Encoding::Ascii),
+            "raw" => Some(Encoding::Raw),
+            _ => None,
+        }
+    }
+
+    /// Gets the largest width in bytes of a field in this encoding.
+    /// Numeric values must fit in 64 bits.
+    pub fn max_width(&self) -> usize {
+        match self {
+            Encoding::Unsigned | Encoding::UnsignedLsb => 9,
+            Encoding::Nybbles | Encoding::NybblesLsb => 16,
+            Encoding::Ascii | Encoding::Raw => usize::MAX,
+        }
+    }
+}
+
+/// Description of one field in a payload.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub offset: usize,
+    /// Width of the field in bytes.
+    #[serde(default = "default_width")]
+    pub width: usize,
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// Labels for the values of the field, keyed by the decimal value.
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+fn default_width() -> usize {
+    1
+}
+
+/// Decoded value of a field.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Value {
+    Number(u64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "\"{}\"", s),
+            Value::Bytes(b) => write!(f, "{}", hex::encode_upper(b)),
+        }
+    }
+}
+
+/// A field decoded from a payload.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FieldValue {
+    pub name: String,
+    pub value: Value,
+    /// The label of the value, if the schema maps it to one.
+    pub label: Option<String>,
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{}: {} ({})", self.name, self.value, label),
+            None => write!(f, "{}: {}", self.name, self.value),
+        }
+    }
+}
+
+impl Field {
+    /// Parses a field from a specification of the form
+    /// `name=offset[:width[:encoding]]`, like `patch=0x10:2:nybbles`.
+    /// The offset and width are decimal, or hex with a `0x` prefix.
+    /// The width defaults to 1 and the encoding to `unsigned`.
+    pub fn from_spec(spec: &str) -> Result<Self, SchemaError> {
+        let invalid = || SchemaError::Parse(format!("invalid field specification '{}'", spec));
+        let (name, layout) = spec.split_once('=').ok_or_else(invalid)?;
+        let mut parts = layout.split(':');
+        let offset = parts.next().and_then(parse_number).ok_or_else(invalid)?;
+        let width = match parts.next() {
+            Some(width) => parse_number(width).ok_or_else(invalid)?,
+            None => default_width(),
+        };
+        let encoding = match parts.next() {
+            Some(encoding) => Encoding::from_name(encoding.trim()).ok_or_else(invalid)?,
+            None => Encoding::default(),
+        };
+        if name.trim().is_empty() || parts.next().is_some() || width == 0 || width > encoding.max_width() {
+            return Err(invalid());
+        }
+        Ok(Field { name: name.trim().to_string(), offset, width, encoding, values: BTreeMap::new() })
+    }
+
+    /// Decodes this field from a payload.
+    pub fn decode(&self, payload: &[u8]) -> Result<FieldValue, SchemaError> {
+        let data = self.offset.checked_add(self.width).and_then(|end| payload.get(self.offset..end))
+            .ok_or_else(|| SchemaError::OutOfRange { field: self.name.clone() })?;
+        let value = match self.encoding {
+            Encoding::Unsigned => Value::Number(data.iter().fold(0, |acc, b| (acc << 7) | (*b as u64 & 0x7f))),
+            Encoding::UnsignedLsb => Value::Number(data.iter().rev().fold(0, |acc, b| (acc << 7) | (*b as u64 & 0x7f))),
+            Encoding::Nybbles => Value::Number(data.iter().fold(0, |acc, b| (acc << 4) | (*b as u64 & 0x0f))),
+            Encoding::NybblesLsb => Value::Number(data.iter().rev().fold(0, |acc, b| (acc << 4) | (*b as u64 & 0x0f))),
+            Encoding::Ascii => {
+                let text: String = data.iter()
+                    .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+                    .collect();
+                Value::Text(text.trim_end().to_string())
+            },
+            Encoding::Raw => Value::Bytes(data.to_vec()),
+        };
+        let label = match &value {
+            Value::Number(n) => self.values.iter()
+                .find(|(key, _)| key.parse::<u64>() == Ok(*n))
+                .map(|(_, label)| label.clone()),
+            _ => None,
+        };
+        Ok(FieldValue { name: self.name.clone(), value, label })
+    }
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// One message in a table of extracted fields.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FieldRow {
+    pub path: PathBuf,
+    /// Index of the message in its file.
+    pub index: usize,
+    /// The values of the fields, or `None` where the payload is too short.
+    pub values: Vec<Option<Value>>,
+}
+
+/// Fields extracted from the messages of many files.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FieldTable {
+    /// The names of the fields, in column order.
+    pub names: Vec<String>,
+    pub rows: Vec<FieldRow>,
+}
+
+impl fmt::Display for FieldTable {
+    /// Writes the table as tab-separated text with a header line.
+    /// Missing values are shown as `-`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file\tmessage")?;
+        for name in &self.names {
+            write!(f, "\t{}", name)?;
+        }
+        writeln!(f)?;
+        for row in &self.rows {
+            write!(f, "{}\t{}", row.path.display(), row.index)?;
+            for value in &row.values {
+                match value {
+                    Some(value) => write!(f, "\t{}", value)?,
+                    None => write!(f, "\t-")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the fields from the payload of every message in the files,
+/// one row per message.
+pub fn extract_fields(fields: &[Field], files: &[FileMessages]) -> FieldTable {
+    let rows = files.iter()
+        .flat_map(|(path, messages)| messages.iter().enumerate().map(move |(index, message)| FieldRow {
+            path: path.clone(),
+            index,
+            values: fields.iter().map(|field| field.decode(message.payload()).ok().map(|v| v.value)).collect(),
+        }))
+        .collect();
+    FieldTable { names: fields.iter().map(|field| field.name.clone()).collect(), rows }
+}
+
+/// A payload layout description.
+#[derive(Clone, Eq, PartialEq, Debug, Deserialize)]
+pub struct Schema {
+    pub name: String,
+    /// Manufacturer ID of the messages this schema applies to, in hex.
+    /// If not given, the schema applies to any manufacturer-specific message.
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    /// Bytes that the payload must start with.
+    #[serde(default)]
+    pub header: Vec<u8>,
+    /// Payload length in bytes that the message must have, if known.
+    #[serde(default)]
+    pub length: Option<usize>,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+impl Schema {
+    /// Parses a schema from TOML.
+    pub fn from_toml(s: &str) -> Result<Self, SchemaError> {
+        toml::from_str::<Schema>(s).map_err(|e| SchemaError::Parse(e.to_string()))?.validated()
+    }
+
+    /// Parses a schema from JSON.
+    pub fn from_json(s: &str) -> Result<Self, SchemaError> {
+        serde_json::from_str::<Schema>(s).map_err(|e| SchemaError::Parse(e.to_string()))?.validated()
+    }
+
+    /// Loads a schema from a `.json` or `.toml` file, by file extension.
+    pub fn load(path: &Path) -> Result<Self, SchemaError> {
+        let text = fs::read_to_string(path)?;
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            Schema::from_json(&text)
+        }
+        else {
+            Schema::from_toml(&text)
+        }
+    }
+
+    fn validated(self) -> Result<Self, SchemaError> {
+        if let Some(id) = &self.manufacturer {
+            Manufacturer::from_hex(id).map_err(|e| SchemaError::Parse(e.to_string()))?;
+        }
+        if let Some(field) = self.fields.iter().find(|field| field.width == 0 || field.width > field.encoding.max_width()) {
+            return Err(SchemaError::Parse(format!("field '{}' has invalid width {}", field.name, field.width)));
+        }
+        Ok(self)
+    }
+
+    /// Returns `true` if the schema applies to the message.
+    pub fn matches(&self, message: &Message) -> bool {
+        let (manufacturer, payload) = match message {
+            Message::ManufacturerSpecific { manufacturer, payload } => (manufacturer, payload),
+            Message::Universal { .. } => return false,
+        };
+        let manufacturer_matches = match &self.manufacturer {
+            Some(id) => Manufacturer::from_hex(id).ok() == Some(*manufacturer),
+            None => true,
+        };
+        manufacturer_matches
+            && payload.starts_with(&self.header)
+            && self.length.is_none_or(|length| payload.len() == length)
+    }
+
+    /// Decodes the fields of the message.
+    pub fn decode(&self, message: &Message) -> Result<Vec<FieldValue>, SchemaError> {
+        if !self.matches(message) {
+            return Err(SchemaError::Mismatch);
+        }
+        self.fields.iter().map(|field| field.decode(message.payload())).collect()
+    }
+
+    /// Describes the fields of the message, one per line.
+    pub fn describe(&self, message: &Message) -> Result<String, SchemaError> {
+        let values = self.decode(message)?;
+        let lines: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+impl SectionProfile for Schema {
+    fn payload_sections(&self, message: &Message) -> Option<Vec<Section>> {
+        if !self.matches(message) {
+            return None;
+        }
+        let payload_offset = message.byte_len() - message.payload().len() - 1;
+        let mut fields: Vec<&Field> = self.fields.iter().collect();
+        fields.sort_by_key(|field| field.offset);
+        Some(fields.iter()
+            .map(|field| {
+                let kind = if field.encoding == Encoding::Ascii { SectionKind::Name } else { SectionKind::Payload };
+                Section::labeled(kind, &field.name, payload_offset + field.offset, field.width)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML_SCHEMA: &str = r#"
+        name = "Example Synth Patch"
+        manufacturer = "43"
+        header = [0x00, 0x01]
+
+        [[fields]]
+        name = "Patch name"
+        offset = 2
+        width = 4
+        encoding = "ascii"
+
+        [[fields]]
+        name = "Waveform"
+        offset = 6
+        values = { "0" = "Sine", "1" = "Saw" }
+
+        [[fields]]
+        name = "Cutoff"
+        offset = 7
+        width = 2
+        encoding = "unsigned-lsb"
+    "#;
+
+    fn message() -> Message {
+        Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, b'B', b'a', b's', b's', 0x01, 0x10, 0x01, 0xF7]).unwrap()
+    }
+
+    #[test]
+    fn decode_with_toml_schema() {
+        let schema = Schema::from_toml(TOML_SCHEMA).unwrap();
+        let values = schema.decode(&message()).unwrap();
+        assert_eq!(values[0].value, Value::Text("Bass".to_string()));
+        assert_eq!(values[1].label, Some("Saw".to_string()));
+        assert_eq!(values[2].value, Value::Number(0x90));
+        assert_eq!(schema.describe(&message()).unwrap(), "Patch name: \"Bass\"\nWaveform: 1 (Saw)\nCutoff: 144");
+
+        let sections = message().sections_with(&schema);
+        assert!(sections.iter().any(|s| s.kind == SectionKind::Name && s.range() == (4..8)));
+    }
+
+    #[test]
+    fn decode_with_json_schema() {
+        let schema = Schema::from_json(r#"{
+            "name": "Nybbles",
+            "fields": [ { "name": "Value", "offset": 0, "width": 2, "encoding": "nybbles-lsb" } ]
+        }"#).unwrap();
+        let message = Message::from_bytes(&[0xF0, 0x41, 0x0F, 0x01, 0xF7]).unwrap();
+        assert_eq!(schema.decode(&message).unwrap()[0].value, Value::Number(0x1F));
+    }
+
+    #[test]
+    fn field_specs() {
+        let field = Field::from_spec("patch=0x10:2:nibbles").unwrap();
+        assert_eq!((field.name.as_str(), field.offset, field.width, field.encoding), ("patch", 16, 2, Encoding::Nybbles));
+        let field = Field::from_spec("bank=3").unwrap();
+        assert_eq!((field.offset, field.width, field.encoding), (3, 1, Encoding::Unsigned));
+
+        for spec in ["patch", "=1", "x=1:0", "x=1:10", "x=1:2:hex", "x=1:2:raw:3", "x=y"] {
+            assert!(matches!(Field::from_spec(spec), Err(SchemaError::Parse(_))), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn extract_table() {
+        let fields = vec![Field::from_spec("name=2:4:ascii").unwrap(), Field::from_spec("wave=6").unwrap()];
+        let short = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, b'B', 0xF7]).unwrap();
+        let files = vec![
+            (PathBuf::from("a.syx"), vec![message()]),
+            (PathBuf::from("b.syx"), vec![message(), short]),
+        ];
+        let table = extract_fields(&fields, &files);
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.rows[2].values, vec![None, None]);
+        assert_eq!(table.to_string(),
+            "file\tmessage\tname\twave\na.syx\t0\t\"Bass\"\t1\nb.syx\t0\t\"Bass\"\t1\nb.syx\t1\t-\t-\n");
+    }
+
+    #[test]
+    fn mismatch_and_errors() {
+        let schema = Schema::from_toml(TOML_SCHEMA).unwrap();
+        let other = Message::from_bytes(&[0xF0, 0x41, 0x00, 0x01, 0xF7]).unwrap();
+        assert!(matches!(schema.decode(&other), Err(SchemaError::Mismatch)));
+
+        let short = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, b'B', 0xF7]).unwrap();
+        assert!(matches!(schema.decode(&short), Err(SchemaError::OutOfRange { .. })));
+        let huge = Field::from_spec("data=2:0xffffffffffffffff:raw").unwrap();
+        assert!(matches!(huge.decode(&[0x00; 8]), Err(SchemaError::OutOfRange { .. })));
+
+        assert!(matches!(Schema::from_toml("name = 1"), Err(SchemaError::Parse(_))));
+        assert!(matches!(Schema::from_toml("name = \"x\"\nmanufacturer = \"zz\""), Err(SchemaError::Parse(_))));
+    }
+}
@@ -0,0 +1,170 @@
+//! # script
+//!
+//! Filter and transform expressions for messages, written in the
+//! [Rhai](https://rhai.rs) scripting language, so that tools can offer
+//! custom filters without recompiling. For example:
+//!
+//! ```text
+//! msg.manufacturer_id == "41" && msg.payload[2] == 0x12
+//! ```
+//!
+//! The message is available to the script as the object map `msg` with these
+//! properties: `kind` (`"universal"` or `"manufacturer"`), `manufacturer`
+//! (the name, empty for universal messages), `manufacturer_id` (in hex),
+//! `device`, `sub_id1` and `sub_id2` (universal messages only, otherwise -1),
+//! `payload` (an array of bytes) and `length` (of the whole message in bytes).
+//!
+//! Only available with the `scripting` feature.
+
+use std::fmt;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use crate::{Message, UniversalKind};
+
+/// Error type for compiling and running scripts.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ScriptError {
+    /// The script could not be compiled.
+    Compile(String),
+    /// The script failed while running, or returned an unexpected value.
+    Runtime(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Compile(e) => write!(f, "Script error: {}", e),
+            ScriptError::Runtime(e) => write!(f, "Script failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A compiled script for filtering or transforming messages.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    /// Compiles a script.
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(1_000_000);
+        let ast = engine.compile(source).map_err(|e| ScriptError::Compile(e.to_string()))?;
+        Ok(Script { engine, ast })
+    }
+
+    /// Runs the script as a filter. The script must evaluate to a boolean.
+    pub fn matches(&self, message: &Message) -> Result<bool, ScriptError> {
+        let mut scope = Scope::new();
+        scope.push("msg", message_map(message));
+        self.engine.eval_ast_with_scope::<bool>(&mut scope, &self.ast)
+            .map_err(|e| ScriptError::Runtime(e.to_string()))
+    }
+
+    /// Runs the script as a transform. The script may change `msg.payload`;
+    /// the message is then rebuilt with the new payload. If the script
+    /// evaluates to `false`, the message is dropped and `None` is returned.
+    pub fn transform(&self, message: &Message) -> Result<Option<Message>, ScriptError> {
+        let mut scope = Scope::new();
+        scope.push("msg", message_map(message));
+        let result: Dynamic = self.engine.eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| ScriptError::Runtime(e.to_string()))?;
+        if result.as_bool() == Ok(false) {
+            return Ok(None);
+        }
+
+        let map = scope.get_value::<Map>("msg")
+            .ok_or_else(|| ScriptError::Runtime("msg is no longer an object map".to_string()))?;
+        let payload = map.get("payload")
+            .and_then(|payload| payload.clone().try_cast::<Array>())
+            .ok_or_else(|| ScriptError::Runtime("msg.payload is not an array".to_string()))?;
+        let payload = payload.iter()
+            .map(|b| match b.as_int() {
+                Ok(b) if (0..0x80).contains(&b) => Ok(b as u8),
+                _ => Err(ScriptError::Runtime(format!("invalid payload byte {}", b))),
+            })
+            .collect::<Result<Vec<u8>, ScriptError>>()?;
+
+        let mut result = message.clone();
+        match &mut result {
+            Message::Universal { payload: p, .. } | Message::ManufacturerSpecific { payload: p, .. } => *p = payload,
+        }
+        Ok(Some(result))
+    }
+}
+
+/// Makes the object map that represents the message in scripts.
+fn message_map(message: &Message) -> Map {
+    let mut map = Map::new();
+    let none: rhai::INT = -1;
+    match message {
+        Message::Universal { kind, target, sub_id1, sub_id2, .. } => {
+            let kind_name = match kind {
+                UniversalKind::NonRealTime => "non-real-time",
+                UniversalKind::RealTime => "real-time",
+            };
+            map.insert("kind".into(), "universal".into());
+            map.insert("universal_kind".into(), kind_name.into());
+            map.insert("manufacturer".into(), "".into());
+            map.insert("manufacturer_id".into(), "".into());
+            map.insert("device".into(), (*target as rhai::INT).into());
+            map.insert("sub_id1".into(), (*sub_id1 as rhai::INT).into());
+            map.insert("sub_id2".into(), (*sub_id2 as rhai::INT).into());
+        },
+        Message::ManufacturerSpecific { manufacturer, .. } => {
+            map.insert("kind".into(), "manufacturer".into());
+            map.insert("universal_kind".into(), "".into());
+            map.insert("manufacturer".into(), manufacturer.name().into());
+            map.insert("manufacturer_id".into(), manufacturer.to_hex().into());
+            map.insert("device".into(), none.into());
+            map.insert("sub_id1".into(), none.into());
+            map.insert("sub_id2".into(), none.into());
+        },
+    }
+    let payload: Array = message.payload().iter().map(|b| Dynamic::from(*b as rhai::INT)).collect();
+    map.insert("payload".into(), payload.into());
+    map.insert("length".into(), (message.byte_len() as rhai::INT).into());
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roland_message() -> Message {
+        Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]).unwrap()
+    }
+
+    #[test]
+    fn filter() {
+        let script = Script::compile(r#"msg.manufacturer_id == "41" && msg.payload[2] == 0x12"#).unwrap();
+        assert_eq!(script.matches(&roland_message()), Ok(true));
+
+        let universal = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        assert_eq!(script.matches(&universal), Ok(false));
+
+        let script = Script::compile("msg.kind == \"universal\" && msg.sub_id1 == 9").unwrap();
+        assert_eq!(script.matches(&universal), Ok(true));
+    }
+
+    #[test]
+    fn transform() {
+        let script = Script::compile("if msg.manufacturer_id == \"41\" { msg.payload[0] = 0x11; true } else { false }").unwrap();
+        let transformed = script.transform(&roland_message()).unwrap().unwrap();
+        assert_eq!(transformed.payload()[0], 0x11);
+
+        let universal = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]).unwrap();
+        assert_eq!(script.transform(&universal).unwrap(), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Script::compile("msg.payload[ == 1"), Err(ScriptError::Compile(_))));
+        let script = Script::compile("msg.payload.len()").unwrap();
+        assert!(matches!(script.matches(&roland_message()), Err(ScriptError::Runtime(_))));
+        let script = Script::compile("msg.payload[0] = 300; true").unwrap();
+        assert!(matches!(script.transform(&roland_message()), Err(ScriptError::Runtime(_))));
+    }
+}
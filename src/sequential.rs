@@ -0,0 +1,255 @@
+//! # sequential
+//!
+//! Helpers for Sequential (formerly Dave Smith Instruments) program dumps
+//! from the Prophet-6, OB-6 and Prophet Rev2.
+
+use std::fmt;
+use crate::{Message, Manufacturer, SystemExclusiveError};
+use crate::patch::{Patch, single_message};
+
+/// Sequential manufacturer ID.
+pub const SEQUENTIAL: u8 = 0x01;
+
+/// Opcode of a program data dump.
+pub const PROGRAM_DUMP: u8 = 0x02;
+
+/// Opcode of an edit buffer data dump.
+pub const EDIT_BUFFER_DUMP: u8 = 0x03;
+
+/// Length of the program name in characters.
+pub const NAME_LENGTH: usize = 20;
+
+/// Sequential product with program dump support.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Product {
+    Prophet6,
+    Ob6,
+    ProphetRev2,
+}
+
+impl Product {
+    /// Gets the product from its System Exclusive ID.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x2d => Some(Product::Prophet6),
+            0x2e => Some(Product::Ob6),
+            0x2f => Some(Product::ProphetRev2),
+            _ => None,
+        }
+    }
+
+    /// Gets the System Exclusive ID of the product.
+    pub fn id(&self) -> u8 {
+        match self {
+            Product::Prophet6 => 0x2d,
+            Product::Ob6 => 0x2e,
+            Product::ProphetRev2 => 0x2f,
+        }
+    }
+
+    /// Gets the offset of the program name in the unpacked program data.
+    pub fn name_offset(&self) -> usize {
+        match self {
+            Product::Prophet6 | Product::Ob6 => 107,
+            Product::ProphetRev2 => 235,  // layer A
+        }
+    }
+}
+
+impl fmt::Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Product::Prophet6 => "Prophet-6",
+            Product::Ob6 => "OB-6",
+            Product::ProphetRev2 => "Prophet Rev2",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Unpacks data in the Sequential "packed MS bit" format, where each
+/// group of eight bytes starts with the most significant bits of
+/// the following seven bytes.
+pub fn unpack_data(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() / 8 * 7 + 7);
+    for chunk in data.chunks(8) {
+        let ms_bits = chunk[0];
+        for (i, b) in chunk[1..].iter().enumerate() {
+            result.push(b | (((ms_bits >> i) & 0x01) << 7));
+        }
+    }
+    result
+}
+
+/// Packs data into the Sequential "packed MS bit" format.
+pub fn pack_data(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() / 7 * 8 + 8);
+    for chunk in data.chunks(7) {
+        let mut ms_bits = 0u8;
+        for (i, b) in chunk.iter().enumerate() {
+            ms_bits |= (b >> 7) << i;
+        }
+        result.push(ms_bits);
+        result.extend(chunk.iter().map(|b| b & 0x7f));
+    }
+    result
+}
+
+/// A Sequential program dump. The data is kept unpacked.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Dump {
+    Program { product: Product, bank: u8, program: u8, data: Vec<u8> },
+    EditBuffer { product: Product, data: Vec<u8> },
+}
+
+impl Dump {
+    /// Parses a program or edit buffer dump from a System Exclusive message.
+    pub fn from_message(message: &Message) -> Result<Self, SystemExclusiveError> {
+        let payload = match message {
+            Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(SEQUENTIAL), payload } => payload,
+            Message::ManufacturerSpecific { .. } => return Err(SystemExclusiveError::InvalidManufacturer),
+            _ => return Err(SystemExclusiveError::InvalidMessage),
+        };
+
+        if payload.len() < 2 {
+            return Err(SystemExclusiveError::InvalidMessage);
+        }
+        let product = Product::from_id(payload[0]).ok_or(SystemExclusiveError::InvalidMessage)?;
+
+        match payload[1] {
+            PROGRAM_DUMP if payload.len() >= 4 => Ok(Dump::Program {
+                product,
+                bank: payload[2],
+                program: payload[3],
+                data: unpack_data(&payload[4..]),
+            }),
+            EDIT_BUFFER_DUMP => Ok(Dump::EditBuffer {
+                product,
+                data: unpack_data(&payload[2..]),
+            }),
+            _ => Err(SystemExclusiveError::InvalidMessage),
+        }
+    }
+
+    /// Makes a System Exclusive message from this dump.
+    pub fn to_message(&self) -> Message {
+        let mut payload = vec![self.product().id()];
+        match self {
+            Dump::Program { bank, program, .. } => {
+                payload.push(PROGRAM_DUMP);
+                payload.push(*bank);
+                payload.push(*program);
+            },
+            Dump::EditBuffer { .. } => payload.push(EDIT_BUFFER_DUMP),
+        }
+        payload.extend(pack_data(self.data()));
+
+        Message::ManufacturerSpecific {
+            manufacturer: Manufacturer::Standard(SEQUENTIAL),
+            payload,
+        }
+    }
+
+    /// Gets the product of this dump.
+    pub fn product(&self) -> Product {
+        match self {
+            Dump::Program { product, .. } | Dump::EditBuffer { product, .. } => *product,
+        }
+    }
+
+    /// Gets the unpacked program data.
+    pub fn data(&self) -> &[u8] {
+        match self {
+            Dump::Program { data, .. } | Dump::EditBuffer { data, .. } => data,
+        }
+    }
+
+    /// Gets the bank and program number, if this is a program dump.
+    pub fn location(&self) -> Option<(u8, u8)> {
+        match self {
+            Dump::Program { bank, program, .. } => Some((*bank, *program)),
+            Dump::EditBuffer { .. } => None,
+        }
+    }
+
+    /// Gets the program name with trailing spaces removed,
+    /// or `None` if the data is too short to contain it.
+    pub fn name(&self) -> Option<String> {
+        let offset = self.product().name_offset();
+        let name_bytes = self.data().get(offset..offset + NAME_LENGTH)?;
+        let name: String = name_bytes.iter()
+            .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+            .collect();
+        Some(name.trim_end().to_string())
+    }
+}
+
+impl Patch for Dump {
+    fn name(&self) -> Option<String> {
+        Dump::name(self)
+    }
+
+    fn to_messages(&self) -> Vec<Message> {
+        vec![self.to_message()]
+    }
+
+    fn from_messages(messages: &[Message]) -> Result<Self, SystemExclusiveError> {
+        Dump::from_message(single_message(messages)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn pack_unpack_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        let packed = pack_data(&data);
+        assert!(packed.iter().all(|b| b & 0x80 == 0));
+        assert_eq!(unpack_data(&packed)[..data.len()], data[..]);
+    }
+
+    #[test]
+    fn unpack_ms_bits() {
+        let packed = [0b0000_0101, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        assert_eq!(unpack_data(&packed), vec![0x81, 0x02, 0x83, 0x04, 0x05, 0x06, 0x07]);
+    }
+
+    #[test]
+    fn program_dump() {
+        let mut data = vec![0u8; 1024];
+        data[107..107 + 9].copy_from_slice(b"Fat Brass");
+        for b in &mut data[107 + 9..107 + NAME_LENGTH] {
+            *b = b' ';
+        }
+        data[0] = 0xc8;
+
+        let dump = Dump::Program { product: Product::Prophet6, bank: 3, program: 42, data: data.clone() };
+        let bytes = dump.to_message().to_bytes();
+        assert_eq!(bytes[..6], [0xF0, 0x01, 0x2D, 0x02, 0x03, 42]);
+
+        let parsed = Dump::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(parsed.product(), Product::Prophet6);
+        assert_eq!(parsed.location(), Some((3, 42)));
+        assert_eq!(parsed.name(), Some("Fat Brass".to_string()));
+        assert_eq!(parsed.data()[..1024], data[..]);
+    }
+
+    #[test]
+    fn edit_buffer_dump() {
+        let bytes = vec![0xF0, 0x01, 0x2E, 0x03, 0x00, 0x10, 0x20, 0xF7];
+        let dump = Dump::from_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(dump.product(), Product::Ob6);
+        assert_eq!(dump.location(), None);
+        assert_eq!(dump.data(), &[0x10, 0x20]);
+    }
+
+    proptest! {
+        #[test]
+        fn pack_unpack_property(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+            prop_assert_eq!(unpack_data(&pack_data(&data)), data);
+        }
+    }
+}
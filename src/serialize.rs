@@ -0,0 +1,307 @@
+//! # serialize
+//!
+//! Serde support for `Message`, `Manufacturer` and `MessageBank`.
+//! Only available with the `serde` feature.
+//!
+//! The representation follows the serializer. Human-readable formats like
+//! JSON get hex strings and manufacturer names:
+//!
+//! ```json
+//! { "manufacturer": "43", "name": "Yamaha", "payload": "0009200000" }
+//! { "kind": "non-real-time", "target": 127, "sub_id1": 6, "sub_id2": 1, "payload": "" }
+//! ```
+//!
+//! Binary formats get the raw bytes of the message or the manufacturer ID.
+//! The manufacturer name is informational and ignored when deserializing.
+//! Wrap a value in [`Compact`] to get the raw bytes in a human-readable
+//! format too, for example for caches stored as JSON.
+
+use std::fmt;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+use crate::{Message, Manufacturer, UniversalKind};
+use crate::bank::MessageBank;
+
+/// A wrapper that always serializes its value as raw bytes,
+/// whether or not the format is human-readable.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Compact<T>(pub T);
+
+const MESSAGE_FIELDS: &[&str] = &["manufacturer", "name", "kind", "target", "sub_id1", "sub_id2", "payload"];
+
+fn kind_name(kind: UniversalKind) -> &'static str {
+    match kind {
+        UniversalKind::NonRealTime => "non-real-time",
+        UniversalKind::RealTime => "real-time",
+    }
+}
+
+fn serialize_message<S: Serializer>(message: &Message, serializer: S, readable: bool) -> Result<S::Ok, S::Error> {
+    if !readable {
+        return serializer.serialize_bytes(&message.to_bytes());
+    }
+    match message {
+        Message::ManufacturerSpecific { manufacturer, payload } => {
+            let mut state = serializer.serialize_struct("Message", 3)?;
+            state.serialize_field("manufacturer", &manufacturer.to_hex())?;
+            state.serialize_field("name", &manufacturer.name())?;
+            state.serialize_field("payload", &hex::encode_upper(payload))?;
+            state.end()
+        },
+        Message::Universal { kind, target, sub_id1, sub_id2, payload } => {
+            let mut state = serializer.serialize_struct("Message", 5)?;
+            state.serialize_field("kind", kind_name(*kind))?;
+            state.serialize_field("target", target)?;
+            state.serialize_field("sub_id1", sub_id1)?;
+            state.serialize_field("sub_id2", sub_id2)?;
+            state.serialize_field("payload", &hex::encode_upper(payload))?;
+            state.end()
+        },
+    }
+}
+
+fn serialize_manufacturer<S: Serializer>(manufacturer: &Manufacturer, serializer: S, readable: bool) -> Result<S::Ok, S::Error> {
+    if readable {
+        serializer.serialize_str(&manufacturer.to_hex())
+    }
+    else {
+        serializer.serialize_bytes(&manufacturer.to_bytes())
+    }
+}
+
+/// Collects raw bytes given either as bytes or as a sequence of numbers.
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut result = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            result.push(b);
+        }
+        Ok(result)
+    }
+}
+
+struct MessageVisitor;
+
+impl<'de> Visitor<'de> for MessageVisitor {
+    type Value = Message;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a System Exclusive message")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut manufacturer: Option<String> = None;
+        let mut kind: Option<String> = None;
+        let mut target: Option<u8> = None;
+        let mut sub_id1: Option<u8> = None;
+        let mut sub_id2: Option<u8> = None;
+        let mut payload: Option<String> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "manufacturer" => manufacturer = Some(map.next_value()?),
+                "kind" => kind = Some(map.next_value()?),
+                "target" => target = Some(map.next_value()?),
+                "sub_id1" => sub_id1 = Some(map.next_value()?),
+                "sub_id2" => sub_id2 = Some(map.next_value()?),
+                "payload" => payload = Some(map.next_value()?),
+                _ => { map.next_value::<IgnoredAny>()?; },
+            }
+        }
+
+        let payload = hex::decode(payload.ok_or_else(|| de::Error::missing_field("payload"))?)
+            .map_err(de::Error::custom)?;
+        let mut data = vec![crate::INITIATOR];
+        if let Some(manufacturer) = manufacturer {
+            let manufacturer = Manufacturer::from_hex(&manufacturer).map_err(de::Error::custom)?;
+            data.extend(manufacturer.to_bytes());
+        }
+        else {
+            let kind = match kind.ok_or_else(|| de::Error::missing_field("kind"))?.as_str() {
+                "non-real-time" => crate::NON_REAL_TIME,
+                "real-time" => crate::REAL_TIME,
+                other => return Err(de::Error::unknown_variant(other, &["non-real-time", "real-time"])),
+            };
+            data.push(kind);
+            data.push(target.ok_or_else(|| de::Error::missing_field("target"))?);
+            data.push(sub_id1.ok_or_else(|| de::Error::missing_field("sub_id1"))?);
+            data.push(sub_id2.ok_or_else(|| de::Error::missing_field("sub_id2"))?);
+        }
+        data.extend(payload);
+        data.push(crate::TERMINATOR);
+        Message::from_bytes(&data).map_err(de::Error::custom)
+    }
+}
+
+fn deserialize_message<'de, D: Deserializer<'de>>(deserializer: D, readable: bool) -> Result<Message, D::Error> {
+    if readable {
+        deserializer.deserialize_struct("Message", MESSAGE_FIELDS, MessageVisitor)
+    }
+    else {
+        let data = deserializer.deserialize_bytes(BytesVisitor)?;
+        Message::from_bytes(&data).map_err(de::Error::custom)
+    }
+}
+
+fn deserialize_manufacturer<'de, D: Deserializer<'de>>(deserializer: D, readable: bool) -> Result<Manufacturer, D::Error> {
+    if readable {
+        let s = String::deserialize(deserializer)?;
+        Manufacturer::from_hex(&s).map_err(de::Error::custom)
+    }
+    else {
+        let data = deserializer.deserialize_bytes(BytesVisitor)?;
+        Manufacturer::from_bytes(&data).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let readable = serializer.is_human_readable();
+        serialize_message(self, serializer, readable)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let readable = deserializer.is_human_readable();
+        deserialize_message(deserializer, readable)
+    }
+}
+
+impl Serialize for Manufacturer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let readable = serializer.is_human_readable();
+        serialize_manufacturer(self, serializer, readable)
+    }
+}
+
+impl<'de> Deserialize<'de> for Manufacturer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let readable = deserializer.is_human_readable();
+        deserialize_manufacturer(deserializer, readable)
+    }
+}
+
+/// A bank is a sequence of messages.
+impl Serialize for MessageBank {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.messages())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageBank {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<Message>::deserialize(deserializer)?.into())
+    }
+}
+
+impl Serialize for Compact<&Message> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_message(self.0, serializer, false)
+    }
+}
+
+impl Serialize for Compact<Message> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_message(&self.0, serializer, false)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compact<Message> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Compact(deserialize_message(deserializer, false)?))
+    }
+}
+
+impl Serialize for Compact<Manufacturer> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_manufacturer(&self.0, serializer, false)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compact<Manufacturer> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Compact(deserialize_manufacturer(deserializer, false)?))
+    }
+}
+
+impl Serialize for Compact<&MessageBank> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Compact))
+    }
+}
+
+impl Serialize for Compact<MessageBank> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Compact(&self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compact<MessageBank> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let messages = Vec::<Compact<Message>>::deserialize(deserializer)?;
+        Ok(Compact(messages.into_iter().map(|message| message.0).collect()))
+    }
+}
+
+// serde_json is only available with the schema feature.
+#[cfg(all(test, feature = "schema"))]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x20, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn human_readable() {
+        let json = serde_json::to_string(&messages()).unwrap();
+        assert!(json.contains("\"manufacturer\":\"43\",\"name\":\""));
+        assert!(json.contains("\"payload\":\"000920\""));
+        assert!(json.contains("{\"kind\":\"non-real-time\",\"target\":127,\"sub_id1\":6,\"sub_id2\":1,\"payload\":\"\"}"));
+        assert_eq!(serde_json::from_str::<Vec<Message>>(&json).unwrap(), messages());
+
+        let manufacturer = Manufacturer::Extended([0x00, 0x20, 0x32]);
+        assert_eq!(serde_json::to_string(&manufacturer).unwrap(), "\"002032\"");
+        assert_eq!(serde_json::from_str::<Manufacturer>("\"00 20 32\"").unwrap(), manufacturer);
+
+        assert!(serde_json::from_str::<Message>("{\"manufacturer\":\"43\"}").is_err());
+        assert!(serde_json::from_str::<Message>("{\"kind\":\"other\",\"target\":0,\"sub_id1\":0,\"sub_id2\":0,\"payload\":\"\"}").is_err());
+    }
+
+    #[test]
+    fn compact() {
+        let message = &messages()[0];
+        assert_eq!(serde_json::to_string(&Compact(message)).unwrap(), "[240,67,0,9,32,247]");
+        let parsed: Compact<Message> = serde_json::from_str("[240,67,0,9,32,247]").unwrap();
+        assert_eq!(&parsed.0, message);
+        assert!(serde_json::from_str::<Compact<Message>>("[240,67]").is_err());
+
+        let bank = MessageBank::from(messages());
+        let json = serde_json::to_string(&Compact(&bank)).unwrap();
+        assert_eq!(serde_json::from_str::<Compact<MessageBank>>(&json).unwrap().0, bank);
+        let readable = serde_json::to_string(&bank).unwrap();
+        assert_eq!(serde_json::from_str::<MessageBank>(&readable).unwrap(), bank);
+
+        assert_eq!(serde_json::to_string(&Compact(Manufacturer::Standard(0x41))).unwrap(), "[65]");
+    }
+}
@@ -0,0 +1,275 @@
+//! # simulator
+//!
+//! A simulated device that answers requests with canned responses, for
+//! testing librarian software without hardware attached.
+//!
+//! Register the responses with [`Simulator::add_response`], keyed by a
+//! [`Pattern`] of the request, and feed the received messages to
+//! [`Simulator::handle`]. The simulator also implements
+//! `transfer::Connection`, so it can stand in for a real device in
+//! transfers: sent messages are answered and the responses are received.
+//!
+//! A simulator can also be loaded from a configuration file with one line
+//! per response. The fields are separated by tabs: the pattern of the
+//! request and the `.syx` or `.mid` file of the responses, relative to the
+//! configuration file. An `identity` line gives the device ID, the
+//! manufacturer ID, the family and member codes and the version, in hex
+//! (tabs shown as spaces here):
+//!
+//! ```text
+//! # syxpack simulator
+//! identity  00  43  0001  0002  01000000
+//! F0 43 ?? 09 F7  dx7/rom1a.syx
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use crate::{Message, Manufacturer, SystemExclusiveError};
+use crate::scan::{read_messages, ScanError};
+use crate::transfer::Connection;
+use crate::universal::DeviceIdentity;
+
+/// Error type for loading simulator configuration files.
+#[derive(Debug)]
+pub enum SimulatorError {
+    /// The configuration file could not be read.
+    Io(io::Error),
+    /// A line of the configuration is invalid. The line numbers start from one.
+    Parse { line: usize },
+    /// A response file could not be loaded.
+    Scan(ScanError),
+}
+
+impl fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulatorError::Io(e) => write!(f, "{}", e),
+            SimulatorError::Parse { line } => write!(f, "Invalid configuration on line {}", line),
+            SimulatorError::Scan(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SimulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimulatorError::Io(e) => Some(e),
+            SimulatorError::Scan(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SimulatorError {
+    fn from(e: io::Error) -> Self {
+        SimulatorError::Io(e)
+    }
+}
+
+impl From<ScanError> for SimulatorError {
+    fn from(e: ScanError) -> Self {
+        SimulatorError::Scan(e)
+    }
+}
+
+/// A pattern of the bytes of a request, where some bytes may be anything.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Pattern {
+    bytes: Vec<Option<u8>>,
+}
+
+impl Pattern {
+    /// Makes a pattern that matches exactly one message.
+    pub fn exact(message: &Message) -> Self {
+        Pattern { bytes: message.to_bytes().into_iter().map(Some).collect() }
+    }
+
+    /// Parses a pattern from hex bytes separated by whitespace, where `??`
+    /// matches any byte, like "F0 43 ?? 09 F7".
+    pub fn parse(s: &str) -> Result<Self, SystemExclusiveError> {
+        let bytes = s.split_whitespace()
+            .map(|token| match token {
+                "??" => Ok(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16).map(Some)
+                    .map_err(|_| SystemExclusiveError::InvalidMessage),
+                _ => Err(SystemExclusiveError::InvalidMessage),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pattern { bytes })
+    }
+
+    /// Returns `true` if the bytes of the message match the pattern.
+    pub fn matches(&self, message: &Message) -> bool {
+        let data = message.to_bytes();
+        data.len() == self.bytes.len()
+            && data.iter().zip(&self.bytes).all(|(b, p)| p.is_none_or(|p| p == *b))
+    }
+}
+
+/// A simulated device.
+#[derive(Clone, Debug, Default)]
+pub struct Simulator {
+    rules: Vec<(Pattern, Vec<Message>)>,
+    identity: Option<(u8, DeviceIdentity)>,
+    received: Vec<Message>,
+    pending: VecDeque<Message>,
+}
+
+impl Simulator {
+    /// Makes a simulator with no responses.
+    pub fn new() -> Self {
+        Simulator::default()
+    }
+
+    /// Makes the simulator answer Identity Requests for the device ID
+    /// `device`, or for all devices, with the identity.
+    pub fn set_identity(&mut self, device: u8, identity: DeviceIdentity) {
+        self.identity = Some((device, identity));
+    }
+
+    /// Adds the responses to requests matching the pattern. The first
+    /// added pattern that matches a request is used.
+    pub fn add_response(&mut self, pattern: Pattern, responses: Vec<Message>) {
+        self.rules.push((pattern, responses));
+    }
+
+    /// Handles a received message and returns the responses to send back,
+    /// which are empty if the message is not a known request.
+    pub fn handle(&mut self, message: &Message) -> Vec<Message> {
+        self.received.push(message.clone());
+        if let Some(reply) = self.identity.and_then(|(device, identity)| identity.respond(device, message)) {
+            return vec![reply];
+        }
+        self.rules.iter()
+            .find(|(pattern, _)| pattern.matches(message))
+            .map(|(_, responses)| responses.clone())
+            .unwrap_or_default()
+    }
+
+    /// Loads a simulator from a configuration file. The response files
+    /// are loaded relative to the directory of the configuration file.
+    pub fn load(path: &Path) -> Result<Self, SimulatorError> {
+        let text = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut simulator = Simulator::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = SimulatorError::Parse { line: index + 1 };
+            let fields: Vec<&str> = line.split('\t').map(|field| field.trim()).collect();
+            match fields.as_slice() {
+                ["identity", rest @ ..] => {
+                    let (device, identity) = parse_identity(rest).ok_or(invalid)?;
+                    simulator.set_identity(device, identity);
+                },
+                [pattern, file] => {
+                    let pattern = Pattern::parse(pattern).map_err(|_| invalid)?;
+                    simulator.add_response(pattern, read_messages(&directory.join(file))?);
+                },
+                _ => return Err(invalid),
+            }
+        }
+        Ok(simulator)
+    }
+
+    /// Gets all the messages received so far, in order.
+    pub fn received(&self) -> &[Message] {
+        &self.received
+    }
+}
+
+fn parse_identity(fields: &[&str]) -> Option<(u8, DeviceIdentity)> {
+    let [device, manufacturer, family, member, version] = fields else {
+        return None;
+    };
+    let version: [u8; 4] = hex::decode(version).ok()?.try_into().ok()?;
+    let identity = DeviceIdentity::new(
+        Manufacturer::from_hex(manufacturer).ok()?,
+        u16::from_str_radix(family, 16).ok()?,
+        u16::from_str_radix(member, 16).ok()?,
+        version);
+    Some((u8::from_str_radix(device, 16).ok()?, identity))
+}
+
+impl Connection for Simulator {
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        let responses = self.handle(message);
+        self.pending.extend(responses);
+        Ok(())
+    }
+
+    /// Returns the next response at once, ignoring the timeout.
+    fn receive(&mut self, _timeout: Duration) -> io::Result<Option<Message>> {
+        Ok(self.pending.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Manufacturer;
+    use crate::universal::{identity_request_bytes, IDENTITY_REQUEST};
+
+    fn message(bytes: &[u8]) -> Message {
+        Message::from_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn patterns() {
+        let pattern = Pattern::parse("F0 43 ?? 09 F7").unwrap();
+        assert!(pattern.matches(&message(&[0xF0, 0x43, 0x20, 0x09, 0xF7])));
+        assert!(!pattern.matches(&message(&[0xF0, 0x43, 0x20, 0x00, 0xF7])));
+        assert!(!pattern.matches(&message(&[0xF0, 0x43, 0x20, 0x09, 0x00, 0xF7])));
+        assert!(Pattern::exact(&message(&[0xF0, 0x41, 0x10, 0xF7])).matches(&message(&[0xF0, 0x41, 0x10, 0xF7])));
+        assert!(Pattern::parse("F0 4").is_err());
+        assert!(Pattern::parse("F0 ZZ").is_err());
+    }
+
+    #[test]
+    fn responses() {
+        let dump = vec![message(&[0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7]), message(&[0xF0, 0x43, 0x00, 0x09, 0x02, 0xF7])];
+        let mut simulator = Simulator::new();
+        simulator.add_response(Pattern::parse("F0 43 ?? 09 F7").unwrap(), dump.clone());
+        simulator.set_identity(0x00, DeviceIdentity::new(Manufacturer::Standard(0x43), 0x01, 0x02, [0; 4]));
+
+        assert_eq!(simulator.handle(&message(&[0xF0, 0x43, 0x20, 0x09, 0xF7])), dump);
+        assert!(simulator.handle(&message(&[0xF0, 0x41, 0x10, 0xF7])).is_empty());
+        let reply = simulator.handle(&message(&IDENTITY_REQUEST));
+        assert!(matches!(reply[0], Message::Universal { sub_id1: 0x06, sub_id2: 0x02, .. }));
+        assert!(simulator.handle(&message(&identity_request_bytes(0x05))).is_empty());
+        assert_eq!(simulator.received().len(), 4);
+
+        simulator.send(&message(&[0xF0, 0x43, 0x20, 0x09, 0xF7])).unwrap();
+        assert_eq!(simulator.receive(Duration::ZERO).unwrap(), Some(dump[0].clone()));
+        assert_eq!(simulator.receive(Duration::ZERO).unwrap(), Some(dump[1].clone()));
+        assert_eq!(simulator.receive(Duration::ZERO).unwrap(), None);
+    }
+
+    #[test]
+    fn load_configuration() {
+        let dir = std::env::temp_dir().join(format!("syxpack-simulator-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("dump.syx"), [0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7]).unwrap();
+        let config = dir.join("device.txt");
+        fs::write(&config, "# syxpack simulator\nidentity\t00\t43\t0001\t0002\t01000000\n\nF0 43 ?? 09 F7\tdump.syx\n").unwrap();
+
+        let mut simulator = Simulator::load(&config).unwrap();
+        assert_eq!(simulator.handle(&message(&[0xF0, 0x43, 0x20, 0x09, 0xF7])), vec![message(&[0xF0, 0x43, 0x00, 0x09, 0x01, 0xF7])]);
+        assert_eq!(simulator.handle(&message(&IDENTITY_REQUEST))[0].to_bytes(),
+            vec![0xF0, 0x7E, 0x00, 0x06, 0x02, 0x43, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF7]);
+
+        fs::write(&config, "identity\t00\t43\n").unwrap();
+        assert!(matches!(Simulator::load(&config), Err(SimulatorError::Parse { line: 1 })));
+        fs::write(&config, "F0 43 F7\tmissing.syx\n").unwrap();
+        assert!(matches!(Simulator::load(&config), Err(SimulatorError::Scan(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+//! # smf
+//!
+//! Extracts System Exclusive messages from Standard MIDI Files,
+//! and makes files of System Exclusive messages.
+
+use std::time::Duration;
+use crate::{Message, SystemExclusiveError, INITIATOR, TERMINATOR};
+
+/// The tempo of a file without tempo events, 120 beats per minute.
+pub const DEFAULT_TEMPO: u32 = 500_000;
+
+/// Ticks per quarter note in the files made by `build`. The tempo is set
+/// to the same number of microseconds per quarter note, so that one tick
+/// is one microsecond.
+const BUILD_DIVISION: u16 = 1000;
+
+/// Largest value of a variable-length quantity.
+const MAX_VARIABLE_LENGTH: u32 = 0x0fff_ffff;
+
+/// A System Exclusive event in a Standard MIDI File.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SysExEvent {
+    /// Index of the track containing the event.
+    pub track: usize,
+    /// Absolute time of the event in ticks from the start of the track.
+    pub ticks: u64,
+    /// The complete message, from initiator to terminator.
+    pub data: Vec<u8>,
+}
+
+/// A change of tempo in a Standard MIDI File.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TempoChange {
+    /// Absolute time of the change in ticks.
+    pub ticks: u64,
+    /// The new tempo in microseconds per quarter note.
+    pub microseconds_per_quarter: u32,
+}
+
+/// Returns `true` if the data starts with a Standard MIDI File header.
+pub fn is_smf(data: &[u8]) -> bool {
+    data.starts_with(b"MThd")
+}
+
+/// Reads a variable-length quantity, returning the value and the number of bytes used.
+fn read_variable_length(data: &[u8]) -> Option<(u32, usize)> {
+    let mut value: u32 = 0;
+    for (i, b) in data.iter().take(4).enumerate() {
+        value = (value << 7) | (*b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Writes a variable-length quantity of at most `MAX_VARIABLE_LENGTH`.
+fn write_variable_length(value: u32, out: &mut Vec<u8>) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Writes the delta time of the next event. Delays too long for one
+/// delta time are padded with empty text events.
+fn write_delta(mut delta: u64, out: &mut Vec<u8>) {
+    while delta > MAX_VARIABLE_LENGTH as u64 {
+        write_variable_length(MAX_VARIABLE_LENGTH, out);
+        out.extend([0xff, 0x01, 0x00]);
+        delta -= MAX_VARIABLE_LENGTH as u64;
+    }
+    write_variable_length(delta as u32, out);
+}
+
+/// Makes a Standard MIDI File of format 0 with the messages at their
+/// times from the start of the file, like those from `timed_sysex_events`
+/// or `timing::Timing::schedule`. The messages must be in time order.
+/// The times are kept to the microsecond.
+pub fn build(messages: &[(Duration, Message)]) -> Vec<u8> {
+    let mut track = vec![0x00, 0xff, 0x51, 0x03];
+    track.extend(&(BUILD_DIVISION as u32).to_be_bytes()[1..]);
+    let mut previous: u64 = 0;
+    for (time, message) in messages {
+        let ticks = (time.as_micros() as u64).max(previous);
+        write_delta(ticks - previous, &mut track);
+        previous = ticks;
+        let data = message.to_bytes();
+        track.push(INITIATOR);
+        write_variable_length(data.len() as u32 - 1, &mut track);
+        track.extend(&data[1..]);
+    }
+    track.extend([0x00, 0xff, 0x2f, 0x00]);
+
+    let mut data = b"MThd".to_vec();
+    data.extend(6u32.to_be_bytes());
+    data.extend(0u16.to_be_bytes());
+    data.extend(1u16.to_be_bytes());
+    data.extend(BUILD_DIVISION.to_be_bytes());
+    data.extend(b"MTrk");
+    data.extend((track.len() as u32).to_be_bytes());
+    data.extend(track);
+    data
+}
+
+/// Reads a big-endian 32-bit value.
+fn read_u32(data: &[u8]) -> Option<u32> {
+    let bytes: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    Some(u32::from_be_bytes(bytes))
+}
+
+/// Extracts all the System Exclusive events from a Standard MIDI File,
+/// in track order. Messages split into several packets with F7 continuation
+/// events are joined back together.
+pub fn sysex_events(data: &[u8]) -> Result<Vec<SysExEvent>, SystemExclusiveError> {
+    Ok(parse_file(data)?.0)
+}
+
+/// Gets the tempo changes of a Standard MIDI File from all its tracks,
+/// in time order.
+pub fn tempo_changes(data: &[u8]) -> Result<Vec<TempoChange>, SystemExclusiveError> {
+    Ok(parse_file(data)?.1)
+}
+
+/// Extracts the System Exclusive events like `sysex_events`, together with
+/// the time of each event from the start of the file, following the tempo
+/// changes. The events are in time order, for playing them back at the
+/// pace of the file.
+pub fn timed_sysex_events(data: &[u8]) -> Result<Vec<(Duration, SysExEvent)>, SystemExclusiveError> {
+    let (mut events, tempos) = parse_file(data)?;
+    let division = read_u16(data.get(12..).unwrap_or_default()).ok_or(SystemExclusiveError::Truncated)?;
+    // No ticks per quarter note, or no ticks per SMPTE frame
+    if division == 0 || (division & 0x8000 != 0 && division & 0xff == 0) {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+    events.sort_by_key(|event| event.ticks);
+    Ok(events.into_iter()
+        .map(|event| (ticks_to_duration(event.ticks, division, &tempos), event))
+        .collect())
+}
+
+/// Converts a time in ticks to a duration, using the time division
+/// of the file header and the tempo changes.
+fn ticks_to_duration(ticks: u64, division: u16, tempos: &[TempoChange]) -> Duration {
+    if division & 0x8000 != 0 {
+        // SMPTE time: frames per second (stored negated) and ticks per frame.
+        // The 29 frames per second drop-frame rate is really 29.97.
+        let frames = match (division >> 8) as u8 as i8 {
+            -29 => 29.97,
+            frames => -(frames as f64),
+        };
+        let ticks_per_frame = (division & 0xff) as f64;
+        return Duration::try_from_secs_f64(ticks as f64 / (frames * ticks_per_frame)).unwrap_or(Duration::MAX);
+    }
+
+    // Ticks and tempos up to 24 bits do not overflow in 128 bits.
+    let mut microseconds: u128 = 0;
+    let mut position = 0;
+    let mut tempo = DEFAULT_TEMPO;
+    for change in tempos.iter().take_while(|change| change.ticks < ticks) {
+        microseconds += (change.ticks - position) as u128 * tempo as u128 / division as u128;
+        position = change.ticks;
+        tempo = change.microseconds_per_quarter;
+    }
+    microseconds += (ticks - position) as u128 * tempo as u128 / division as u128;
+    Duration::from_micros(u64::try_from(microseconds).unwrap_or(u64::MAX))
+}
+
+/// Reads a big-endian 16-bit value.
+fn read_u16(data: &[u8]) -> Option<u16> {
+    let bytes: [u8; 2] = data.get(..2)?.try_into().ok()?;
+    Some(u16::from_be_bytes(bytes))
+}
+
+/// Parses all the tracks, returning the System Exclusive events
+/// in track order and the tempo changes in time order.
+fn parse_file(data: &[u8]) -> Result<(Vec<SysExEvent>, Vec<TempoChange>), SystemExclusiveError> {
+    if !is_smf(data) {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+
+    let mut events = Vec::new();
+    let mut tempos = Vec::new();
+    let mut offset = 0;
+    let mut track = 0;
+    while offset + 8 <= data.len() {
+        let chunk_type = &data[offset..offset + 4];
+        let length = read_u32(&data[offset + 4..]).ok_or(SystemExclusiveError::InvalidMessage)? as usize;
+        let start = offset + 8;
+        let end = start.checked_add(length)
+            .filter(|end| *end <= data.len())
+            .ok_or(SystemExclusiveError::Truncated)?;
+
+        if chunk_type == b"MTrk" {
+            parse_track(&data[start..end], track, &mut events, &mut tempos)?;
+            track += 1;
+        }
+        offset = end;
+    }
+
+    tempos.sort_by_key(|change| change.ticks);
+    Ok((events, tempos))
+}
+
+fn parse_track(data: &[u8], track: usize, events: &mut Vec<SysExEvent>, tempos: &mut Vec<TempoChange>) -> Result<(), SystemExclusiveError> {
+    let mut offset = 0;
+    let mut ticks: u64 = 0;
+    let mut running_status: Option<u8> = None;
+    let mut pending: Option<SysExEvent> = None;
+
+    while offset < data.len() {
+        let (delta, count) = read_variable_length(&data[offset..]).ok_or(SystemExclusiveError::InvalidMessage)?;
+        offset += count;
+        ticks += delta as u64;
+
+        let status = *data.get(offset).ok_or(SystemExclusiveError::InvalidMessage)?;
+        match status {
+            0xf0 | 0xf7 => {
+                offset += 1;
+                let (length, count) = read_variable_length(data.get(offset..).unwrap_or_default())
+                    .ok_or(SystemExclusiveError::InvalidMessage)?;
+                offset += count;
+                let bytes = data.get(offset..offset + length as usize).ok_or(SystemExclusiveError::Truncated)?;
+                offset += length as usize;
+                running_status = None;
+
+                if status == INITIATOR {
+                    let mut message = vec![INITIATOR];
+                    message.extend(bytes);
+                    pending = Some(SysExEvent { track, ticks, data: message });
+                }
+                else if let Some(event) = pending.as_mut() {
+                    event.data.extend(bytes);
+                }
+                // An F7 event without a pending message is an escape, not SysEx.
+
+                if pending.as_ref().is_some_and(|e| e.data.last() == Some(&TERMINATOR)) {
+                    events.extend(pending.take());
+                }
+            },
+            0xff => {
+                let length_offset = offset + 2;
+                let (length, count) = read_variable_length(data.get(length_offset..).unwrap_or_default())
+                    .ok_or(SystemExclusiveError::InvalidMessage)?;
+                let meta_type = data.get(offset + 1);
+                let meta_data = data.get(length_offset + count..length_offset + count + length as usize)
+                    .ok_or(SystemExclusiveError::Truncated)?;
+                if let (Some(0x51), [a, b, c]) = (meta_type, meta_data) {
+                    tempos.push(TempoChange { ticks, microseconds_per_quarter: u32::from_be_bytes([0, *a, *b, *c]) });
+                }
+                offset = length_offset + count + length as usize;
+                running_status = None;
+            },
+            _ => {
+                let status = if status & 0x80 != 0 {
+                    offset += 1;
+                    running_status = Some(status);
+                    status
+                }
+                else {
+                    running_status.ok_or(SystemExclusiveError::InvalidMessage)?
+                };
+                offset += match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+            },
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_smf(track: &[u8]) -> Vec<u8> {
+        let mut data = b"MThd".to_vec();
+        data.extend([0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        data.extend(b"MTrk");
+        data.extend((track.len() as u32).to_be_bytes());
+        data.extend(track);
+        data
+    }
+
+    #[test]
+    fn extract_sysex() {
+        let track = [
+            0x00, 0x90, 0x40, 0x7F,  // note on
+            0x10, 0x40, 0x00,  // running status
+            0x00, 0xF0, 0x04, 0x43, 0x10, 0x00, 0xF7,  // SysEx
+            0x81, 0x00, 0xF0, 0x02, 0x41, 0x10,  // first packet
+            0x05, 0xF7, 0x02, 0x20, 0xF7,  // continuation
+            0x00, 0xFF, 0x2F, 0x00,  // end of track
+        ];
+        let events = sysex_events(&make_smf(&track)).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], SysExEvent { track: 0, ticks: 0x10, data: vec![0xF0, 0x43, 0x10, 0x00, 0xF7] });
+        assert_eq!(events[1].ticks, 0x10 + 0x80);
+        assert_eq!(events[1].data, vec![0xF0, 0x41, 0x10, 0x20, 0xF7]);
+    }
+
+    #[test]
+    fn event_times() {
+        let track = [
+            0x00, 0xF0, 0x02, 0x43, 0xF7,  // SysEx at 0
+            0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40,  // one second per quarter at 96
+            0x00, 0xF0, 0x02, 0x41, 0xF7,  // SysEx at 96
+            0x30, 0xF0, 0x02, 0x42, 0xF7,  // SysEx at 144
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let data = make_smf(&track);
+        assert_eq!(tempo_changes(&data).unwrap(), vec![TempoChange { ticks: 0x60, microseconds_per_quarter: 1_000_000 }]);
+        let times: Vec<Duration> = timed_sysex_events(&data).unwrap().iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![Duration::ZERO, Duration::from_millis(500), Duration::from_millis(1000)]);
+
+        // 25 frames per second, 40 ticks per frame
+        assert_eq!(ticks_to_duration(1000, 0xE728, &[]), Duration::from_secs(1));
+
+        // SMPTE time without ticks per frame
+        let mut data = make_smf(&track);
+        data[12..14].copy_from_slice(&[0xE7, 0x00]);
+        assert!(matches!(timed_sysex_events(&data), Err(SystemExclusiveError::InvalidMessage)));
+
+        // Ticks and tempos too large for 64-bit microseconds
+        let slow = [TempoChange { ticks: 1, microseconds_per_quarter: 0xff_ffff }];
+        assert_eq!(ticks_to_duration(u64::MAX, 1, &slow), Duration::from_micros(u64::MAX));
+    }
+
+    #[test]
+    fn build_file() {
+        let messages = vec![
+            (Duration::ZERO, Message::from_bytes(&[0xF0, 0x43, 0x00, 0xF7]).unwrap()),
+            (Duration::from_micros(120_500), Message::from_bytes(&[0xF0, 0x41, 0x10, 0xF7]).unwrap()),
+            (Duration::from_secs(600), Message::from_bytes(&[0xF0, 0x7D, 0xF7]).unwrap()),
+        ];
+        let data = build(&messages);
+        let timed = timed_sysex_events(&data).unwrap();
+        assert_eq!(timed.len(), 3);
+        for ((time, event), (expected_time, message)) in timed.iter().zip(&messages) {
+            assert_eq!(time, expected_time);
+            assert_eq!(event.data, message.to_bytes());
+        }
+
+        let mut out = Vec::new();
+        write_variable_length(0x4000, &mut out);
+        assert_eq!(out, vec![0x81, 0x80, 0x00]);
+        assert_eq!(read_variable_length(&out), Some((0x4000, 3)));
+    }
+
+    #[test]
+    fn not_smf() {
+        assert!(sysex_events(&[0xF0, 0x43, 0xF7]).is_err());
+    }
+
+    #[test]
+    fn truncated_track() {
+        let mut data = make_smf(&[0x00, 0xF0, 0x05, 0x43]);
+        data.truncate(data.len() - 1);
+        assert!(sysex_events(&data).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! # stats
+//!
+//! Summary statistics over a collection of messages: counts per
+//! manufacturer, per manufacturer group and per universal sub-ID,
+//! and the distribution of payload sizes.
+//!
+//! [`SysexStats`] is updated one message at a time and keeps the sizes
+//! in a fixed histogram instead of storing them, so it can run over
+//! a streaming capture of any length.
+
+use std::collections::HashMap;
+use crate::{Message, Manufacturer, ManufacturerGroup, UniversalKind};
+
+/// Number of histogram buckets for each power of two.
+const BUCKETS_PER_OCTAVE: usize = 8;
+
+/// Total number of histogram buckets, enough for any `usize`.
+const BUCKET_COUNT: usize = 512;
+
+/// Gets the histogram bucket of a size. Sizes below 16 have a bucket
+/// of their own; larger sizes share a bucket with sizes within 1/8 of them.
+fn bucket(size: usize) -> usize {
+    if size < BUCKETS_PER_OCTAVE {
+        return size;
+    }
+    let shift = (usize::BITS - 1 - size.leading_zeros()) as usize - 3;
+    BUCKETS_PER_OCTAVE + shift * BUCKETS_PER_OCTAVE + ((size >> shift) & 7)
+}
+
+/// Gets the smallest size in a histogram bucket.
+fn bucket_start(index: usize) -> usize {
+    if index < BUCKETS_PER_OCTAVE {
+        return index;
+    }
+    let shift = (index - BUCKETS_PER_OCTAVE) / BUCKETS_PER_OCTAVE;
+    (BUCKETS_PER_OCTAVE + index % BUCKETS_PER_OCTAVE) << shift
+}
+
+/// Statistics of a collection of messages.
+#[derive(Clone, Debug)]
+pub struct SysexStats {
+    /// Number of messages.
+    pub messages: usize,
+    /// Total size of the messages in bytes, with the initiator and terminator.
+    pub total_bytes: usize,
+    /// Number of manufacturer-specific messages per manufacturer.
+    pub by_manufacturer: HashMap<Manufacturer, usize>,
+    /// Number of manufacturer-specific messages per manufacturer group.
+    pub by_group: HashMap<ManufacturerGroup, usize>,
+    /// Number of universal messages per kind, sub-ID #1 and sub-ID #2.
+    pub by_universal_sub_id: HashMap<(UniversalKind, u8, u8), usize>,
+    /// Size of the smallest payload.
+    pub min_payload: Option<usize>,
+    /// Size of the largest payload.
+    pub max_payload: Option<usize>,
+    sizes: Box<[usize; BUCKET_COUNT]>,
+}
+
+impl SysexStats {
+    /// Creates empty statistics.
+    pub fn new() -> Self {
+        SysexStats {
+            messages: 0,
+            total_bytes: 0,
+            by_manufacturer: HashMap::new(),
+            by_group: HashMap::new(),
+            by_universal_sub_id: HashMap::new(),
+            min_payload: None,
+            max_payload: None,
+            sizes: Box::new([0; BUCKET_COUNT]),
+        }
+    }
+
+    /// Adds a message to the statistics.
+    pub fn add(&mut self, message: &Message) {
+        self.messages += 1;
+        self.total_bytes += message.byte_len();
+        match message {
+            Message::Universal { kind, sub_id1, sub_id2, .. } =>
+                *self.by_universal_sub_id.entry((*kind, *sub_id1, *sub_id2)).or_insert(0) += 1,
+            Message::ManufacturerSpecific { manufacturer, .. } => {
+                *self.by_manufacturer.entry(*manufacturer).or_insert(0) += 1;
+                *self.by_group.entry(manufacturer.group()).or_insert(0) += 1;
+            }
+        }
+
+        let size = message.payload().len();
+        self.min_payload = Some(self.min_payload.map_or(size, |min| min.min(size)));
+        self.max_payload = Some(self.max_payload.map_or(size, |max| max.max(size)));
+        self.sizes[bucket(size)] += 1;
+    }
+
+    /// Gets the manufacturers with their message counts, the most common first.
+    pub fn manufacturers_by_count(&self) -> Vec<(Manufacturer, usize)> {
+        let mut result: Vec<(Manufacturer, usize)> = self.by_manufacturer.iter().map(|(m, c)| (*m, *c)).collect();
+        result.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        result
+    }
+
+    /// Gets the manufacturers that are not in the registry of names with
+    /// their message counts, the most common first, for finding the IDs
+    /// missing from the registry.
+    pub fn unknown_manufacturers(&self) -> Vec<(Manufacturer, usize)> {
+        self.manufacturers_by_count().into_iter().filter(|(m, _)| !m.is_known()).collect()
+    }
+
+    /// Gets the payload size at the quantile `q` (0.0 to 1.0), like 0.5 for
+    /// the median. Sizes of 16 bytes and up are approximate, within 1/8 of
+    /// the actual size. Returns `None` if there are no messages.
+    pub fn payload_quantile(&self, q: f64) -> Option<usize> {
+        if self.messages == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.messages as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        let index = self.sizes.iter().position(|count| {
+            seen += count;
+            seen >= rank
+        })?;
+        let size = bucket_start(index);
+        Some(size.clamp(self.min_payload?, self.max_payload?))
+    }
+
+    /// Gets the median payload size.
+    pub fn median_payload(&self) -> Option<usize> {
+        self.payload_quantile(0.5)
+    }
+}
+
+impl Default for SysexStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Extend<&'a Message> for SysexStats {
+    fn extend<I: IntoIterator<Item = &'a Message>>(&mut self, iter: I) {
+        for message in iter {
+            self.add(message);
+        }
+    }
+}
+
+/// Computes the statistics of the messages.
+pub fn stats(messages: &[Message]) -> SysexStats {
+    let mut result = SysexStats::new();
+    result.extend(messages);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        for size in [0, 1, 7, 8, 15, 16, 17, 100, 1000, 65536, usize::MAX] {
+            let index = bucket(size);
+            assert!(index < BUCKET_COUNT);
+            assert!(bucket_start(index) <= size);
+            assert!(size - bucket_start(index) <= size / 8);
+        }
+        assert_eq!(bucket_start(bucket(15)), 15);
+    }
+
+    #[test]
+    fn counts_and_quantiles() {
+        let messages = vec![
+            Message::from_bytes(&[0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x43, 0x10, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x00, 0x20, 0x29, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap(),
+        ];
+        let stats = stats(&messages);
+        assert_eq!(stats.messages, 4);
+        assert_eq!(stats.total_bytes, 23);
+        assert_eq!(stats.manufacturers_by_count()[0], (Manufacturer::Standard(0x43), 2));
+        assert_eq!(stats.by_group[&ManufacturerGroup::Japanese], 2);
+        assert_eq!(stats.by_group[&ManufacturerGroup::EuropeanAndOther], 1);
+        assert_eq!(stats.by_universal_sub_id[&(UniversalKind::NonRealTime, 0x06, 0x01)], 1);
+        assert_eq!((stats.min_payload, stats.max_payload), (Some(0), Some(4)));
+        assert_eq!(stats.median_payload(), Some(1));
+        assert_eq!(stats.payload_quantile(1.0), Some(4));
+        assert_eq!(SysexStats::new().median_payload(), None);
+    }
+
+    #[cfg(feature = "manufacturer-names")]
+    #[test]
+    fn unknown_manufacturers() {
+        let messages = vec![
+            Message::from_bytes(&[0xF0, 0x43, 0x10, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x00, 0x7F, 0x7F, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x60, 0x01, 0xF7]).unwrap(),
+            Message::from_bytes(&[0xF0, 0x60, 0x02, 0xF7]).unwrap(),
+        ];
+        assert_eq!(stats(&messages).unknown_manufacturers(),
+            vec![(Manufacturer::Standard(0x60), 2), (Manufacturer::Extended([0x00, 0x7F, 0x7F]), 1)]);
+    }
+}
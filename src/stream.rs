@@ -0,0 +1,307 @@
+//! # stream
+//!
+//! Push-based parsing of messages from a byte stream that arrives in
+//! pieces of any size, like the chunks read from a serial port. A
+//! message that spans several pieces is kept in a buffer between
+//! pushes, up to a size limit.
+//!
+//! [`StreamParser`] only looks for System Exclusive messages. [`Deframer`]
+//! also follows the other MIDI messages of a raw DIN-MIDI stream, with
+//! running status, and hands them to a callback.
+
+use crate::{Message, SystemExclusiveError, DEFAULT_SIZE_LIMIT, INITIATOR, TERMINATOR};
+
+/// State of the parser between pushes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum State {
+    /// Waiting for an initiator.
+    Idle,
+    /// Collecting the bytes of a message.
+    Message,
+    /// Skipping the rest of a message over the size limit.
+    Skipping,
+}
+
+/// A parser that is fed the bytes of a stream in pieces.
+#[derive(Clone, Debug)]
+pub struct StreamParser {
+    buffer: Vec<u8>,
+    state: State,
+    limit: usize,
+    position: u64,
+    start: u64,
+}
+
+impl StreamParser {
+    /// Makes a parser with the default size limit.
+    pub fn new() -> Self {
+        StreamParser::with_limit(DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Makes a parser that buffers at most `limit` bytes of a message.
+    pub fn with_limit(limit: usize) -> Self {
+        StreamParser { buffer: Vec::new(), state: State::Idle, limit, position: 0, start: 0 }
+    }
+
+    /// Gets the number of bytes of an incomplete message kept in the buffer.
+    pub fn pending_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Gets the number of bytes pushed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Discards any incomplete message.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.state = State::Idle;
+    }
+
+    /// Parses the next piece of the stream, and returns the messages
+    /// completed by it with the positions of their initiators. Real-time
+    /// status bytes inside messages are ignored, and bytes outside messages
+    /// are skipped. A message interrupted by another status byte is
+    /// reported as `Truncated`, and a message over the size limit as
+    /// `MessageTooLong`; parsing continues with the next message.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Result<(u64, Message), SystemExclusiveError>> {
+        let mut result = Vec::new();
+        for b in data {
+            let position = self.position;
+            self.position += 1;
+            match (*b, self.state) {
+                (0xf8..=0xff, _) => {},
+                (INITIATOR, state) => {
+                    if state == State::Message {
+                        result.push(Err(SystemExclusiveError::Truncated));
+                    }
+                    self.buffer.clear();
+                    self.buffer.push(INITIATOR);
+                    self.start = position;
+                    self.state = State::Message;
+                },
+                (TERMINATOR, State::Message) => {
+                    self.buffer.push(TERMINATOR);
+                    result.push(Message::from_bytes(&self.buffer).map(|message| (self.start, message)));
+                    self.reset();
+                },
+                (b, State::Message) if b & 0x80 != 0 => {
+                    result.push(Err(SystemExclusiveError::Truncated));
+                    self.reset();
+                },
+                (b, State::Message) => {
+                    if self.buffer.len() + 1 >= self.limit {
+                        result.push(Err(SystemExclusiveError::MessageTooLong));
+                        self.buffer.clear();
+                        self.state = State::Skipping;
+                    }
+                    else {
+                        self.buffer.push(b);
+                    }
+                },
+                (b, State::Skipping) if b & 0x80 != 0 => self.state = State::Idle,
+                _ => {},
+            }
+        }
+        result
+    }
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A MIDI message other than System Exclusive, found by a `Deframer`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum MidiEvent {
+    /// A channel message. The status byte is given also when the message
+    /// was sent with running status. Program Change and Channel Pressure
+    /// have only the first data byte.
+    Channel { status: u8, data1: u8, data2: Option<u8> },
+    /// A System Common message other than System Exclusive, with up to
+    /// two data bytes.
+    SystemCommon { status: u8, data1: Option<u8>, data2: Option<u8> },
+    /// A System Real-Time message.
+    RealTime(u8),
+}
+
+/// Gets the number of data bytes of a channel or System Common message.
+fn data_length(status: u8) -> usize {
+    match status {
+        0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+        0x80..=0xef | 0xf2 => 2,
+        _ => 0,
+    }
+}
+
+/// A parser for raw MIDI 1.0 streams, like the ones captured from a
+/// serial port, that are fed to it in pieces. Complete System Exclusive
+/// messages are returned, and the other messages are given to a callback
+/// or discarded.
+#[derive(Clone, Debug)]
+pub struct Deframer {
+    sysex: StreamParser,
+    in_sysex: bool,
+    status: Option<u8>,
+    data: [u8; 2],
+    count: usize,
+}
+
+impl Deframer {
+    /// Makes a deframer with the default size limit for System Exclusive messages.
+    pub fn new() -> Self {
+        Deframer::with_limit(DEFAULT_SIZE_LIMIT)
+    }
+
+    /// Makes a deframer that buffers at most `limit` bytes of a System Exclusive message.
+    pub fn with_limit(limit: usize) -> Self {
+        Deframer { sysex: StreamParser::with_limit(limit), in_sysex: false, status: None, data: [0; 2], count: 0 }
+    }
+
+    /// Parses the next piece of the stream, discarding the messages
+    /// other than System Exclusive.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Result<(u64, Message), SystemExclusiveError>> {
+        self.push_with(data, |_| {})
+    }
+
+    /// Parses the next piece of the stream like `StreamParser::push`,
+    /// giving the other messages to `on_event` as they are completed.
+    /// Real-time messages are given also when they occur inside other
+    /// messages. Data bytes without a status byte are skipped.
+    pub fn push_with<F>(&mut self, data: &[u8], mut on_event: F) -> Vec<Result<(u64, Message), SystemExclusiveError>>
+    where
+        F: FnMut(MidiEvent),
+    {
+        let mut result = Vec::new();
+        for b in data {
+            let b = *b;
+            if b >= 0xf8 {
+                on_event(MidiEvent::RealTime(b));
+                self.sysex.position += 1;
+                continue;
+            }
+            if self.in_sysex || b == INITIATOR {
+                result.extend(self.sysex.push(&[b]));
+                self.in_sysex = self.sysex.state == State::Message;
+                if b != INITIATOR && b & 0x80 != 0 && b != TERMINATOR {
+                    // The status byte that interrupted the message starts a new one.
+                    self.start_message(b, &mut on_event);
+                }
+                else if b == INITIATOR {
+                    self.status = None;
+                }
+                continue;
+            }
+
+            self.sysex.position += 1;
+            if b & 0x80 != 0 {
+                self.start_message(b, &mut on_event);
+            }
+            else if let Some(status) = self.status {
+                self.data[self.count] = b;
+                self.count += 1;
+                if self.count == data_length(status) {
+                    self.complete_message(status, &mut on_event);
+                }
+            }
+        }
+        result
+    }
+
+    fn start_message<F: FnMut(MidiEvent)>(&mut self, status: u8, on_event: &mut F) {
+        self.count = 0;
+        self.status = if status == TERMINATOR { None } else { Some(status) };
+        if status != TERMINATOR && data_length(status) == 0 {
+            self.complete_message(status, on_event);
+        }
+    }
+
+    fn complete_message<F: FnMut(MidiEvent)>(&mut self, status: u8, on_event: &mut F) {
+        let length = data_length(status);
+        let data1 = (length >= 1).then_some(self.data[0]);
+        let data2 = (length >= 2).then_some(self.data[1]);
+        self.count = 0;
+        if status < 0xf0 {
+            on_event(MidiEvent::Channel { status, data1: self.data[0], data2 });
+        }
+        else {
+            // System Common messages cancel running status.
+            self.status = None;
+            on_event(MidiEvent::SystemCommon { status, data1, data2 });
+        }
+    }
+}
+
+impl Default for Deframer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_across_pushes() {
+        let mut parser = StreamParser::new();
+        assert!(parser.push(&[0x00, 0xF0, 0x43]).is_empty());
+        assert_eq!(parser.pending_len(), 2);
+        assert!(parser.push(&[0x00, 0xF8, 0x01]).is_empty());
+        assert_eq!(parser.pending_len(), 4);
+        let messages: Vec<(u64, Message)> = parser.push(&[0x02, 0xF7, 0xF0]).into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(messages, vec![(1, Message::from_bytes(&[0xF0, 0x43, 0x00, 0x01, 0x02, 0xF7]).unwrap())]);
+        assert_eq!(parser.pending_len(), 1);
+        assert_eq!(parser.position(), 9);
+    }
+
+    #[test]
+    fn limit_and_interruptions() {
+        let mut parser = StreamParser::with_limit(4);
+        let results = parser.push(&[0xF0, 0x43, 0x01, 0x02, 0x03, 0x04, 0xF7, 0xF0, 0x43, 0x01, 0xF7]);
+        assert!(matches!(results[0], Err(SystemExclusiveError::MessageTooLong)));
+        assert!(matches!(results[1], Ok((7, _))));
+        assert_eq!(results.len(), 2);
+
+        let results = parser.push(&[0xF0, 0x43, 0x90, 0x40, 0xF0, 0x43, 0xF0, 0x43, 0x01, 0xF7]);
+        assert!(matches!(results[0], Err(SystemExclusiveError::Truncated)));
+        assert!(matches!(results[1], Err(SystemExclusiveError::Truncated)));
+        assert!(matches!(results[2], Ok((17, _))));
+        assert_eq!(parser.pending_len(), 0);
+    }
+
+    #[test]
+    fn deframe_mixed_stream() {
+        let mut deframer = Deframer::new();
+        let mut events = Vec::new();
+        let stream = [
+            0x40, // data byte without status
+            0x90, 0x40, 0x7F, 0x41, 0x7F,  // note on, then running status
+            0xF0, 0x43, 0xF8, 0x00, 0xF7,  // SysEx with a clock inside
+            0x42, // no running status after SysEx
+            0xC1, 0x05, 0x06,  // program changes with running status
+            0xF3, 0x01, 0xF6,  // song select, tune request
+            0xF0, 0x41, 0xB0, 0x07, 0x64,  // SysEx interrupted by a control change
+        ];
+        let results = deframer.push_with(&stream[..8], |event| events.push(event));
+        assert!(results.is_empty());
+        let results = deframer.push_with(&stream[8..], |event| events.push(event));
+        assert!(matches!(&results[0], Ok((6, message)) if message.to_bytes() == [0xF0, 0x43, 0x00, 0xF7]));
+        assert!(matches!(results[1], Err(SystemExclusiveError::Truncated)));
+        assert_eq!(results.len(), 2);
+        assert_eq!(events, vec![
+            MidiEvent::Channel { status: 0x90, data1: 0x40, data2: Some(0x7F) },
+            MidiEvent::Channel { status: 0x90, data1: 0x41, data2: Some(0x7F) },
+            MidiEvent::RealTime(0xF8),
+            MidiEvent::Channel { status: 0xC1, data1: 0x05, data2: None },
+            MidiEvent::Channel { status: 0xC1, data1: 0x06, data2: None },
+            MidiEvent::SystemCommon { status: 0xF3, data1: Some(0x01), data2: None },
+            MidiEvent::SystemCommon { status: 0xF6, data1: None, data2: None },
+            MidiEvent::Channel { status: 0xB0, data1: 0x07, data2: Some(0x64) },
+        ]);
+        assert_eq!(deframer.push(&[0xF0, 0x7D, 0xF7]).len(), 1);
+    }
+}
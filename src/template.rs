@@ -0,0 +1,261 @@
+//! # template
+//!
+//! Text templates for constructing messages from parameters, like
+//!
+//! ```text
+//! F0 41 {device} 42 12 {addr:3} {data} {checksum:roland} F7
+//! ```
+//!
+//! A template is a list of hex bytes and placeholders in braces:
+//!
+//! - `{name}` is replaced by the parameter `name`. A number becomes one
+//!   7-bit byte, and a byte string is inserted as it is.
+//! - `{name:N}` is replaced by the parameter as `N` 7-bit bytes, most
+//!   significant byte first. A byte string must be exactly `N` bytes long.
+//! - `{checksum:kind}` is replaced by a checksum of the bytes between the
+//!   last hex byte before the placeholders that precede it and the checksum
+//!   itself. In the example above that is the address and the data.
+//!   `{checksum:kind:offset}` computes it from the given message offset instead.
+//!
+//! The checksum kinds are `roland` (also `yamaha`) for the two's complement
+//! of the sum, `sum` for the plain sum and `xor` for the exclusive OR,
+//! all masked to seven bits.
+
+use std::fmt;
+use crate::{Message, SystemExclusiveError};
+use crate::checksum::ChecksumKind;
+
+/// Error type for parsing and instantiating templates.
+#[derive(Debug)]
+pub enum TemplateError {
+    /// The template text could not be parsed.
+    Syntax(String),
+    /// A parameter used in the template was not given.
+    MissingParameter(String),
+    /// A parameter value does not fit in its placeholder.
+    OutOfRange(String),
+    /// The instantiated bytes are not a valid message.
+    Message(SystemExclusiveError),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Syntax(s) => write!(f, "Syntax error: {}", s),
+            TemplateError::MissingParameter(name) => write!(f, "Missing parameter '{}'", name),
+            TemplateError::OutOfRange(name) => write!(f, "Parameter '{}' is out of range", name),
+            TemplateError::Message(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TemplateError::Message(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SystemExclusiveError> for TemplateError {
+    fn from(e: SystemExclusiveError) -> Self {
+        TemplateError::Message(e)
+    }
+}
+
+/// Value of a template parameter.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Argument {
+    Number(u64),
+    Bytes(Vec<u8>),
+}
+
+/// One part of a template.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Token {
+    Byte(u8),
+    Parameter { name: String, width: Option<usize> },
+    Checksum { kind: ChecksumKind, start: Option<usize> },
+}
+
+/// A parsed message template.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Template {
+    tokens: Vec<Token>,
+}
+
+impl Template {
+    /// Parses a template from text.
+    pub fn parse(text: &str) -> Result<Self, TemplateError> {
+        let mut tokens = Vec::new();
+        let mut rest = text.trim_start();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('{') {
+                let end = after.find('}')
+                    .ok_or_else(|| TemplateError::Syntax("unterminated placeholder".to_string()))?;
+                tokens.push(parse_placeholder(&after[..end])?);
+                rest = &after[end + 1..];
+            }
+            else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '{').unwrap_or(rest.len());
+                let word = &rest[..end];
+                if word.len() != 2 {
+                    return Err(TemplateError::Syntax(format!("expected a hex byte, found '{}'", word)));
+                }
+                let b = u8::from_str_radix(word, 16)
+                    .map_err(|_| TemplateError::Syntax(format!("invalid hex byte '{}'", word)))?;
+                tokens.push(Token::Byte(b));
+                rest = &rest[end..];
+            }
+            rest = rest.trim_start();
+        }
+        Ok(Template { tokens })
+    }
+
+    /// Gets the names of the parameters used in the template, in order.
+    pub fn parameters(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for token in &self.tokens {
+            if let Token::Parameter { name, .. } = token {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Makes the message bytes from the template and the parameters.
+    pub fn instantiate_bytes(&self, arguments: &[(&str, Argument)]) -> Result<Vec<u8>, TemplateError> {
+        let mut result = Vec::new();
+        let mut run_start = 0;
+        for token in &self.tokens {
+            match token {
+                Token::Byte(b) => {
+                    result.push(*b);
+                    run_start = result.len();
+                },
+                Token::Parameter { name, width } => {
+                    let argument = arguments.iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, argument)| argument)
+                        .ok_or_else(|| TemplateError::MissingParameter(name.clone()))?;
+                    result.extend(encode_argument(name, argument, *width)?);
+                },
+                Token::Checksum { kind, start } => {
+                    let start = start.unwrap_or(run_start);
+                    let data = result.get(start..)
+                        .ok_or_else(|| TemplateError::Syntax(format!("checksum offset {} is past the data", start)))?;
+                    result.push(kind.compute(data));
+                    run_start = result.len();
+                },
+            }
+        }
+        Ok(result)
+    }
+
+    /// Makes a message from the template and the parameters.
+    pub fn instantiate(&self, arguments: &[(&str, Argument)]) -> Result<Message, TemplateError> {
+        Ok(Message::from_bytes(&self.instantiate_bytes(arguments)?)?)
+    }
+}
+
+fn parse_placeholder(text: &str) -> Result<Token, TemplateError> {
+    let parts: Vec<&str> = text.split(':').map(|part| part.trim()).collect();
+    if parts[0].is_empty() {
+        return Err(TemplateError::Syntax("empty placeholder".to_string()));
+    }
+    if parts[0] == "checksum" {
+        let kind = parts.get(1)
+            .and_then(|name| ChecksumKind::from_name(name))
+            .ok_or_else(|| TemplateError::Syntax(format!("unknown checksum in '{{{}}}'", text)))?;
+        let start = match parts.get(2) {
+            Some(offset) => Some(offset.parse::<usize>()
+                .map_err(|_| TemplateError::Syntax(format!("invalid checksum offset '{}'", offset)))?),
+            None => None,
+        };
+        if parts.len() > 3 {
+            return Err(TemplateError::Syntax(format!("too many fields in '{{{}}}'", text)));
+        }
+        return Ok(Token::Checksum { kind, start });
+    }
+
+    let width = match parts.get(1) {
+        Some(width) => match width.parse::<usize>() {
+            Ok(width) if (1..=9).contains(&width) => Some(width),
+            _ => return Err(TemplateError::Syntax(format!("invalid width '{}'", width))),
+        },
+        None => None,
+    };
+    if parts.len() > 2 {
+        return Err(TemplateError::Syntax(format!("too many fields in '{{{}}}'", text)));
+    }
+    Ok(Token::Parameter { name: parts[0].to_string(), width })
+}
+
+fn encode_argument(name: &str, argument: &Argument, width: Option<usize>) -> Result<Vec<u8>, TemplateError> {
+    let out_of_range = || TemplateError::OutOfRange(name.to_string());
+    match argument {
+        Argument::Number(value) => {
+            let width = width.unwrap_or(1);
+            // Nine seven-bit bytes hold 63 bits, so the range is checked for them too.
+            if *value >= 1u64 << (7 * width) {
+                return Err(out_of_range());
+            }
+            Ok((0..width).rev().map(|i| ((value >> (7 * i)) & 0x7f) as u8).collect())
+        },
+        Argument::Bytes(bytes) => {
+            if bytes.iter().any(|b| b & 0x80 != 0) || width.is_some_and(|width| width != bytes.len()) {
+                return Err(out_of_range());
+            }
+            Ok(bytes.clone())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roland_data_set() {
+        let template = Template::parse("F0 41 {device} 42 12 {addr:3} {data} {checksum:roland} F7").unwrap();
+        assert_eq!(template.parameters(), vec!["device", "addr", "data"]);
+
+        // Roland GS reset
+        let bytes = template.instantiate_bytes(&[
+            ("device", Argument::Number(0x10)),
+            ("addr", Argument::Number((0x40 << 14) | 0x7f)),
+            ("data", Argument::Bytes(vec![0x00])),
+        ]).unwrap();
+        assert_eq!(bytes, vec![0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]);
+    }
+
+    #[test]
+    fn explicit_checksum_offset() {
+        let template = Template::parse("F0 7D {a}{b} {checksum:xor:1} F7").unwrap();
+        let message = template.instantiate(&[("a", Argument::Number(0x01)), ("b", Argument::Number(0x03))]).unwrap();
+        assert_eq!(message.to_bytes(), vec![0xF0, 0x7D, 0x01, 0x03, 0x7F, 0xF7]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Template::parse("F0 4 F7"), Err(TemplateError::Syntax(_))));
+        assert!(matches!(Template::parse("F0 {device F7"), Err(TemplateError::Syntax(_))));
+        assert!(matches!(Template::parse("F0 {checksum:crc} F7"), Err(TemplateError::Syntax(_))));
+
+        let template = Template::parse("F0 43 {channel} {value:2} F7").unwrap();
+        assert!(matches!(template.instantiate(&[("channel", Argument::Number(0))]),
+            Err(TemplateError::MissingParameter(name)) if name == "value"));
+        assert!(matches!(template.instantiate(&[("channel", Argument::Number(128)), ("value", Argument::Number(0))]),
+            Err(TemplateError::OutOfRange(name)) if name == "channel"));
+        assert!(matches!(template.instantiate(&[("channel", Argument::Number(0)), ("value", Argument::Bytes(vec![1]))]),
+            Err(TemplateError::OutOfRange(name)) if name == "value"));
+
+        let template = Template::parse("F0 7D {value:9} F7").unwrap();
+        assert_eq!(template.instantiate_bytes(&[("value", Argument::Number((1 << 63) - 1))]).unwrap()[2..11], [0x7F; 9]);
+        assert!(matches!(template.instantiate(&[("value", Argument::Number(1 << 63))]),
+            Err(TemplateError::OutOfRange(name)) if name == "value"));
+    }
+}
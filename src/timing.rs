@@ -0,0 +1,250 @@
+//! # timing
+//!
+//! Timing sidecar files that record the pacing of a dump sequence, so that
+//! captured messages can be sent again with the delays the device used.
+//!
+//! The sidecar of `bank.syx` is `bank.syx.timing`. It is a text file with
+//! one line per message, giving the delay in milliseconds before the message
+//! is sent, measured from the previous message:
+//!
+//! ```text
+//! # syxpack timing
+//! 0
+//! 120.5
+//! 35
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored.
+//!
+//! The timing of the messages in a Standard MIDI File is kept when it is
+//! converted to a `.syx` file with a sidecar by [`smf_to_syx`], and
+//! restored when the file is made again by [`syx_to_smf`].
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::{Message, SystemExclusiveError, write_messages};
+use crate::scan::parse_messages;
+use crate::smf;
+
+/// The file name extension of timing sidecars.
+pub const EXTENSION: &str = "timing";
+
+/// Error type for reading timing sidecars.
+#[derive(Debug)]
+pub enum TimingError {
+    /// The sidecar could not be read.
+    Io(io::Error),
+    /// A line of the sidecar is not a delay. The line numbers start from one.
+    Parse { line: usize },
+    /// The messages could not be parsed.
+    Message(SystemExclusiveError),
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimingError::Io(e) => write!(f, "{}", e),
+            TimingError::Parse { line } => write!(f, "Invalid delay on line {}", line),
+            TimingError::Message(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TimingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TimingError::Io(e) => Some(e),
+            TimingError::Message(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TimingError {
+    fn from(e: io::Error) -> Self {
+        TimingError::Io(e)
+    }
+}
+
+impl From<SystemExclusiveError> for TimingError {
+    fn from(e: SystemExclusiveError) -> Self {
+        TimingError::Message(e)
+    }
+}
+
+/// Gets the path of the timing sidecar of a file, like `bank.syx.timing` for `bank.syx`.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(EXTENSION);
+    PathBuf::from(name)
+}
+
+/// The delays before each message of a sequence.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct Timing {
+    /// Delay before each message, from the previous message.
+    pub delays: Vec<Duration>,
+}
+
+impl Timing {
+    /// Makes the timing from the times when messages were received,
+    /// as recorded while capturing a dump. The first delay is zero.
+    pub fn from_instants(instants: &[Instant]) -> Self {
+        let mut delays = Vec::with_capacity(instants.len());
+        let mut previous = instants.first().copied();
+        for instant in instants {
+            delays.push(previous.map(|p| instant.saturating_duration_since(p)).unwrap_or_default());
+            previous = Some(*instant);
+        }
+        Timing { delays }
+    }
+
+    /// Makes the timing from the times of messages from the start of
+    /// the sequence, which must be in order. The first delay is the time
+    /// of the first message.
+    pub fn from_times(times: &[Duration]) -> Self {
+        let mut previous = Duration::ZERO;
+        let delays = times.iter()
+            .map(|time| {
+                let delay = time.saturating_sub(previous);
+                previous = *time;
+                delay
+            })
+            .collect();
+        Timing { delays }
+    }
+
+    /// Parses the timing from the text of a sidecar.
+    pub fn parse(text: &str) -> Result<Self, TimingError> {
+        let mut delays = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let delay = line.parse::<f64>().ok()
+                .and_then(|milliseconds| Duration::try_from_secs_f64(milliseconds / 1000.0).ok())
+                .ok_or(TimingError::Parse { line: index + 1 })?;
+            delays.push(delay);
+        }
+        Ok(Timing { delays })
+    }
+
+    /// Loads the timing from a sidecar file.
+    pub fn load(path: &Path) -> Result<Self, TimingError> {
+        Timing::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Saves the timing to a sidecar file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Pairs the messages with their times from the start of the sequence,
+    /// for sending with `transfer::send_timed`. Messages without a delay
+    /// in the timing are sent right after the previous one. Times past
+    /// `Duration::MAX` stay at it.
+    pub fn schedule(&self, messages: &[Message]) -> Vec<(Duration, Message)> {
+        let mut time = Duration::ZERO;
+        messages.iter().enumerate()
+            .map(|(index, message)| {
+                time = time.checked_add(self.delays.get(index).copied().unwrap_or_default()).unwrap_or(Duration::MAX);
+                (time, message.clone())
+            })
+            .collect()
+    }
+}
+
+/// Extracts the messages of a Standard MIDI File to a `.syx` file and
+/// writes their timing to its sidecar. Returns the number of messages.
+pub fn smf_to_syx(smf_path: &Path, syx_path: &Path) -> Result<usize, TimingError> {
+    let events = smf::timed_sysex_events(&fs::read(smf_path)?)?;
+    let messages = events.iter()
+        .map(|(_, event)| Message::from_bytes(&event.data))
+        .collect::<Result<Vec<Message>, SystemExclusiveError>>()?;
+    let times: Vec<Duration> = events.iter().map(|(time, _)| *time).collect();
+    write_messages(syx_path, &messages)?;
+    Timing::from_times(&times).save(&sidecar_path(syx_path))?;
+    Ok(messages.len())
+}
+
+/// Makes a Standard MIDI File of the messages in a `.syx` file, spaced
+/// by the delays in its sidecar. Without a sidecar the messages are
+/// all at the start. Returns the number of messages.
+pub fn syx_to_smf(syx_path: &Path, smf_path: &Path) -> Result<usize, TimingError> {
+    let messages = parse_messages(&fs::read(syx_path)?)?;
+    let sidecar = sidecar_path(syx_path);
+    let timing = if sidecar.exists() { Timing::load(&sidecar)? } else { Timing::default() };
+    fs::write(smf_path, smf::build(&timing.schedule(&messages)))?;
+    Ok(messages.len())
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# syxpack timing")?;
+        for delay in &self.delays {
+            writeln!(f, "{}", delay.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let timing = Timing::parse("# syxpack timing\n0\n\n120.5\n35\n").unwrap();
+        assert_eq!(timing.delays, vec![Duration::ZERO, Duration::from_micros(120_500), Duration::from_millis(35)]);
+        assert_eq!(Timing::parse(&timing.to_string()).unwrap(), timing);
+        assert!(matches!(Timing::parse("0\n-1\n"), Err(TimingError::Parse { line: 2 })));
+        assert!(matches!(Timing::parse("1e30\n"), Err(TimingError::Parse { line: 1 })));
+        assert!(matches!(Timing::parse("NaN\n"), Err(TimingError::Parse { line: 1 })));
+        assert_eq!(sidecar_path(Path::new("dumps/bank.syx")), PathBuf::from("dumps/bank.syx.timing"));
+    }
+
+    #[test]
+    fn record_and_schedule() {
+        let start = Instant::now();
+        let timing = Timing::from_instants(&[start, start + Duration::from_millis(10), start + Duration::from_millis(15)]);
+        assert_eq!(timing.delays, vec![Duration::ZERO, Duration::from_millis(10), Duration::from_millis(5)]);
+
+        let message = Message::from_bytes(&[0xF0, 0x7D, 0x01, 0xF7]).unwrap();
+        let schedule = timing.schedule(&[message.clone(), message.clone(), message.clone(), message.clone()]);
+        let times: Vec<Duration> = schedule.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![Duration::ZERO, Duration::from_millis(10), Duration::from_millis(15), Duration::from_millis(15)]);
+
+        let long = Timing { delays: vec![Duration::MAX, Duration::MAX] };
+        let schedule = long.schedule(&[message.clone(), message]);
+        assert_eq!(schedule[1].0, Duration::MAX);
+    }
+
+    #[test]
+    fn smf_round_trip() {
+        let dir = std::env::temp_dir().join(format!("syxpack-timing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let messages = vec![
+            (Duration::from_millis(5), Message::from_bytes(&[0xF0, 0x43, 0x00, 0xF7]).unwrap()),
+            (Duration::from_micros(125_250), Message::from_bytes(&[0xF0, 0x43, 0x01, 0xF7]).unwrap()),
+        ];
+        let original = dir.join("firmware.mid");
+        fs::write(&original, smf::build(&messages)).unwrap();
+
+        let syx = dir.join("firmware.syx");
+        assert_eq!(smf_to_syx(&original, &syx).unwrap(), 2);
+        assert_eq!(Timing::load(&sidecar_path(&syx)).unwrap().delays,
+            vec![Duration::from_millis(5), Duration::from_micros(120_250)]);
+
+        let rebuilt = dir.join("rebuilt.mid");
+        assert_eq!(syx_to_smf(&syx, &rebuilt).unwrap(), 2);
+        assert_eq!(smf::timed_sysex_events(&fs::read(&rebuilt).unwrap()).unwrap(),
+            smf::timed_sysex_events(&fs::read(&original).unwrap()).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,543 @@
+//! # transfer
+//!
+//! Send-and-wait message transfers with handshaking, timeout and retry,
+//! as used by firmware updates and closed-loop dumps.
+//!
+//! The transfer logic does not do any MIDI I/O itself. Instead it talks to
+//! a [`Connection`], which you implement on top of whatever MIDI library you use.
+
+use std::fmt;
+use std::io;
+use std::ops::ControlFlow;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::{Message, Progress};
+
+/// MIDI 1.0 wire rate in bits per second.
+pub const MIDI_BAUD_RATE: u32 = 31_250;
+
+/// Number of bits on the wire for each byte, including start and stop bits.
+const BITS_PER_BYTE: u32 = 10;
+
+/// A bidirectional connection to a device.
+pub trait Connection {
+    /// Sends a message to the device.
+    fn send(&mut self, message: &Message) -> io::Result<()>;
+
+    /// Waits at most `timeout` for a message from the device.
+    /// Returns `Ok(None)` if nothing was received in time.
+    fn receive(&mut self, timeout: Duration) -> io::Result<Option<Message>>;
+}
+
+/// The interpretation of a received message, as decided by the caller.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Reply {
+    /// The message was accepted, continue with the next one.
+    Ack,
+    /// The message was rejected, send it again.
+    Nak,
+    /// The received message is not a reply, keep waiting.
+    Ignore,
+}
+
+/// Timeout and retry policy for a transfer.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RetryPolicy {
+    /// How long to wait for a reply to each attempt.
+    pub timeout: Duration,
+    /// How many times to resend a message after a timeout or NAK.
+    pub retries: u32,
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy.
+    pub fn new(timeout: Duration, retries: u32) -> Self {
+        RetryPolicy { timeout, retries }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(Duration::from_millis(200), 3)
+    }
+}
+
+/// Computes the delays needed between messages so that the receiving
+/// device is not overrun. The delay for a message is the time it takes
+/// to transmit at the wire rate, plus a settling time for the device
+/// to process it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Pacer {
+    /// Time taken by each byte on the wire.
+    pub byte_time: Duration,
+    /// Time the device needs after each message.
+    pub settle_time: Duration,
+}
+
+impl Pacer {
+    /// Creates a pacer for the MIDI 1.0 wire rate with the given settling time.
+    pub fn new(settle_time: Duration) -> Self {
+        Pacer {
+            byte_time: Duration::from_micros((1_000_000 * BITS_PER_BYTE / MIDI_BAUD_RATE) as u64),
+            settle_time,
+        }
+    }
+
+    /// Pacer for the plain wire rate, with no settling time.
+    pub fn wire_rate() -> Self {
+        Pacer::new(Duration::ZERO)
+    }
+
+    /// Pacer for devices that are fairly tolerant, with a short settling time.
+    pub fn relaxed() -> Self {
+        Pacer::new(Duration::from_millis(20))
+    }
+
+    /// Pacer for picky vintage hardware with small receive buffers and slow
+    /// processors (for example older samplers and 1980s synthesizers).
+    pub fn vintage() -> Self {
+        let mut pacer = Pacer::new(Duration::from_millis(150));
+        pacer.byte_time *= 2;
+        pacer
+    }
+
+    /// Gets the transmission time of `byte_count` bytes, saturating
+    /// at `Duration::MAX`.
+    pub fn transmission_time(&self, byte_count: usize) -> Duration {
+        self.byte_time.as_nanos().checked_mul(byte_count as u128)
+            .and_then(|nanos| u64::try_from(nanos / 1_000_000_000).ok()
+                .map(|secs| Duration::new(secs, (nanos % 1_000_000_000) as u32)))
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Gets the delay to wait after sending the message.
+    pub fn delay(&self, message: &Message) -> Duration {
+        self.transmission_time(message.byte_len()).saturating_add(self.settle_time)
+    }
+
+    /// Gets the total time needed to send all the messages.
+    pub fn total_time(&self, messages: &[Message]) -> Duration {
+        messages.iter().fold(Duration::ZERO, |total, m| total.saturating_add(self.delay(m)))
+    }
+
+    /// Blocks the current thread for the delay of the message.
+    pub fn wait(&self, message: &Message) {
+        thread::sleep(self.delay(message));
+    }
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Pacer::wire_rate()
+    }
+}
+
+/// Options for planning sends to a device with a small receive buffer.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PlanOptions {
+    /// Size of the receive buffer of the device in bytes.
+    pub buffer_size: usize,
+    /// Time the device needs to empty its buffer after each chunk.
+    pub chunk_delay: Duration,
+    /// Pacing of the individual messages.
+    pub pacer: Pacer,
+}
+
+impl PlanOptions {
+    /// Creates plan options with the wire-rate pacer.
+    pub fn new(buffer_size: usize, chunk_delay: Duration) -> Self {
+        PlanOptions { buffer_size, chunk_delay, pacer: Pacer::wire_rate() }
+    }
+}
+
+/// One message of a send plan, with the delay to wait after sending it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlannedMessage {
+    pub message: Message,
+    pub delay: Duration,
+}
+
+/// Plans sending the messages to a device with a small receive buffer.
+/// The messages are grouped in their original order into chunks that fit
+/// in the buffer, and after each chunk the chunk delay is added to the
+/// delay of the pacer. A message larger than the buffer is passed to
+/// `split` with the buffer size, to be split into smaller messages if the
+/// device supports a multi-part form of the dump. If `split` returns `None`,
+/// the message is sent as it is, in a chunk of its own.
+pub fn plan_sends<S>(messages: &[Message], options: &PlanOptions, mut split: S) -> Vec<PlannedMessage>
+where
+    S: FnMut(&Message, usize) -> Option<Vec<Message>>,
+{
+    let mut parts = Vec::new();
+    for message in messages {
+        if message.byte_len() > options.buffer_size {
+            if let Some(split_messages) = split(message, options.buffer_size) {
+                parts.extend(split_messages);
+                continue;
+            }
+        }
+        parts.push(message.clone());
+    }
+
+    let mut plan: Vec<PlannedMessage> = Vec::with_capacity(parts.len());
+    let mut chunk_size = 0;
+    for message in parts {
+        let size = message.byte_len();
+        if chunk_size > 0 && chunk_size + size > options.buffer_size {
+            if let Some(last) = plan.last_mut() {
+                last.delay += options.chunk_delay;
+            }
+            chunk_size = 0;
+        }
+        chunk_size += size;
+        plan.push(PlannedMessage { delay: options.pacer.delay(&message), message });
+    }
+    if let Some(last) = plan.last_mut() {
+        last.delay += options.chunk_delay;
+    }
+    plan
+}
+
+/// Error type for transfers.
+#[derive(Debug)]
+pub enum TransferError {
+    /// No acknowledgement was received after all retries.
+    Timeout { index: usize },
+    /// The device rejected the message on every attempt.
+    Rejected { index: usize },
+    /// The transfer was cancelled by the progress callback.
+    Cancelled { index: usize },
+    /// The connection failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferError::Timeout { index } => write!(f, "Timed out waiting for reply to message {}", index),
+            TransferError::Rejected { index } => write!(f, "Message {} rejected by device", index),
+            TransferError::Cancelled { index } => write!(f, "Transfer cancelled before message {}", index),
+            TransferError::Io(e) => write!(f, "Connection error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransferError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TransferError {
+    fn from(e: io::Error) -> Self {
+        TransferError::Io(e)
+    }
+}
+
+/// Sends one message and waits for the reply accepted by `reply`.
+/// Returns the acknowledging message.
+pub fn send_and_wait<C, F>(
+    connection: &mut C,
+    message: &Message,
+    policy: &RetryPolicy,
+    reply: F) -> Result<Message, TransferError>
+where
+    C: Connection,
+    F: FnMut(&Message) -> Reply,
+{
+    send_message(connection, message, 0, policy, reply)
+}
+
+/// Sends all the messages in order, waiting for each one to be acknowledged
+/// before continuing with the next one. Returns the acknowledging messages.
+pub fn send_all<C, F>(
+    connection: &mut C,
+    messages: &[Message],
+    policy: &RetryPolicy,
+    reply: F) -> Result<Vec<Message>, TransferError>
+where
+    C: Connection,
+    F: FnMut(&Message) -> Reply,
+{
+    send_all_with_progress(connection, messages, policy, reply, |_| ControlFlow::Continue(()))
+}
+
+/// Sends all the messages like `send_all`, reporting the number of
+/// acknowledged messages after each one.
+pub fn send_all_with_progress<C, F, P>(
+    connection: &mut C,
+    messages: &[Message],
+    policy: &RetryPolicy,
+    mut reply: F,
+    mut progress: P) -> Result<Vec<Message>, TransferError>
+where
+    C: Connection,
+    F: FnMut(&Message) -> Reply,
+    P: FnMut(Progress) -> ControlFlow<()>,
+{
+    let mut acks = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        acks.push(send_message(connection, message, index, policy, &mut reply)?);
+        if progress(Progress::new(index + 1, Some(messages.len()))).is_break() {
+            return Err(TransferError::Cancelled { index: index + 1 });
+        }
+    }
+    Ok(acks)
+}
+
+/// Sends all the messages in order without waiting for replies,
+/// pausing after each one as determined by `pacer`.
+pub fn send_paced<C: Connection>(
+    connection: &mut C,
+    messages: &[Message],
+    pacer: &Pacer) -> Result<(), TransferError>
+{
+    send_paced_with_progress(connection, messages, pacer, |_| ControlFlow::Continue(()))
+}
+
+/// Sends all the messages like `send_paced`, reporting the number of
+/// sent messages after each one.
+pub fn send_paced_with_progress<C, P>(
+    connection: &mut C,
+    messages: &[Message],
+    pacer: &Pacer,
+    mut progress: P) -> Result<(), TransferError>
+where
+    C: Connection,
+    P: FnMut(Progress) -> ControlFlow<()>,
+{
+    for (index, message) in messages.iter().enumerate() {
+        connection.send(message)?;
+        pacer.wait(message);
+        if progress(Progress::new(index + 1, Some(messages.len()))).is_break() {
+            return Err(TransferError::Cancelled { index: index + 1 });
+        }
+    }
+    Ok(())
+}
+
+/// Sends the messages at the given times from the start of the transfer,
+/// as read from a Standard MIDI File with `smf::timed_sysex_events`.
+/// The times are multiplied by `scale`, so that 2.0 plays at half speed.
+/// The messages must be in time order. A negative scale counts as zero.
+/// A scale that is not finite fails with an `InvalidInput` error before
+/// anything is sent, and so does a scaled time too far in the future
+/// before its message is sent.
+pub fn send_timed<C: Connection>(
+    connection: &mut C,
+    messages: &[(Duration, Message)],
+    scale: f64) -> Result<(), TransferError>
+{
+    if !scale.is_finite() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Scale is not finite").into());
+    }
+    let start = Instant::now();
+    for (time, message) in messages {
+        let due = Duration::try_from_secs_f64(time.as_secs_f64() * scale.max(0.0)).ok()
+            .and_then(|time| start.checked_add(time))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Time out of range"))?;
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+        connection.send(message)?;
+    }
+    Ok(())
+}
+
+/// Sends the messages of a plan made with `plan_sends`, waiting for the
+/// planned delay after each one.
+pub fn send_planned<C: Connection>(connection: &mut C, plan: &[PlannedMessage]) -> Result<(), TransferError> {
+    for planned in plan {
+        connection.send(&planned.message)?;
+        thread::sleep(planned.delay);
+    }
+    Ok(())
+}
+
+fn send_message<C, F>(
+    connection: &mut C,
+    message: &Message,
+    index: usize,
+    policy: &RetryPolicy,
+    mut reply: F) -> Result<Message, TransferError>
+where
+    C: Connection,
+    F: FnMut(&Message) -> Reply,
+{
+    let mut rejected = false;
+    for _attempt in 0..=policy.retries {
+        connection.send(message)?;
+        let deadline = Instant::now() + policy.timeout;
+
+        rejected = false;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match connection.receive(deadline - now)? {
+                Some(received) => match reply(&received) {
+                    Reply::Ack => return Ok(received),
+                    Reply::Nak => {
+                        rejected = true;
+                        break;
+                    },
+                    Reply::Ignore => continue,
+                },
+                None => break,
+            }
+        }
+    }
+
+    if rejected {
+        Err(TransferError::Rejected { index })
+    }
+    else {
+        Err(TransferError::Timeout { index })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use crate::{Manufacturer, DEVELOPMENT};
+
+    /// Connection that replies with canned messages, one per receive call.
+    struct MockConnection {
+        sent: Vec<Message>,
+        replies: VecDeque<Option<Message>>,
+    }
+
+    impl Connection for MockConnection {
+        fn send(&mut self, message: &Message) -> io::Result<()> {
+            self.sent.push(message.clone());
+            Ok(())
+        }
+
+        fn receive(&mut self, _timeout: Duration) -> io::Result<Option<Message>> {
+            Ok(self.replies.pop_front().flatten())
+        }
+    }
+
+    fn development_message(b: u8) -> Message {
+        Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(DEVELOPMENT), payload: vec![b] }
+    }
+
+    fn check(message: &Message) -> Reply {
+        match message {
+            Message::ManufacturerSpecific { payload, .. } if payload[0] == 0x7f => Reply::Ack,
+            Message::ManufacturerSpecific { payload, .. } if payload[0] == 0x7e => Reply::Nak,
+            _ => Reply::Ignore,
+        }
+    }
+
+    #[test]
+    fn ack_after_nak_and_noise() {
+        let mut connection = MockConnection {
+            sent: Vec::new(),
+            replies: VecDeque::from([
+                Some(development_message(0x7e)),  // NAK
+                Some(development_message(0x01)),  // unrelated
+                Some(development_message(0x7f)),  // ACK
+            ]),
+        };
+        let messages = vec![development_message(0x10)];
+        let acks = send_all(&mut connection, &messages, &RetryPolicy::default(), check).unwrap();
+        assert_eq!(acks.len(), 1);
+        assert_eq!(connection.sent.len(), 2);
+    }
+
+    #[test]
+    fn timeout_after_retries() {
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        let policy = RetryPolicy::new(Duration::from_millis(10), 2);
+        let result = send_and_wait(&mut connection, &development_message(0x10), &policy, check);
+        assert!(matches!(result, Err(TransferError::Timeout { index: 0 })));
+        assert_eq!(connection.sent.len(), 3);
+    }
+
+    #[test]
+    fn pacer_delay() {
+        let message = development_message(0x10);  // four bytes
+        let pacer = Pacer::new(Duration::from_millis(5));
+        assert_eq!(pacer.byte_time, Duration::from_micros(320));
+        assert_eq!(pacer.delay(&message), Duration::from_micros(4 * 320 + 5000));
+        assert_eq!(Pacer::wire_rate().total_time(&[message.clone(), message]), Duration::from_micros(8 * 320));
+    }
+
+    #[test]
+    fn pacer_large_counts() {
+        let pacer = Pacer::wire_rate();
+        let count = u32::MAX as usize + 2;
+        assert_eq!(pacer.transmission_time(count), Duration::from_micros(320 * count as u64));
+        assert_eq!(pacer.transmission_time(usize::MAX).as_nanos(), usize::MAX as u128 * 320_000);
+        let slow = Pacer { byte_time: Duration::MAX, settle_time: Duration::MAX };
+        assert_eq!(slow.transmission_time(2), Duration::MAX);
+        assert_eq!(slow.total_time(&vec![Message::from_bytes(&[0xF0, 0x43, 0xF7]).unwrap(); 2]), Duration::MAX);
+    }
+
+    #[test]
+    fn paced_send() {
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        let messages = vec![development_message(0x10), development_message(0x11)];
+        send_paced(&mut connection, &messages, &Pacer::wire_rate()).unwrap();
+        assert_eq!(connection.sent, messages);
+    }
+
+    #[test]
+    fn send_plan() {
+        let small = development_message(0x10);  // four bytes
+        let large = Message::ManufacturerSpecific { manufacturer: Manufacturer::Standard(DEVELOPMENT), payload: vec![0; 10] };
+        let options = PlanOptions::new(10, Duration::from_millis(50));
+        let split = |message: &Message, _size: usize| match message.payload().len() {
+            10 => Some(vec![development_message(0x01), development_message(0x02)]),
+            _ => None,
+        };
+        let plan = plan_sends(&[small.clone(), small.clone(), large, small.clone()], &options, split);
+        let delays: Vec<Duration> = plan.iter().map(|p| p.delay - options.pacer.delay(&p.message)).collect();
+        let chunk = Duration::from_millis(50);
+        assert_eq!(plan.len(), 5);
+        assert_eq!(delays, vec![Duration::ZERO, chunk, Duration::ZERO, chunk, chunk]);
+
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        send_planned(&mut connection, &plan_sends(&[small], &PlanOptions::new(10, Duration::ZERO), split)).unwrap();
+        assert_eq!(connection.sent.len(), 1);
+    }
+
+    #[test]
+    fn timed_send() {
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        let messages = vec![
+            (Duration::ZERO, development_message(0x10)),
+            (Duration::from_millis(20), development_message(0x11)),
+        ];
+        let start = Instant::now();
+        send_timed(&mut connection, &messages, 0.5).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(connection.sent.len(), 2);
+
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        for scale in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            let result = send_timed(&mut connection, &messages[..1], scale);
+            assert!(matches!(result, Err(TransferError::Io(e)) if e.kind() == io::ErrorKind::InvalidInput));
+        }
+        assert!(connection.sent.is_empty());
+        assert!(matches!(send_timed(&mut connection, &messages, 1e300), Err(TransferError::Io(_))));
+        assert_eq!(connection.sent.len(), 1);
+    }
+
+    #[test]
+    fn paced_send_cancelled() {
+        let mut connection = MockConnection { sent: Vec::new(), replies: VecDeque::new() };
+        let messages = vec![development_message(0x10), development_message(0x11)];
+        let result = send_paced_with_progress(&mut connection, &messages, &Pacer::wire_rate(),
+            |p| if p.done == 1 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) });
+        assert!(matches!(result, Err(TransferError::Cancelled { index: 1 })));
+        assert_eq!(connection.sent.len(), 1);
+    }
+}
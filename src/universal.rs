@@ -0,0 +1,1234 @@
+//! # universal
+//!
+//! Fully decoded Universal System Exclusive messages.
+//!
+//! Use `UniversalMessage::from_message` (or `Message::decode_universal`)
+//! to decode a universal message, and `UniversalMessage::to_message`
+//! to make one.
+
+use std::fmt;
+use crate::{Message, Manufacturer, UniversalKind, SystemExclusiveError,
+    ALL_CALL, INITIATOR, NON_REAL_TIME, REAL_TIME, TERMINATOR};
+use crate::fixed::FixedMessage;
+use crate::transfer::Reply;
+
+/// Sub-ID #1 values of Universal Non-Real-time messages.
+const SAMPLE_DUMP_HEADER: u8 = 0x01;
+const SAMPLE_DATA_PACKET: u8 = 0x02;
+const SAMPLE_DUMP_REQUEST: u8 = 0x03;
+const SAMPLE_DUMP_EXTENSIONS: u8 = 0x05;
+const GENERAL_INFORMATION: u8 = 0x06;
+const TUNING_STANDARD: u8 = 0x08;
+const GENERAL_MIDI: u8 = 0x09;
+
+/// Sub-ID #1 values of Universal Real-time messages.
+const DEVICE_CONTROL: u8 = 0x04;
+const REAL_TIME_TUNING: u8 = 0x08;
+// 0x0b is Scalable Polyphony MIP, not Mobile Phone Control.
+const MOBILE_PHONE_CONTROL: u8 = 0x0c;
+
+/// Sub-ID #2 values of real-time MIDI Tuning Standard messages.
+const SINGLE_NOTE_TUNING_CHANGE: u8 = 0x02;
+const SINGLE_NOTE_TUNING_CHANGE_BANK: u8 = 0x07;
+
+/// Number of data bytes in a sample data packet.
+pub const SAMPLE_PACKET_SIZE: usize = 120;
+
+/// Number of notes in a bulk tuning dump.
+pub const TUNING_NOTE_COUNT: usize = 128;
+
+/// Length of the tuning program name in characters.
+pub const TUNING_NAME_LENGTH: usize = 16;
+
+/// Maximum number of note changes in one single note tuning change message.
+pub const MAX_NOTE_TUNING_CHANGES: usize = 127;
+
+/// Registered parameter number of the MIDI Tuning Standard Tuning Program Select.
+pub const TUNING_PROGRAM_SELECT_RPN: u16 = 0x0003;
+
+/// Registered parameter number of the MIDI Tuning Standard Tuning Bank Select.
+pub const TUNING_BANK_SELECT_RPN: u16 = 0x0004;
+
+/// Maximum length of a sample name in characters.
+pub const MAX_SAMPLE_NAME_LENGTH: usize = 127;
+
+/// Loop number that refers to all loops of a sample. In a loop point
+/// transmission it deletes all loops.
+pub const ALL_LOOPS: u16 = 0x3fff;
+
+/// Sub-ID #2 values of Sample Dump Extensions messages.
+const LOOP_POINT_TRANSMISSION: u8 = 0x01;
+const LOOP_POINTS_REQUEST: u8 = 0x02;
+const EXTENDED_DUMP_HEADER: u8 = 0x05;
+const SAMPLE_NAME_TRANSMISSION: u8 = 0x03;
+const SAMPLE_NAME_REQUEST: u8 = 0x04;
+
+/// Makes the bytes of a General MIDI System On message for the device.
+/// Like the other `const fn` message constructors, this can be used
+/// to make canned messages at compile time.
+pub const fn gm_on_bytes(device: u8) -> [u8; 6] {
+    [INITIATOR, NON_REAL_TIME, device, GENERAL_MIDI, 0x01, TERMINATOR]
+}
+
+/// Makes the bytes of a General MIDI System Off message for the device.
+pub const fn gm_off_bytes(device: u8) -> [u8; 6] {
+    [INITIATOR, NON_REAL_TIME, device, GENERAL_MIDI, 0x02, TERMINATOR]
+}
+
+/// Makes the bytes of a General MIDI 2 System On message for the device.
+pub const fn gm2_on_bytes(device: u8) -> [u8; 6] {
+    [INITIATOR, NON_REAL_TIME, device, GENERAL_MIDI, 0x03, TERMINATOR]
+}
+
+/// Makes the bytes of an Identity Request message for the device.
+pub const fn identity_request_bytes(device: u8) -> [u8; 6] {
+    [INITIATOR, NON_REAL_TIME, device, GENERAL_INFORMATION, 0x01, TERMINATOR]
+}
+
+/// Makes the bytes of a Master Volume message for the device,
+/// with a 14-bit volume.
+pub const fn master_volume_bytes(device: u8, volume: u16) -> [u8; 8] {
+    let value = encode_u14(volume);
+    [INITIATOR, REAL_TIME, device, DEVICE_CONTROL, 0x01, value[0], value[1], TERMINATOR]
+}
+
+/// General MIDI System On for all devices.
+pub const GM_ON: [u8; 6] = gm_on_bytes(ALL_CALL);
+
+/// General MIDI System Off for all devices.
+pub const GM_OFF: [u8; 6] = gm_off_bytes(ALL_CALL);
+
+/// General MIDI 2 System On for all devices.
+pub const GM2_ON: [u8; 6] = gm2_on_bytes(ALL_CALL);
+
+/// Identity Request for all devices.
+pub const IDENTITY_REQUEST: [u8; 6] = identity_request_bytes(ALL_CALL);
+
+/// Decodes a 14-bit value transmitted least significant byte first.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than two bytes.
+pub fn decode_u14(data: &[u8]) -> u16 {
+    (data[0] as u16 & 0x7f) | ((data[1] as u16 & 0x7f) << 7)
+}
+
+/// Encodes a 14-bit value least significant byte first.
+pub const fn encode_u14(value: u16) -> [u8; 2] {
+    [(value & 0x7f) as u8, ((value >> 7) & 0x7f) as u8]
+}
+
+/// Decodes a 21-bit value transmitted least significant byte first.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than three bytes.
+pub fn decode_u21(data: &[u8]) -> u32 {
+    (data[0] as u32 & 0x7f) | ((data[1] as u32 & 0x7f) << 7) | ((data[2] as u32 & 0x7f) << 14)
+}
+
+/// Encodes a 21-bit value least significant byte first.
+pub const fn encode_u21(value: u32) -> [u8; 3] {
+    [(value & 0x7f) as u8, ((value >> 7) & 0x7f) as u8, ((value >> 14) & 0x7f) as u8]
+}
+
+/// Decodes a value of up to 63 bits transmitted as 7-bit bytes,
+/// least significant byte first.
+pub fn decode_u7_bytes(data: &[u8]) -> u64 {
+    data.iter().rev().fold(0u64, |acc, b| (acc << 7) | (*b as u64 & 0x7f))
+}
+
+/// Encodes a value into `count` 7-bit bytes, least significant byte first.
+pub fn encode_u7_bytes(value: u64, count: usize) -> Vec<u8> {
+    (0..count).map(|i| ((value >> (7 * i)) & 0x7f) as u8).collect()
+}
+
+/// Computes the checksum used by sample data packets and tuning dumps:
+/// the exclusive OR of the bytes, masked to seven bits.
+pub fn xor_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, b| acc ^ b) & 0x7f
+}
+
+/// Handshake message used by the Sample Dump Standard, File Dump
+/// and MIDI-CI protocols. Each one carries the number of the packet
+/// it refers to.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Handshake {
+    Ack,
+    Nak,
+    Wait,
+    Cancel,
+    EndOfFile,
+}
+
+impl Handshake {
+    /// Gets the handshake from its sub-ID #1 value.
+    pub fn from_sub_id(sub_id1: u8) -> Option<Self> {
+        match sub_id1 {
+            0x7f => Some(Handshake::Ack),
+            0x7e => Some(Handshake::Nak),
+            0x7c => Some(Handshake::Wait),
+            0x7d => Some(Handshake::Cancel),
+            0x7b => Some(Handshake::EndOfFile),
+            _ => None,
+        }
+    }
+
+    /// Gets the sub-ID #1 value of the handshake.
+    pub fn sub_id(&self) -> u8 {
+        match self {
+            Handshake::Ack => 0x7f,
+            Handshake::Nak => 0x7e,
+            Handshake::Wait => 0x7c,
+            Handshake::Cancel => 0x7d,
+            Handshake::EndOfFile => 0x7b,
+        }
+    }
+
+    /// Makes a handshake message for the given device and packet number.
+    pub fn to_message(&self, device: u8, packet: u8) -> Message {
+        UniversalMessage::Handshake { device, handshake: *self, packet }.to_message()
+    }
+
+    /// Gets the handshake and packet number from a message,
+    /// or `None` if it is not a handshake message.
+    pub fn from_message(message: &Message) -> Option<(Self, u8)> {
+        match message {
+            Message::Universal { kind: UniversalKind::NonRealTime, sub_id1, sub_id2, payload, .. } if payload.is_empty() =>
+                Handshake::from_sub_id(*sub_id1).map(|handshake| (handshake, *sub_id2)),
+            _ => None,
+        }
+    }
+
+    /// Gets the transfer reply corresponding to this handshake. `Wait` is
+    /// ignored, so that the sender keeps waiting for the next reply.
+    pub fn reply(&self) -> Reply {
+        match self {
+            Handshake::Ack | Handshake::EndOfFile => Reply::Ack,
+            Handshake::Nak | Handshake::Cancel => Reply::Nak,
+            Handshake::Wait => Reply::Ignore,
+        }
+    }
+}
+
+impl fmt::Display for Handshake {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Handshake::Ack => "ACK",
+            Handshake::Nak => "NAK",
+            Handshake::Wait => "WAIT",
+            Handshake::Cancel => "CANCEL",
+            Handshake::EndOfFile => "EOF",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Gets the transfer reply for a received message, for use with
+/// `transfer::send_all`. Messages other than handshakes are ignored.
+pub fn handshake_reply(message: &Message) -> Reply {
+    match Handshake::from_message(message) {
+        Some((handshake, _)) => handshake.reply(),
+        None => Reply::Ignore,
+    }
+}
+
+/// Length in bytes of the longest Identity Reply, with an extended manufacturer ID.
+pub const IDENTITY_REPLY_MAX_LENGTH: usize = 17;
+
+/// The identity of a device, for answering Identity Requests on the
+/// device side of the protocol. The reply can be made without allocating
+/// with `reply_fixed` and `respond_to_bytes`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DeviceIdentity {
+    pub manufacturer: Manufacturer,
+    /// Device family code, 14 bits.
+    pub family: u16,
+    /// Device family member code, 14 bits.
+    pub member: u16,
+    /// Software revision level, four bytes of 7 bits.
+    pub version: [u8; 4],
+}
+
+impl DeviceIdentity {
+    /// Creates a new device identity.
+    pub const fn new(manufacturer: Manufacturer, family: u16, member: u16, version: [u8; 4]) -> Self {
+        DeviceIdentity { manufacturer, family, member, version }
+    }
+
+    /// Makes the Identity Reply of this device with the device ID `device`.
+    pub fn reply(&self, device: u8) -> UniversalMessage {
+        UniversalMessage::IdentityReply {
+            device,
+            manufacturer: self.manufacturer,
+            family: self.family,
+            member: self.member,
+            version: self.version,
+        }
+    }
+
+    /// Makes the Identity Reply of this device without allocating.
+    pub fn reply_fixed(&self, device: u8) -> FixedMessage<IDENTITY_REPLY_MAX_LENGTH> {
+        let mut bytes = [0u8; IDENTITY_REPLY_MAX_LENGTH];
+        bytes[..5].copy_from_slice(&[INITIATOR, NON_REAL_TIME, device & 0x7f, GENERAL_INFORMATION, 0x02]);
+        let mut len = 5;
+        let (id, id_length) = match self.manufacturer {
+            Manufacturer::Standard(b) => ([b, 0, 0], 1),
+            Manufacturer::Extended(bs) => (bs, 3),
+        };
+        for b in id[..id_length].iter()
+            .chain(&encode_u14(self.family))
+            .chain(&encode_u14(self.member))
+            .chain(&self.version)
+        {
+            bytes[len] = b & 0x7f;
+            len += 1;
+        }
+        bytes[len] = TERMINATOR;
+        FixedMessage::from_bytes(&bytes[..len + 1]).expect("identity reply is valid")
+    }
+
+    /// Returns `true` if the message is an Identity Request addressed
+    /// to the device ID `device` or to all devices.
+    pub fn is_request_for(device: u8, request: &Message) -> bool {
+        match request {
+            Message::Universal { kind: UniversalKind::NonRealTime, target, sub_id1: GENERAL_INFORMATION, sub_id2: 0x01, payload } =>
+                payload.is_empty() && (*target == device || *target == ALL_CALL),
+            _ => false,
+        }
+    }
+
+    /// Answers a received message: returns the Identity Reply if the
+    /// message is an Identity Request for the device ID `device` or for
+    /// all devices, and `None` otherwise. The reply carries the device ID
+    /// of this device, also for requests sent to all devices.
+    pub fn respond(&self, device: u8, request: &Message) -> Option<Message> {
+        if DeviceIdentity::is_request_for(device, request) {
+            Some(self.reply(device).to_message())
+        }
+        else {
+            None
+        }
+    }
+
+    /// Like `respond`, but works on raw bytes and does not allocate.
+    pub fn respond_to_bytes(&self, device: u8, request: &[u8]) -> Option<FixedMessage<IDENTITY_REPLY_MAX_LENGTH>> {
+        if request == identity_request_bytes(device) || request == IDENTITY_REQUEST {
+            Some(self.reply_fixed(device))
+        }
+        else {
+            None
+        }
+    }
+}
+
+/// Loop type of a sample. The classic dump header and loop point messages
+/// only use `Forward`, `Alternating` and `Off`; the others were added
+/// with the extended dump header.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LoopType {
+    Forward,
+    Alternating,
+    ForwardRelease,
+    AlternatingRelease,
+    Backward,
+    BackwardAlternating,
+    BackwardRelease,
+    BackwardAlternatingRelease,
+    Off,
+}
+
+impl LoopType {
+    /// Gets the loop type from its byte value.
+    pub fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0x00 => Some(LoopType::Forward),
+            0x01 => Some(LoopType::Alternating),
+            0x02 => Some(LoopType::ForwardRelease),
+            0x03 => Some(LoopType::AlternatingRelease),
+            0x40 => Some(LoopType::Backward),
+            0x41 => Some(LoopType::BackwardAlternating),
+            0x42 => Some(LoopType::BackwardRelease),
+            0x43 => Some(LoopType::BackwardAlternatingRelease),
+            0x7f => Some(LoopType::Off),
+            _ => None,
+        }
+    }
+
+    /// Gets the byte value of the loop type.
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            LoopType::Forward => 0x00,
+            LoopType::Alternating => 0x01,
+            LoopType::ForwardRelease => 0x02,
+            LoopType::AlternatingRelease => 0x03,
+            LoopType::Backward => 0x40,
+            LoopType::BackwardAlternating => 0x41,
+            LoopType::BackwardRelease => 0x42,
+            LoopType::BackwardAlternatingRelease => 0x43,
+            LoopType::Off => 0x7f,
+        }
+    }
+}
+
+impl fmt::Display for LoopType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LoopType::Forward => "Forward",
+            LoopType::Alternating => "Alternating",
+            LoopType::ForwardRelease => "Forward with release",
+            LoopType::AlternatingRelease => "Alternating with release",
+            LoopType::Backward => "Backward",
+            LoopType::BackwardAlternating => "Backward alternating",
+            LoopType::BackwardRelease => "Backward with release",
+            LoopType::BackwardAlternatingRelease => "Backward alternating with release",
+            LoopType::Off => "Off",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Sample Dump Standard header information.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SampleHeader {
+    pub sample_number: u16,
+    /// Significant bits per sample, from 8 to 28.
+    pub sample_format: u8,
+    /// Sample period in nanoseconds.
+    pub sample_period: u32,
+    /// Sample length in words.
+    pub length: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub loop_type: LoopType,
+}
+
+impl SampleHeader {
+    /// Gets the sample rate in Hz.
+    pub fn sample_rate(&self) -> f64 {
+        if self.sample_period == 0 {
+            0.0
+        }
+        else {
+            1_000_000_000.0 / self.sample_period as f64
+        }
+    }
+}
+
+/// Extended Sample Dump Standard header information, which allows
+/// higher sample rates, longer samples and more than one channel.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ExtendedSampleHeader {
+    pub sample_number: u16,
+    /// Significant bits per sample, from 8 to 28.
+    pub sample_format: u8,
+    /// Integer part of the sample rate in Hz (28 bits).
+    pub sample_rate: u32,
+    /// Fractional part of the sample rate in units of 1/2^28 Hz.
+    pub sample_rate_fraction: u32,
+    /// Sample length in words (35 bits).
+    pub length: u64,
+    pub loop_start: u64,
+    pub loop_end: u64,
+    pub loop_type: LoopType,
+    pub channels: u8,
+}
+
+impl ExtendedSampleHeader {
+    /// Gets the sample rate in Hz, including the fractional part.
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate as f64 + self.sample_rate_fraction as f64 / (1u64 << 28) as f64
+    }
+}
+
+impl From<&SampleHeader> for ExtendedSampleHeader {
+    fn from(header: &SampleHeader) -> Self {
+        let rate = header.sample_rate();
+        ExtendedSampleHeader {
+            sample_number: header.sample_number,
+            sample_format: header.sample_format,
+            sample_rate: rate.trunc() as u32,
+            sample_rate_fraction: (rate.fract() * (1u64 << 28) as f64) as u32,
+            length: header.length as u64,
+            loop_start: header.loop_start as u64,
+            loop_end: header.loop_end as u64,
+            loop_type: header.loop_type,
+            channels: 1,
+        }
+    }
+}
+
+/// One loop of a sample, as sent in a loop point transmission.
+/// The start and end are word offsets into the sample.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LoopPoint {
+    pub number: u16,
+    pub loop_type: LoopType,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Makes loop point transmission messages for the loops of a sample.
+pub fn loop_point_messages(device: u8, sample_number: u16, loops: &[LoopPoint]) -> Vec<Message> {
+    loops.iter()
+        .map(|loop_point| UniversalMessage::LoopPoints { device, sample_number, loop_point: *loop_point }.to_message())
+        .collect()
+}
+
+/// Tuning of one note in a tuning dump, as a semitone and
+/// a 14-bit fraction of a semitone (in units of 100/16384 cents).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct NoteTuning {
+    pub semitone: u8,
+    pub fraction: u16,
+}
+
+impl NoteTuning {
+    /// Tuning bytes meaning "no change".
+    pub const NO_CHANGE: NoteTuning = NoteTuning { semitone: 0x7f, fraction: 0x3fff };
+
+    fn from_bytes(data: &[u8]) -> Self {
+        // The fraction is sent most significant byte first.
+        NoteTuning {
+            semitone: data[0] & 0x7f,
+            fraction: ((data[1] as u16 & 0x7f) << 7) | (data[2] as u16 & 0x7f),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 3] {
+        [self.semitone & 0x7f, ((self.fraction >> 7) & 0x7f) as u8, (self.fraction & 0x7f) as u8]
+    }
+
+    /// Gets the tuning in cents from MIDI note 0 (C-1 in 12-TET).
+    pub fn cents(&self) -> f64 {
+        self.semitone as f64 * 100.0 + self.fraction as f64 * 100.0 / 16384.0
+    }
+}
+
+/// Makes single note tuning change messages for the changes, with at most
+/// `MAX_NOTE_TUNING_CHANGES` changes in each message.
+pub fn note_tuning_change_messages(device: u8, bank: Option<u8>, program: u8, changes: &[(u8, NoteTuning)]) -> Vec<Message> {
+    changes.chunks(MAX_NOTE_TUNING_CHANGES)
+        .map(|chunk| UniversalMessage::NoteTuningChange { device, bank, program, changes: chunk.to_vec() }.to_message())
+        .collect()
+}
+
+/// Selection of a tuning program or bank. The MIDI Tuning Standard selects
+/// them with registered parameter numbers in channel messages, not with
+/// System Exclusive messages.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum TuningSelect {
+    Program(u8),
+    Bank(u8),
+}
+
+impl TuningSelect {
+    /// Gets the registered parameter number of the selection.
+    pub fn rpn(&self) -> u16 {
+        match self {
+            TuningSelect::Program(_) => TUNING_PROGRAM_SELECT_RPN,
+            TuningSelect::Bank(_) => TUNING_BANK_SELECT_RPN,
+        }
+    }
+
+    /// Makes the control change messages on the channel (0 to 15) that select
+    /// the tuning program or bank: the RPN number, the data entry and
+    /// the null RPN that ends the selection.
+    pub fn to_bytes(&self, channel: u8) -> [u8; 15] {
+        let status = 0xb0 | (channel & 0x0f);
+        let [msb, lsb] = self.rpn().to_be_bytes();
+        let value = match self {
+            TuningSelect::Program(value) | TuningSelect::Bank(value) => value & 0x7f,
+        };
+        [
+            status, 0x65, msb, status, 0x64, lsb,
+            status, 0x06, value,
+            status, 0x65, 0x7f, status, 0x64, 0x7f,
+        ]
+    }
+
+    /// Recognizes the control changes of a tuning program or bank selection
+    /// at the start of the data. Returns the channel and the selection.
+    pub fn from_bytes(data: &[u8]) -> Option<(u8, Self)> {
+        match data.get(..9)? {
+            [status, 0x65, 0x00, s2, 0x64, lsb, s3, 0x06, value]
+                if status & 0xf0 == 0xb0 && s2 == status && s3 == status && *value < 0x80 => {
+                let selection = match *lsb {
+                    0x03 => TuningSelect::Program(*value),
+                    0x04 => TuningSelect::Bank(*value),
+                    _ => return None,
+                };
+                Some((status & 0x0f, selection))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A fully decoded Universal System Exclusive message.
+/// The `device` field is the target device ID (0x7F for all devices).
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum UniversalMessage {
+    IdentityRequest { device: u8 },
+    IdentityReply { device: u8, manufacturer: Manufacturer, family: u16, member: u16, version: [u8; 4] },
+    SampleDumpHeader { device: u8, header: SampleHeader },
+    ExtendedSampleDumpHeader { device: u8, header: ExtendedSampleHeader },
+    SampleDataPacket { device: u8, packet: u8, data: Vec<u8> },
+    SampleDumpRequest { device: u8, sample_number: u16 },
+    LoopPointsRequest { device: u8, sample_number: u16, loop_number: u16 },
+    LoopPoints { device: u8, sample_number: u16, loop_point: LoopPoint },
+    SampleNameRequest { device: u8, sample_number: u16 },
+    SampleName { device: u8, sample_number: u16, name: String },
+    TuningDumpRequest { device: u8, program: u8 },
+    TuningDump { device: u8, program: u8, name: String, tunings: Vec<NoteTuning> },
+    GmOn { device: u8 },
+    GmOff { device: u8 },
+    Gm2On { device: u8 },
+    MasterVolume { device: u8, volume: u16 },
+    MasterBalance { device: u8, balance: u16 },
+    MasterFineTuning { device: u8, tuning: u16 },
+    MasterCoarseTuning { device: u8, tuning: u16 },
+    /// Real-time change of the tuning of some notes in a tuning program,
+    /// optionally in a tuning bank, taking effect immediately. One message
+    /// carries at most `MAX_NOTE_TUNING_CHANGES` changes, and `to_message`
+    /// leaves out the rest; use `note_tuning_change_messages` for more.
+    NoteTuningChange { device: u8, bank: Option<u8>, program: u8, changes: Vec<(u8, NoteTuning)> },
+    /// Mobile Phone Control message addressed to a device of the phone,
+    /// like its vibrator or an LED. See `mobile_device_class_name` and
+    /// `mobile_command_name` for the names of the fields.
+    MobilePhoneControl { device: u8, device_class: u8, device_index: u8, command: u8, data: Vec<u8> },
+    Handshake { device: u8, handshake: Handshake, packet: u8 },
+}
+
+impl UniversalMessage {
+    /// Decodes a universal message. Checksums are verified. Universal
+    /// messages of types not listed here fail with `UnsupportedFormat`.
+    pub fn from_message(message: &Message) -> Result<Self, SystemExclusiveError> {
+        let (kind, device, sub_id1, sub_id2, payload) = match message {
+            Message::Universal { kind, target, sub_id1, sub_id2, payload } =>
+                (*kind, *target, *sub_id1, *sub_id2, payload),
+            Message::ManufacturerSpecific { .. } => return Err(SystemExclusiveError::InvalidMessage),
+        };
+
+        match kind {
+            UniversalKind::NonRealTime => decode_non_real_time(device, sub_id1, sub_id2, payload),
+            UniversalKind::RealTime => decode_real_time(device, sub_id1, sub_id2, payload),
+        }
+    }
+
+    /// Makes a System Exclusive message from this universal message.
+    pub fn to_message(&self) -> Message {
+        let (kind, device, sub_id1, sub_id2, payload) = match self {
+            UniversalMessage::IdentityRequest { device } =>
+                (UniversalKind::NonRealTime, *device, GENERAL_INFORMATION, 0x01, vec![]),
+            UniversalMessage::IdentityReply { device, manufacturer, family, member, version } => {
+                let mut payload = manufacturer.to_bytes();
+                payload.extend(encode_u14(*family));
+                payload.extend(encode_u14(*member));
+                payload.extend(version);
+                (UniversalKind::NonRealTime, *device, GENERAL_INFORMATION, 0x02, payload)
+            },
+            UniversalMessage::SampleDumpHeader { device, header } => {
+                let number = encode_u14(header.sample_number);
+                let mut payload = vec![number[1], header.sample_format];
+                payload.extend(encode_u21(header.sample_period));
+                payload.extend(encode_u21(header.length));
+                payload.extend(encode_u21(header.loop_start));
+                payload.extend(encode_u21(header.loop_end));
+                payload.push(header.loop_type.to_byte());
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_HEADER, number[0], payload)
+            },
+            UniversalMessage::ExtendedSampleDumpHeader { device, header } => {
+                let mut payload = encode_u14(header.sample_number).to_vec();
+                payload.push(header.sample_format);
+                payload.extend(encode_u7_bytes(header.sample_rate as u64, 4));
+                payload.extend(encode_u7_bytes(header.sample_rate_fraction as u64, 4));
+                payload.extend(encode_u7_bytes(header.length, 5));
+                payload.extend(encode_u7_bytes(header.loop_start, 5));
+                payload.extend(encode_u7_bytes(header.loop_end, 5));
+                payload.push(header.loop_type.to_byte());
+                payload.push(header.channels);
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_EXTENSIONS, EXTENDED_DUMP_HEADER, payload)
+            },
+            UniversalMessage::SampleDataPacket { device, packet, data } => {
+                let mut payload = data.clone();
+                let mut checked = vec![crate::NON_REAL_TIME, *device, SAMPLE_DATA_PACKET, *packet];
+                checked.extend(data);
+                payload.push(xor_checksum(&checked));
+                (UniversalKind::NonRealTime, *device, SAMPLE_DATA_PACKET, *packet, payload)
+            },
+            UniversalMessage::SampleDumpRequest { device, sample_number } => {
+                let number = encode_u14(*sample_number);
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_REQUEST, number[0], vec![number[1]])
+            },
+            UniversalMessage::LoopPointsRequest { device, sample_number, loop_number } => {
+                let mut payload = encode_u14(*sample_number).to_vec();
+                payload.extend(encode_u14(*loop_number));
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_EXTENSIONS, LOOP_POINTS_REQUEST, payload)
+            },
+            UniversalMessage::LoopPoints { device, sample_number, loop_point } => {
+                let mut payload = encode_u14(*sample_number).to_vec();
+                payload.extend(encode_u14(loop_point.number));
+                payload.push(loop_point.loop_type.to_byte());
+                payload.extend(encode_u21(loop_point.start));
+                payload.extend(encode_u21(loop_point.end));
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_EXTENSIONS, LOOP_POINT_TRANSMISSION, payload)
+            },
+            UniversalMessage::SampleNameRequest { device, sample_number } =>
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_EXTENSIONS, SAMPLE_NAME_REQUEST,
+                    encode_u14(*sample_number).to_vec()),
+            UniversalMessage::SampleName { device, sample_number, name } => {
+                let mut payload = encode_u14(*sample_number).to_vec();
+                let name_bytes = encode_sample_name(name);
+                payload.push(0x00);  // no language tag
+                payload.push(name_bytes.len() as u8);
+                payload.extend(name_bytes);
+                (UniversalKind::NonRealTime, *device, SAMPLE_DUMP_EXTENSIONS, SAMPLE_NAME_TRANSMISSION, payload)
+            },
+            UniversalMessage::TuningDumpRequest { device, program } =>
+                (UniversalKind::NonRealTime, *device, TUNING_STANDARD, 0x00, vec![*program]),
+            UniversalMessage::TuningDump { device, program, name, tunings } => {
+                let mut payload = vec![*program];
+                let mut name_bytes: Vec<u8> = name.bytes().filter(|b| (0x20..0x7f).contains(b))
+                    .take(TUNING_NAME_LENGTH).collect();
+                name_bytes.resize(TUNING_NAME_LENGTH, b' ');
+                payload.extend(name_bytes);
+                for tuning in tunings {
+                    payload.extend(tuning.to_bytes());
+                }
+                let mut checked = vec![crate::NON_REAL_TIME, *device, TUNING_STANDARD, 0x01];
+                checked.extend(&payload);
+                payload.push(xor_checksum(&checked));
+                (UniversalKind::NonRealTime, *device, TUNING_STANDARD, 0x01, payload)
+            },
+            UniversalMessage::GmOn { device } =>
+                (UniversalKind::NonRealTime, *device, GENERAL_MIDI, 0x01, vec![]),
+            UniversalMessage::GmOff { device } =>
+                (UniversalKind::NonRealTime, *device, GENERAL_MIDI, 0x02, vec![]),
+            UniversalMessage::Gm2On { device } =>
+                (UniversalKind::NonRealTime, *device, GENERAL_MIDI, 0x03, vec![]),
+            UniversalMessage::MasterVolume { device, volume } =>
+                (UniversalKind::RealTime, *device, DEVICE_CONTROL, 0x01, encode_u14(*volume).to_vec()),
+            UniversalMessage::MasterBalance { device, balance } =>
+                (UniversalKind::RealTime, *device, DEVICE_CONTROL, 0x02, encode_u14(*balance).to_vec()),
+            UniversalMessage::MasterFineTuning { device, tuning } =>
+                (UniversalKind::RealTime, *device, DEVICE_CONTROL, 0x03, encode_u14(*tuning).to_vec()),
+            UniversalMessage::MasterCoarseTuning { device, tuning } =>
+                (UniversalKind::RealTime, *device, DEVICE_CONTROL, 0x04, encode_u14(*tuning).to_vec()),
+            UniversalMessage::NoteTuningChange { device, bank, program, changes } => {
+                let changes = &changes[..changes.len().min(MAX_NOTE_TUNING_CHANGES)];
+                let mut payload: Vec<u8> = bank.iter().copied().collect();
+                payload.extend([*program, changes.len() as u8]);
+                for (key, tuning) in changes {
+                    payload.push(*key);
+                    payload.extend(tuning.to_bytes());
+                }
+                let sub_id2 = if bank.is_some() { SINGLE_NOTE_TUNING_CHANGE_BANK } else { SINGLE_NOTE_TUNING_CHANGE };
+                (UniversalKind::RealTime, *device, REAL_TIME_TUNING, sub_id2, payload)
+            },
+            UniversalMessage::MobilePhoneControl { device, device_class, device_index, command, data } => {
+                let mut payload = vec![*device_class, *device_index, *command];
+                payload.extend(data);
+                (UniversalKind::RealTime, *device, MOBILE_PHONE_CONTROL, 0x00, payload)
+            },
+            UniversalMessage::Handshake { device, handshake, packet } =>
+                (UniversalKind::NonRealTime, *device, handshake.sub_id(), *packet, vec![]),
+        };
+
+        Message::Universal { kind, target: device, sub_id1, sub_id2, payload }
+    }
+}
+
+/// Decodes a sample name. Characters outside printable ASCII become spaces,
+/// and trailing spaces are removed.
+fn decode_sample_name(data: &[u8]) -> String {
+    let name: String = data.iter()
+        .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+        .collect();
+    name.trim_end().to_string()
+}
+
+/// Encodes a sample name. Characters outside printable ASCII become
+/// question marks, and the name is truncated to the maximum length.
+fn encode_sample_name(name: &str) -> Vec<u8> {
+    name.chars()
+        .map(|c| if (' '..='~').contains(&c) { c as u8 } else { b'?' })
+        .take(MAX_SAMPLE_NAME_LENGTH)
+        .collect()
+}
+
+fn decode_non_real_time(device: u8, sub_id1: u8, sub_id2: u8, payload: &[u8]) -> Result<UniversalMessage, SystemExclusiveError> {
+    match (sub_id1, sub_id2) {
+        (GENERAL_INFORMATION, 0x01) => Ok(UniversalMessage::IdentityRequest { device }),
+        (GENERAL_INFORMATION, 0x02) => {
+            let id_length = if payload.first() == Some(&0x00) { 3 } else { 1 };
+            if payload.len() != id_length + 8 {
+                return Err(SystemExclusiveError::InvalidMessage);
+            }
+            let manufacturer = Manufacturer::from_bytes(&payload[..id_length])?;
+            let rest = &payload[id_length..];
+            Ok(UniversalMessage::IdentityReply {
+                device,
+                manufacturer,
+                family: decode_u14(&rest[0..2]),
+                member: decode_u14(&rest[2..4]),
+                version: [rest[4], rest[5], rest[6], rest[7]],
+            })
+        },
+        (SAMPLE_DUMP_HEADER, _) => {
+            // The sub-ID #2 position holds the low byte of the sample number.
+            if payload.len() != 15 {
+                return Err(SystemExclusiveError::InvalidMessage);
+            }
+            let loop_type = LoopType::from_byte(payload[14]).ok_or(SystemExclusiveError::InvalidMessage)?;
+            Ok(UniversalMessage::SampleDumpHeader {
+                device,
+                header: SampleHeader {
+                    sample_number: decode_u14(&[sub_id2, payload[0]]),
+                    sample_format: payload[1],
+                    sample_period: decode_u21(&payload[2..5]),
+                    length: decode_u21(&payload[5..8]),
+                    loop_start: decode_u21(&payload[8..11]),
+                    loop_end: decode_u21(&payload[11..14]),
+                    loop_type,
+                },
+            })
+        },
+        (SAMPLE_DATA_PACKET, packet) => {
+            if payload.len() != SAMPLE_PACKET_SIZE + 1 {
+                return Err(SystemExclusiveError::InvalidMessage);
+            }
+            let (data, checksum) = payload.split_at(SAMPLE_PACKET_SIZE);
+            let mut checked = vec![crate::NON_REAL_TIME, device, SAMPLE_DATA_PACKET, packet];
+            checked.extend(data);
+            if xor_checksum(&checked) != checksum[0] {
+                return Err(SystemExclusiveError::InvalidChecksum);
+            }
+            Ok(UniversalMessage::SampleDataPacket { device, packet, data: data.to_vec() })
+        },
+        (SAMPLE_DUMP_REQUEST, _) if payload.len() == 1 =>
+            Ok(UniversalMessage::SampleDumpRequest { device, sample_number: decode_u14(&[sub_id2, payload[0]]) }),
+        (SAMPLE_DUMP_EXTENSIONS, LOOP_POINTS_REQUEST) if payload.len() == 4 =>
+            Ok(UniversalMessage::LoopPointsRequest {
+                device,
+                sample_number: decode_u14(&payload[0..2]),
+                loop_number: decode_u14(&payload[2..4]),
+            }),
+        (SAMPLE_DUMP_EXTENSIONS, LOOP_POINT_TRANSMISSION) if payload.len() == 11 => {
+            let loop_type = LoopType::from_byte(payload[4]).ok_or(SystemExclusiveError::InvalidMessage)?;
+            Ok(UniversalMessage::LoopPoints {
+                device,
+                sample_number: decode_u14(&payload[0..2]),
+                loop_point: LoopPoint {
+                    number: decode_u14(&payload[2..4]),
+                    loop_type,
+                    start: decode_u21(&payload[5..8]),
+                    end: decode_u21(&payload[8..11]),
+                },
+            })
+        },
+        (SAMPLE_DUMP_EXTENSIONS, EXTENDED_DUMP_HEADER) => {
+            if payload.len() != 28 {
+                return Err(SystemExclusiveError::InvalidMessage);
+            }
+            let loop_type = LoopType::from_byte(payload[26]).ok_or(SystemExclusiveError::InvalidMessage)?;
+            Ok(UniversalMessage::ExtendedSampleDumpHeader {
+                device,
+                header: ExtendedSampleHeader {
+                    sample_number: decode_u14(&payload[0..2]),
+                    sample_format: payload[2],
+                    sample_rate: decode_u7_bytes(&payload[3..7]) as u32,
+                    sample_rate_fraction: decode_u7_bytes(&payload[7..11]) as u32,
+                    length: decode_u7_bytes(&payload[11..16]),
+                    loop_start: decode_u7_bytes(&payload[16..21]),
+                    loop_end: decode_u7_bytes(&payload[21..26]),
+                    loop_type,
+                    channels: payload[27],
+                },
+            })
+        },
+        (SAMPLE_DUMP_EXTENSIONS, SAMPLE_NAME_REQUEST) if payload.len() == 2 =>
+            Ok(UniversalMessage::SampleNameRequest { device, sample_number: decode_u14(payload) }),
+        (SAMPLE_DUMP_EXTENSIONS, SAMPLE_NAME_TRANSMISSION) => {
+            // Sample number, language tag length, language tag, name length, name
+            let tag_length = *payload.get(2).ok_or(SystemExclusiveError::Truncated)? as usize;
+            let name_length = *payload.get(3 + tag_length).ok_or(SystemExclusiveError::Truncated)? as usize;
+            let name_bytes = payload.get(4 + tag_length..4 + tag_length + name_length)
+                .ok_or(SystemExclusiveError::Truncated)?;
+            Ok(UniversalMessage::SampleName {
+                device,
+                sample_number: decode_u14(payload),
+                name: decode_sample_name(name_bytes),
+            })
+        },
+        (TUNING_STANDARD, 0x00) if payload.len() == 1 =>
+            Ok(UniversalMessage::TuningDumpRequest { device, program: payload[0] }),
+        (TUNING_STANDARD, 0x01) => {
+            let expected_length = 1 + TUNING_NAME_LENGTH + TUNING_NOTE_COUNT * 3 + 1;
+            if payload.len() != expected_length {
+                return Err(SystemExclusiveError::InvalidMessage);
+            }
+            let mut checked = vec![crate::NON_REAL_TIME, device, TUNING_STANDARD, 0x01];
+            checked.extend(&payload[..expected_length - 1]);
+            if xor_checksum(&checked) != payload[expected_length - 1] {
+                return Err(SystemExclusiveError::InvalidChecksum);
+            }
+            let name: String = payload[1..1 + TUNING_NAME_LENGTH].iter()
+                .map(|b| if (0x20..0x7f).contains(b) { *b as char } else { ' ' })
+                .collect();
+            Ok(UniversalMessage::TuningDump {
+                device,
+                program: payload[0],
+                name: name.trim_end().to_string(),
+                tunings: payload[1 + TUNING_NAME_LENGTH..expected_length - 1]
+                    .chunks(3)
+                    .map(NoteTuning::from_bytes)
+                    .collect(),
+            })
+        },
+        (GENERAL_MIDI, 0x01) => Ok(UniversalMessage::GmOn { device }),
+        (GENERAL_MIDI, 0x02) => Ok(UniversalMessage::GmOff { device }),
+        (GENERAL_MIDI, 0x03) => Ok(UniversalMessage::Gm2On { device }),
+        (_, packet) => match Handshake::from_sub_id(sub_id1) {
+            Some(handshake) if payload.is_empty() => Ok(UniversalMessage::Handshake { device, handshake, packet }),
+            Some(_) => Err(SystemExclusiveError::InvalidMessage),
+            None => Err(SystemExclusiveError::UnsupportedFormat),
+        },
+    }
+}
+
+fn decode_real_time(device: u8, sub_id1: u8, sub_id2: u8, payload: &[u8]) -> Result<UniversalMessage, SystemExclusiveError> {
+    if sub_id1 == REAL_TIME_TUNING {
+        return decode_note_tuning_change(device, sub_id2, payload);
+    }
+    if sub_id1 == MOBILE_PHONE_CONTROL && sub_id2 == 0x00 {
+        return match payload {
+            [device_class, device_index, command, data @ ..] => Ok(UniversalMessage::MobilePhoneControl {
+                device, device_class: *device_class, device_index: *device_index, command: *command, data: data.to_vec(),
+            }),
+            _ => Err(SystemExclusiveError::Truncated),
+        };
+    }
+    if sub_id1 != DEVICE_CONTROL {
+        return Err(SystemExclusiveError::UnsupportedFormat);
+    }
+    if payload.len() != 2 {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+    let value = decode_u14(payload);
+    match sub_id2 {
+        0x01 => Ok(UniversalMessage::MasterVolume { device, volume: value }),
+        0x02 => Ok(UniversalMessage::MasterBalance { device, balance: value }),
+        0x03 => Ok(UniversalMessage::MasterFineTuning { device, tuning: value }),
+        0x04 => Ok(UniversalMessage::MasterCoarseTuning { device, tuning: value }),
+        _ => Err(SystemExclusiveError::UnsupportedFormat),
+    }
+}
+
+/// Gets the name of the device class of a Mobile Phone Control message.
+pub fn mobile_device_class_name(device_class: u8) -> Option<&'static str> {
+    match device_class {
+        0x01 => Some("Vibrator"),
+        0x02 => Some("LED"),
+        0x03 => Some("Display"),
+        0x04 => Some("Keypad"),
+        0x7f => Some("All"),
+        _ => None,
+    }
+}
+
+/// Gets the name of the command of a Mobile Phone Control message.
+pub fn mobile_command_name(command: u8) -> Option<&'static str> {
+    match command {
+        0x10 => Some("Device On"),
+        0x11 => Some("Device Off"),
+        0x12 => Some("Follow MIDI Channels"),
+        0x13 => Some("Follow Audio"),
+        0x20 => Some("Set Color"),
+        0x21 => Some("Set Level"),
+        0x7f => Some("Reset"),
+        _ => None,
+    }
+}
+
+fn decode_note_tuning_change(device: u8, sub_id2: u8, payload: &[u8]) -> Result<UniversalMessage, SystemExclusiveError> {
+    let (bank, rest) = match sub_id2 {
+        SINGLE_NOTE_TUNING_CHANGE => (None, payload),
+        SINGLE_NOTE_TUNING_CHANGE_BANK => match payload.split_first() {
+            Some((bank, rest)) => (Some(*bank), rest),
+            None => return Err(SystemExclusiveError::Truncated),
+        },
+        _ => return Err(SystemExclusiveError::UnsupportedFormat),
+    };
+    let (program, count) = match rest {
+        [program, count, ..] => (*program, *count as usize),
+        _ => return Err(SystemExclusiveError::Truncated),
+    };
+    let data = &rest[2..];
+    if data.len() < count * 4 {
+        return Err(SystemExclusiveError::Truncated);
+    }
+    if data.len() > count * 4 {
+        return Err(SystemExclusiveError::InvalidMessage);
+    }
+    let changes = data.chunks(4).map(|change| (change[0], NoteTuning::from_bytes(&change[1..]))).collect();
+    Ok(UniversalMessage::NoteTuningChange { device, bank, program, changes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> UniversalMessage {
+        Message::from_bytes(bytes).unwrap().decode_universal().unwrap()
+    }
+
+    #[test]
+    fn const_messages() {
+        const ON: [u8; 6] = GM_ON;
+        const VOLUME: [u8; 8] = master_volume_bytes(0x10, 0x3fff);
+        assert_eq!(decode(&ON), UniversalMessage::GmOn { device: 0x7F });
+        assert_eq!(decode(&GM2_ON), UniversalMessage::Gm2On { device: 0x7F });
+        assert_eq!(decode(&identity_request_bytes(0x05)), UniversalMessage::IdentityRequest { device: 0x05 });
+        assert_eq!(decode(&VOLUME), UniversalMessage::MasterVolume { device: 0x10, volume: 0x3fff });
+    }
+
+    #[test]
+    fn identity_reply() {
+        // Kawai K5000S
+        let bytes = [0xF0, 0x7E, 0x00, 0x06, 0x02, 0x40, 0x0A, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF7];
+        let message = decode(&bytes);
+        assert_eq!(message, UniversalMessage::IdentityReply {
+            device: 0x00,
+            manufacturer: Manufacturer::Standard(0x40),
+            family: 0x0A,
+            member: 0x02,
+            version: [0x01, 0x00, 0x00, 0x00],
+        });
+        assert_eq!(message.to_message().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn identity_responder() {
+        let identity = DeviceIdentity::new(Manufacturer::Standard(0x40), 0x0A, 0x02, [0x01, 0x00, 0x00, 0x00]);
+        let reply = [0xF0, 0x7E, 0x00, 0x06, 0x02, 0x40, 0x0A, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF7];
+        assert_eq!(identity.reply_fixed(0x00).as_bytes(), reply);
+
+        let request = Message::from_bytes(&IDENTITY_REQUEST).unwrap();
+        assert_eq!(identity.respond(0x00, &request).unwrap().to_bytes(), reply);
+        assert_eq!(identity.respond_to_bytes(0x00, &identity_request_bytes(0x00)).unwrap().as_bytes(), reply);
+        assert!(identity.respond(0x01, &Message::from_bytes(&identity_request_bytes(0x02)).unwrap()).is_none());
+        assert!(identity.respond_to_bytes(0x00, &GM_ON).is_none());
+
+        let extended = DeviceIdentity::new(Manufacturer::Extended([0x00, 0x20, 0x29]), 0x93, 0x01, [0x00, 0x01, 0x02, 0x03]);
+        let fixed = extended.reply_fixed(0x7F);
+        assert_eq!(fixed.byte_len(), IDENTITY_REPLY_MAX_LENGTH);
+        assert_eq!(fixed.to_message(), extended.reply(0x7F).to_message());
+    }
+
+    #[test]
+    fn identity_reply_extended_manufacturer() {
+        let bytes = [0xF0, 0x7E, 0x7F, 0x06, 0x02, 0x00, 0x20, 0x29, 0x13, 0x01, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0xF7];
+        let message = decode(&bytes);
+        assert!(matches!(message, UniversalMessage::IdentityReply { manufacturer: Manufacturer::Extended([0x00, 0x20, 0x29]), family: 0x93, .. }));
+        assert_eq!(message.to_message().to_bytes(), bytes);
+    }
+
+    #[test]
+    fn sample_dump_header() {
+        let header = SampleHeader {
+            sample_number: 200,
+            sample_format: 16,
+            sample_period: 22675,  // 44.1 kHz
+            length: 100000,
+            loop_start: 1000,
+            loop_end: 99999,
+            loop_type: LoopType::Forward,
+        };
+        let message = UniversalMessage::SampleDumpHeader { device: 1, header: header.clone() };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes.len(), 21);
+        assert_eq!(bytes[..6], [0xF0, 0x7E, 0x01, 0x01, 0x48, 0x01]);
+        assert_eq!(decode(&bytes), message);
+        assert_eq!(header.sample_rate().round(), 44101.0);
+    }
+
+    #[test]
+    fn extended_sample_dump_header() {
+        let header = ExtendedSampleHeader {
+            sample_number: 2,
+            sample_format: 24,
+            sample_rate: 96000,
+            sample_rate_fraction: 1 << 27,
+            length: 10_000_000_000,
+            loop_start: 0,
+            loop_end: 9_999_999_999,
+            loop_type: LoopType::BackwardRelease,
+            channels: 2,
+        };
+        let message = UniversalMessage::ExtendedSampleDumpHeader { device: 0, header: header.clone() };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes.len(), 34);
+        assert_eq!(bytes[..5], [0xF0, 0x7E, 0x00, 0x05, 0x05]);
+        assert_eq!(decode(&bytes), message);
+        assert_eq!(header.sample_rate(), 96000.5);
+
+        // A classic header is still decoded as one
+        let classic = SampleHeader {
+            sample_number: 2,
+            sample_format: 16,
+            sample_period: 20000,
+            length: 1000,
+            loop_start: 0,
+            loop_end: 999,
+            loop_type: LoopType::Off,
+        };
+        let bytes = UniversalMessage::SampleDumpHeader { device: 0, header: classic.clone() }.to_message().to_bytes();
+        assert!(matches!(decode(&bytes), UniversalMessage::SampleDumpHeader { .. }));
+        let converted = ExtendedSampleHeader::from(&classic);
+        assert_eq!(converted.sample_rate, 50000);
+        assert_eq!(converted.channels, 1);
+    }
+
+    #[test]
+    fn sample_data_packet() {
+        let message = UniversalMessage::SampleDataPacket { device: 0, packet: 5, data: vec![0x40; SAMPLE_PACKET_SIZE] };
+        let mut bytes = message.to_message().to_bytes();
+        assert_eq!(bytes.len(), 127);
+        assert_eq!(decode(&bytes), message);
+
+        bytes[10] = 0x41;
+        let result = Message::from_bytes(&bytes).unwrap().decode_universal();
+        assert!(matches!(result, Err(SystemExclusiveError::InvalidChecksum)));
+    }
+
+    #[test]
+    fn tuning_dump() {
+        let tunings: Vec<NoteTuning> = (0..TUNING_NOTE_COUNT as u8)
+            .map(|n| NoteTuning { semitone: n, fraction: 0x2000 })
+            .collect();
+        let message = UniversalMessage::TuningDump { device: 0x7F, program: 3, name: "Quarter shift".to_string(), tunings };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes.len(), 408);
+        assert_eq!(decode(&bytes), message);
+    }
+
+    #[test]
+    fn note_tuning_change() {
+        let changes = vec![(60, NoteTuning { semitone: 60, fraction: 0x2000 }), (64, NoteTuning::NO_CHANGE)];
+        let message = UniversalMessage::NoteTuningChange { device: 0x7F, bank: None, program: 2, changes: changes.clone() };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes[..12], [0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x02, 0x02, 0x3C, 0x3C, 0x40, 0x00, 0x40]);
+        assert_eq!(decode(&bytes), message);
+
+        let message = UniversalMessage::NoteTuningChange { device: 0x00, bank: Some(5), program: 2, changes };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes[4..7], [0x07, 0x05, 0x02]);
+        assert_eq!(decode(&bytes), message);
+
+        let truncated = Message::from_bytes(&[0xF0, 0x7F, 0x7F, 0x08, 0x02, 0x02, 0x02, 0x3C, 0xF7]).unwrap();
+        assert!(matches!(truncated.decode_universal(), Err(SystemExclusiveError::Truncated)));
+    }
+
+    #[test]
+    fn note_tuning_change_limit() {
+        let changes: Vec<(u8, NoteTuning)> = (0..200).map(|i| ((i % 128) as u8, NoteTuning::NO_CHANGE)).collect();
+        let messages = note_tuning_change_messages(0x7F, None, 0, &changes);
+        assert_eq!(messages.len(), 2);
+        let counts: Vec<usize> = messages.iter().map(|message| match message.decode_universal().unwrap() {
+            UniversalMessage::NoteTuningChange { changes, .. } => changes.len(),
+            _ => 0,
+        }).collect();
+        assert_eq!(counts, vec![127, 73]);
+
+        let message = UniversalMessage::NoteTuningChange { device: 0x7F, bank: None, program: 0, changes };
+        assert_eq!(message.to_message().to_bytes()[6], 127);
+        assert!(message.to_message().decode_universal().is_ok());
+    }
+
+    #[test]
+    fn tuning_select() {
+        let bytes = TuningSelect::Program(5).to_bytes(2);
+        assert_eq!(bytes, [0xB2, 0x65, 0x00, 0xB2, 0x64, 0x03, 0xB2, 0x06, 0x05, 0xB2, 0x65, 0x7F, 0xB2, 0x64, 0x7F]);
+        assert_eq!(TuningSelect::from_bytes(&bytes), Some((2, TuningSelect::Program(5))));
+        assert_eq!(TuningSelect::from_bytes(&TuningSelect::Bank(1).to_bytes(0)), Some((0, TuningSelect::Bank(1))));
+        assert_eq!(TuningSelect::Bank(1).rpn(), TUNING_BANK_SELECT_RPN);
+        // Master tuning RPN
+        assert_eq!(TuningSelect::from_bytes(&[0xB0, 0x65, 0x00, 0xB0, 0x64, 0x01, 0xB0, 0x06, 0x40]), None);
+    }
+
+    #[test]
+    fn mobile_phone_control() {
+        let bytes = [0xF0, 0x7F, 0x7F, 0x0C, 0x00, 0x02, 0x00, 0x20, 0x7F, 0x00, 0x00, 0xF7];
+        let message = decode(&bytes);
+        assert_eq!(message, UniversalMessage::MobilePhoneControl {
+            device: 0x7F, device_class: 0x02, device_index: 0, command: 0x20, data: vec![0x7F, 0x00, 0x00],
+        });
+        assert_eq!(message.to_message().to_bytes(), bytes);
+        assert_eq!(mobile_device_class_name(0x02), Some("LED"));
+        assert_eq!(mobile_command_name(0x20), Some("Set Color"));
+    }
+
+    #[test]
+    fn loop_points() {
+        let loops = [
+            LoopPoint { number: 0, loop_type: LoopType::Forward, start: 1000, end: 20000 },
+            LoopPoint { number: 1, loop_type: LoopType::Alternating, start: 20000, end: 40000 },
+        ];
+        let messages = loop_point_messages(0x10, 7, &loops);
+        assert_eq!(messages.len(), 2);
+        let bytes = messages[1].to_bytes();
+        assert_eq!(bytes.len(), 17);
+        assert_eq!(bytes[..10], [0xF0, 0x7E, 0x10, 0x05, 0x01, 0x07, 0x00, 0x01, 0x00, 0x01]);
+        assert_eq!(decode(&bytes), UniversalMessage::LoopPoints { device: 0x10, sample_number: 7, loop_point: loops[1] });
+
+        let request = UniversalMessage::LoopPointsRequest { device: 0x10, sample_number: 7, loop_number: ALL_LOOPS };
+        let bytes = request.to_message().to_bytes();
+        assert_eq!(bytes, [0xF0, 0x7E, 0x10, 0x05, 0x02, 0x07, 0x00, 0x7F, 0x7F, 0xF7]);
+        assert_eq!(decode(&bytes), request);
+    }
+
+    #[test]
+    fn sample_name() {
+        let message = UniversalMessage::SampleName { device: 0, sample_number: 300, name: "Piano C3".to_string() };
+        let bytes = message.to_message().to_bytes();
+        assert_eq!(bytes[..10], [0xF0, 0x7E, 0x00, 0x05, 0x03, 0x2C, 0x02, 0x00, 0x08, b'P']);
+        assert_eq!(decode(&bytes), message);
+
+        let encoded = UniversalMessage::SampleName { device: 0, sample_number: 1, name: "Flöte".to_string() }
+            .to_message().to_bytes();
+        assert!(matches!(decode(&encoded), UniversalMessage::SampleName { name, .. } if name == "Fl?te"));
+
+        // Language tag is skipped
+        let tagged = [0xF0, 0x7E, 0x00, 0x05, 0x03, 0x01, 0x00, 0x02, b'e', b'n', 0x03, b'P', b'a', b'd', 0xF7];
+        assert!(matches!(decode(&tagged), UniversalMessage::SampleName { name, .. } if name == "Pad"));
+
+        let truncated = [0xF0, 0x7E, 0x00, 0x05, 0x03, 0x01, 0x00, 0x00, 0x05, b'P', 0xF7];
+        assert!(matches!(Message::from_bytes(&truncated).unwrap().decode_universal(), Err(SystemExclusiveError::Truncated)));
+
+        assert_eq!(decode(&[0xF0, 0x7E, 0x00, 0x05, 0x04, 0x01, 0x00, 0xF7]),
+            UniversalMessage::SampleNameRequest { device: 0, sample_number: 1 });
+    }
+
+    #[test]
+    fn handshake() {
+        let message = Handshake::Wait.to_message(0x05, 0x12);
+        assert_eq!(message.to_bytes(), [0xF0, 0x7E, 0x05, 0x7C, 0x12, 0xF7]);
+        assert_eq!(Handshake::from_message(&message), Some((Handshake::Wait, 0x12)));
+        assert_eq!(message.decode_universal().unwrap(),
+            UniversalMessage::Handshake { device: 0x05, handshake: Handshake::Wait, packet: 0x12 });
+        assert_eq!(handshake_reply(&message), Reply::Ignore);
+        assert_eq!(handshake_reply(&Handshake::Ack.to_message(0x05, 0x12)), Reply::Ack);
+        assert_eq!(handshake_reply(&UniversalMessage::GmOn { device: 0x05 }.to_message()), Reply::Ignore);
+    }
+
+    #[test]
+    fn simple_messages() {
+        assert_eq!(decode(&[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]), UniversalMessage::GmOn { device: 0x7F });
+        assert_eq!(decode(&[0xF0, 0x7E, 0x10, 0x06, 0x01, 0xF7]), UniversalMessage::IdentityRequest { device: 0x10 });
+        assert_eq!(decode(&[0xF0, 0x7F, 0x7F, 0x04, 0x01, 0x00, 0x40, 0xF7]),
+            UniversalMessage::MasterVolume { device: 0x7F, volume: 0x2000 });
+        assert_eq!(decode(&[0xF0, 0x7E, 0x00, 0x03, 0x05, 0x01, 0xF7]),
+            UniversalMessage::SampleDumpRequest { device: 0, sample_number: 133 });
+    }
+}
@@ -0,0 +1,140 @@
+//! # usbmidi
+//!
+//! Conversion between System Exclusive messages and USB-MIDI event packets,
+//! as sent over USB by class-compliant devices.
+//!
+//! Each packet has four bytes: the cable number in the high nybble and the
+//! Code Index Number (CIN) in the low nybble of the first byte, followed by
+//! three MIDI bytes. A message is sent three bytes at a time with CIN 0x4,
+//! and the packet with the terminator uses CIN 0x5, 0x6 or 0x7 for one,
+//! two or three bytes. Unused bytes are zero.
+//!
+//! Making the packets with [`packets`] does not allocate.
+
+use crate::{Message, SystemExclusiveError, INITIATOR, TERMINATOR};
+
+/// A USB-MIDI event packet.
+pub type Packet = [u8; 4];
+
+/// Code Index Number of a packet that starts or continues a message.
+pub const CIN_SYSEX_START: u8 = 0x4;
+
+/// Code Index Number of a packet that ends a message with one byte.
+/// Also used for single-byte System Common messages.
+pub const CIN_SYSEX_END_1: u8 = 0x5;
+
+/// Code Index Number of a packet that ends a message with two bytes.
+pub const CIN_SYSEX_END_2: u8 = 0x6;
+
+/// Code Index Number of a packet that ends a message with three bytes.
+pub const CIN_SYSEX_END_3: u8 = 0x7;
+
+/// Number of virtual cables in a USB-MIDI endpoint.
+pub const CABLE_COUNT: usize = 16;
+
+/// Makes the event packets of the bytes of a message, from initiator
+/// to terminator, on the given cable (0 to 15).
+pub fn packets(data: &[u8], cable: u8) -> impl Iterator<Item = Packet> + '_ {
+    let cable = (cable & 0x0f) << 4;
+    data.chunks(3).map(move |chunk| {
+        let cin = match (chunk.last() == Some(&TERMINATOR), chunk.len()) {
+            (true, 1) => CIN_SYSEX_END_1,
+            (true, 2) => CIN_SYSEX_END_2,
+            (true, _) => CIN_SYSEX_END_3,
+            (false, _) => CIN_SYSEX_START,
+        };
+        let mut packet = [cable | cin, 0, 0, 0];
+        packet[1..1 + chunk.len()].copy_from_slice(chunk);
+        packet
+    })
+}
+
+/// Makes the event packets of a message on the given cable.
+pub fn to_packets(message: &Message, cable: u8) -> Vec<Packet> {
+    packets(&message.to_bytes(), cable).collect()
+}
+
+/// Joins USB-MIDI event packets back into messages, separately for
+/// each cable. Packets of other kinds are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct PacketDecoder {
+    pending: [Option<Vec<u8>>; CABLE_COUNT],
+}
+
+impl PacketDecoder {
+    /// Makes a decoder with no messages in progress.
+    pub fn new() -> Self {
+        PacketDecoder::default()
+    }
+
+    /// Handles one packet. Returns the cable number and the message when
+    /// the packet completes one, or `None` otherwise.
+    ///
+    /// A packet continuing or ending a message that was not started fails
+    /// with `SystemExclusiveError::Truncated`. A new message on a cable
+    /// drops the one in progress on that cable.
+    pub fn push(&mut self, packet: Packet) -> Result<Option<(u8, Message)>, SystemExclusiveError> {
+        let cable = packet[0] >> 4;
+        let count = match packet[0] & 0x0f {
+            CIN_SYSEX_START => 3,
+            CIN_SYSEX_END_1 if packet[1] == TERMINATOR => 1,
+            CIN_SYSEX_END_2 => 2,
+            CIN_SYSEX_END_3 => 3,
+            _ => return Ok(None),
+        };
+        let bytes = &packet[1..1 + count];
+        let pending = &mut self.pending[cable as usize];
+        if bytes[0] == INITIATOR {
+            *pending = Some(Vec::new());
+        }
+        let data = pending.as_mut().ok_or(SystemExclusiveError::Truncated)?;
+        data.extend(bytes);
+        if bytes.last() == Some(&TERMINATOR) {
+            let data = pending.take().unwrap_or_default();
+            return Message::from_bytes(&data).map(|message| Some((cable, message)));
+        }
+        Ok(None)
+    }
+}
+
+/// Gets the messages in a sequence of packets.
+pub fn from_packets(packets: &[Packet]) -> Result<Vec<(u8, Message)>, SystemExclusiveError> {
+    let mut decoder = PacketDecoder::new();
+    let mut result = Vec::new();
+    for packet in packets {
+        result.extend(decoder.push(*packet)?);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_packets() {
+        let message = Message::from_bytes(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        assert_eq!(to_packets(&message, 1), vec![[0x14, 0xF0, 0x7E, 0x7F], [0x17, 0x06, 0x01, 0xF7]]);
+
+        let short = Message::from_bytes(&[0xF0, 0x7D, 0x01, 0xF7]).unwrap();
+        assert_eq!(to_packets(&short, 0), vec![[0x04, 0xF0, 0x7D, 0x01], [0x05, 0xF7, 0x00, 0x00]]);
+        assert_eq!(packets(&[0xF0, 0x7D, 0xF7], 0).collect::<Vec<_>>(), vec![[0x07, 0xF0, 0x7D, 0xF7]]);
+        assert_eq!(packets(&[0xF0, 0x7D, 0x01, 0x02, 0xF7], 0).nth(1), Some([0x06, 0x02, 0xF7, 0x00]));
+    }
+
+    #[test]
+    fn decode_packets() {
+        let a = Message::from_bytes(&[0xF0, 0x43, 0x00, 0x09, 0x20, 0x00, 0x01, 0xF7]).unwrap();
+        let b = Message::from_bytes(&[0xF0, 0x41, 0x10, 0x42, 0xF7]).unwrap();
+        let packets_a = to_packets(&a, 0);
+        let packets_b = to_packets(&b, 2);
+        // Interleaved cables, with a note on in between
+        let mut packets = vec![packets_a[0], packets_b[0], [0x09, 0x90, 0x40, 0x7F]];
+        packets.extend(&packets_a[1..]);
+        packets.extend(&packets_b[1..]);
+        assert_eq!(from_packets(&packets).unwrap(), vec![(0, a), (2, b)]);
+
+        assert!(matches!(from_packets(&[[0x06, 0x01, 0xF7, 0x00]]), Err(SystemExclusiveError::Truncated)));
+        assert_eq!(from_packets(&[[0x05, 0xF6, 0x00, 0x00]]).unwrap(), vec![]);  // tune request
+    }
+}
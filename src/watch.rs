@@ -0,0 +1,196 @@
+//! # watch
+//!
+//! Live capture logging: appending every incoming message to a dated log
+//! directory with a line identifying it, and watching a directory that
+//! another program (like ReceiveMIDI) writes `.syx` files into.
+//!
+//! The library does no MIDI input itself. A tool reading a MIDI input
+//! passes each received message to [`CaptureLog::append`].
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{Message, universal_sub_id_description};
+use crate::scan::{is_supported_file, read_messages, FileMessages};
+
+/// Name of the file in each dated directory with the captured messages.
+pub const MESSAGES_FILE: &str = "capture.syx";
+
+/// Name of the file in each dated directory with the identification lines.
+pub const LOG_FILE: &str = "capture.log";
+
+/// Makes a one-line identification of a message, like
+/// "Yamaha (43), 4104 bytes".
+pub fn identification(message: &Message) -> String {
+    match message {
+        Message::Universal { kind, sub_id1, sub_id2, .. } => {
+            let name = universal_sub_id_description(*kind, *sub_id1, *sub_id2)
+                .unwrap_or_else(|| format!("{:02X} {:02X}", sub_id1, sub_id2));
+            format!("Universal {} {}, {} bytes", kind, name, message.byte_len())
+        },
+        Message::ManufacturerSpecific { manufacturer, .. } =>
+            format!("{} ({}), {} bytes", manufacturer.name(), manufacturer.to_hex(), message.byte_len()),
+    }
+}
+
+/// Converts days since 1970-01-01 to the year, month and day.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Splits a time into the UTC date as "YYYY-MM-DD" and the time of day as "HH:MM:SS.mmm".
+fn date_and_time(time: SystemTime) -> (String, String) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let of_day = seconds.rem_euclid(86_400);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}:{:02}:{:02}.{:03}", of_day / 3600, of_day % 3600 / 60, of_day % 60, since_epoch.subsec_millis()),
+    )
+}
+
+/// An append-only log of captured messages. The messages received on each
+/// day (in UTC) go into a subdirectory named by the date, like `2024-05-17`,
+/// which has the messages in `capture.syx` and one identification line per
+/// message, with the time it was received, in `capture.log`.
+#[derive(Clone, Debug)]
+pub struct CaptureLog {
+    root: PathBuf,
+}
+
+impl CaptureLog {
+    /// Makes a log in the root directory, which is created if needed.
+    pub fn new(root: &Path) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        Ok(CaptureLog { root: root.to_path_buf() })
+    }
+
+    /// Gets the dated directory for a time.
+    pub fn directory(&self, time: SystemTime) -> PathBuf {
+        self.root.join(date_and_time(time).0)
+    }
+
+    /// Appends a message received now.
+    pub fn append(&self, message: &Message) -> io::Result<()> {
+        self.append_at(message, SystemTime::now())
+    }
+
+    /// Appends a message received at the given time.
+    pub fn append_at(&self, message: &Message, time: SystemTime) -> io::Result<()> {
+        let (date, time_of_day) = date_and_time(time);
+        let directory = self.root.join(date);
+        fs::create_dir_all(&directory)?;
+
+        let mut messages = OpenOptions::new().create(true).append(true).open(directory.join(MESSAGES_FILE))?;
+        message.write_to(&mut messages)?;
+        let mut log = OpenOptions::new().create(true).append(true).open(directory.join(LOG_FILE))?;
+        writeln!(log, "{} {}", time_of_day, identification(message))
+    }
+}
+
+/// Watches a directory for new or changed `.syx` and `.mid` files by polling.
+#[derive(Clone, Debug)]
+pub struct DirectoryWatcher {
+    root: PathBuf,
+    seen: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl DirectoryWatcher {
+    /// Starts watching a directory. Files already in it are not reported
+    /// unless they change.
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let mut watcher = DirectoryWatcher { root: root.to_path_buf(), seen: HashMap::new() };
+        watcher.changed_files()?;
+        Ok(watcher)
+    }
+
+    /// Finds the supported files that are new or changed since the last call.
+    fn changed_files(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || !is_supported_file(&path) {
+                continue;
+            }
+            let stamp = (metadata.modified()?, metadata.len());
+            if self.seen.insert(path.clone(), stamp) != Some(stamp) {
+                changed.push(path);
+            }
+        }
+        changed.sort();
+        Ok(changed)
+    }
+
+    /// Reads the messages of the files that are new or changed since the
+    /// last poll. A file that cannot be parsed yet, because it is still
+    /// being written, is tried again on the next poll.
+    pub fn poll(&mut self) -> io::Result<Vec<FileMessages>> {
+        let mut result = Vec::new();
+        for path in self.changed_files()? {
+            match read_messages(&path) {
+                Ok(messages) => result.push((path, messages)),
+                Err(_) => {
+                    self.seen.remove(&path);
+                },
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_860), (2024, 5, 17));
+        let time = UNIX_EPOCH + Duration::from_millis(951_782_400_123 + 3_723_000);
+        assert_eq!(date_and_time(time), ("2000-02-29".to_string(), "01:02:03.123".to_string()));
+    }
+
+    #[test]
+    fn capture_and_watch() {
+        let dir = std::env::temp_dir().join(format!("syxpack-watch-{}", std::process::id()));
+        let inbox = dir.join("inbox");
+        fs::create_dir_all(&inbox).unwrap();
+        fs::write(inbox.join("old.syx"), [0xF0, 0x43, 0x00, 0x01, 0xF7]).unwrap();
+
+        let mut watcher = DirectoryWatcher::new(&inbox).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        fs::write(inbox.join("new.syx"), [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]).unwrap();
+        let files = watcher.poll().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, inbox.join("new.syx"));
+
+        let log = CaptureLog::new(&dir.join("log")).unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(19_860 * 86_400);
+        for message in &files[0].1 {
+            log.append_at(message, time).unwrap();
+            log.append_at(message, time).unwrap();
+        }
+        let directory = log.directory(time);
+        assert!(directory.ends_with("2024-05-17"));
+        assert_eq!(fs::read(directory.join(MESSAGES_FILE)).unwrap().len(), 12);
+        let text = fs::read_to_string(directory.join(LOG_FILE)).unwrap();
+        assert_eq!(text.lines().next(), Some("00:00:00.000 Universal Non-Real-time General Information — Identity Request, 6 bytes"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
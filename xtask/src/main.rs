@@ -0,0 +1,349 @@
+//! # xtask
+//!
+//! Maintainer tasks for syxpack, run with `cargo xtask <task>`.
+//!
+//! `update-manufacturers <listing> [--write]` reads a saved copy of the MMA
+//! manufacturer ID listing, either the HTML page or a CSV export with the
+//! ID and the name in the first columns, and compares it with the table of
+//! manufacturer names in `src/lib.rs`. The changes are printed with `+` for
+//! new IDs, `-` for IDs not in the listing and `~` for changed names. With
+//! `--write` the table is regenerated from the listing.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Manufacturer names keyed by the ID in uppercase hex, like in the registry.
+type Table = BTreeMap<String, String>;
+
+/// The start of the table in `src/lib.rs`.
+const TABLE_START: &str = "static MANUFACTURER_NAMES: phf::Map<&'static str, &'static str> = phf::phf_map! {\n";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>().as_slice() {
+        ["update-manufacturers", listing] => update_manufacturers(Path::new(listing), false),
+        ["update-manufacturers", listing, "--write"] => update_manufacturers(Path::new(listing), true),
+        _ => Err("usage: cargo xtask update-manufacturers <listing.html|listing.csv> [--write]".to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn update_manufacturers(listing: &Path, write: bool) -> Result<(), String> {
+    let text = fs::read_to_string(listing).map_err(|e| format!("{}: {}", listing.display(), e))?;
+    let new = parse_listing(&text);
+    if new.is_empty() {
+        return Err(format!("{}: no manufacturer IDs found", listing.display()));
+    }
+
+    let lib = Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/lib.rs");
+    let source = fs::read_to_string(&lib).map_err(|e| format!("{}: {}", lib.display(), e))?;
+    let (range, old) = read_registry(&source).ok_or("the manufacturer table was not found in src/lib.rs")?;
+
+    let changes = diff(&old, &new);
+    for change in &changes {
+        println!("{}", change);
+    }
+    println!("{} changes, {} IDs in the listing", changes.len(), new.len());
+
+    if write && !changes.is_empty() {
+        let mut updated = source[..range.start].to_string();
+        updated.push_str(&render(&new));
+        updated.push_str(&source[range.end..]);
+        fs::write(&lib, updated).map_err(|e| format!("{}: {}", lib.display(), e))?;
+        println!("Wrote {}", lib.display());
+    }
+    Ok(())
+}
+
+/// Normalizes a manufacturer ID like "43H", "00H 20H 29H", "0x41" or
+/// "00 20 29" to uppercase hex without spaces, like "43" or "002029".
+/// The universal and development IDs and 00 00 00 are not manufacturers.
+fn normalize_id(s: &str) -> Option<String> {
+    let hex: String = s.split_whitespace()
+        .map(|part| part.trim_start_matches("0x").trim_start_matches("0X").trim_end_matches(['h', 'H']))
+        .collect();
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = hex.to_uppercase();
+    match hex.len() {
+        2 if ("01".."7D").contains(&hex.as_str()) => Some(hex),
+        6 if hex.starts_with("00") && hex != "000000" => Some(hex),
+        _ => None,
+    }
+}
+
+/// Gets the ID and name of a row of the listing: the first cell that is
+/// an ID and the next cell that is not empty.
+fn parse_row(cells: &[String]) -> Option<(String, String)> {
+    let position = cells.iter().position(|cell| normalize_id(cell).is_some())?;
+    let name = cells[position + 1..].iter().find(|cell| !cell.is_empty())?;
+    Some((normalize_id(&cells[position])?, name.clone()))
+}
+
+/// Parses the listing as HTML if it has table rows, otherwise as CSV.
+/// If an ID is listed more than once, the first name is used.
+fn parse_listing(text: &str) -> Table {
+    let rows = if text.to_lowercase().contains("<tr") { html_rows(text) } else { csv_rows(text) };
+    let mut table = Table::new();
+    for (id, name) in rows.iter().filter_map(|cells| parse_row(cells)) {
+        table.entry(id).or_insert(name);
+    }
+    table
+}
+
+/// Splits CSV into rows of cells, with quoted cells.
+fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| {
+            let mut cells = Vec::new();
+            let mut cell = String::new();
+            let mut quoted = false;
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        cell.push('"');
+                        chars.next();
+                    },
+                    '"' => quoted = !quoted,
+                    ',' if !quoted => cells.push(std::mem::take(&mut cell).trim().to_string()),
+                    _ => cell.push(c),
+                }
+            }
+            cells.push(cell.trim().to_string());
+            cells
+        })
+        .collect()
+}
+
+/// Gets the text of the cells of each table row in HTML, without tags
+/// and with entities decoded.
+fn html_rows(text: &str) -> Vec<Vec<String>> {
+    // ASCII lowercasing keeps the byte offsets of the text
+    let lower = text.to_ascii_lowercase();
+    let mut rows = Vec::new();
+    let mut position = 0;
+    while let Some(start) = lower[position..].find("<tr").map(|i| i + position) {
+        let end = lower[start..].find("</tr").map(|i| i + start).unwrap_or(text.len());
+        let row = &lower[start..end];
+        let mut cells = Vec::new();
+        let mut cell_position = 0;
+        while let Some(cell_start) = row[cell_position..].find("<t").map(|i| i + cell_position) {
+            cell_position = cell_start + 2;
+            if !matches!(row.as_bytes().get(cell_start + 2), Some(b'd' | b'h')) {
+                continue;
+            }
+            let Some(content_start) = row[cell_start..].find('>').map(|i| i + cell_start + 1) else {
+                break;
+            };
+            let content_end = ["</td", "</th", "<td", "<th"].iter()
+                .filter_map(|tag| row[content_start..].find(tag))
+                .min()
+                .map(|i| i + content_start)
+                .unwrap_or(row.len());
+            cells.push(cell_text(&text[start + content_start..start + content_end]));
+            cell_position = content_end;
+        }
+        rows.push(cells);
+        position = end.max(start + 3);
+    }
+    rows
+}
+
+/// Strips the tags from HTML, decodes the common entities and collapses whitespace.
+fn cell_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {},
+        }
+    }
+    let mut decoded = String::new();
+    let mut rest = text.as_str();
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest.find(';').filter(|end| *end <= 10).map(|end| (&rest[1..end], end));
+        let replacement = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ if name.starts_with("#x") => u32::from_str_radix(&name[2..], 16).ok().and_then(char::from_u32),
+            _ if name.starts_with('#') => name[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        });
+        match (replacement, entity) {
+            (Some(c), Some((_, end))) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Finds the table in the source of `src/lib.rs`. Returns the range of
+/// its entries, between the opening and closing lines, and the entries.
+fn read_registry(source: &str) -> Option<(Range<usize>, Table)> {
+    let start = source.find(TABLE_START)? + TABLE_START.len();
+    let end = source[start..].find("\n};").map(|i| i + start + 1)?;
+    let mut table = Table::new();
+    for line in source[start..end].lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let (id, name) = line.strip_prefix('"')?.strip_suffix("\",")?.split_once("\" => \"")?;
+        table.insert(id.to_string(), name.replace("\\\"", "\"").replace("\\\\", "\\"));
+    }
+    Some((start..end, table))
+}
+
+/// Gets the group of a manufacturer ID, by its first byte or the middle
+/// byte of an extended ID, for ordering the table.
+fn group(id: &str) -> (usize, &'static str) {
+    let byte = if id.len() == 2 { &id[..2] } else { &id[2..4] };
+    match u8::from_str_radix(byte, 16).unwrap_or_default() {
+        0x00..=0x1f => (0, "North American Group"),
+        0x20..=0x3f => (1, "European & Other Group"),
+        0x40..=0x5f => (2, "Japanese Group"),
+        _ => (3, "Other"),
+    }
+}
+
+/// Makes the entries of the table, grouped like the listing: the
+/// standard IDs of each group followed by its extended IDs.
+fn render(table: &Table) -> String {
+    let mut ids: Vec<&String> = table.keys().collect();
+    ids.sort_by_key(|id| (group(id).0, id.len(), *id));
+    let mut out = String::new();
+    let mut previous: Option<(usize, usize)> = None;
+    for id in ids {
+        let (index, name) = group(id);
+        match previous {
+            Some((previous_index, _)) if previous_index != index => out.push_str(&format!("\n    // {}\n", name)),
+            Some((_, length)) if length != id.len() => out.push('\n'),
+            _ => {},
+        }
+        previous = Some((index, id.len()));
+        let escaped = table[id].replace('\\', "\\\\").replace('"', "\\\"");
+        out.push_str(&format!("    \"{}\" => \"{}\",\n", id, escaped));
+    }
+    out
+}
+
+/// A difference between the table and the listing.
+#[derive(Debug, PartialEq)]
+enum Change {
+    Added(String, String),
+    Removed(String, String),
+    Renamed(String, String, String),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::Added(id, name) => write!(f, "+ {}\t{}", id, name),
+            Change::Removed(id, name) => write!(f, "- {}\t{}", id, name),
+            Change::Renamed(id, old, new) => write!(f, "~ {}\t{} -> {}", id, old, new),
+        }
+    }
+}
+
+fn diff(old: &Table, new: &Table) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (id, name) in new {
+        match old.get(id) {
+            None => changes.push(Change::Added(id.clone(), name.clone())),
+            Some(old_name) if old_name != name => changes.push(Change::Renamed(id.clone(), old_name.clone(), name.clone())),
+            Some(_) => {},
+        }
+    }
+    for (id, name) in old {
+        if !new.contains_key(id) {
+            changes.push(Change::Removed(id.clone(), name.clone()));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        assert_eq!(normalize_id("43H").as_deref(), Some("43"));
+        assert_eq!(normalize_id("00H 20H 29H").as_deref(), Some("002029"));
+        assert_eq!(normalize_id("00 21 1d").as_deref(), Some("00211D"));
+        assert_eq!(normalize_id("0x41").as_deref(), Some("41"));
+        assert_eq!(normalize_id("7EH"), None);
+        assert_eq!(normalize_id("00 00 00"), None);
+        assert_eq!(normalize_id("SysEx ID"), None);
+    }
+
+    #[test]
+    fn listings() {
+        let csv = "SysEx ID,Company\n43H,Yamaha\n\"00H 20H 29H\",\"Focusrite/Novation, Ltd.\"\n";
+        let table = parse_listing(csv);
+        assert_eq!(table["002029"], "Focusrite/Novation, Ltd.");
+        assert_eq!(table.len(), 2);
+
+        let html = "<table><tr><th>ID</th><th>Company</th></tr>\n\
+            <tr><td>41H</td><td><a href=\"#\">Roland Corporation</a></td></tr>\
+            <TR><TD>00H 40H 03H</TD><TD>D&amp;M Holdings&nbsp;Inc.</TD></TR></table>";
+        let table = parse_listing(html);
+        assert_eq!(table["41"], "Roland Corporation");
+        assert_eq!(table["004003"], "D&M Holdings Inc.");
+
+        // Characters whose full lowercase has another length in UTF-8
+        let html = "<tr><td>\u{212a}\u{130}</td></tr><tr><td>40H</td><td>\u{130}nc. K\u{212a}<t\u{434}</td></tr>";
+        let rows = html_rows(html);
+        assert_eq!(rows[0], vec!["\u{212a}\u{130}".to_string()]);
+        assert_eq!(rows[1], vec!["40H".to_string(), "\u{130}nc. K\u{212a}".to_string()]);
+    }
+
+    #[test]
+    fn registry_round_trip() {
+        let source = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/lib.rs")).unwrap();
+        let (range, table) = read_registry(&source).unwrap();
+        assert_eq!(table["43"], "Yamaha");
+        let rendered = render(&table);
+        let regenerated = format!("{}{}{}", &source[..range.start], rendered, &source[range.end..]);
+        assert_eq!(read_registry(&regenerated).unwrap().1, table);
+        assert!(rendered.contains("\n    // Japanese Group\n    \"40\""));
+
+        let mut new = table.clone();
+        new.insert("002150".to_string(), "New Company".to_string());
+        new.insert("43".to_string(), "Yamaha Corporation".to_string());
+        new.remove("01");
+        assert_eq!(diff(&table, &new), vec![
+            Change::Added("002150".to_string(), "New Company".to_string()),
+            Change::Renamed("43".to_string(), "Yamaha".to_string(), "Yamaha Corporation".to_string()),
+            Change::Removed("01".to_string(), "Sequential Circuits".to_string()),
+        ]);
+    }
+}